@@ -0,0 +1,24 @@
+use fs::ArchiveCache;
+use gpui::{AppContext, Global};
+
+/// The listings of the archives being browsed as folders. Paths inside an
+/// archive go through it as if it were a folder, e.g.
+/// `~/Downloads/photos.zip/2023/beach.jpg`.
+#[derive(Default)]
+pub struct Archives {
+    cache: ArchiveCache,
+}
+
+impl Global for Archives {}
+
+pub fn init(cx: &mut AppContext) {
+    cx.set_global(Archives::default());
+}
+
+impl Archives {
+    pub fn cache(cx: &AppContext) -> ArchiveCache {
+        cx.try_global::<Self>()
+            .map(|this| this.cache.clone())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,362 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use ::fs::FsWatcher;
+use gpui::{AppContext, Global};
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::{paths::FAVORITES, settings::Settings};
+
+/// How often changes to the bookmark files are picked up.
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+const GTK_FILE: &str = "bookmarks";
+const KDE_FILE: &str = "user-places.xbel";
+/// Remote locations the explorer can open, kept when bookmarked.
+const REMOTE_SCHEMES: [&str; 3] = ["sftp", "dav", "davs"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bookmark {
+    pub label: String,
+    pub path: PathBuf,
+}
+
+/// The bookmarks of GTK file managers and of KDE's places panel, shown in
+/// the sidebar and read again whenever either file changes.
+#[derive(Default)]
+pub struct Bookmarks {
+    bookmarks: Vec<Bookmark>,
+}
+
+impl Global for Bookmarks {}
+
+pub fn init(cx: &mut AppContext) {
+    let settings = Settings::get(cx).bookmarks.clone();
+    if !settings.import && !settings.write_favorites {
+        return;
+    }
+    if settings.write_favorites {
+        export_favorites();
+    }
+    if settings.import {
+        cx.set_global(Bookmarks {
+            bookmarks: load_bookmarks(),
+        });
+    }
+
+    let bookmarks_changed = Arc::new(AtomicBool::new(false));
+    let favorites_changed = Arc::new(AtomicBool::new(false));
+    let watcher = FsWatcher::new(&[], false, {
+        let bookmarks_changed = bookmarks_changed.clone();
+        let favorites_changed = favorites_changed.clone();
+        move |events| {
+            if events.is_empty() {
+                bookmarks_changed.store(true, Ordering::Relaxed);
+                favorites_changed.store(true, Ordering::Relaxed);
+            }
+            for event in events {
+                if event.path.starts_with(FAVORITES.as_path()) {
+                    favorites_changed.store(true, Ordering::Relaxed);
+                } else if event
+                    .path
+                    .file_name()
+                    .is_some_and(|name| name == GTK_FILE || name == KDE_FILE)
+                {
+                    bookmarks_changed.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+    });
+    let watcher = match watcher {
+        Ok(mut watcher) => {
+            // Editors and file managers save by replacing the files, so
+            // their folders are watched. Ones that don't exist are skipped.
+            let folders = [gtk_bookmarks_path(), kde_places_path()]
+                .into_iter()
+                .flatten()
+                .filter_map(|path| path.parent().map(Path::to_path_buf))
+                .chain(Some(FAVORITES.clone()));
+            for folder in folders {
+                watcher.watch(&folder, false).ok();
+            }
+            Some(watcher)
+        }
+        Err(e) => {
+            eprintln!("Failed to watch bookmarks. Error: {}", e);
+            None
+        }
+    };
+
+    cx.spawn(|mut cx| async move {
+        let _watcher = watcher;
+        loop {
+            cx.background_executor().timer(CHECK_INTERVAL).await;
+            if settings.write_favorites && favorites_changed.swap(false, Ordering::Relaxed) {
+                export_favorites();
+            }
+            if !settings.import || !bookmarks_changed.swap(false, Ordering::Relaxed) {
+                continue;
+            }
+            let bookmarks = load_bookmarks();
+            let updated = cx.update(|cx| {
+                if Bookmarks::get(cx) != bookmarks.as_slice() {
+                    cx.set_global(Bookmarks { bookmarks });
+                    cx.refresh();
+                }
+            });
+            if updated.is_err() {
+                break;
+            }
+        }
+    })
+    .detach();
+}
+
+impl Bookmarks {
+    pub fn get(cx: &AppContext) -> &[Bookmark] {
+        cx.try_global::<Self>()
+            .map_or(&[], |this| this.bookmarks.as_slice())
+    }
+}
+
+fn gtk_bookmarks_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("gtk-3.0").join(GTK_FILE))
+}
+
+fn kde_places_path() -> Option<PathBuf> {
+    Some(dirs::data_dir()?.join(KDE_FILE))
+}
+
+/// GTK's bookmarks, then KDE's that aren't among them.
+fn load_bookmarks() -> Vec<Bookmark> {
+    let read = |path: Option<PathBuf>| path.and_then(|path| fs::read_to_string(path).ok());
+    let mut bookmarks = read(gtk_bookmarks_path())
+        .map(|contents| parse_gtk_bookmarks(&contents))
+        .unwrap_or_default();
+    let kde = read(kde_places_path())
+        .map(|contents| parse_kde_places(&contents))
+        .unwrap_or_default();
+    for bookmark in kde {
+        if !bookmarks.iter().any(|known| known.path == bookmark.path) {
+            bookmarks.push(bookmark);
+        }
+    }
+    bookmarks
+}
+
+/// One `uri [label]` per line.
+fn parse_gtk_bookmarks(contents: &str) -> Vec<Bookmark> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (uri, label) = match line.trim().split_once(' ') {
+                Some((uri, label)) => (uri, Some(label.trim())),
+                None => (line.trim(), None),
+            };
+            bookmark(uri, label.filter(|label| !label.is_empty()))
+        })
+        .collect()
+}
+
+/// The places KDE users added themselves, leaving out the built-in ones and
+/// those they hid.
+fn parse_kde_places(contents: &str) -> Vec<Bookmark> {
+    // The file starts with `<!DOCTYPE xbel>`.
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let Ok(document) = roxmltree::Document::parse_with_options(contents, options) else {
+        return Vec::new();
+    };
+    let metadata_flag = |node: roxmltree::Node, name: &str| {
+        node.descendants()
+            .any(|child| child.has_tag_name(name) && child.text() == Some("true"))
+    };
+
+    document
+        .root_element()
+        .children()
+        .filter(|node| node.has_tag_name("bookmark"))
+        .filter(|node| !metadata_flag(*node, "isSystemItem") && !metadata_flag(*node, "IsHidden"))
+        .filter_map(|node| {
+            let label = node
+                .children()
+                .find(|child| child.has_tag_name("title"))
+                .and_then(|title| title.text());
+            bookmark(node.attribute("href")?, label)
+        })
+        .collect()
+}
+
+/// Local folders and remote locations the explorer can open; other URIs,
+/// like `trash:/` or `smb://`, are skipped.
+fn bookmark(uri: &str, label: Option<&str>) -> Option<Bookmark> {
+    let url = Url::parse(uri).ok()?;
+    let path = if url.scheme() == "file" {
+        url.to_file_path().ok()?
+    } else if REMOTE_SCHEMES.contains(&url.scheme()) {
+        let path = percent_decode_str(url.path()).decode_utf8_lossy();
+        let path = path.trim_end_matches('/');
+        let mut server = url.clone();
+        server.set_path("");
+        PathBuf::from(format!("{}{}", server.as_str().trim_end_matches('/'), path))
+    } else {
+        return None;
+    };
+
+    let label = match label {
+        Some(label) => label.to_string(),
+        None => path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .or_else(|| url.host_str().map(str::to_string))
+            .unwrap_or_else(|| path.to_string_lossy().into_owned()),
+    };
+    Some(Bookmark { label, path })
+}
+
+/// Adds the favorites to GTK's bookmarks, so other file managers show them
+/// too. Bookmarks already there are left alone, and nothing is removed.
+fn export_favorites() {
+    let Some(bookmarks_path) = gtk_bookmarks_path() else {
+        return;
+    };
+    let Ok(entries) = fs::read_dir(FAVORITES.as_path()) else {
+        return;
+    };
+    let contents = fs::read_to_string(&bookmarks_path).unwrap_or_default();
+    let known = parse_gtk_bookmarks(&contents);
+
+    let mut lines = Vec::new();
+    for entry in entries.filter_map(Result::ok) {
+        let path = fs::canonicalize(entry.path()).unwrap_or_else(|_| entry.path());
+        if !path.is_dir() || known.iter().any(|bookmark| bookmark.path == path) {
+            continue;
+        }
+        let Ok(uri) = Url::from_file_path(&path) else {
+            continue;
+        };
+        let label = entry.file_name().to_string_lossy().into_owned();
+        if path.file_name() == Some(entry.file_name().as_os_str()) {
+            lines.push(uri.to_string());
+        } else {
+            lines.push(format!("{} {}", uri, label));
+        }
+    }
+    if lines.is_empty() {
+        return;
+    }
+
+    let mut contents = contents;
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    for line in lines {
+        contents.push_str(&line);
+        contents.push('\n');
+    }
+    let result = bookmarks_path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&bookmarks_path, contents));
+    if let Err(e) = result {
+        eprintln!(
+            "Failed to write bookmarks: {}. Error: {}",
+            bookmarks_path.display(),
+            e
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expected(label: &str, path: &str) -> Bookmark {
+        Bookmark {
+            label: label.into(),
+            path: path.into(),
+        }
+    }
+
+    #[test]
+    fn parses_gtk_bookmarks() {
+        let contents = "\
+file:///home/me/Projects
+file:///home/me/My%20Music Tunes and more
+sftp://me@example.com:2222/srv/www/ Web server
+trash:///
+smb://nas/share
+
+not a uri
+";
+        assert_eq!(
+            parse_gtk_bookmarks(contents),
+            [
+                expected("Projects", "/home/me/Projects"),
+                expected("Tunes and more", "/home/me/My Music"),
+                expected("Web server", "sftp://me@example.com:2222/srv/www"),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_unlabelled_bookmarks() {
+        assert_eq!(
+            bookmark("davs://me@example.com/My%20Files/", None),
+            Some(expected("My Files", "davs://me@example.com/My Files"))
+        );
+        assert_eq!(
+            bookmark("sftp://example.com/", None),
+            Some(expected("example.com", "sftp://example.com"))
+        );
+        assert_eq!(bookmark("file:///", None), Some(expected("/", "/")));
+    }
+
+    #[test]
+    fn parses_kde_places() {
+        let contents = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE xbel>
+<xbel xmlns:bookmark="http://www.freedesktop.org/standards/desktop-bookmarks">
+ <bookmark href="file:///home/me">
+  <title>Home</title>
+  <info><metadata owner="http://freedesktop.org"><isSystemItem>true</isSystemItem></metadata></info>
+ </bookmark>
+ <bookmark href="file:///home/me/Work">
+  <title>Work</title>
+  <info><metadata owner="http://freedesktop.org"><isSystemItem>false</isSystemItem></metadata></info>
+ </bookmark>
+ <bookmark href="file:///home/me/Old">
+  <title>Old</title>
+  <info><metadata owner="http://kde.org"><IsHidden>true</IsHidden></metadata></info>
+ </bookmark>
+ <bookmark href="remote:/">
+  <title>Network</title>
+ </bookmark>
+ <bookmark href="dav://example.com/files">
+ </bookmark>
+</xbel>
+"#;
+        assert_eq!(
+            parse_kde_places(contents),
+            [
+                expected("Work", "/home/me/Work"),
+                expected("files", "dav://example.com/files"),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_malformed_kde_places() {
+        assert!(parse_kde_places("").is_empty());
+        assert!(parse_kde_places("<xbel><bookmark href=\"file:///tmp\">").is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+use std::path::{Path, PathBuf};
+
+use fs::{ArchiveFormat, CompressionLevel};
+use gpui::{
+    div, px, rgb, rgba, AppContext, DismissEvent, EventEmitter, FocusHandle, FocusableView,
+    InteractiveElement, IntoElement, Model, ParentElement, Render, StatefulInteractiveElement,
+    Styled, View, ViewContext, VisualContext,
+};
+use ui::{TextInput, TextInputEvent};
+
+use crate::jobs::{JobKind, Jobs};
+
+/// Asks for the name, format and compression level of a new archive made
+/// from the selection, then packs it in the background.
+pub struct CompressDialog {
+    sources: Vec<PathBuf>,
+    folder: PathBuf,
+    jobs: Model<Jobs>,
+    name: View<TextInput>,
+    format: ArchiveFormat,
+    level: CompressionLevel,
+}
+
+impl EventEmitter<DismissEvent> for CompressDialog {}
+
+impl CompressDialog {
+    /// `folder` is where the archive goes.
+    pub fn new(
+        sources: Vec<PathBuf>,
+        folder: PathBuf,
+        jobs: Model<Jobs>,
+        cx: &mut ViewContext<Self>,
+    ) -> Self {
+        let name = cx.new_view(|cx| TextInput::new("Archive name", cx));
+        let default_name = default_name(&sources, &folder);
+        name.update(cx, |input, cx| input.set_text(default_name, cx));
+        cx.subscribe(
+            &name,
+            |this, _input, event: &TextInputEvent, cx| match event {
+                TextInputEvent::Confirmed(_) => this.confirm(cx),
+                TextInputEvent::Cancelled => cx.emit(DismissEvent),
+                TextInputEvent::Changed(_) => {}
+            },
+        )
+        .detach();
+
+        Self {
+            sources,
+            folder,
+            jobs,
+            name,
+            format: ArchiveFormat::Zip,
+            level: CompressionLevel::default(),
+        }
+    }
+
+    fn confirm(&mut self, cx: &mut ViewContext<Self>) {
+        let name = self.name.read(cx).text().trim().to_string();
+        if name.is_empty() || name.contains('/') {
+            return;
+        }
+
+        let file_name = format!("{}{}", name, self.format.extension());
+        let destination = fs::unique_destination(&self.folder, file_name.as_ref());
+        let kind = JobKind::Compress(self.format, self.level);
+        let sources = self.sources.clone();
+        self.jobs
+            .update(cx, |jobs, cx| jobs.start(kind, sources, destination, cx));
+        cx.emit(DismissEvent);
+    }
+}
+
+/// The single item's name, or else the folder's.
+fn default_name(sources: &[PathBuf], folder: &Path) -> String {
+    let path = match sources {
+        [source] => source.as_path(),
+        _ => folder,
+    };
+    ArchiveFormat::stem(path)
+        .map(str::to_string)
+        .or_else(|| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "Archive".into())
+}
+
+impl FocusableView for CompressDialog {
+    fn focus_handle(&self, cx: &AppContext) -> FocusHandle {
+        self.name.focus_handle(cx)
+    }
+}
+
+impl Render for CompressDialog {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let button = |id: &'static str, label: &'static str, selected: bool| {
+            div()
+                .id(id)
+                .px(px(12.))
+                .py(px(4.))
+                .rounded(px(6.))
+                .bg(if selected {
+                    rgba(0xffffff1a)
+                } else {
+                    rgba(0xffffff0d)
+                })
+                .hover(|style| style.bg(rgba(0xffffff1a)))
+                .child(label)
+        };
+        let row = |label: &'static str| {
+            div()
+                .flex()
+                .flex_row()
+                .items_center()
+                .gap(px(8.))
+                .child(div().w(px(80.)).text_color(rgb(0xa3a3a3)).child(label))
+        };
+
+        let formats = ArchiveFormat::CREATABLE.map(|format| {
+            button(format.name(), format.name(), format == self.format).on_click(cx.listener(
+                move |this, _event, cx| {
+                    this.format = format;
+                    cx.notify();
+                },
+            ))
+        });
+        let levels = CompressionLevel::ALL.map(|level| {
+            button(level.name(), level.name(), level == self.level).on_click(cx.listener(
+                move |this, _event, cx| {
+                    this.level = level;
+                    cx.notify();
+                },
+            ))
+        });
+
+        div()
+            .w(px(480.))
+            .p(px(16.))
+            .flex()
+            .flex_col()
+            .gap(px(12.))
+            .rounded(px(10.))
+            .bg(rgb(0x2b2a2e))
+            .border_1()
+            .border_color(rgba(0xffffff1a))
+            .text_color(rgb(0xf3f3f3))
+            .child(div().text_size(px(16.)).child("Compress"))
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .gap(px(8.))
+                    .child(div().flex_1().child(self.name.clone()))
+                    .child(
+                        div()
+                            .text_color(rgb(0xa3a3a3))
+                            .child(self.format.extension()),
+                    ),
+            )
+            .child(row("Format").children(formats))
+            .child(row("Level").children(levels))
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .justify_end()
+                    .gap(px(8.))
+                    .child(
+                        button("cancel", "Cancel", false)
+                            .on_click(cx.listener(|_this, _event, cx| cx.emit(DismissEvent))),
+                    )
+                    .child(
+                        button("compress", "Compress", false)
+                            .on_click(cx.listener(|this, _event, cx| this.confirm(cx))),
+                    ),
+            )
+    }
+}
@@ -0,0 +1,251 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use fs::RemoteLocation;
+use gpui::{
+    div, px, rgb, rgba, AppContext, DismissEvent, EventEmitter, FocusHandle, FocusableView,
+    InteractiveElement, IntoElement, Model, ParentElement, Render, StatefulInteractiveElement,
+    Styled, Task, View, ViewContext, VisualContext,
+};
+use ui::{Checkbox, Clickable, TextInput, TextInputEvent};
+
+use crate::{
+    connections::{Connections, SavedConnection},
+    providers::Providers,
+    FileExplorer,
+};
+
+/// Asks for a `sftp://user@host/path` or `davs://user@host/path` address
+/// and, for WebDAV, a password, logs in on the background executor and opens
+/// the folder there, optionally saving the server in the sidebar.
+pub struct ConnectDialog {
+    file_explorer: Model<FileExplorer>,
+    address: View<TextInput>,
+    password: View<TextInput>,
+    /// Set when opening a saved server whose password isn't known, so
+    /// only the password is asked for.
+    asking_password: bool,
+    save: bool,
+    error: Option<String>,
+    connecting: Option<Task<()>>,
+}
+
+impl EventEmitter<DismissEvent> for ConnectDialog {}
+
+impl ConnectDialog {
+    pub fn new(file_explorer: Model<FileExplorer>, cx: &mut ViewContext<Self>) -> Self {
+        let address = cx.new_view(|cx| TextInput::new("sftp://user@host/path", cx));
+        let password = cx.new_view(|cx| TextInput::new("Password (WebDAV only)", cx).masked());
+        for input in [&address, &password] {
+            cx.subscribe(
+                input,
+                |this, _input, event: &TextInputEvent, cx| match event {
+                    TextInputEvent::Confirmed(_) => this.confirm(cx),
+                    TextInputEvent::Cancelled => cx.emit(DismissEvent),
+                    TextInputEvent::Changed(_) => {
+                        this.error = None;
+                        cx.notify();
+                    }
+                },
+            )
+            .detach();
+        }
+
+        Self {
+            file_explorer,
+            address,
+            password,
+            asking_password: false,
+            save: true,
+            error: None,
+            connecting: None,
+        }
+    }
+
+    /// Asks for the password of the saved server at `location`, since
+    /// passwords aren't saved with it.
+    pub fn asking_password(
+        location: &Path,
+        file_explorer: Model<FileExplorer>,
+        cx: &mut ViewContext<Self>,
+    ) -> Self {
+        let mut this = Self::new(file_explorer, cx);
+        this.address.update(cx, |input, cx| {
+            input.set_text(location.to_string_lossy().into_owned(), cx)
+        });
+        this.asking_password = true;
+        this.save = false;
+        this
+    }
+
+    fn confirm(&mut self, cx: &mut ViewContext<Self>) {
+        if self.connecting.is_some() {
+            return;
+        }
+
+        // A bare `user@host` means SFTP.
+        let address = self.address.read(cx).text().trim().to_string();
+        let address = if fs::location_scheme(Path::new(&address)).is_some() {
+            address
+        } else {
+            format!("sftp://{}", address)
+        };
+        let location = PathBuf::from(&address);
+        let remote = match RemoteLocation::parse(&location) {
+            Ok(remote) => remote,
+            Err(e) => return self.show_error(e.to_string(), cx),
+        };
+        // The provider remembers a password given in the location.
+        let password = self.password.read(cx).text().to_string();
+        let location = if password.is_empty() {
+            location
+        } else {
+            let remote = RemoteLocation {
+                password: Some(password),
+                ..remote.clone()
+            };
+            remote.with_path(&remote.path)
+        };
+        let provider = match Providers::get(cx).provider(&location) {
+            Ok(provider) => provider,
+            Err(e) => return self.show_error(e.to_string(), cx),
+        };
+
+        // Without a path, SFTP opens the home folder, like `sftp user@host`.
+        let sftp = Providers::sftp(cx).filter(|_| {
+            remote.scheme == "sftp" && !address[remote.scheme.len() + 3..].contains('/')
+        });
+        let task = cx.background_executor().spawn(async move {
+            let folder = match sftp {
+                Some(sftp) => sftp.connect(&location)?,
+                None => location,
+            };
+            if !provider.stat(&folder)?.is_dir() {
+                return Err(anyhow!("{} is not a folder", folder.display()));
+            }
+            Ok(folder)
+        });
+        self.connecting = Some(cx.spawn(|this, mut cx| async move {
+            let result = task.await;
+            this.update(&mut cx, |this, cx| this.connected(result, cx))
+                .ok();
+        }));
+        cx.notify();
+    }
+
+    fn connected(&mut self, result: Result<PathBuf>, cx: &mut ViewContext<Self>) {
+        self.connecting = None;
+        let folder = match result {
+            Ok(folder) => folder,
+            Err(e) => return self.show_error(format!("Failed to connect: {}", e), cx),
+        };
+
+        // The provider remembers the password, so it needn't show in the
+        // address bar.
+        let folder = without_password(&folder).unwrap_or(folder);
+        if self.save {
+            let name = RemoteLocation::parse(&folder)
+                .map(|remote| remote.server())
+                .unwrap_or_default();
+            let connection = SavedConnection {
+                name,
+                location: folder.clone(),
+            };
+            cx.update_global::<Connections, _>(|connections, _cx| connections.add(connection));
+        }
+        self.file_explorer.update(cx, |file_explorer, cx| {
+            file_explorer.open_folder(folder, cx)
+        });
+        cx.emit(DismissEvent);
+    }
+
+    fn show_error(&mut self, error: String, cx: &mut ViewContext<Self>) {
+        self.error = Some(error);
+        cx.notify();
+    }
+}
+
+fn without_password(location: &Path) -> Option<PathBuf> {
+    let remote = RemoteLocation::parse(location).ok()?;
+    let remote = RemoteLocation {
+        password: None,
+        ..remote
+    };
+    Some(remote.with_path(&remote.path))
+}
+
+impl FocusableView for ConnectDialog {
+    fn focus_handle(&self, cx: &AppContext) -> FocusHandle {
+        if self.asking_password {
+            self.password.focus_handle(cx)
+        } else {
+            self.address.focus_handle(cx)
+        }
+    }
+}
+
+impl Render for ConnectDialog {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let button = |id: &'static str, label: &'static str| {
+            div()
+                .id(id)
+                .px(px(12.))
+                .py(px(4.))
+                .rounded(px(6.))
+                .bg(rgba(0xffffff0d))
+                .hover(|style| style.bg(rgba(0xffffff1a)))
+                .child(label)
+        };
+        let status = if self.connecting.is_some() {
+            Some(div().text_color(rgb(0xa3a3a3)).child("Connecting..."))
+        } else {
+            self.error
+                .clone()
+                .map(|error| div().text_color(rgb(0xf87171)).child(error))
+        };
+
+        div()
+            .w(px(480.))
+            .p(px(16.))
+            .flex()
+            .flex_col()
+            .gap(px(12.))
+            .rounded(px(10.))
+            .bg(rgb(0x2b2a2e))
+            .border_1()
+            .border_color(rgba(0xffffff1a))
+            .text_color(rgb(0xf3f3f3))
+            .child(div().text_size(px(16.)).child("Connect to Server"))
+            .children(self.asking_password.then(|| {
+                div()
+                    .text_color(rgb(0xa3a3a3))
+                    .child("Passwords aren't saved. Enter it again to connect.")
+            }))
+            .child(self.address.clone())
+            .child(self.password.clone())
+            .child(
+                Checkbox::new("save", Some(self.save))
+                    .label("Save in the sidebar")
+                    .on_click(cx.listener(|this, _event, cx| {
+                        this.save = !this.save;
+                        cx.notify();
+                    })),
+            )
+            .children(status)
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .justify_end()
+                    .gap(px(8.))
+                    .child(
+                        button("cancel", "Cancel")
+                            .on_click(cx.listener(|_this, _event, cx| cx.emit(DismissEvent))),
+                    )
+                    .child(
+                        button("connect", "Connect")
+                            .on_click(cx.listener(|this, _event, cx| this.confirm(cx))),
+                    ),
+            )
+    }
+}
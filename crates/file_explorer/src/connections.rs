@@ -0,0 +1,72 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use gpui::Global;
+use serde::{Deserialize, Serialize};
+
+/// A server saved from the Connect to Server dialog, listed in the sidebar.
+/// Passwords aren't saved, so opening a server that needs one asks for it
+/// again after a restart.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SavedConnection {
+    pub name: String,
+    pub location: PathBuf,
+}
+
+pub struct Connections {
+    db_path: PathBuf,
+    connections: Vec<SavedConnection>,
+}
+
+impl Global for Connections {}
+
+impl Connections {
+    pub fn load(db_path: PathBuf) -> Self {
+        let connections = fs::read_to_string(&db_path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Self {
+            db_path,
+            connections,
+        }
+    }
+
+    pub fn all(&self) -> &[SavedConnection] {
+        &self.connections
+    }
+
+    /// Saves `connection`, replacing one saved before for the same location.
+    pub fn add(&mut self, connection: SavedConnection) {
+        self.connections
+            .retain(|saved| saved.location != connection.location);
+        self.connections.push(connection);
+        self.save();
+    }
+
+    pub fn remove(&mut self, location: &Path) {
+        self.connections.retain(|saved| saved.location != location);
+        self.save();
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_string_pretty(&self.connections)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| {
+                if let Some(parent) = self.db_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                Ok(fs::write(&self.db_path, contents)?)
+            });
+        if let Err(e) = result {
+            eprintln!(
+                "Failed to save connections: {}. Error: {}",
+                self.db_path.display(),
+                e
+            );
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+
+use gpui::{
+    div, px, rgb, rgba, AppContext, DismissEvent, EventEmitter, FocusHandle, FocusableView,
+    InteractiveElement, IntoElement, KeyDownEvent, ParentElement, Render,
+    StatefulInteractiveElement, Styled, ViewContext,
+};
+
+/// Emitted when the deletion is confirmed, before the dialog is dismissed.
+pub struct DeleteConfirmed(pub Vec<PathBuf>);
+
+/// Asks before deleting entries that can't go to the trash, since they are
+/// gone for good.
+pub struct DeleteDialog {
+    paths: Vec<PathBuf>,
+    /// How many of `paths` are deleted for good rather than trashed.
+    permanent: usize,
+    focus_handle: FocusHandle,
+}
+
+impl EventEmitter<DismissEvent> for DeleteDialog {}
+impl EventEmitter<DeleteConfirmed> for DeleteDialog {}
+
+impl DeleteDialog {
+    pub fn new(paths: Vec<PathBuf>, permanent: usize, cx: &mut ViewContext<Self>) -> Self {
+        Self {
+            paths,
+            permanent,
+            focus_handle: cx.focus_handle(),
+        }
+    }
+
+    fn confirm(&mut self, cx: &mut ViewContext<Self>) {
+        cx.emit(DeleteConfirmed(self.paths.clone()));
+        cx.emit(DismissEvent);
+    }
+
+    fn handle_key_down(&mut self, event: &KeyDownEvent, cx: &mut ViewContext<Self>) {
+        match event.keystroke.key.as_str() {
+            "enter" => self.confirm(cx),
+            "escape" => cx.emit(DismissEvent),
+            _ => return,
+        }
+        cx.stop_propagation();
+    }
+}
+
+impl FocusableView for DeleteDialog {
+    fn focus_handle(&self, _cx: &AppContext) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for DeleteDialog {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let button = |id: &'static str, label: &'static str| {
+            div()
+                .id(id)
+                .px(px(12.))
+                .py(px(4.))
+                .rounded(px(6.))
+                .bg(rgba(0xffffff0d))
+                .hover(|style| style.bg(rgba(0xffffff1a)))
+                .child(label)
+        };
+        let title = match self.paths.as_slice() {
+            [path] => format!(
+                "Delete {} permanently?",
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.to_string_lossy().into_owned())
+            ),
+            paths if self.permanent == paths.len() => {
+                format!("Delete {} items permanently?", paths.len())
+            }
+            paths => format!(
+                "Delete {} items? {} of them will be deleted permanently.",
+                paths.len(),
+                self.permanent
+            ),
+        };
+
+        div()
+            .track_focus(&self.focus_handle)
+            .on_key_down(cx.listener(Self::handle_key_down))
+            .w(px(480.))
+            .p(px(16.))
+            .flex()
+            .flex_col()
+            .gap(px(12.))
+            .rounded(px(10.))
+            .bg(rgb(0x2b2a2e))
+            .border_1()
+            .border_color(rgba(0xffffff1a))
+            .text_color(rgb(0xf3f3f3))
+            .child(div().text_size(px(16.)).child(title))
+            .child(
+                div()
+                    .text_color(rgb(0xa3a3a3))
+                    .child("Remote locations have no trash, so this can't be undone."),
+            )
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .justify_end()
+                    .gap(px(8.))
+                    .child(
+                        button("cancel", "Cancel")
+                            .on_click(cx.listener(|_this, _event, cx| cx.emit(DismissEvent))),
+                    )
+                    .child(
+                        button("delete", "Delete")
+                            .text_color(rgb(0xf87171))
+                            .on_click(cx.listener(|this, _event, cx| this.confirm(cx))),
+                    ),
+            )
+    }
+}
@@ -0,0 +1,556 @@
+use std::{
+    path::PathBuf,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use fs::{Cancelled, FolderSizeOptions, ScanProgress, UsageKind, UsageNode};
+use gpui::{
+    div, prelude::FluentBuilder, px, rgb, rgba, AppContext, DismissEvent, EventEmitter,
+    FocusHandle, FocusableView, InteractiveElement, IntoElement, KeyDownEvent, ParentElement,
+    Render, StatefulInteractiveElement, Styled, Task, ViewContext,
+};
+use search::format_size;
+
+use crate::settings::Settings;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+const TREEMAP_WIDTH: f32 = 520.;
+const TREEMAP_HEIGHT: f32 = 420.;
+/// Tiles smaller than this in either direction get no label.
+const MIN_LABEL_SIZE: f32 = 40.;
+const FOLDER_COLORS: [u32; 6] = [0x3b82f6, 0x8b5cf6, 0x10b981, 0xf59e0b, 0xef4444, 0x06b6d4];
+
+pub enum DiskUsageEvent {
+    /// Show `path` in the active tab.
+    Reveal(PathBuf),
+    /// `path` went to the trash, so folders showing it are out of date.
+    Trashed(PathBuf),
+}
+
+/// Where the space below a folder goes, as a treemap of the folder being
+/// looked at and a list of its largest entries. Folders can be opened to
+/// look further down.
+pub struct DiskUsageView {
+    root_path: PathBuf,
+    root: Option<UsageNode>,
+    progress: Arc<ScanProgress>,
+    error: Option<String>,
+    /// The names leading from the root to the folder being looked at.
+    current: Vec<String>,
+    task: Option<Task<()>>,
+    focus_handle: FocusHandle,
+}
+
+impl EventEmitter<DismissEvent> for DiskUsageView {}
+impl EventEmitter<DiskUsageEvent> for DiskUsageView {}
+
+impl DiskUsageView {
+    pub fn new(root_path: PathBuf, cx: &mut ViewContext<Self>) -> Self {
+        let mut this = Self {
+            root_path,
+            root: None,
+            progress: Arc::default(),
+            error: None,
+            current: Vec::new(),
+            task: None,
+            focus_handle: cx.focus_handle(),
+        };
+        this.scan(cx);
+        this
+    }
+
+    fn scan(&mut self, cx: &mut ViewContext<Self>) {
+        let options = FolderSizeOptions {
+            cross_mounts: Settings::get(cx).folder_sizes.cross_mounts,
+        };
+        let path = self.root_path.clone();
+        let progress = self.progress.clone();
+        let work = cx
+            .background_executor()
+            .spawn(async move { fs::scan_disk_usage(&path, &options, &progress) });
+
+        self.task = Some(cx.spawn(|this, mut cx| async move {
+            let result = work.await;
+            this.update(&mut cx, |this, cx| {
+                match result {
+                    Ok(root) => this.root = Some(root),
+                    Err(error) if error.is::<Cancelled>() => {}
+                    Err(error) => {
+                        eprintln!(
+                            "Failed to scan disk usage: {}. Error: {}",
+                            this.root_path.display(),
+                            error
+                        );
+                        this.error = Some(error.to_string());
+                    }
+                }
+                this.task = None;
+                cx.notify();
+            })
+            .ok();
+        }));
+
+        cx.spawn(|this, mut cx| async move {
+            loop {
+                cx.background_executor().timer(PROGRESS_INTERVAL).await;
+                let scanning = this.update(&mut cx, |this, cx| {
+                    cx.notify();
+                    this.task.is_some()
+                });
+                if !scanning.unwrap_or(false) {
+                    break;
+                }
+            }
+        })
+        .detach();
+    }
+
+    fn path_of(&self, name: &str) -> PathBuf {
+        let mut path = self.root_path.clone();
+        path.extend(&self.current);
+        path.push(name);
+        path
+    }
+
+    /// Looks into the folder `name` of the folder being looked at.
+    fn open(&mut self, name: String, cx: &mut ViewContext<Self>) {
+        self.current.push(name);
+        cx.notify();
+    }
+
+    fn go_up_to(&mut self, depth: usize, cx: &mut ViewContext<Self>) {
+        self.current.truncate(depth);
+        cx.notify();
+    }
+
+    fn reveal(&mut self, name: &str, cx: &mut ViewContext<Self>) {
+        cx.emit(DiskUsageEvent::Reveal(self.path_of(name)));
+        cx.emit(DismissEvent);
+    }
+
+    fn trash(&mut self, name: String, cx: &mut ViewContext<Self>) {
+        let path = self.path_of(&name);
+        let mut names = self.current.clone();
+        names.push(name);
+
+        let work = cx.background_executor().spawn({
+            let path = path.clone();
+            async move { fs::trash(&path) }
+        });
+        cx.spawn(|this, mut cx| async move {
+            let result = work.await;
+            this.update(&mut cx, |this, cx| {
+                match result {
+                    Ok(_) => {
+                        if let Some(root) = this.root.as_mut() {
+                            root.remove(&names);
+                        }
+                        this.error = None;
+                        cx.emit(DiskUsageEvent::Trashed(path));
+                    }
+                    Err(error) => {
+                        eprintln!("Failed to trash: {}. Error: {}", path.display(), error);
+                        this.error = Some(format!("{}: {}", path.display(), error));
+                    }
+                }
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn handle_key_down(&mut self, event: &KeyDownEvent, cx: &mut ViewContext<Self>) {
+        match event.keystroke.key.as_str() {
+            "escape" => cx.emit(DismissEvent),
+            "backspace" if !self.current.is_empty() => self.go_up_to(self.current.len() - 1, cx),
+            _ => return,
+        }
+        cx.stop_propagation();
+    }
+
+    fn render_breadcrumbs(&self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let root_name = self.root_path.to_string_lossy().into_owned();
+        let crumbs = std::iter::once(root_name)
+            .chain(self.current.iter().cloned())
+            .enumerate()
+            .map(|(depth, name)| {
+                div()
+                    .flex()
+                    .flex_row()
+                    .gap(px(2.))
+                    .when(depth > 0, |this| {
+                        this.child(div().text_color(rgb(0xa3a3a3)).child("›"))
+                    })
+                    .child(
+                        div()
+                            .id(("crumb", depth))
+                            .px(px(4.))
+                            .rounded(px(4.))
+                            .hover(|style| style.bg(rgba(0xffffff0d)))
+                            .on_click(cx.listener(move |this, _event, cx| this.go_up_to(depth, cx)))
+                            .child(name),
+                    )
+            })
+            .collect::<Vec<_>>();
+
+        div()
+            .flex()
+            .flex_row()
+            .flex_wrap()
+            .items_center()
+            .gap(px(2.))
+            .children(crumbs)
+    }
+
+    fn render_treemap(&self, node: &UsageNode, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let sizes = node
+            .children
+            .iter()
+            .map(|child| child.size)
+            .collect::<Vec<_>>();
+        let tiles = squarify(&sizes, TREEMAP_WIDTH, TREEMAP_HEIGHT)
+            .into_iter()
+            .map(|tile| {
+                let child = &node.children[tile.ix];
+                let name = child.name.clone();
+                let color = match child.kind {
+                    UsageKind::Folder => FOLDER_COLORS[tile.ix % FOLDER_COLORS.len()],
+                    UsageKind::File => 0x52525b,
+                    UsageKind::Rest => 0x3f3f46,
+                };
+                let is_folder = child.kind == UsageKind::Folder;
+                let labelled = tile.width >= MIN_LABEL_SIZE && tile.height >= MIN_LABEL_SIZE / 2.;
+
+                div()
+                    .id(("tile", tile.ix))
+                    .absolute()
+                    .left(px(tile.x))
+                    .top(px(tile.y))
+                    .w(px(tile.width))
+                    .h(px(tile.height))
+                    .overflow_hidden()
+                    .p(px(2.))
+                    .border_1()
+                    .border_color(rgb(0x232225))
+                    .bg(rgb(color))
+                    .hover(|style| style.border_color(rgba(0xffffffb3)))
+                    .text_size(px(11.))
+                    .when(is_folder, |this| {
+                        this.on_click(
+                            cx.listener(move |this, _event, cx| this.open(name.clone(), cx)),
+                        )
+                    })
+                    .when(labelled, |this| {
+                        this.child(div().whitespace_nowrap().child(child.name.clone()))
+                            .child(
+                                div()
+                                    .whitespace_nowrap()
+                                    .text_color(rgba(0xffffffb3))
+                                    .child(format_size(child.size)),
+                            )
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        div()
+            .relative()
+            .flex_none()
+            .w(px(TREEMAP_WIDTH))
+            .h(px(TREEMAP_HEIGHT))
+            .rounded(px(4.))
+            .bg(rgb(0x19191a))
+            .overflow_hidden()
+            .children(tiles)
+    }
+
+    fn render_list(&self, node: &UsageNode, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let total = node.size.max(1);
+        let button = |id: (&'static str, usize), label: &'static str| {
+            div()
+                .id(id)
+                .px(px(6.))
+                .rounded(px(4.))
+                .text_size(px(12.))
+                .bg(rgba(0xffffff0d))
+                .hover(|style| style.bg(rgba(0xffffff1a)))
+                .child(label)
+        };
+
+        let rows = node
+            .children
+            .iter()
+            .enumerate()
+            .map(|(ix, child)| {
+                let fraction = child.size as f32 / total as f32;
+                let is_folder = child.kind == UsageKind::Folder;
+                let is_entry = child.kind != UsageKind::Rest;
+                let (open, reveal, trash) =
+                    (child.name.clone(), child.name.clone(), child.name.clone());
+
+                div()
+                    .id(("usage", ix))
+                    .flex()
+                    .flex_col()
+                    .gap(px(2.))
+                    .px(px(6.))
+                    .py(px(4.))
+                    .rounded(px(4.))
+                    .hover(|style| style.bg(rgba(0xffffff0d)))
+                    .when(is_folder, |this| {
+                        this.on_click(
+                            cx.listener(move |this, _event, cx| this.open(open.clone(), cx)),
+                        )
+                    })
+                    .child(
+                        div()
+                            .flex()
+                            .flex_row()
+                            .items_center()
+                            .gap(px(6.))
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .overflow_hidden()
+                                    .whitespace_nowrap()
+                                    .child(child.name.clone()),
+                            )
+                            .child(
+                                div()
+                                    .text_color(rgb(0xa3a3a3))
+                                    .child(format_size(child.size)),
+                            )
+                            .when(is_entry, |this| {
+                                this.child(button(("reveal", ix), "Show").on_click(cx.listener(
+                                    move |this, _event, cx| {
+                                        cx.stop_propagation();
+                                        this.reveal(&reveal, cx)
+                                    },
+                                )))
+                                .child(
+                                    button(("trash", ix), "Trash").on_click(cx.listener(
+                                        move |this, _event, cx| {
+                                            cx.stop_propagation();
+                                            this.trash(trash.clone(), cx)
+                                        },
+                                    )),
+                                )
+                            }),
+                    )
+                    .child(
+                        div()
+                            .h(px(3.))
+                            .w_full()
+                            .rounded(px(2.))
+                            .bg(rgba(0xffffff0d))
+                            .child(
+                                div()
+                                    .h_full()
+                                    .w(gpui::relative(fraction))
+                                    .rounded(px(2.))
+                                    .bg(rgb(0x3b82f6)),
+                            ),
+                    )
+            })
+            .collect::<Vec<_>>();
+
+        div()
+            .id("usage-list")
+            .flex_1()
+            .h(px(TREEMAP_HEIGHT))
+            .overflow_y_scroll()
+            .flex()
+            .flex_col()
+            .children(rows)
+    }
+}
+
+impl Drop for DiskUsageView {
+    fn drop(&mut self) {
+        self.progress.cancel();
+    }
+}
+
+impl FocusableView for DiskUsageView {
+    fn focus_handle(&self, _cx: &AppContext) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for DiskUsageView {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        // Taken out while rendering rather than cloned, as it can hold a
+        // whole drive.
+        let root = self.root.take();
+        let body = match root
+            .as_ref()
+            .and_then(|root| root.descendant(&self.current))
+        {
+            Some(node) => div()
+                .flex()
+                .flex_col()
+                .gap(px(8.))
+                .child(div().text_color(rgb(0xa3a3a3)).child(format!(
+                    "{} in {} files",
+                    format_size(node.size),
+                    node.files
+                )))
+                .child(
+                    div()
+                        .flex()
+                        .flex_row()
+                        .gap(px(12.))
+                        .child(self.render_treemap(node, cx))
+                        .child(self.render_list(node, cx)),
+                )
+                .into_any_element(),
+            None if self.task.is_some() => div()
+                .flex()
+                .flex_row()
+                .items_center()
+                .gap(px(12.))
+                .child(div().text_color(rgb(0xa3a3a3)).child(format!(
+                    "Scanning… {} files, {}",
+                    self.progress.files.load(Ordering::Relaxed),
+                    format_size(self.progress.bytes.load(Ordering::Relaxed))
+                )))
+                .child(
+                    div()
+                        .id("cancel-scan")
+                        .px(px(8.))
+                        .rounded(px(4.))
+                        .bg(rgba(0xffffff0d))
+                        .hover(|style| style.bg(rgba(0xffffff1a)))
+                        .on_click(cx.listener(|_this, _event, cx| cx.emit(DismissEvent)))
+                        .child("Cancel"),
+                )
+                .into_any_element(),
+            None => div().into_any_element(),
+        };
+        self.root = root;
+
+        div()
+            .id("disk-usage")
+            .track_focus(&self.focus_handle)
+            .on_key_down(cx.listener(Self::handle_key_down))
+            .w(px(900.))
+            .flex()
+            .flex_col()
+            .gap(px(12.))
+            .p(px(16.))
+            .rounded(px(10.))
+            .bg(rgb(0x2b2a2e))
+            .border_1()
+            .border_color(rgba(0xffffff1a))
+            .text_color(rgb(0xf3f3f3))
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .gap(px(12.))
+                    .child(div().text_size(px(16.)).child("Disk usage"))
+                    .child(div().flex_1().child(self.render_breadcrumbs(cx))),
+            )
+            .child(body)
+            .children(
+                self.error
+                    .clone()
+                    .map(|error| div().text_color(rgb(0xf87171)).child(error)),
+            )
+    }
+}
+
+/// A rectangle of the treemap, in pixels from its top left corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Tile {
+    ix: usize,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+/// Lays out `sizes`, largest first, as tiles filling a `width` by `height`
+/// rectangle with areas in proportion to the sizes and sides kept as close to
+/// square as possible (Bruls, Huizing and van Wijk's squarified treemap).
+/// Empty entries get no tile.
+fn squarify(sizes: &[u64], width: f32, height: f32) -> Vec<Tile> {
+    let total = sizes.iter().sum::<u64>();
+    if total == 0 {
+        return Vec::new();
+    }
+    let scale = width * height / total as f32;
+    let areas = sizes
+        .iter()
+        .enumerate()
+        .filter(|(_, size)| **size > 0)
+        .map(|(ix, size)| (ix, *size as f32 * scale))
+        .collect::<Vec<_>>();
+
+    // The worst ratio of long to short side among tiles of `row` when laid
+    // along a side of length `side`.
+    let worst = |row: &[(usize, f32)], side: f32| {
+        let sum = row.iter().map(|(_, area)| area).sum::<f32>();
+        row.iter()
+            .map(|(_, area)| {
+                let ratio = side * side * area / (sum * sum);
+                ratio.max(1. / ratio)
+            })
+            .fold(0., f32::max)
+    };
+
+    let mut tiles = Vec::new();
+    let (mut x, mut y, mut width, mut height) = (0., 0., width, height);
+    let mut start = 0;
+    while start < areas.len() {
+        let side = width.min(height);
+        let mut end = start + 1;
+        while end < areas.len()
+            && worst(&areas[start..=end], side) <= worst(&areas[start..end], side)
+        {
+            end += 1;
+        }
+
+        let row = &areas[start..end];
+        let row_area = row.iter().map(|(_, area)| area).sum::<f32>();
+        if width >= height {
+            // A column down the left of what's left.
+            let column_width = row_area / height;
+            let mut tile_y = y;
+            for &(ix, area) in row {
+                let tile_height = area / column_width;
+                tiles.push(Tile {
+                    ix,
+                    x,
+                    y: tile_y,
+                    width: column_width,
+                    height: tile_height,
+                });
+                tile_y += tile_height;
+            }
+            x += column_width;
+            width -= column_width;
+        } else {
+            // A row along the top of what's left.
+            let row_height = row_area / width;
+            let mut tile_x = x;
+            for &(ix, area) in row {
+                let tile_width = area / row_height;
+                tiles.push(Tile {
+                    ix,
+                    x: tile_x,
+                    y,
+                    width: tile_width,
+                    height: row_height,
+                });
+                tile_x += tile_width;
+            }
+            y += row_height;
+            height -= row_height;
+        }
+        start = end;
+    }
+    tiles
+}
@@ -0,0 +1,145 @@
+use std::path::PathBuf;
+
+use gpui::{
+    div, px, rgb, rgba, AppContext, DismissEvent, EventEmitter, FocusHandle, FocusableView,
+    InteractiveElement, IntoElement, Model, ParentElement, Render, StatefulInteractiveElement,
+    Styled, View, ViewContext, VisualContext,
+};
+use ui::{TextInput, TextInputEvent};
+use util::paths::{expand_tilde, PathExt};
+
+use crate::{
+    jobs::{JobKind, Jobs},
+    providers::Providers,
+};
+
+/// Asks which folder to extract archives into.
+pub struct ExtractDialog {
+    archives: Vec<PathBuf>,
+    jobs: Model<Jobs>,
+    folder: View<TextInput>,
+    error: Option<String>,
+}
+
+impl EventEmitter<DismissEvent> for ExtractDialog {}
+
+impl ExtractDialog {
+    /// Starts out with `folder` filled in.
+    pub fn new(
+        archives: Vec<PathBuf>,
+        folder: PathBuf,
+        jobs: Model<Jobs>,
+        cx: &mut ViewContext<Self>,
+    ) -> Self {
+        let input = cx.new_view(|cx| TextInput::new("Folder", cx));
+        input.update(cx, |input, cx| {
+            input.set_text(folder.compact().to_string_lossy().into_owned(), cx)
+        });
+        cx.subscribe(
+            &input,
+            |this, _input, event: &TextInputEvent, cx| match event {
+                TextInputEvent::Confirmed(_) => this.confirm(cx),
+                TextInputEvent::Cancelled => cx.emit(DismissEvent),
+                TextInputEvent::Changed(_) => {
+                    this.error = None;
+                    cx.notify();
+                }
+            },
+        )
+        .detach();
+
+        Self {
+            archives,
+            jobs,
+            folder: input,
+            error: None,
+        }
+    }
+
+    fn confirm(&mut self, cx: &mut ViewContext<Self>) {
+        let folder = expand_tilde(self.folder.read(cx).text().trim());
+        let error = if !Providers::get(cx).is_local(&folder) {
+            Some(format!("{} is not a folder on disk", folder.display()))
+        } else if !Providers::stat(&folder, cx).is_some_and(|stat| stat.is_dir()) {
+            Some(format!("{} is not a folder", folder.display()))
+        } else {
+            None
+        };
+        if error.is_some() {
+            self.error = error;
+            cx.notify();
+            return;
+        }
+
+        let archives = self.archives.clone();
+        self.jobs.update(cx, |jobs, cx| {
+            jobs.start(JobKind::Extract, archives, folder, cx)
+        });
+        cx.emit(DismissEvent);
+    }
+}
+
+impl FocusableView for ExtractDialog {
+    fn focus_handle(&self, cx: &AppContext) -> FocusHandle {
+        self.folder.focus_handle(cx)
+    }
+}
+
+impl Render for ExtractDialog {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let button = |id: &'static str, label: &'static str| {
+            div()
+                .id(id)
+                .px(px(12.))
+                .py(px(4.))
+                .rounded(px(6.))
+                .bg(rgba(0xffffff0d))
+                .hover(|style| style.bg(rgba(0xffffff1a)))
+                .child(label)
+        };
+        let title = match self.archives.as_slice() {
+            [archive] => format!(
+                "Extract {} to",
+                archive
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            ),
+            archives => format!("Extract {} archives to", archives.len()),
+        };
+
+        div()
+            .w(px(480.))
+            .p(px(16.))
+            .flex()
+            .flex_col()
+            .gap(px(12.))
+            .rounded(px(10.))
+            .bg(rgb(0x2b2a2e))
+            .border_1()
+            .border_color(rgba(0xffffff1a))
+            .text_color(rgb(0xf3f3f3))
+            .child(div().text_size(px(16.)).child(title))
+            .child(self.folder.clone())
+            .children(
+                self.error
+                    .clone()
+                    .map(|error| div().text_color(rgb(0xf87171)).child(error)),
+            )
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .justify_end()
+                    .gap(px(8.))
+                    .child(
+                        button("cancel", "Cancel")
+                            .on_click(cx.listener(|_this, _event, cx| cx.emit(DismissEvent))),
+                    )
+                    .child(
+                        button("extract", "Extract")
+                            .on_click(cx.listener(|this, _event, cx| this.confirm(cx))),
+                    ),
+            )
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+use fs::{FolderSize, FolderSizeCache, FolderSizeOptions, FsWatcher};
+use gpui::{AppContext, Global};
+
+use crate::settings::Settings;
+
+/// How often changes reported by the watcher are shown.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+/// Once this many folders are watched, everything is forgotten and measured
+/// again as it comes back on screen.
+const MAX_WATCHED: usize = 256;
+
+/// The sizes of the folders on screen, added up on background threads and
+/// kept until something inside them changes.
+pub struct FolderSizes {
+    enabled: bool,
+    cache: FolderSizeCache,
+    pending: Arc<Mutex<HashSet<PathBuf>>>,
+    watcher: Arc<Mutex<Option<FsWatcher>>>,
+    watched: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+impl Global for FolderSizes {}
+
+pub fn init(cx: &mut AppContext) {
+    let settings = Settings::get(cx).folder_sizes.clone();
+    let cache = FolderSizeCache::new(FolderSizeOptions {
+        cross_mounts: settings.cross_mounts,
+    });
+    let changed = Arc::new(AtomicBool::new(false));
+
+    let watcher = FsWatcher::new(&[], true, {
+        let cache = cache.clone();
+        let changed = changed.clone();
+        move |events| {
+            if events.is_empty() {
+                cache.clear();
+            }
+            for event in events {
+                cache.invalidate(&event.path);
+            }
+            changed.store(true, Ordering::Relaxed);
+        }
+    })
+    .map_err(|e| eprintln!("Failed to watch folder sizes. Error: {}", e))
+    .ok();
+
+    cx.set_global(FolderSizes {
+        enabled: settings.enabled,
+        cache,
+        pending: Default::default(),
+        watcher: Arc::new(Mutex::new(watcher)),
+        watched: Default::default(),
+    });
+
+    // Changes deep inside a big folder come in bursts, so redraw for them
+    // now and then rather than for each one.
+    cx.spawn(|mut cx| async move {
+        loop {
+            cx.background_executor().timer(REFRESH_INTERVAL).await;
+            if changed.swap(false, Ordering::Relaxed) && cx.refresh().is_err() {
+                break;
+            }
+        }
+    })
+    .detach();
+}
+
+impl FolderSizes {
+    /// Measures folders the same way the details view does.
+    pub fn cache(cx: &AppContext) -> FolderSizeCache {
+        cx.try_global::<Self>()
+            .map(|this| this.cache.clone())
+            .unwrap_or_default()
+    }
+
+    /// The size of `path` if it is known. Otherwise starts adding it up in the
+    /// background and redraws the windows once it's done. `modified` comes
+    /// from the folder's listing.
+    pub fn get(
+        path: &Path,
+        modified: Option<SystemTime>,
+        cx: &mut AppContext,
+    ) -> Option<FolderSize> {
+        let this = cx.try_global::<Self>().filter(|this| this.enabled)?;
+        if let Some(size) = modified.and_then(|modified| this.cache.cached(path, modified)) {
+            return Some(size);
+        }
+        if !this.pending.lock().unwrap().insert(path.to_path_buf()) {
+            return None;
+        }
+
+        let cache = this.cache.clone();
+        let pending = this.pending.clone();
+        let watcher = this.watcher.clone();
+        let watched = this.watched.clone();
+        let path = path.to_path_buf();
+        let work = cx.background_executor().spawn({
+            let path = path.clone();
+            async move {
+                // Watch first so nothing that changes during the walk is
+                // missed.
+                watch(&path, &cache, &watcher, &watched);
+                cache.size(&path)
+            }
+        });
+        cx.spawn(|mut cx| async move {
+            work.await;
+            pending.lock().unwrap().remove(&path);
+            cx.refresh().ok();
+        })
+        .detach();
+
+        None
+    }
+}
+
+fn watch(
+    path: &Path,
+    cache: &FolderSizeCache,
+    watcher: &Mutex<Option<FsWatcher>>,
+    watched: &Mutex<HashSet<PathBuf>>,
+) {
+    let mut watcher = watcher.lock().unwrap();
+    let Some(watcher) = watcher.as_mut() else {
+        return;
+    };
+    let mut watched = watched.lock().unwrap();
+    if watched.contains(path) {
+        return;
+    }
+
+    if watched.len() >= MAX_WATCHED {
+        for folder in watched.drain() {
+            watcher.unwatch(&folder).ok();
+        }
+        cache.clear();
+    }
+    match watcher.watch(path, true) {
+        Ok(()) => {
+            watched.insert(path.to_path_buf());
+        }
+        Err(e) => eprintln!("Failed to watch: {}. Error: {}", path.display(), e),
+    }
+}
@@ -0,0 +1,431 @@
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use ::fs::{FileSystems, FsEventKind, FsWatcher};
+use file_icons::FileIcons;
+use gpui::{
+    div, prelude::FluentBuilder, px, rgb, rgba, svg, white, AppContext, EventEmitter, FocusHandle,
+    FocusableView, InteractiveElement, IntoElement, KeyDownEvent, ParentElement, Render,
+    StatefulInteractiveElement, Styled, ViewContext,
+};
+
+use crate::{paths::HOME, providers::Providers};
+
+const INDENT: f32 = 12.;
+/// How often folders that changed on disk are read again.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+pub enum FolderTreeEvent {
+    OpenFolder(PathBuf),
+}
+
+/// An expandable tree of folders below a few roots. Children are read the
+/// first time a folder is expanded, and again when folders are created,
+/// renamed or deleted in it.
+pub struct FolderTree {
+    roots: Vec<PathBuf>,
+    children: HashMap<PathBuf, Vec<PathBuf>>,
+    expanded: HashSet<PathBuf>,
+    selected: Option<PathBuf>,
+    focus_handle: FocusHandle,
+    file_systems: FileSystems,
+    /// Watches the folders whose children were read.
+    watcher: Option<FsWatcher>,
+}
+
+impl EventEmitter<FolderTreeEvent> for FolderTree {}
+
+impl FolderTree {
+    pub fn new(roots: Vec<PathBuf>, cx: &mut ViewContext<Self>) -> Self {
+        // Folders whose children changed since they were read, and whether
+        // the watcher lost track so all of them should be read again.
+        let changed = Arc::new(Mutex::new(HashSet::new()));
+        let rescan = Arc::new(AtomicBool::new(false));
+        let watcher = FsWatcher::new(&[], false, {
+            let changed = changed.clone();
+            let rescan = rescan.clone();
+            move |events| {
+                if events.is_empty() {
+                    rescan.store(true, Ordering::Relaxed);
+                }
+                let mut changed = changed.lock().unwrap();
+                for event in events {
+                    if event.kind != FsEventKind::Modified {
+                        changed.extend(event.path.parent().map(Path::to_path_buf));
+                    }
+                }
+            }
+        })
+        .map_err(|e| eprintln!("Failed to watch the folder tree. Error: {}", e))
+        .ok();
+
+        cx.spawn(|this, mut cx| async move {
+            loop {
+                cx.background_executor().timer(REFRESH_INTERVAL).await;
+                let mut folders = mem::take(&mut *changed.lock().unwrap());
+                let rescan = rescan.swap(false, Ordering::Relaxed);
+                if folders.is_empty() && !rescan {
+                    continue;
+                }
+                let updated = this.update(&mut cx, |this, cx| {
+                    if rescan {
+                        folders.extend(this.children.keys().cloned());
+                    }
+                    this.refresh(folders, cx)
+                });
+                if updated.is_err() {
+                    break;
+                }
+            }
+        })
+        .detach();
+
+        Self {
+            roots,
+            children: HashMap::new(),
+            expanded: HashSet::new(),
+            selected: None,
+            focus_handle: cx.focus_handle(),
+            file_systems: Providers::get(cx),
+            watcher,
+        }
+    }
+
+    /// Swaps the roots for `roots`, forgetting what was read below the ones
+    /// that are gone.
+    pub fn set_roots(&mut self, roots: Vec<PathBuf>, cx: &mut ViewContext<Self>) {
+        let kept = |path: &PathBuf| roots.iter().any(|root| path.starts_with(root));
+        self.forget(|folder| !kept(folder));
+        if !self.selected.as_ref().is_some_and(kept) {
+            self.selected = None;
+        }
+        self.roots = roots;
+        cx.notify();
+    }
+
+    /// Expands the ancestors of `path` so it is visible and selects it.
+    pub fn reveal(&mut self, path: &Path, cx: &mut ViewContext<Self>) {
+        if self.selected.as_deref() == Some(path) {
+            return;
+        }
+
+        // Prefer the innermost root, so folders in the home folder reveal
+        // below it rather than below `/`.
+        let Some(root) = self
+            .roots
+            .iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.components().count())
+            .cloned()
+        else {
+            self.selected = None;
+            cx.notify();
+            return;
+        };
+
+        let relative = path.strip_prefix(&root).unwrap_or(Path::new(""));
+        let mut ancestor = root;
+        for component in relative.components() {
+            self.expand(ancestor.clone());
+            ancestor.push(component);
+        }
+
+        self.selected = Some(path.to_path_buf());
+        cx.notify();
+    }
+
+    fn expand(&mut self, folder: PathBuf) {
+        if !self.children.contains_key(&folder) {
+            let children = self.read_children(&folder);
+            if let Some(watcher) = &mut self.watcher {
+                watcher.watch(&folder, false).ok();
+            }
+            self.children.insert(folder.clone(), children);
+        }
+        self.expanded.insert(folder);
+    }
+
+    /// Reads `folders` again, dropping what was read below children that
+    /// are gone.
+    fn refresh(&mut self, folders: HashSet<PathBuf>, cx: &mut ViewContext<Self>) {
+        for folder in folders {
+            let Some(old_children) = self.children.get(&folder) else {
+                continue;
+            };
+            let children = self.read_children(&folder);
+            let removed = old_children
+                .iter()
+                .filter(|child| !children.contains(child))
+                .cloned()
+                .collect::<Vec<_>>();
+            self.children.insert(folder, children);
+            for removed in removed {
+                self.forget(|path| path.starts_with(&removed));
+            }
+        }
+        cx.notify();
+    }
+
+    /// Forgets the children read for, and the expansion of, folders that
+    /// `forget` picks, and stops watching them.
+    fn forget(&mut self, forget: impl Fn(&PathBuf) -> bool) {
+        let forgotten = self
+            .children
+            .keys()
+            .filter(|folder| forget(folder))
+            .cloned()
+            .collect::<Vec<_>>();
+        for folder in forgotten {
+            self.children.remove(&folder);
+            if let Some(watcher) = &mut self.watcher {
+                watcher.unwatch(&folder).ok();
+            }
+        }
+        self.expanded.retain(|folder| !forget(folder));
+    }
+
+    fn toggle(&mut self, folder: PathBuf, cx: &mut ViewContext<Self>) {
+        if !self.expanded.remove(&folder) {
+            self.expand(folder);
+        }
+        cx.notify();
+    }
+
+    fn read_children(&self, folder: &Path) -> Vec<PathBuf> {
+        let listing = self
+            .file_systems
+            .provider(folder)
+            .and_then(|provider| Ok((provider.list(folder)?, provider)));
+        let mut children = match listing {
+            Ok((entries, provider)) => entries
+                .into_iter()
+                .filter(|path| {
+                    !path
+                        .file_name()
+                        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+                })
+                .filter(|path| provider.stat(path).is_ok_and(|stat| stat.is_dir()))
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                eprintln!(
+                    "Failed to read directory: {}. Error: {}",
+                    folder.display(),
+                    e
+                );
+                vec![]
+            }
+        };
+        children.sort_by_key(|path| path.file_name().map(|name| name.to_ascii_lowercase()));
+        children
+    }
+
+    /// The children of `folder`, along with the hidden one leading to the
+    /// selected folder if there is one, so revealing `~/.config` shows it.
+    fn shown_children(&self, folder: &Path) -> Option<Vec<PathBuf>> {
+        let mut children = self.children.get(folder)?.clone();
+        let on_the_way = self
+            .selected
+            .as_ref()
+            .and_then(|selected| selected.strip_prefix(folder).ok())
+            .and_then(|relative| relative.components().next())
+            .map(|component| folder.join(component));
+        if let Some(child) = on_the_way {
+            if !children.contains(&child) {
+                let sort_key =
+                    |path: &PathBuf| path.file_name().map(|name| name.to_ascii_lowercase());
+                let ix = children
+                    .binary_search_by_key(&sort_key(&child), sort_key)
+                    .unwrap_or_else(|ix| ix);
+                children.insert(ix, child);
+            }
+        }
+        Some(children)
+    }
+
+    /// The folders currently shown, in order, with their depth.
+    fn visible_entries(&self) -> Vec<(PathBuf, usize)> {
+        let mut entries = Vec::new();
+        let mut stack = self
+            .roots
+            .iter()
+            .rev()
+            .map(|root| (root.clone(), 0))
+            .collect::<Vec<_>>();
+
+        while let Some((folder, depth)) = stack.pop() {
+            if self.expanded.contains(&folder) {
+                if let Some(children) = self.shown_children(&folder) {
+                    stack.extend(
+                        children
+                            .iter()
+                            .rev()
+                            .map(|child| (child.clone(), depth + 1)),
+                    );
+                }
+            }
+            entries.push((folder, depth));
+        }
+        entries
+    }
+
+    fn open(&mut self, folder: PathBuf, cx: &mut ViewContext<Self>) {
+        self.selected = Some(folder.clone());
+        cx.emit(FolderTreeEvent::OpenFolder(folder));
+        cx.notify();
+    }
+
+    fn handle_key_down(&mut self, event: &KeyDownEvent, cx: &mut ViewContext<Self>) {
+        let entries = self.visible_entries();
+        let selected_ix = self
+            .selected
+            .as_ref()
+            .and_then(|selected| entries.iter().position(|(path, _)| path == selected));
+
+        match (event.keystroke.key.as_str(), selected_ix) {
+            ("down", None) | ("up", None) => {
+                self.selected = entries.first().map(|(path, _)| path.clone());
+            }
+            ("down", Some(ix)) => {
+                if let Some((path, _)) = entries.get(ix + 1) {
+                    self.selected = Some(path.clone());
+                }
+            }
+            ("up", Some(ix)) => {
+                if let Some((path, _)) = ix.checked_sub(1).and_then(|ix| entries.get(ix)) {
+                    self.selected = Some(path.clone());
+                }
+            }
+            ("right", Some(ix)) => {
+                let (folder, depth) = entries[ix].clone();
+                if !self.expanded.contains(&folder) {
+                    self.expand(folder);
+                } else if let Some((child, _)) = entries
+                    .get(ix + 1)
+                    .filter(|(_, child_depth)| *child_depth > depth)
+                {
+                    self.selected = Some(child.clone());
+                }
+            }
+            ("left", Some(ix)) => {
+                let (folder, depth) = entries[ix].clone();
+                if !self.expanded.remove(&folder) {
+                    let parent = entries[..ix]
+                        .iter()
+                        .rev()
+                        .find(|(_, parent_depth)| *parent_depth < depth);
+                    if let Some((parent, _)) = parent {
+                        self.selected = Some(parent.clone());
+                    }
+                }
+            }
+            ("enter", Some(ix)) => {
+                let folder = entries[ix].0.clone();
+                self.open(folder, cx);
+            }
+            _ => return,
+        }
+
+        cx.stop_propagation();
+        cx.notify();
+    }
+
+    fn label(&self, folder: &Path) -> String {
+        if folder == HOME.as_path() {
+            return "Home".into();
+        }
+        folder
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| folder.to_string_lossy().into_owned())
+    }
+}
+
+impl FocusableView for FolderTree {
+    fn focus_handle(&self, _cx: &AppContext) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for FolderTree {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let rows = self
+            .visible_entries()
+            .into_iter()
+            .enumerate()
+            .map(|(ix, (folder, depth))| {
+                let expanded = self.expanded.contains(&folder);
+                let has_children = !self
+                    .children
+                    .get(&folder)
+                    .is_some_and(|children| children.is_empty());
+                let selected = self.selected.as_ref() == Some(&folder);
+                let folder_icon = FileIcons::get_folder_icon_for_path(&folder, expanded, cx);
+                let chevron_icon = FileIcons::get_chevron_icon(expanded, cx);
+                let label = self.label(&folder);
+                let toggled_folder = folder.clone();
+
+                div()
+                    .id(("folder", ix))
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .gap(px(4.))
+                    .pl(px(depth as f32 * INDENT))
+                    .pr(px(4.))
+                    .py(px(2.))
+                    .rounded(px(6.))
+                    .text_color(rgb(0xf3f3f3))
+                    .when(selected, |this| this.bg(rgba(0xffffff1a)))
+                    .hover(|style| style.bg(rgba(0xffffff05)))
+                    .on_click(cx.listener(move |this, _event, cx| {
+                        cx.focus(&this.focus_handle);
+                        this.open(folder.clone(), cx)
+                    }))
+                    .child(
+                        div()
+                            .id(("chevron", ix))
+                            .w(px(12.))
+                            .h(px(12.))
+                            .flex_none()
+                            .on_click(cx.listener(move |this, _event, cx| {
+                                cx.stop_propagation();
+                                this.toggle(toggled_folder.clone(), cx)
+                            }))
+                            .children(chevron_icon.filter(|_| has_children).map(|icon| {
+                                svg()
+                                    .path(icon.to_string())
+                                    .size_full()
+                                    .text_color(rgba(0xffffff99))
+                            })),
+                    )
+                    .children(folder_icon.map(|icon| {
+                        svg()
+                            .path(icon.to_string())
+                            .w(px(14.))
+                            .h(px(14.))
+                            .flex_none()
+                            .text_color(white())
+                    }))
+                    .child(div().overflow_hidden().whitespace_nowrap().child(label))
+            })
+            .collect::<Vec<_>>();
+
+        div()
+            .id("folder-tree")
+            .track_focus(&self.focus_handle)
+            .on_key_down(cx.listener(Self::handle_key_down))
+            .flex()
+            .flex_col()
+            .flex_1()
+            .overflow_y_scroll()
+            .children(rows)
+    }
+}
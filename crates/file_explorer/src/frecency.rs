@@ -0,0 +1,316 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use gpui::{AppContext, Global};
+use serde::{Deserialize, Serialize};
+
+/// How often new visits are written out, so that browsing around doesn't
+/// write the whole database for every folder opened.
+const SAVE_INTERVAL: Duration = Duration::from_secs(2);
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+/// Once the ranks add up to more than this, all of them are scaled down and
+/// folders that haven't been visited in a while drop out.
+const MAX_TOTAL_RANK: f64 = 10_000.;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Visit {
+    pub path: PathBuf,
+    pub rank: f64,
+    /// Seconds since the unix epoch.
+    pub last_accessed: u64,
+}
+
+impl Visit {
+    /// Weighs the rank by how recently the folder was visited, the same way
+    /// zoxide does.
+    pub fn score(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_accessed);
+        if age < HOUR {
+            self.rank * 4.
+        } else if age < DAY {
+            self.rank * 2.
+        } else if age < WEEK {
+            self.rank / 2.
+        } else {
+            self.rank / 4.
+        }
+    }
+}
+
+/// Visited folders ranked by frequency and recency of visits.
+pub struct Frecency {
+    db_path: PathBuf,
+    visits: HashMap<PathBuf, Visit>,
+    unsaved: bool,
+}
+
+impl Global for Frecency {}
+
+/// Loads the visited folders and saves them on the background executor
+/// every so often after they change, and once more on quit.
+pub fn init(db_path: PathBuf, cx: &mut AppContext) {
+    cx.set_global(Frecency::load(db_path));
+
+    cx.spawn(|mut cx| async move {
+        loop {
+            cx.background_executor().timer(SAVE_INTERVAL).await;
+            let Ok(unsaved) =
+                cx.update_global::<Frecency, _>(|frecency, _cx| frecency.take_unsaved())
+            else {
+                break;
+            };
+            if let Some((db_path, contents)) = unsaved {
+                cx.background_executor()
+                    .spawn(async move { write_visits(&db_path, contents) })
+                    .await;
+            }
+        }
+    })
+    .detach();
+
+    cx.on_app_quit(|cx| {
+        let unsaved = cx.update_global::<Frecency, _>(|frecency, _cx| frecency.take_unsaved());
+        async move {
+            if let Some((db_path, contents)) = unsaved {
+                write_visits(&db_path, contents);
+            }
+        }
+    })
+    .detach();
+}
+
+impl Frecency {
+    pub fn load(db_path: PathBuf) -> Self {
+        let visits = fs::read_to_string(&db_path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Vec<Visit>>(&contents).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|visit| (visit.path.clone(), visit))
+            .collect();
+
+        Self {
+            db_path,
+            visits,
+            unsaved: false,
+        }
+    }
+
+    pub fn visits(&self) -> impl Iterator<Item = &Visit> {
+        self.visits.values()
+    }
+
+    pub fn record_visit(&mut self, path: &Path) {
+        let now = now();
+        self.visits
+            .entry(path.to_path_buf())
+            .and_modify(|visit| {
+                visit.rank += 1.;
+                visit.last_accessed = now;
+            })
+            .or_insert_with(|| Visit {
+                path: path.to_path_buf(),
+                rank: 1.,
+                last_accessed: now,
+            });
+
+        self.age();
+        self.unsaved = true;
+    }
+
+    /// Merges in the database of the `zoxide` shell tool, returning how many
+    /// folders were imported.
+    pub fn import_zoxide(&mut self, db_path: &Path) -> Result<usize> {
+        let bytes =
+            fs::read(db_path).with_context(|| format!("failed to read {}", db_path.display()))?;
+        let dirs = parse_zoxide_db(&bytes)?;
+
+        let imported = dirs.len();
+        for dir in dirs {
+            self.visits
+                .entry(dir.path.clone())
+                .and_modify(|visit| {
+                    visit.rank += dir.rank;
+                    visit.last_accessed = visit.last_accessed.max(dir.last_accessed);
+                })
+                .or_insert(dir);
+        }
+
+        self.age();
+        self.unsaved = true;
+        Ok(imported)
+    }
+
+    fn age(&mut self) {
+        let total: f64 = self.visits.values().map(|visit| visit.rank).sum();
+        if total <= MAX_TOTAL_RANK {
+            return;
+        }
+
+        let factor = 0.9 * MAX_TOTAL_RANK / total;
+        self.visits.retain(|_, visit| {
+            visit.rank *= factor;
+            visit.rank >= 1.
+        });
+    }
+
+    /// The database to write, if anything changed since it was last taken.
+    fn take_unsaved(&mut self) -> Option<(PathBuf, String)> {
+        if !std::mem::take(&mut self.unsaved) {
+            return None;
+        }
+        let visits = self.visits.values().collect::<Vec<_>>();
+        match serde_json::to_string(&visits) {
+            Ok(contents) => Some((self.db_path.clone(), contents)),
+            Err(e) => {
+                eprintln!(
+                    "Failed to save visited folders: {}. Error: {}",
+                    self.db_path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+}
+
+fn write_visits(db_path: &Path, contents: String) {
+    if let Err(e) = fs::write(db_path, contents) {
+        eprintln!(
+            "Failed to save visited folders: {}. Error: {}",
+            db_path.display(),
+            e
+        );
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Where zoxide keeps its database unless `_ZO_DATA_DIR` says otherwise.
+pub fn zoxide_db_path() -> Option<PathBuf> {
+    let data_dir = std::env::var_os("_ZO_DATA_DIR")
+        .map(PathBuf::from)
+        .or_else(|| dirs::data_local_dir().map(|dir| dir.join("zoxide")))?;
+    Some(data_dir.join("db.zo"))
+}
+
+/// Parses a version 3 zoxide database: a bincode encoded `u32` version
+/// followed by a `Vec` of `(path: String, rank: f64, last_accessed: u64)`.
+fn parse_zoxide_db(bytes: &[u8]) -> Result<Vec<Visit>> {
+    const VERSION: u32 = 3;
+
+    let mut reader = BincodeReader { bytes };
+    let version = u32::from_le_bytes(reader.take()?);
+    if version != VERSION {
+        return Err(anyhow!("unsupported zoxide database version {}", version));
+    }
+
+    let len = u64::from_le_bytes(reader.take()?);
+    let mut visits = Vec::new();
+    for _ in 0..len {
+        let path_len = u64::from_le_bytes(reader.take()?) as usize;
+        let path = String::from_utf8(reader.take_slice(path_len)?.to_vec())?;
+        let rank = f64::from_le_bytes(reader.take()?);
+        let last_accessed = u64::from_le_bytes(reader.take()?);
+        visits.push(Visit {
+            path: PathBuf::from(path),
+            rank,
+            last_accessed,
+        });
+    }
+
+    Ok(visits)
+}
+
+struct BincodeReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> BincodeReader<'a> {
+    fn take_slice(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(anyhow!("unexpected end of zoxide database"));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take_slice(N)?.try_into()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zoxide_db(version: u32, dirs: &[(&str, f64, u64)]) -> Vec<u8> {
+        let mut bytes = version.to_le_bytes().to_vec();
+        bytes.extend((dirs.len() as u64).to_le_bytes());
+        for (path, rank, last_accessed) in dirs {
+            bytes.extend((path.len() as u64).to_le_bytes());
+            bytes.extend(path.as_bytes());
+            bytes.extend(rank.to_le_bytes());
+            bytes.extend(last_accessed.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn parses_zoxide_databases() {
+        let bytes = zoxide_db(
+            3,
+            &[("/home/me/src", 12.5, 1_700_000_000), ("/tmp", 1., 42)],
+        );
+        let visits = parse_zoxide_db(&bytes).unwrap();
+        assert_eq!(visits.len(), 2);
+        assert_eq!(visits[0].path, Path::new("/home/me/src"));
+        assert_eq!(visits[0].rank, 12.5);
+        assert_eq!(visits[0].last_accessed, 1_700_000_000);
+        assert_eq!(visits[1].path, Path::new("/tmp"));
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        assert!(parse_zoxide_db(&zoxide_db(2, &[])).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_databases() {
+        let bytes = zoxide_db(3, &[("/home/me/src", 1., 1)]);
+        for len in [0, 3, 11, bytes.len() - 1] {
+            assert!(parse_zoxide_db(&bytes[..len]).is_err());
+        }
+        // A length far past the end mustn't be trusted.
+        let mut bytes = zoxide_db(3, &[]);
+        bytes[4..12].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(parse_zoxide_db(&bytes).is_err());
+    }
+
+    #[test]
+    fn recent_visits_score_higher() {
+        let visit = Visit {
+            path: PathBuf::from("/tmp"),
+            rank: 8.,
+            last_accessed: 10 * WEEK,
+        };
+        assert_eq!(visit.score(10 * WEEK + 1), 32.);
+        assert_eq!(visit.score(10 * WEEK + HOUR), 16.);
+        assert_eq!(visit.score(10 * WEEK + DAY), 4.);
+        assert_eq!(visit.score(11 * WEEK), 2.);
+    }
+}
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use file_index::{IndexMatch, IndexService};
+use gpui::{
+    div, px, rgba, svg, white, AnyElement, IntoElement, Model, ParentElement, SharedString, Styled,
+    Task, ViewContext,
+};
+use ui::{Picker, PickerDelegate};
+use util::paths::PathExt;
+
+use crate::FileExplorer;
+
+const MAX_MATCHES: usize = 100;
+
+/// Fuzzy finds files and folders in the background file index.
+pub struct GlobalSearchDelegate {
+    index: Option<Arc<IndexService>>,
+    file_explorer: Model<FileExplorer>,
+    matches: Vec<IndexMatch>,
+}
+
+impl GlobalSearchDelegate {
+    pub fn new(index: Option<Arc<IndexService>>, file_explorer: Model<FileExplorer>) -> Self {
+        Self {
+            index,
+            file_explorer,
+            matches: Vec::new(),
+        }
+    }
+}
+
+impl PickerDelegate for GlobalSearchDelegate {
+    fn placeholder_text(&self) -> SharedString {
+        "Search all indexed files...".into()
+    }
+
+    fn match_count(&self) -> usize {
+        self.matches.len()
+    }
+
+    fn update_matches(&mut self, query: String, cx: &mut ViewContext<Picker<Self>>) -> Task<()> {
+        let Some(index) = self.index.clone() else {
+            return Task::ready(());
+        };
+        if query.trim().is_empty() {
+            self.matches.clear();
+            return Task::ready(());
+        }
+
+        cx.spawn(|picker, mut cx| async move {
+            let matches = cx
+                .background_executor()
+                .spawn(async move { index.search(&query, MAX_MATCHES) })
+                .await;
+
+            picker
+                .update(&mut cx, |picker, cx| {
+                    picker.delegate.matches = matches;
+                    picker.matches_updated(cx);
+                })
+                .ok();
+        })
+    }
+
+    fn confirm(&mut self, ix: Option<usize>, _query: &str, cx: &mut ViewContext<Picker<Self>>) {
+        let Some(selected) = ix.and_then(|ix| self.matches.get(ix)) else {
+            return;
+        };
+
+        let folder = if selected.metadata.is_dir {
+            selected.path.clone()
+        } else {
+            match selected.path.parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => return,
+            }
+        };
+        self.file_explorer.update(cx, |file_explorer, cx| {
+            file_explorer.open_folder(folder, cx)
+        });
+    }
+
+    fn render_match(
+        &self,
+        ix: usize,
+        _selected: bool,
+        _cx: &mut ViewContext<Picker<Self>>,
+    ) -> AnyElement {
+        let entry = &self.matches[ix];
+        let name = entry
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let parent = entry
+            .path
+            .parent()
+            .map(|parent| parent.compact().to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let icon = if entry.metadata.is_dir {
+            "icons/file_icons/folder.svg"
+        } else {
+            "icons/file_icons/file_text.svg"
+        };
+
+        div()
+            .flex()
+            .flex_row()
+            .items_center()
+            .gap(px(8.))
+            .overflow_hidden()
+            .whitespace_nowrap()
+            .child(svg().path(icon).w(px(14.)).h(px(14.)).text_color(white()))
+            .child(name)
+            .child(
+                div()
+                    .text_size(px(12.))
+                    .text_color(rgba(0xffffff66))
+                    .child(parent),
+            )
+            .into_any_element()
+    }
+
+    fn render_empty(&self, _cx: &mut ViewContext<Picker<Self>>) -> Option<AnyElement> {
+        let message = match &self.index {
+            None => "File indexing is off. Set \"index.enabled\" in settings.json to turn it on.",
+            Some(index) if !index.is_ready() => "Indexing files...",
+            Some(_) => return None,
+        };
+        Some(message.into_any_element())
+    }
+}
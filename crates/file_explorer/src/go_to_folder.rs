@@ -0,0 +1,295 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ::fs::FileSystems;
+use gpui::{
+    div, px, rgba, svg, white, AnyElement, AppContext, IntoElement, Model, ParentElement,
+    SharedString, Styled, Task, ViewContext,
+};
+use ui::{Picker, PickerDelegate};
+use util::{
+    fuzzy::fuzzy_match,
+    paths::{expand_tilde, PathExt},
+};
+
+use crate::{
+    frecency::{self, Frecency},
+    paths::FAVORITES,
+    places,
+    providers::Providers,
+    FileExplorer,
+};
+
+/// How much a folder's frecency counts relative to the fuzzy match score.
+const FRECENCY_WEIGHT: f64 = 10.;
+const MAX_MATCHES: usize = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CandidateKind {
+    Visited,
+    Favorite,
+    Place,
+    Drive,
+    TypedPath,
+    ImportZoxide,
+}
+
+#[derive(Clone, Debug)]
+struct Candidate {
+    label: String,
+    path: PathBuf,
+    kind: CandidateKind,
+    frecency: f64,
+}
+
+impl Candidate {
+    fn icon(&self) -> &'static str {
+        match self.kind {
+            CandidateKind::Visited | CandidateKind::TypedPath => "icons/file_icons/folder.svg",
+            CandidateKind::Favorite => "icons/file_icons/star.svg",
+            CandidateKind::Place => "icons/file_icons/house.svg",
+            CandidateKind::Drive => "icons/file_icons/hard_drive.svg",
+            CandidateKind::ImportZoxide => "icons/file_icons/download.svg",
+        }
+    }
+}
+
+/// Jumps to visited folders, favorites, places and drives, ranked by
+/// frecency. Also accepts a path typed out in full.
+pub struct GoToFolderDelegate {
+    file_explorer: Model<FileExplorer>,
+    file_systems: FileSystems,
+    candidates: Vec<Candidate>,
+    matches: Vec<Candidate>,
+}
+
+impl GoToFolderDelegate {
+    pub fn new(file_explorer: Model<FileExplorer>, cx: &AppContext) -> Self {
+        let file_systems = Providers::get(cx);
+        let mut candidates: Vec<Candidate> = Vec::new();
+
+        for place in places(cx).into_iter().filter(|place| !place.missing) {
+            candidates.push(Candidate {
+                label: place.label.to_string(),
+                path: place.path,
+                kind: CandidateKind::Place,
+                frecency: 0.,
+            });
+        }
+
+        let favorites = file_systems
+            .provider(FAVORITES.as_path())
+            .and_then(|provider| provider.list(FAVORITES.as_path()));
+        if let Ok(entries) = favorites {
+            for entry in entries {
+                let path = fs::canonicalize(&entry).unwrap_or_else(|_| entry.clone());
+                if Self::is_folder(&path, &file_systems) {
+                    candidates.push(Candidate {
+                        label: entry
+                            .file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or_default(),
+                        path,
+                        kind: CandidateKind::Favorite,
+                        frecency: 0.,
+                    });
+                }
+            }
+        }
+
+        for drive in &file_explorer.read(cx).drives {
+            candidates.push(Candidate {
+                label: drive.display_name(),
+                path: drive.mount_point.clone(),
+                kind: CandidateKind::Drive,
+                frecency: 0.,
+            });
+        }
+
+        if let Some(frecency) = cx.try_global::<Frecency>() {
+            let now = frecency::now();
+            for visit in frecency.visits() {
+                let score = visit.score(now);
+                match candidates
+                    .iter_mut()
+                    .find(|candidate| candidate.path == visit.path)
+                {
+                    Some(candidate) => candidate.frecency = score,
+                    None if Self::is_folder(&visit.path, &file_systems) => {
+                        candidates.push(Candidate {
+                            label: visit
+                                .path
+                                .file_name()
+                                .map(|name| name.to_string_lossy().into_owned())
+                                .unwrap_or_else(|| visit.path.to_string_lossy().into_owned()),
+                            path: visit.path.clone(),
+                            kind: CandidateKind::Visited,
+                            frecency: score,
+                        })
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        if let Some(db_path) = frecency::zoxide_db_path().filter(|path| path.exists()) {
+            candidates.push(Candidate {
+                label: "Import zoxide history".into(),
+                path: db_path,
+                kind: CandidateKind::ImportZoxide,
+                frecency: 0.,
+            });
+        }
+
+        Self {
+            file_explorer,
+            file_systems,
+            candidates,
+            matches: Vec::new(),
+        }
+    }
+
+    /// Whether `path` is a folder worth offering. Remote locations are
+    /// checked when opened rather than here, so the picker doesn't wait on
+    /// the network.
+    fn is_folder(path: &Path, file_systems: &FileSystems) -> bool {
+        ::fs::location_scheme(path).is_some()
+            || file_systems
+                .provider(path)
+                .and_then(|provider| provider.stat(path))
+                .is_ok_and(|stat| stat.is_dir())
+    }
+
+    fn is_typed_path(query: &str) -> bool {
+        ::fs::location_scheme(Path::new(query)).is_some()
+            || query.starts_with(['/', '~', '\\'])
+            || query.as_bytes().get(1) == Some(&b':') && query.as_bytes()[0].is_ascii_alphabetic()
+    }
+}
+
+impl PickerDelegate for GoToFolderDelegate {
+    fn placeholder_text(&self) -> SharedString {
+        "Go to folder...".into()
+    }
+
+    fn match_count(&self) -> usize {
+        self.matches.len()
+    }
+
+    fn update_matches(&mut self, query: String, _cx: &mut ViewContext<Picker<Self>>) -> Task<()> {
+        let query = query.trim();
+
+        let mut matches = if query.is_empty() {
+            let mut matches = self
+                .candidates
+                .iter()
+                .filter(|candidate| candidate.kind != CandidateKind::ImportZoxide)
+                .cloned()
+                .collect::<Vec<_>>();
+            // Sorting is stable, so folders never visited keep sidebar order.
+            matches.sort_by(|a, b| b.frecency.total_cmp(&a.frecency));
+            matches
+        } else {
+            let mut scored = self
+                .candidates
+                .iter()
+                .filter_map(|candidate| {
+                    let path = candidate.path.compact().to_string_lossy().into_owned();
+                    let score = if candidate.kind == CandidateKind::ImportZoxide {
+                        fuzzy_match(query, &candidate.label)?.score
+                    } else {
+                        let label_score = fuzzy_match(query, &candidate.label).map(|m| m.score);
+                        let path_score = fuzzy_match(query, &path).map(|m| m.score);
+                        label_score.max(path_score)?
+                    };
+                    let rank = score as f64 + (1. + candidate.frecency).ln() * FRECENCY_WEIGHT;
+                    Some((rank, candidate.clone()))
+                })
+                .collect::<Vec<_>>();
+            scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+            scored.into_iter().map(|(_, candidate)| candidate).collect()
+        };
+
+        if Self::is_typed_path(query) {
+            let path = expand_tilde(query);
+            if Self::is_folder(&path, &self.file_systems)
+                && !matches.iter().any(|candidate| candidate.path == path)
+            {
+                matches.insert(
+                    0,
+                    Candidate {
+                        label: query.to_string(),
+                        path,
+                        kind: CandidateKind::TypedPath,
+                        frecency: 0.,
+                    },
+                );
+            }
+        }
+
+        matches.truncate(MAX_MATCHES);
+        self.matches = matches;
+        Task::ready(())
+    }
+
+    fn confirm(&mut self, ix: Option<usize>, query: &str, cx: &mut ViewContext<Picker<Self>>) {
+        let selected = ix.and_then(|ix| self.matches.get(ix)).cloned();
+        let folder = match selected {
+            Some(candidate) if candidate.kind == CandidateKind::ImportZoxide => {
+                let result = cx.update_global::<Frecency, _>(|frecency, _cx| {
+                    frecency.import_zoxide(&candidate.path)
+                });
+                if let Err(e) = result {
+                    eprintln!("Failed to import zoxide history. Error: {}", e);
+                }
+                return;
+            }
+            Some(candidate) => candidate.path,
+            None => expand_tilde(query.trim()),
+        };
+
+        if Self::is_folder(&folder, &self.file_systems) {
+            self.file_explorer.update(cx, |file_explorer, cx| {
+                file_explorer.open_folder(folder, cx)
+            });
+        }
+    }
+
+    fn render_match(
+        &self,
+        ix: usize,
+        _selected: bool,
+        _cx: &mut ViewContext<Picker<Self>>,
+    ) -> AnyElement {
+        let candidate = &self.matches[ix];
+        let detail = match candidate.kind {
+            CandidateKind::ImportZoxide => String::new(),
+            _ => candidate.path.compact().to_string_lossy().into_owned(),
+        };
+
+        div()
+            .flex()
+            .flex_row()
+            .items_center()
+            .gap(px(8.))
+            .overflow_hidden()
+            .whitespace_nowrap()
+            .child(
+                svg()
+                    .path(candidate.icon())
+                    .w(px(14.))
+                    .h(px(14.))
+                    .text_color(white()),
+            )
+            .child(candidate.label.clone())
+            .child(
+                div()
+                    .text_size(px(12.))
+                    .text_color(rgba(0xffffff66))
+                    .child(detail),
+            )
+            .into_any_element()
+    }
+}
@@ -0,0 +1,324 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use fs::{ArchiveCache, ArchiveFormat, Cancelled, CompressionLevel, FileSystems, TransferProgress};
+use gpui::{EventEmitter, ModelContext};
+
+use crate::{archives::Archives, providers::Providers};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobKind {
+    Copy,
+    Move,
+    /// Packs the sources into a new archive; the destination is the archive
+    /// rather than a folder.
+    Compress(ArchiveFormat, CompressionLevel),
+    /// Unpacks each source archive into the destination folder.
+    Extract,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JobState {
+    Running,
+    /// One message per item that couldn't be transferred.
+    Failed(Vec<String>),
+}
+
+pub struct Job {
+    pub id: usize,
+    pub kind: JobKind,
+    pub sources: Vec<PathBuf>,
+    pub destination: PathBuf,
+    pub progress: Arc<TransferProgress>,
+    pub state: JobState,
+}
+
+impl Job {
+    pub fn description(&self) -> String {
+        let verb = match self.kind {
+            JobKind::Copy => "Copying",
+            JobKind::Move => "Moving",
+            JobKind::Compress(..) => "Compressing",
+            JobKind::Extract => "Extracting",
+        };
+        let items = match self.sources.as_slice() {
+            [source] => source
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| source.to_string_lossy().into_owned()),
+            sources => format!("{} items", sources.len()),
+        };
+        let destination = self
+            .destination
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.destination.to_string_lossy().into_owned());
+        format!("{} {} to {}", verb, items, destination)
+    }
+}
+
+pub enum JobEvent {
+    /// A job is done, successfully or not, and folders showing its sources or
+    /// destination are out of date.
+    Finished {
+        sources: Vec<PathBuf>,
+        destination: PathBuf,
+    },
+}
+
+/// Copies, moves and archive jobs running in the background. Pasting and the
+/// commander mode copy/move keys all go through here.
+#[derive(Default)]
+pub struct Jobs {
+    jobs: Vec<Job>,
+    next_id: usize,
+}
+
+impl EventEmitter<JobEvent> for Jobs {}
+
+impl Jobs {
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    /// Copies, moves or extracts `sources` into the folder `destination`, or
+    /// compresses them into the new archive `destination`.
+    pub fn start(
+        &mut self,
+        kind: JobKind,
+        sources: Vec<PathBuf>,
+        destination: PathBuf,
+        cx: &mut ModelContext<Self>,
+    ) {
+        // Moving something to the folder it's already in is a no-op.
+        let sources = sources
+            .into_iter()
+            .filter(|source| {
+                kind != JobKind::Move || source.parent() != Some(destination.as_path())
+            })
+            .collect::<Vec<_>>();
+        if sources.is_empty() {
+            return;
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        let progress = Arc::new(TransferProgress::default());
+        self.jobs.push(Job {
+            id,
+            kind,
+            sources: sources.clone(),
+            destination: destination.clone(),
+            progress: progress.clone(),
+            state: JobState::Running,
+        });
+        cx.notify();
+
+        let archives = Archives::cache(cx);
+        let file_systems = Providers::get(cx);
+        let work = cx.background_executor().spawn({
+            let sources = sources.clone();
+            let destination = destination.clone();
+            async move {
+                run_job(
+                    kind,
+                    &sources,
+                    &destination,
+                    &archives,
+                    &file_systems,
+                    &progress,
+                )
+            }
+        });
+
+        cx.spawn(|this, mut cx| async move {
+            let errors = work.await;
+            this.update(&mut cx, |this, cx| {
+                this.finish(id, errors, cx);
+                cx.emit(JobEvent::Finished {
+                    sources,
+                    destination,
+                });
+            })
+            .ok();
+        })
+        .detach();
+
+        cx.spawn(|this, mut cx| async move {
+            loop {
+                cx.background_executor().timer(PROGRESS_INTERVAL).await;
+                let running = this.update(&mut cx, |this, cx| {
+                    cx.notify();
+                    this.jobs
+                        .iter()
+                        .any(|job| job.id == id && job.state == JobState::Running)
+                });
+                if !running.unwrap_or(false) {
+                    break;
+                }
+            }
+        })
+        .detach();
+    }
+
+    pub fn cancel(&mut self, id: usize) {
+        if let Some(job) = self.jobs.iter().find(|job| job.id == id) {
+            job.progress.cancel();
+        }
+    }
+
+    pub fn dismiss(&mut self, id: usize, cx: &mut ModelContext<Self>) {
+        self.jobs.retain(|job| job.id != id);
+        cx.notify();
+    }
+
+    fn finish(&mut self, id: usize, errors: Vec<String>, cx: &mut ModelContext<Self>) {
+        if errors.is_empty() {
+            self.jobs.retain(|job| job.id != id);
+        } else if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+            job.state = JobState::Failed(errors);
+        }
+        cx.notify();
+    }
+}
+
+/// Sources inside archives are extracted; archives can't be written to.
+/// Anything involving a remote location goes through its provider, while
+/// compressing and extracting only work on the disk.
+fn run_job(
+    kind: JobKind,
+    sources: &[PathBuf],
+    destination: &Path,
+    archives: &ArchiveCache,
+    file_systems: &FileSystems,
+    progress: &TransferProgress,
+) -> Vec<String> {
+    if fs::split_archive_path(destination).is_some() {
+        return vec![format!(
+            "{}: archives can't be changed",
+            destination.display()
+        )];
+    }
+    let remote = |path: &Path| fs::location_scheme(path).is_some();
+    if matches!(kind, JobKind::Compress(..) | JobKind::Extract)
+        && (remote(destination) || sources.iter().any(|source| remote(source)))
+    {
+        return vec!["Archives can only be made and unpacked on this computer".into()];
+    }
+    if let JobKind::Compress(format, level) = kind {
+        return compress(sources, destination, format, level, progress);
+    }
+
+    let total = sources
+        .iter()
+        .map(|source| match kind {
+            JobKind::Extract => archives
+                .open(source)
+                .ok()
+                .and_then(|archive| archive.entry(Path::new("")).map(|entry| entry.size))
+                .unwrap_or(0),
+            _ => archives
+                .entry(source)
+                .map_or_else(|| file_systems.total_size(source), |entry| entry.size),
+        })
+        .sum();
+    progress.total_bytes.store(total, Ordering::Relaxed);
+
+    let mut errors = Vec::new();
+    for source in sources {
+        if progress.is_cancelled() {
+            break;
+        }
+
+        let Some(file_name) = source.file_name() else {
+            continue;
+        };
+        let in_archive =
+            fs::split_archive_path(source).filter(|(_, inner)| !inner.as_os_str().is_empty());
+        // Extractions report how many entries they left out for leading
+        // outside the destination, but keep the rest.
+        let result = match (in_archive, kind) {
+            (_, JobKind::Extract) => archives
+                .open(source)
+                .and_then(|archive| archive.extract_all(destination, progress))
+                .map(|(_, refused)| refused),
+            (Some(_), JobKind::Move) => Err(anyhow!("can't be moved out of an archive")),
+            (Some((archive, inner)), _) if !remote(destination) => {
+                let target = fs::unique_destination(destination, file_name);
+                archives
+                    .open(&archive)
+                    .and_then(|archive| archive.extract(&inner, &target, progress))
+            }
+            (None, JobKind::Move) => file_systems
+                .move_path(
+                    source,
+                    &file_systems.unique_destination(destination, file_name),
+                    progress,
+                )
+                .map(|()| 0),
+            (_, _) => file_systems
+                .copy(
+                    source,
+                    &file_systems.unique_destination(destination, file_name),
+                    progress,
+                )
+                .map(|()| 0),
+        };
+
+        match result {
+            Ok(0) => {}
+            Ok(refused) => errors.push(format!(
+                "{}: left out {} entries that lead outside the archive",
+                file_name.to_string_lossy(),
+                refused
+            )),
+            Err(error) if error.is::<Cancelled>() => break,
+            Err(error) => {
+                eprintln!("Failed to transfer: {}. Error: {}", source.display(), error);
+                errors.push(format!("{}: {}", file_name.to_string_lossy(), error));
+            }
+        }
+    }
+    errors
+}
+
+fn compress(
+    sources: &[PathBuf],
+    destination: &Path,
+    format: ArchiveFormat,
+    level: CompressionLevel,
+    progress: &TransferProgress,
+) -> Vec<String> {
+    let in_archive = |source: &PathBuf| {
+        fs::split_archive_path(source).is_some_and(|(_, inner)| !inner.as_os_str().is_empty())
+    };
+    if sources.iter().any(in_archive) {
+        return vec!["Items in archives can't be compressed again".into()];
+    }
+
+    let total = sources.iter().map(|source| fs::total_size(source)).sum();
+    progress.total_bytes.store(total, Ordering::Relaxed);
+
+    match fs::create_archive(sources, destination, format, level, progress) {
+        Ok(()) => Vec::new(),
+        Err(error) if error.is::<Cancelled>() => Vec::new(),
+        Err(error) => {
+            eprintln!(
+                "Failed to compress: {}. Error: {}",
+                destination.display(),
+                error
+            );
+            let name = destination
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            vec![format!("{}: {}", name, error)]
+        }
+    }
+}
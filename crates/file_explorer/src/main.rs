@@ -1,18 +1,111 @@
+mod archives;
+mod bookmarks;
+mod compress;
+mod connect;
+mod connections;
+mod delete;
+mod disk_usage;
+mod extract;
+mod folder_sizes;
+mod folder_tree;
+mod frecency;
+mod global_search;
+mod go_to_folder;
+mod jobs;
+mod pane;
+mod preview;
+mod properties;
+mod providers;
+mod quick_look;
+mod rename;
+mod search_bar;
+mod settings;
+mod user_dirs;
+
 use std::{
+    collections::HashMap,
     fs,
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
 };
 
+use ::fs::{ArchiveFormat, Drive, FileStat, MountWatcher, Watch};
 use assets::Assets;
+use bookmarks::Bookmarks;
+use compress::CompressDialog;
+use connect::ConnectDialog;
+use connections::Connections;
+use delete::{DeleteConfirmed, DeleteDialog};
+use disk_usage::{DiskUsageEvent, DiskUsageView};
+use extract::ExtractDialog;
+use file_icons::FileIcons;
+use file_index::IndexService;
+use folder_tree::{FolderTree, FolderTreeEvent};
+use frecency::Frecency;
+use global_search::GlobalSearchDelegate;
+use go_to_folder::GoToFolderDelegate;
 use gpui::{
-    div, px, rgb, rgba, size, svg, white, AnyElement, App, AppContext, Bounds, Context,
-    EventEmitter, InteractiveElement, IntoElement, Model, ParentElement, Pixels, Render, Styled,
-    View, ViewContext, VisualContext, WindowBounds, WindowOptions,
+    actions, div, prelude::FluentBuilder, px, relative, rgb, rgba, size, svg, white, AnyView, App,
+    AppContext, Bounds, Context, DismissEvent, EventEmitter, FocusHandle, FocusableView,
+    InteractiveElement, IntoElement, KeyBinding, ManagedView, Model, ModelContext, ParentElement,
+    Pixels, Render, SharedString, Styled, Task, View, ViewContext, VisualContext, WindowBounds,
+    WindowOptions,
 };
+use jobs::{JobEvent, JobKind, JobState, Jobs};
 use lazy_static::lazy_static;
+use pane::Pane;
 use paths::*;
-use ui::{FileItem, TitleBar};
+use preview::PreviewPane;
+use properties::PropertiesDialog;
+use providers::Providers;
+use quick_look::QuickLookModal;
+use rename::RenameDialog;
+use search::format_size;
+use search_bar::{SearchBar, SearchEvent};
+use settings::Settings;
+use ui::{Picker, TitleBar};
+use user_dirs::UserDirs;
+
+const MOUNT_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+const FOLDER_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+actions!(
+    file_explorer,
+    [
+        GlobalSearch,
+        GoToFolder,
+        NewTab,
+        CloseTab,
+        NextTab,
+        PreviousTab,
+        ReopenClosedTab,
+        GoBack,
+        GoForward,
+        ShowGrid,
+        ShowDetails,
+        Copy,
+        Cut,
+        Paste,
+        ToggleDualPane,
+        SwitchPane,
+        CopyToOtherPane,
+        MoveToOtherPane,
+        TogglePreview,
+        QuickLook,
+        ShowProperties,
+        AnalyzeDiskUsage,
+        Compress,
+        ExtractHere,
+        ExtractTo,
+        ConnectToServer,
+        Rename,
+        Delete,
+    ]
+);
 
 #[cfg(target_os = "linux")]
 mod paths {
@@ -20,22 +113,15 @@ mod paths {
     use dirs;
 
     lazy_static! {
-        pub static ref HOME: PathBuf =
-            dirs::home_dir().expect("failed to determine home directory");
-        pub static ref DOCUMENTS: PathBuf =
-            dirs::document_dir().expect("Failed to determine documents directory");
-        pub static ref DOWNLOADS: PathBuf =
-            dirs::download_dir().expect("Failed to determine downloads directory");
-        pub static ref MUSIC: PathBuf =
-            dirs::audio_dir().expect("Failed to determine music directory");
-        pub static ref PICTURES: PathBuf =
-            dirs::picture_dir().expect("Failed to determine pictures directory");
-        pub static ref VIDEOS: PathBuf =
-            dirs::video_dir().expect("Failed to determine videos directory");
+        pub static ref HOME: PathBuf = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/"));
         pub static ref LOCAL: PathBuf = HOME.join(".local");
         pub static ref TRASH: PathBuf = HOME.join(".local/share/Trash/files");
         pub static ref RECENT: PathBuf = LOCAL.join("share/file_explorer/recent");
         pub static ref FAVORITES: PathBuf = LOCAL.join("share/file_explorer/favorites");
+        pub static ref SETTINGS: PathBuf = LOCAL.join("share/file_explorer/settings.json");
+        pub static ref INDEX: PathBuf = LOCAL.join("share/file_explorer/index");
+        pub static ref FRECENCY: PathBuf = LOCAL.join("share/file_explorer/frecency.json");
+        pub static ref CONNECTIONS: PathBuf = LOCAL.join("share/file_explorer/connections.json");
     }
 }
 
@@ -45,23 +131,15 @@ mod paths {
     use dirs;
 
     lazy_static! {
-        pub static ref HOME: PathBuf =
-            dirs::data_dir().expect("failed to determine data directory");
-        pub static ref DOCUMENTS: PathBuf =
-            dirs::document_dir().expect("Failed to determine documents directory");
-        pub static ref DOWNLOADS: PathBuf =
-            dirs::download_dir().expect("Failed to determine downloads directory");
-        pub static ref MUSIC: PathBuf =
-            dirs::audio_dir().expect("Failed to determine music directory");
-        pub static ref PICTURES: PathBuf =
-            dirs::picture_dir().expect("Failed to determine pictures directory");
-        pub static ref VIDEOS: PathBuf =
-            dirs::video_dir().expect("Failed to determine videos directory");
-        pub static ref LOCAL: PathBuf =
-            dirs::data_dir().expect("Failed to determine data directory");
+        pub static ref HOME: PathBuf = dirs::data_dir().unwrap_or_else(|| PathBuf::from("C:\\"));
+        pub static ref LOCAL: PathBuf = HOME.clone();
         pub static ref TRASH: PathBuf = LOCAL.join("Trash");
         pub static ref RECENT: PathBuf = LOCAL.join("file_explorer/recent");
         pub static ref FAVORITES: PathBuf = LOCAL.join("file_explorer/favorites");
+        pub static ref SETTINGS: PathBuf = LOCAL.join("file_explorer/settings.json");
+        pub static ref INDEX: PathBuf = LOCAL.join("file_explorer/index");
+        pub static ref FRECENCY: PathBuf = LOCAL.join("file_explorer/frecency.json");
+        pub static ref CONNECTIONS: PathBuf = LOCAL.join("file_explorer/connections.json");
     }
 }
 
@@ -71,25 +149,75 @@ mod paths {
     use dirs;
 
     lazy_static! {
-        pub static ref HOME: PathBuf =
-            dirs::home_dir().expect("failed to determine home directory");
-        pub static ref DOCUMENTS: PathBuf =
-            dirs::document_dir().expect("Failed to determine documents directory");
-        pub static ref DOWNLOADS: PathBuf =
-            dirs::download_dir().expect("Failed to determine downloads directory");
-        pub static ref MUSIC: PathBuf =
-            dirs::audio_dir().expect("Failed to determine music directory");
-        pub static ref PICTURES: PathBuf =
-            dirs::picture_dir().expect("Failed to determine pictures directory");
-        pub static ref VIDEOS: PathBuf =
-            dirs::video_dir().expect("Failed to determine videos directory");
+        pub static ref HOME: PathBuf = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/"));
         pub static ref LOCAL: PathBuf = HOME.join(".local");
         pub static ref TRASH: PathBuf = HOME.join(".local/share/Trash/files");
         pub static ref RECENT: PathBuf = LOCAL.join("share/file_explorer/recent");
         pub static ref FAVORITES: PathBuf = LOCAL.join("share/file_explorer/favorites");
+        pub static ref SETTINGS: PathBuf = LOCAL.join("share/file_explorer/settings.json");
+        pub static ref INDEX: PathBuf = LOCAL.join("share/file_explorer/index");
+        pub static ref FRECENCY: PathBuf = LOCAL.join("share/file_explorer/frecency.json");
+        pub static ref CONNECTIONS: PathBuf = LOCAL.join("share/file_explorer/connections.json");
     }
 }
 
+/// A fixed location shown at the top of the sidebar.
+pub struct Place {
+    pub label: &'static str,
+    pub path: PathBuf,
+    /// The icon type in `file_types.json`, so the sidebar shows the same
+    /// icons as the folders it points to.
+    pub icon_type: &'static str,
+    /// Set in `user-dirs.dirs` but not on disk, so shown greyed out.
+    pub missing: bool,
+}
+
+impl Place {
+    pub fn icon(&self, cx: &AppContext) -> SharedString {
+        FileIcons::get(cx)
+            .get_type_icon(self.icon_type)
+            .map(|icon| SharedString::from(icon.to_string()))
+            .unwrap_or_else(|| "icons/file_icons/folder.svg".into())
+    }
+}
+
+/// The fixed locations, leaving out the user folders that aren't set.
+pub fn places(cx: &AppContext) -> Vec<Place> {
+    let user_dirs = UserDirs::get(cx);
+    let place = |label, path: &PathBuf, icon_type| {
+        Some(Place {
+            label,
+            path: path.clone(),
+            icon_type,
+            missing: false,
+        })
+    };
+    let user_dir = |label, path: &Option<PathBuf>, icon_type| {
+        let path = path.clone()?;
+        Some(Place {
+            label,
+            missing: !path.is_dir(),
+            path,
+            icon_type,
+        })
+    };
+
+    [
+        place("Recent", &RECENT, "recent"),
+        place("Favorites", &FAVORITES, "favorites"),
+        place("Home", &HOME, "home"),
+        user_dir("Documents", &user_dirs.documents, "folder_documents"),
+        user_dir("Downloads", &user_dirs.downloads, "folder_downloads"),
+        user_dir("Music", &user_dirs.music, "folder_music"),
+        user_dir("Pictures", &user_dirs.pictures, "folder_pictures"),
+        user_dir("Videos", &user_dirs.videos, "folder_videos"),
+        place("Trash", &TRASH, "trash"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
 #[derive(Clone)]
 pub struct Style {
     pub scrollbar_width: Pixels,
@@ -110,15 +238,51 @@ struct FileChange {
 impl EventEmitter<FileChange> for Main {}
 impl EventEmitter<FileChange> for FileExplorer {}
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ViewMode {
+    #[default]
+    Grid,
+    Details,
+}
+
 pub struct FileExplorer {
     text: String,
     folder_contents: Vec<PathBuf>,
+    /// What the provider said about each entry when the folder was read,
+    /// so drawing it doesn't ask again.
+    stats: HashMap<PathBuf, FileStat>,
+    /// Reads the folder on the background executor, since providers may be
+    /// slow to list it.
+    loading: Option<Task<()>>,
     path: PathBuf,
-    drives: Vec<PathBuf>,
+    drives: Vec<Drive>,
     current_folder: PathBuf,
+    history: Vec<PathBuf>,
+    history_ix: usize,
+    selection: Vec<PathBuf>,
+    view_mode: ViewMode,
+    /// Reads the folder again when its contents change.
+    watch: Option<(Watch, Task<()>)>,
 }
 
 impl FileExplorer {
+    fn new(drives: Vec<Drive>) -> Self {
+        Self {
+            text: "Favorites".into(),
+            folder_contents: vec![],
+            stats: HashMap::new(),
+            loading: None,
+            path: PathBuf::new(),
+            drives,
+            current_folder: PathBuf::new(),
+            history: vec![],
+            history_ix: 0,
+            selection: vec![],
+            view_mode: ViewMode::default(),
+            watch: None,
+        }
+    }
+
     fn check_or_create_folder(&self, folder: &Path) {
         if !folder.exists() {
             match fs::create_dir_all(folder) {
@@ -133,66 +297,330 @@ impl FileExplorer {
     }
 
     #[cfg(target_os = "windows")]
-    fn fetch_drives(&mut self) {
-        use std::io;
-        use std::ptr;
-        use winapi::um::fileapi::GetLogicalDriveStringsW;
+    fn fetch_drives(_show_all: bool) -> Vec<Drive> {
+        use std::{ffi::OsStr, os::windows::ffi::OsStrExt, ptr};
+        use winapi::um::{
+            fileapi::{
+                GetDiskFreeSpaceExW, GetDriveTypeW, GetLogicalDriveStringsW, GetVolumeInformationW,
+            },
+            winbase::DRIVE_REMOVABLE,
+            winnt::ULARGE_INTEGER,
+        };
 
         unsafe {
             let mut drive_strings = [0u16; 256];
             let result = GetLogicalDriveStringsW(256, drive_strings.as_mut_ptr());
             if result == 0 {
                 println!("Failed to get logical drives");
-                return;
+                return Vec::new();
             }
 
             let mut drives = Vec::new();
             let mut i = 0;
             while i < result as usize {
-                let drive_str =
-                    String::from_utf16_lossy(&drive_strings[i..i + 4]).trim_end_matches('\u{0}');
+                let drive_str = String::from_utf16_lossy(&drive_strings[i..i + 4]);
+                let drive_str = drive_str.trim_end_matches('\u{0}');
                 let drive = PathBuf::from(drive_str);
+                i += 4;
 
                 // Check for access before adding the drive
-                if fs::metadata(&drive).is_ok() {
-                    drives.push(drive);
-                } else {
+                if fs::metadata(&drive).is_err() {
                     println!("Access denied to drive: {}", drive_str);
+                    continue;
                 }
-                i += 4;
+
+                let root = OsStr::new(drive_str)
+                    .encode_wide()
+                    .chain(Some(0))
+                    .collect::<Vec<_>>();
+                let mut label = [0u16; 261];
+                let mut fs_type = [0u16; 261];
+                let has_volume_information = GetVolumeInformationW(
+                    root.as_ptr(),
+                    label.as_mut_ptr(),
+                    label.len() as u32,
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    fs_type.as_mut_ptr(),
+                    fs_type.len() as u32,
+                ) != 0;
+                let from_wide = |buffer: &[u16]| {
+                    let len = buffer.iter().position(|c| *c == 0).unwrap_or(buffer.len());
+                    String::from_utf16_lossy(&buffer[..len])
+                };
+
+                let mut available: ULARGE_INTEGER = std::mem::zeroed();
+                let mut total: ULARGE_INTEGER = std::mem::zeroed();
+                let (total_bytes, available_bytes) = if GetDiskFreeSpaceExW(
+                    root.as_ptr(),
+                    &mut available,
+                    &mut total,
+                    ptr::null_mut(),
+                ) != 0
+                {
+                    (*total.QuadPart(), *available.QuadPart())
+                } else {
+                    (0, 0)
+                };
+
+                drives.push(Drive {
+                    mount_point: drive,
+                    device: drive_str.to_string(),
+                    label: Some(from_wide(&label))
+                        .filter(|label| has_volume_information && !label.is_empty()),
+                    fs_type: if has_volume_information {
+                        from_wide(&fs_type)
+                    } else {
+                        String::new()
+                    },
+                    total_bytes,
+                    available_bytes,
+                    removable: GetDriveTypeW(root.as_ptr()) == DRIVE_REMOVABLE,
+                    pseudo: false,
+                });
             }
-            self.drives = drives;
+            drives
         }
     }
 
-    #[cfg(target_family = "unix")]
-    fn fetch_drives(&mut self) {
+    /// Reads the mount table, leaving out pseudo filesystems unless
+    /// `show_all` is set.
+    #[cfg(target_os = "linux")]
+    fn fetch_drives(show_all: bool) -> Vec<Drive> {
+        ::fs::mounted_drives()
+            .into_iter()
+            .filter(|drive| show_all || !drive.pseudo)
+            .filter(|drive| {
+                // Check for access before adding the drive
+                if fs::metadata(&drive.mount_point).is_ok() {
+                    true
+                } else {
+                    println!("Access denied to drive: {}", drive.mount_point.display());
+                    false
+                }
+            })
+            .collect()
+    }
+
+    #[cfg(all(target_family = "unix", not(target_os = "linux")))]
+    fn fetch_drives(show_all: bool) -> Vec<Drive> {
         use sysinfo::{DiskExt, System, SystemExt};
 
         let sys = System::new_all();
         let drives = sys
             .disks()
-            .into_iter()
+            .iter()
             .filter_map(|disk| {
                 let path = disk.mount_point().to_path_buf();
                 // Check for access before adding the drive
-                if fs::metadata(&path).is_ok() {
-                    Some(path)
-                } else {
+                if fs::metadata(&path).is_err() {
                     println!("Access denied to drive: {}", path.display());
-                    None
+                    return None;
                 }
+
+                let fs_type = String::from_utf8_lossy(disk.file_system()).into_owned();
+                let pseudo = path.starts_with("/System/Volumes")
+                    || path.starts_with("/private/var/vm")
+                    || ["devfs", "autofs", "nullfs"].contains(&fs_type.as_str());
+                Some(Drive {
+                    label: path
+                        .starts_with("/Volumes")
+                        .then(|| path.file_name())
+                        .flatten()
+                        .map(|name| name.to_string_lossy().into_owned()),
+                    removable: path.starts_with("/Volumes"),
+                    device: disk.name().to_string_lossy().into_owned(),
+                    fs_type,
+                    total_bytes: disk.total_space(),
+                    available_bytes: disk.available_space(),
+                    mount_point: path,
+                    pseudo,
+                })
             })
+            .filter(|drive| show_all || !drive.pseudo)
             .collect::<Vec<_>>();
 
+        drives
+    }
+
+    fn open_folder(&mut self, folder: PathBuf, cx: &mut ModelContext<Self>) {
+        if self.history.get(self.history_ix) != Some(&folder) {
+            if !self.history.is_empty() {
+                self.history.truncate(self.history_ix + 1);
+            }
+            self.history.push(folder.clone());
+            self.history_ix = self.history.len() - 1;
+        }
+        self.load_folder(folder, cx);
+    }
+
+    /// Replaces the drive list after something was mounted or unmounted,
+    /// leaving for the home folder if the folder on show was on a drive that
+    /// is gone.
+    fn set_drives(&mut self, drives: Vec<Drive>, cx: &mut ModelContext<Self>) {
+        let unmounted = self.drives.iter().any(|drive| {
+            drive.mount_point.parent().is_some()
+                && self.path.starts_with(&drive.mount_point)
+                && !drives
+                    .iter()
+                    .any(|new_drive| new_drive.mount_point == drive.mount_point)
+        });
         self.drives = drives;
+        if unmounted {
+            self.open_folder(HOME.clone(), cx);
+        }
+        cx.notify();
     }
 
-    fn fetch_folder_contents(&mut self, folder: &str) {
-        self.folder_contents = fs::read_dir(folder)
-            .unwrap()
-            .filter_map(|entry| entry.ok().map(|e| e.path()))
-            .collect();
+    fn load_folder(&mut self, folder: PathBuf, cx: &mut ModelContext<Self>) {
+        self.folder_contents.clear();
+        self.stats.clear();
+        self.fetch_folder_contents(&folder, cx);
+        self.watch_folder(&folder, cx);
+        if cx.has_global::<Frecency>() {
+            cx.update_global::<Frecency, _>(|frecency, _cx| frecency.record_visit(&folder));
+        }
+        self.path = folder;
+        self.selection.clear();
+        cx.notify();
+    }
+
+    fn go_back(&mut self, cx: &mut ModelContext<Self>) {
+        if self.history_ix > 0 {
+            self.history_ix -= 1;
+            self.load_folder(self.history[self.history_ix].clone(), cx);
+        }
+    }
+
+    fn go_forward(&mut self, cx: &mut ModelContext<Self>) {
+        if self.history_ix + 1 < self.history.len() {
+            self.history_ix += 1;
+            self.load_folder(self.history[self.history_ix].clone(), cx);
+        }
+    }
+
+    /// Restores the history of a closed tab and opens the folder it was on.
+    fn restore_history(
+        &mut self,
+        history: Vec<PathBuf>,
+        history_ix: usize,
+        cx: &mut ModelContext<Self>,
+    ) {
+        let Some(folder) = history.get(history_ix).cloned() else {
+            return;
+        };
+        self.history = history;
+        self.history_ix = history_ix;
+        self.load_folder(folder, cx);
+    }
+
+    /// Selects `path`, or with `toggle` adds it to or removes it from the
+    /// selection.
+    fn select(&mut self, path: PathBuf, toggle: bool, cx: &mut ModelContext<Self>) {
+        if !toggle {
+            self.selection.clear();
+            self.selection.push(path);
+        } else if let Some(ix) = self.selection.iter().position(|selected| *selected == path) {
+            self.selection.remove(ix);
+        } else {
+            self.selection.push(path);
+        }
+        cx.notify();
+    }
+
+    fn set_view_mode(&mut self, view_mode: ViewMode, cx: &mut ModelContext<Self>) {
+        self.view_mode = view_mode;
+        cx.notify();
+    }
+
+    /// Reads the current folder again after its contents changed.
+    fn reload(&mut self, cx: &mut ModelContext<Self>) {
+        if self.path.as_os_str().is_empty() {
+            return;
+        }
+        self.fetch_folder_contents(&self.path.clone(), cx);
+    }
+
+    /// Lists `folder` and stats what's in it on the background executor,
+    /// then shows it. A folder read again replaces the listing once the new
+    /// one is in, keeping what's still there selected.
+    fn fetch_folder_contents(&mut self, folder: &Path, cx: &mut ModelContext<Self>) {
+        let folder = folder.to_path_buf();
+        let file_systems = Providers::get(cx);
+        let work = cx.background_executor().spawn({
+            let folder = folder.clone();
+            async move {
+                let provider = file_systems.provider(&folder)?;
+                let contents = provider.list(&folder)?;
+                let stats = contents
+                    .iter()
+                    .filter_map(|path| Some((path.clone(), provider.stat(path).ok()?)))
+                    .collect::<HashMap<_, _>>();
+                anyhow::Ok((contents, stats))
+            }
+        });
+
+        // Replacing the task drops the one reading a folder shown before.
+        self.loading = Some(cx.spawn(|this, mut cx| async move {
+            let result = work.await;
+            this.update(&mut cx, |this, cx| {
+                this.loading = None;
+                let (contents, stats) = result.unwrap_or_else(|e| {
+                    eprintln!(
+                        "Failed to read directory: {}. Error: {}",
+                        folder.display(),
+                        e
+                    );
+                    Default::default()
+                });
+                this.folder_contents = contents;
+                this.stats = stats;
+                this.selection
+                    .retain(|path| this.folder_contents.contains(path));
+                cx.notify();
+            })
+            .ok();
+        }));
+    }
+
+    /// What the folder's listing said about `path`.
+    fn stat(&self, path: &Path) -> Option<&FileStat> {
+        self.stats.get(path)
+    }
+
+    /// Changes only raise a flag, which is checked now and then, so a burst
+    /// of them reads the folder once. Locations that can't be watched are
+    /// read again after jobs instead.
+    fn watch_folder(&mut self, folder: &Path, cx: &mut ModelContext<Self>) {
+        let changed = Arc::new(AtomicBool::new(false));
+        let watch = Providers::get(cx).provider(folder).and_then(|provider| {
+            let changed = changed.clone();
+            provider.watch(
+                folder,
+                Box::new(move |_events| changed.store(true, Ordering::Relaxed)),
+            )
+        });
+        let Ok(watch) = watch else {
+            self.watch = None;
+            return;
+        };
+
+        let task = cx.spawn(|this, mut cx| async move {
+            loop {
+                cx.background_executor().timer(FOLDER_CHECK_INTERVAL).await;
+                if !changed.swap(false, Ordering::Relaxed) {
+                    continue;
+                }
+                if this
+                    .update(&mut cx, |file_explorer, cx| file_explorer.reload(cx))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        self.watch = Some((watch, task));
     }
 
     fn initialize_directories(&self) {
@@ -201,32 +629,644 @@ impl FileExplorer {
     }
 }
 
-#[derive(Clone)]
 struct Main {
-    file_explorer: Model<FileExplorer>,
+    /// One pane normally, two side by side in commander mode.
+    panes: Vec<View<Pane>>,
+    active_pane: usize,
+    jobs: Model<Jobs>,
+    folder_tree: View<FolderTree>,
+    /// Shown on the right when toggled on.
+    preview: Option<View<PreviewPane>>,
+    clipboard: Option<(JobKind, Vec<PathBuf>)>,
     title_bar: View<TitleBar>,
+    search_bar: View<SearchBar>,
+    index: Option<Arc<IndexService>>,
+    modal: Option<AnyView>,
+    focus_handle: FocusHandle,
     new_path: Option<String>,
+    _mount_watcher: Option<MountWatcher>,
 }
 
 impl Main {
-    fn folder_contents_elements(&mut self, _cx: &mut ViewContext<Self>) -> Vec<AnyElement> {
-        let folder_contents = self.file_explorer.read(_cx).folder_contents.clone();
+    fn new(file_explorer: Model<FileExplorer>, cx: &mut ViewContext<Self>) -> Self {
+        let index_settings = &Settings::get(cx).index;
+        let index = index_settings.enabled.then(|| {
+            Arc::new(IndexService::start(
+                index_settings.roots.clone(),
+                index_settings.excluded.clone(),
+                INDEX.clone(),
+            ))
+        });
+
+        let roots = Self::folder_tree_roots(&file_explorer.read(cx).drives);
+        let folder_tree = cx.new_view(|cx| FolderTree::new(roots, cx));
+        cx.subscribe(
+            &folder_tree,
+            |this, _folder_tree, event: &FolderTreeEvent, cx| match event {
+                FolderTreeEvent::OpenFolder(folder) => {
+                    this.file_explorer(cx).update(cx, |file_explorer, cx| {
+                        file_explorer.open_folder(folder.clone(), cx)
+                    })
+                }
+            },
+        )
+        .detach();
+
+        let pane = cx.new_view(|cx| Pane::new(file_explorer, cx));
+        Self::observe_pane(&pane, cx);
+
+        let jobs = cx.new_model(|_cx| Jobs::default());
+        cx.observe(&jobs, |_this, _jobs, cx| cx.notify()).detach();
+        cx.subscribe(&jobs, |this, _jobs, event: &JobEvent, cx| match event {
+            JobEvent::Finished {
+                sources,
+                destination,
+            } => {
+                let mut folders = sources
+                    .iter()
+                    .filter_map(|source| source.parent().map(Path::to_path_buf))
+                    .collect::<Vec<_>>();
+                folders.push(destination.clone());
+                // Compressing writes a file rather than into a folder.
+                folders.extend(destination.parent().map(Path::to_path_buf));
+                for pane in &this.panes {
+                    pane.update(cx, |pane, cx| pane.reload_folders(&folders, cx));
+                }
+            }
+        })
+        .detach();
+
+        let mount_watcher = Self::watch_mounts(cx);
+
+        let title_bar = cx.new_view(|_cx| TitleBar::new("title_bar"));
+        let search_bar = cx.new_view(SearchBar::new);
+        cx.subscribe(
+            &search_bar,
+            |this, _search_bar, event: &SearchEvent, cx| match event {
+                SearchEvent::QueryChanged(query) => this
+                    .pane()
+                    .update(cx, |pane, cx| pane.search(query.clone(), cx)),
+            },
+        )
+        .detach();
+
+        let mut this = Self {
+            panes: vec![pane],
+            active_pane: 0,
+            jobs,
+            folder_tree,
+            preview: None,
+            clipboard: None,
+            title_bar,
+            search_bar,
+            index,
+            modal: None,
+            focus_handle: cx.focus_handle(),
+            new_path: None,
+            _mount_watcher: mount_watcher,
+        };
+        this.reveal_current_folder(cx);
+        this
+    }
+
+    fn folder_tree_roots(drives: &[Drive]) -> Vec<PathBuf> {
+        std::iter::once(HOME.clone())
+            .chain(drives.iter().map(|drive| drive.mount_point.clone()))
+            .collect()
+    }
+
+    /// Reads the drives again whenever something is mounted or unmounted.
+    /// The watcher only raises a flag, which is checked now and then, so a
+    /// burst of mounts is read once.
+    fn watch_mounts(cx: &mut ViewContext<Self>) -> Option<MountWatcher> {
+        let changed = Arc::new(AtomicBool::new(false));
+        let watcher = MountWatcher::new({
+            let changed = changed.clone();
+            move || changed.store(true, Ordering::Relaxed)
+        })
+        .map_err(|e| eprintln!("Failed to watch mounts. Error: {}", e))
+        .ok()?;
+
+        let show_all = Settings::get(cx).drives.show_all;
+        cx.spawn(|this, mut cx| async move {
+            loop {
+                cx.background_executor().timer(MOUNT_CHECK_INTERVAL).await;
+                if !changed.swap(false, Ordering::Relaxed) {
+                    continue;
+                }
+                let drives = cx
+                    .background_executor()
+                    .spawn(async move { FileExplorer::fetch_drives(show_all) })
+                    .await;
+                if this
+                    .update(&mut cx, |this, cx| this.set_drives(drives, cx))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        })
+        .detach();
+        Some(watcher)
+    }
+
+    fn set_drives(&mut self, drives: Vec<Drive>, cx: &mut ViewContext<Self>) {
+        let roots = Self::folder_tree_roots(&drives);
+        self.folder_tree
+            .update(cx, |folder_tree, cx| folder_tree.set_roots(roots, cx));
+        for pane in &self.panes {
+            pane.update(cx, |pane, cx| pane.set_drives(&drives, cx));
+        }
+        cx.notify();
+    }
+
+    fn observe_pane(pane: &View<Pane>, cx: &mut ViewContext<Self>) {
+        cx.observe(pane, |this, _pane, cx| {
+            this.reveal_current_folder(cx);
+            this.update_preview(cx);
+            cx.notify();
+        })
+        .detach();
+    }
+
+    /// Keeps the folder tree in step with the folder open in the active tab.
+    fn reveal_current_folder(&mut self, cx: &mut ViewContext<Self>) {
+        let mut path = self.file_explorer(cx).read(cx).path.clone();
+        // The tree only has real folders, so inside an archive it shows the
+        // folder holding the archive.
+        if let Some(folder) = ::fs::split_archive_path(&path)
+            .and_then(|(archive, _)| archive.parent().map(Path::to_path_buf))
+        {
+            path = folder;
+        }
+        self.folder_tree
+            .update(cx, |folder_tree, cx| folder_tree.reveal(&path, cx));
+    }
+
+    fn toggle_preview(&mut self, _: &TogglePreview, cx: &mut ViewContext<Self>) {
+        if self.preview.take().is_none() {
+            self.preview = Some(cx.new_view(|_cx| PreviewPane::default()));
+            self.update_preview(cx);
+        }
+        cx.notify();
+    }
+
+    /// Points the preview at the last selected entry of the active tab.
+    fn update_preview(&mut self, cx: &mut ViewContext<Self>) {
+        let Some(preview) = self.preview.clone() else {
+            return;
+        };
+        let selected = self.file_explorer(cx).read(cx).selection.last().cloned();
+        preview.update(cx, |preview, cx| preview.show(selected, cx));
+    }
 
-        let elements: Vec<AnyElement> = folder_contents
+    /// Opens Quick Look on the last selected item of the active tab.
+    fn quick_look(&mut self, _: &QuickLook, cx: &mut ViewContext<Self>) {
+        let Some(selected) = self.file_explorer(cx).read(cx).selection.last().cloned() else {
+            return;
+        };
+        let mut items = self.pane().read(cx).visible_items(cx);
+        let ix = match items.iter().position(|item| *item == selected) {
+            Some(ix) => ix,
+            None => {
+                items = vec![selected];
+                0
+            }
+        };
+        let quick_look = cx.new_view(|cx| QuickLookModal::new(items, ix, cx));
+        self.show_modal(quick_look, cx);
+    }
+
+    /// Shows the properties of the selection in the active tab, or of the
+    /// current folder when nothing is selected.
+    fn show_properties(&mut self, _: &ShowProperties, cx: &mut ViewContext<Self>) {
+        let file_explorer = self.file_explorer(cx);
+        let file_explorer = file_explorer.read(cx);
+        let paths = if file_explorer.selection.is_empty() {
+            vec![file_explorer.path.clone()]
+        } else {
+            file_explorer.selection.clone()
+        };
+        let properties = cx.new_view(|cx| PropertiesDialog::new(paths, cx));
+        self.show_modal(properties, cx);
+    }
+
+    fn analyze_current_folder(&mut self, _: &AnalyzeDiskUsage, cx: &mut ViewContext<Self>) {
+        let path = self.file_explorer(cx).read(cx).path.clone();
+        self.analyze_disk_usage(path, cx);
+    }
+
+    /// Opens the disk usage analyzer on `path`. Right-clicking a drive in the
+    /// sidebar does this for the drive.
+    fn analyze_disk_usage(&mut self, path: PathBuf, cx: &mut ViewContext<Self>) {
+        let disk_usage = cx.new_view(|cx| DiskUsageView::new(path, cx));
+        cx.subscribe(
+            &disk_usage,
+            |this, _disk_usage, event: &DiskUsageEvent, cx| match event {
+                DiskUsageEvent::Reveal(path) => {
+                    let (folder, selected) = if path.is_dir() {
+                        (path.clone(), None)
+                    } else {
+                        match path.parent() {
+                            Some(parent) => (parent.to_path_buf(), Some(path.clone())),
+                            None => return,
+                        }
+                    };
+                    this.file_explorer(cx).update(cx, |file_explorer, cx| {
+                        file_explorer.open_folder(folder, cx);
+                        if let Some(selected) = selected {
+                            file_explorer.select(selected, false, cx);
+                        }
+                    });
+                }
+                DiskUsageEvent::Trashed(path) => {
+                    let folders = path
+                        .parent()
+                        .map(Path::to_path_buf)
+                        .into_iter()
+                        .collect::<Vec<_>>();
+                    for pane in &this.panes {
+                        pane.update(cx, |pane, cx| pane.reload_folders(&folders, cx));
+                    }
+                }
+            },
+        )
+        .detach();
+        self.show_modal(disk_usage, cx);
+    }
+
+    fn compress(&mut self, _: &Compress, cx: &mut ViewContext<Self>) {
+        let (sources, folder) = {
+            let file_explorer = self.file_explorer(cx);
+            let file_explorer = file_explorer.read(cx);
+            (file_explorer.selection.clone(), file_explorer.path.clone())
+        };
+        if sources.is_empty() || folder.as_os_str().is_empty() {
+            return;
+        }
+
+        let jobs = self.jobs.clone();
+        let dialog = cx.new_view(|cx| CompressDialog::new(sources, folder, jobs, cx));
+        self.show_modal(dialog, cx);
+    }
+
+    /// The selected archives in the active tab.
+    fn selected_archives(&self, cx: &AppContext) -> Vec<PathBuf> {
+        self.file_explorer(cx)
+            .read(cx)
+            .selection
             .iter()
-            .map(|item| {
-                let is_folder = item.is_dir();
+            .filter(|path| ArchiveFormat::from_path(path).is_some() && path.is_file())
+            .cloned()
+            .collect()
+    }
 
-                FileItem::new(
-                    item,
-                    Some(Arc::new(Mutex::new(move |path: &str| {}))),
-                    is_folder,
-                )
-                .into_any_element()
+    fn extract_here(&mut self, _: &ExtractHere, cx: &mut ViewContext<Self>) {
+        let archives = self.selected_archives(cx);
+        let destination = self.file_explorer(cx).read(cx).path.clone();
+        if archives.is_empty() || destination.as_os_str().is_empty() {
+            return;
+        }
+
+        self.jobs.update(cx, |jobs, cx| {
+            jobs.start(JobKind::Extract, archives, destination, cx)
+        });
+    }
+
+    /// Asks where to extract the selected archives, suggesting the folder
+    /// open in the other pane if there is one.
+    fn extract_to(&mut self, _: &ExtractTo, cx: &mut ViewContext<Self>) {
+        let archives = self.selected_archives(cx);
+        if archives.is_empty() {
+            return;
+        }
+
+        let pane = &self.panes[(self.active_pane + 1) % self.panes.len()];
+        let folder = pane.read(cx).file_explorer().read(cx).path.clone();
+        let jobs = self.jobs.clone();
+        let dialog = cx.new_view(|cx| ExtractDialog::new(archives, folder, jobs, cx));
+        self.show_modal(dialog, cx);
+    }
+
+    fn connect_to_server(&mut self, _: &ConnectToServer, cx: &mut ViewContext<Self>) {
+        let file_explorer = self.file_explorer(cx);
+        let dialog = cx.new_view(|cx| ConnectDialog::new(file_explorer, cx));
+        self.show_modal(dialog, cx);
+    }
+
+    /// Opens a folder picked in the sidebar. Saved WebDAV servers whose
+    /// password isn't known since the start ask for it first.
+    fn open_sidebar_folder(&mut self, label: String, folder: PathBuf, cx: &mut ViewContext<Self>) {
+        let file_explorer = self.file_explorer(cx);
+        if Providers::webdav(cx).is_some_and(|webdav| webdav.needs_password(&folder)) {
+            let dialog =
+                cx.new_view(|cx| ConnectDialog::asking_password(&folder, file_explorer, cx));
+            self.show_modal(dialog, cx);
+            return;
+        }
+
+        file_explorer.update(cx, |file_explorer, cx| {
+            file_explorer.text = label;
+            file_explorer.open_folder(folder, cx);
+        });
+        cx.notify();
+    }
+
+    fn rename(&mut self, _: &Rename, cx: &mut ViewContext<Self>) {
+        let file_explorer = self.file_explorer(cx);
+        let [path] = file_explorer.read(cx).selection.as_slice() else {
+            return;
+        };
+        let path = path.clone();
+        let dialog = cx.new_view(|cx| RenameDialog::new(path, file_explorer, cx));
+        self.show_modal(dialog, cx);
+    }
+
+    /// Moves the selection to the trash, or deletes it for good on remote
+    /// locations, which have no trash, once that is confirmed.
+    fn delete(&mut self, _: &Delete, cx: &mut ViewContext<Self>) {
+        let paths = self.file_explorer(cx).read(cx).selection.clone();
+        if paths.is_empty() {
+            return;
+        }
+
+        let file_systems = Providers::get(cx);
+        let permanent = paths
+            .iter()
+            .filter(|path| !file_systems.is_local(path))
+            .count();
+        if permanent == 0 {
+            return self.delete_paths(paths, cx);
+        }
+
+        let dialog = cx.new_view(|cx| DeleteDialog::new(paths, permanent, cx));
+        cx.subscribe(&dialog, |this, _dialog, event: &DeleteConfirmed, cx| {
+            this.delete_paths(event.0.clone(), cx)
+        })
+        .detach();
+        self.show_modal(dialog, cx);
+    }
+
+    fn delete_paths(&mut self, paths: Vec<PathBuf>, cx: &mut ViewContext<Self>) {
+        let folder = self.file_explorer(cx).read(cx).path.clone();
+        let file_systems = Providers::get(cx);
+        let work = cx.background_executor().spawn(async move {
+            for path in &paths {
+                let result = if file_systems.is_local(path) {
+                    ::fs::trash(path).map(|_| ())
+                } else {
+                    file_systems
+                        .provider(path)
+                        .and_then(|provider| provider.delete(path))
+                };
+                if let Err(e) = result {
+                    eprintln!("Failed to delete: {}. Error: {}", path.display(), e);
+                }
+            }
+        });
+        cx.spawn(|this, mut cx| async move {
+            work.await;
+            this.update(&mut cx, |this, cx| {
+                for pane in &this.panes {
+                    pane.update(cx, |pane, cx| pane.reload_folders(&[folder.clone()], cx));
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn show_modal<V: ManagedView>(&mut self, modal: View<V>, cx: &mut ViewContext<Self>) {
+        cx.subscribe(&modal, |this, _modal, _event: &DismissEvent, cx| {
+            this.modal = None;
+            cx.focus(&this.focus_handle);
+            cx.notify();
+        })
+        .detach();
+
+        modal.focus_handle(cx).focus(cx);
+        self.modal = Some(modal.into());
+        cx.notify();
+    }
+
+    fn global_search(&mut self, _: &GlobalSearch, cx: &mut ViewContext<Self>) {
+        let delegate = GlobalSearchDelegate::new(self.index.clone(), self.file_explorer(cx));
+        let picker = cx.new_view(|cx| Picker::new(delegate, cx));
+        self.show_modal(picker, cx);
+    }
+
+    fn go_to_folder(&mut self, _: &GoToFolder, cx: &mut ViewContext<Self>) {
+        let delegate = GoToFolderDelegate::new(self.file_explorer(cx), cx);
+        let picker = cx.new_view(|cx| Picker::new(delegate, cx));
+        self.show_modal(picker, cx);
+    }
+
+    fn pane(&self) -> &View<Pane> {
+        &self.panes[self.active_pane]
+    }
+
+    fn file_explorer(&self, cx: &AppContext) -> Model<FileExplorer> {
+        self.pane().read(cx).file_explorer().clone()
+    }
+
+    fn new_tab(&mut self, _: &NewTab, cx: &mut ViewContext<Self>) {
+        self.pane().update(cx, |pane, cx| pane.new_tab(None, cx));
+    }
+
+    fn close_tab(&mut self, _: &CloseTab, cx: &mut ViewContext<Self>) {
+        self.pane().update(cx, |pane, cx| pane.close_active_tab(cx));
+    }
+
+    fn next_tab(&mut self, _: &NextTab, cx: &mut ViewContext<Self>) {
+        self.pane()
+            .update(cx, |pane, cx| pane.activate_next_tab(cx));
+    }
+
+    fn previous_tab(&mut self, _: &PreviousTab, cx: &mut ViewContext<Self>) {
+        self.pane()
+            .update(cx, |pane, cx| pane.activate_previous_tab(cx));
+    }
+
+    fn reopen_closed_tab(&mut self, _: &ReopenClosedTab, cx: &mut ViewContext<Self>) {
+        self.pane()
+            .update(cx, |pane, cx| pane.reopen_closed_tab(cx));
+    }
+
+    fn go_back(&mut self, _: &GoBack, cx: &mut ViewContext<Self>) {
+        self.file_explorer(cx)
+            .update(cx, |file_explorer, cx| file_explorer.go_back(cx));
+    }
+
+    fn go_forward(&mut self, _: &GoForward, cx: &mut ViewContext<Self>) {
+        self.file_explorer(cx)
+            .update(cx, |file_explorer, cx| file_explorer.go_forward(cx));
+    }
+
+    fn show_grid(&mut self, _: &ShowGrid, cx: &mut ViewContext<Self>) {
+        self.file_explorer(cx).update(cx, |file_explorer, cx| {
+            file_explorer.set_view_mode(ViewMode::Grid, cx)
+        });
+    }
+
+    fn show_details(&mut self, _: &ShowDetails, cx: &mut ViewContext<Self>) {
+        self.file_explorer(cx).update(cx, |file_explorer, cx| {
+            file_explorer.set_view_mode(ViewMode::Details, cx)
+        });
+    }
+
+    fn copy(&mut self, _: &Copy, cx: &mut ViewContext<Self>) {
+        self.set_clipboard(JobKind::Copy, cx);
+    }
+
+    fn cut(&mut self, _: &Cut, cx: &mut ViewContext<Self>) {
+        self.set_clipboard(JobKind::Move, cx);
+    }
+
+    fn set_clipboard(&mut self, kind: JobKind, cx: &mut ViewContext<Self>) {
+        let selection = self.file_explorer(cx).read(cx).selection.clone();
+        if !selection.is_empty() {
+            self.clipboard = Some((kind, selection));
+        }
+    }
+
+    fn paste(&mut self, _: &Paste, cx: &mut ViewContext<Self>) {
+        let Some((kind, sources)) = self.clipboard.clone() else {
+            return;
+        };
+        let destination = self.file_explorer(cx).read(cx).path.clone();
+        if destination.as_os_str().is_empty() {
+            return;
+        }
+
+        // Cut files can only be pasted once, they're gone from where they were.
+        if kind == JobKind::Move {
+            self.clipboard = None;
+        }
+        self.jobs
+            .update(cx, |jobs, cx| jobs.start(kind, sources, destination, cx));
+    }
+
+    fn toggle_dual_pane(&mut self, _: &ToggleDualPane, cx: &mut ViewContext<Self>) {
+        if self.panes.len() > 1 {
+            let pane = self.pane().clone();
+            self.panes = vec![pane];
+            self.active_pane = 0;
+            self.reveal_current_folder(cx);
+        } else {
+            let (folder, drives) = {
+                let file_explorer = self.file_explorer(cx);
+                let file_explorer = file_explorer.read(cx);
+                (file_explorer.path.clone(), file_explorer.drives.clone())
+            };
+            let file_explorer = cx.new_model(|cx| {
+                let mut file_explorer = FileExplorer::new(drives);
+                file_explorer.open_folder(folder, cx);
+                file_explorer
+            });
+            let pane = cx.new_view(|cx| Pane::new(file_explorer, cx));
+            Self::observe_pane(&pane, cx);
+            self.panes.push(pane);
+        }
+        cx.notify();
+    }
+
+    fn switch_pane(&mut self, _: &SwitchPane, cx: &mut ViewContext<Self>) {
+        self.activate_pane((self.active_pane + 1) % self.panes.len(), cx);
+    }
+
+    fn activate_pane(&mut self, ix: usize, cx: &mut ViewContext<Self>) {
+        if ix < self.panes.len() && ix != self.active_pane {
+            self.active_pane = ix;
+            let query = self.search_bar.read(cx).query().clone();
+            self.pane().update(cx, |pane, cx| pane.search(query, cx));
+            self.reveal_current_folder(cx);
+            self.update_preview(cx);
+            cx.notify();
+        }
+    }
+
+    fn copy_to_other_pane(&mut self, _: &CopyToOtherPane, cx: &mut ViewContext<Self>) {
+        self.transfer_to_other_pane(JobKind::Copy, cx);
+    }
+
+    fn move_to_other_pane(&mut self, _: &MoveToOtherPane, cx: &mut ViewContext<Self>) {
+        self.transfer_to_other_pane(JobKind::Move, cx);
+    }
+
+    /// Copies or moves the selection in the active pane into the folder open
+    /// in the other one.
+    fn transfer_to_other_pane(&mut self, kind: JobKind, cx: &mut ViewContext<Self>) {
+        if self.panes.len() < 2 {
+            return;
+        }
+
+        let other_pane = &self.panes[(self.active_pane + 1) % self.panes.len()];
+        let destination = other_pane.read(cx).file_explorer().read(cx).path.clone();
+        let sources = self.file_explorer(cx).read(cx).selection.clone();
+        if sources.is_empty() || destination.as_os_str().is_empty() {
+            return;
+        }
+
+        self.jobs
+            .update(cx, |jobs, cx| jobs.start(kind, sources, destination, cx));
+    }
+
+    fn render_jobs(&self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let jobs = self
+            .jobs
+            .read(cx)
+            .jobs()
+            .iter()
+            .map(|job| {
+                let id = job.id;
+                let (status, action) = match &job.state {
+                    JobState::Running => {
+                        (format!("{:.0}%", job.progress.fraction() * 100.), "Cancel")
+                    }
+                    JobState::Failed(errors) => (errors.join(", "), "Dismiss"),
+                };
+                let failed = matches!(job.state, JobState::Failed(_));
+
+                div()
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .gap(px(10.))
+                    .px(px(10.))
+                    .py(px(6.))
+                    .rounded(px(8.))
+                    .bg(rgba(0xffffff0d))
+                    .child(div().flex_1().child(job.description()))
+                    .child(
+                        div()
+                            .text_color(if failed { rgb(0xf87171) } else { rgb(0xa3a3a3) })
+                            .overflow_hidden()
+                            .whitespace_nowrap()
+                            .child(status),
+                    )
+                    .child(
+                        div()
+                            .px(px(6.))
+                            .rounded(px(4.))
+                            .hover(|style| style.bg(rgba(0xffffff1a)))
+                            .on_mouse_down(
+                                gpui::MouseButton::Left,
+                                cx.listener(move |this, _event, cx| {
+                                    this.jobs.update(cx, |jobs, cx| {
+                                        if failed {
+                                            jobs.dismiss(id, cx)
+                                        } else {
+                                            jobs.cancel(id)
+                                        }
+                                    })
+                                }),
+                            )
+                            .child(action),
+                    )
             })
-            .collect();
+            .collect::<Vec<_>>();
 
-        elements
+        div().flex().flex_col().gap(px(4.)).children(jobs)
     }
 
     fn check_and_update_path(
@@ -236,7 +1276,7 @@ impl Main {
     ) {
         let new_path_locked = new_path.lock().unwrap();
         if let Some(ref new_path_str) = *new_path_locked {
-            self.file_explorer.update(cx, |file_explorer, _cx| {
+            self.file_explorer(cx).update(cx, |file_explorer, _cx| {
                 file_explorer.path = PathBuf::from(new_path_str);
             });
         }
@@ -245,7 +1285,7 @@ impl Main {
 
 impl Render for Main {
     fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
-        let self_clone = self.clone();
+        let file_explorer = self.file_explorer(cx);
 
         let make_separator = || {
             div()
@@ -286,19 +1326,14 @@ impl Render for Main {
                     .hover(|style| style.bg(rgba(0xffffff05)))
                     .on_mouse_down(
                         gpui::MouseButton::Left,
-                        cx.listener(move |_this, _event, cx| {
-                            self_clone.file_explorer.update(cx, |_file_explorer, _cx| {
-                                _file_explorer.text = label_owned.clone();
-                                _file_explorer.path = folder_owned.clone();
-                                _file_explorer.fetch_folder_contents(folder_owned.to_str().unwrap())
-                            });
-                            cx.notify();
+                        cx.listener(move |this, _event, cx| {
+                            this.open_sidebar_folder(label_owned.clone(), folder_owned.clone(), cx)
                         }),
                     )
             };
 
         let file_explorer_path = self
-            .file_explorer
+            .file_explorer(cx)
             .read(cx)
             .path
             .to_str()
@@ -314,9 +1349,9 @@ impl Render for Main {
 
         if let Some(ref new_path) = self.new_path {
             if !new_path.is_empty() {
-                self.file_explorer.update(cx, |file_explorer, _cx| {
+                self.file_explorer(cx).update(cx, |file_explorer, cx| {
                     file_explorer.path = PathBuf::from(new_path);
-                    file_explorer.fetch_folder_contents(new_path);
+                    file_explorer.fetch_folder_contents(Path::new(new_path), cx);
                 });
                 self.new_path = None;
             }
@@ -325,80 +1360,236 @@ impl Render for Main {
         let mut sidebar_items_after_separator = div();
         sidebar_items_after_separator = sidebar_items_after_separator.child(make_separator());
 
-        let drives = self.file_explorer.read(cx).drives.clone();
+        let drives = self.file_explorer(cx).read(cx).drives.clone();
 
+        let drive_icon = |icon_type: &str| {
+            FileIcons::get(cx)
+                .get_type_icon(icon_type)
+                .map(|icon| icon.to_string())
+                .unwrap_or_else(|| "icons/file_icons/hard_drive.svg".into())
+        };
+        let (fixed_icon, removable_icon) = (drive_icon("drive"), drive_icon("drive_removable"));
         for drive in drives {
-            sidebar_items_after_separator = sidebar_items_after_separator.child(make_sidebar_item
-                .clone()(
-                drive.to_str().unwrap(),
-                &drive,
+            let icon = if drive.removable {
+                &removable_icon
+            } else {
+                &fixed_icon
+            };
+            let mut details = vec![format_size(drive.available_bytes) + " free"];
+            if !drive.fs_type.is_empty() {
+                details.insert(0, drive.fs_type.clone());
+            }
+            let path = drive.mount_point.clone();
+
+            let item = div()
+                .flex()
+                .flex_col()
+                .on_mouse_down(
+                    gpui::MouseButton::Right,
+                    cx.listener(move |this, _event, cx| this.analyze_disk_usage(path.clone(), cx)),
+                )
+                .child(make_sidebar_item.clone()(
+                    &drive.display_name(),
+                    &drive.mount_point,
+                    cx,
+                    icon,
+                ))
+                .when(drive.total_bytes > 0, |this| {
+                    this.child(
+                        div()
+                            .flex()
+                            .flex_col()
+                            .gap(px(3.))
+                            .px(px(10.))
+                            .pb(px(6.))
+                            .child(
+                                div()
+                                    .h(px(3.))
+                                    .w_full()
+                                    .rounded(px(2.))
+                                    .bg(rgba(0xffffff1a))
+                                    .child(
+                                        div()
+                                            .h_full()
+                                            .w(relative(drive.used_fraction()))
+                                            .rounded(px(2.))
+                                            .bg(if drive.used_fraction() > 0.9 {
+                                                rgb(0xf87171)
+                                            } else {
+                                                rgb(0x3b82f6)
+                                            }),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .text_size(px(11.))
+                                    .text_color(rgb(0xa3a3a3))
+                                    .overflow_hidden()
+                                    .whitespace_nowrap()
+                                    .child(details.join(" · ")),
+                            ),
+                    )
+                });
+            sidebar_items_after_separator = sidebar_items_after_separator.child(item);
+        }
+
+        let connections = cx.global::<Connections>().all().to_vec();
+        if !connections.is_empty() {
+            sidebar_items_after_separator = sidebar_items_after_separator.child(make_separator());
+        }
+        let server_icon = FileIcons::get(cx)
+            .get_type_icon("server")
+            .map(|icon| icon.to_string())
+            .unwrap_or_else(|| "icons/file_icons/hard_drive.svg".into());
+        for connection in connections {
+            let location = connection.location.clone();
+            // Right-clicking a saved server forgets it.
+            let item = div()
+                .on_mouse_down(
+                    gpui::MouseButton::Right,
+                    cx.listener(move |_this, _event, cx| {
+                        cx.update_global::<Connections, _>(|connections, _cx| {
+                            connections.remove(&location)
+                        });
+                        cx.notify();
+                    }),
+                )
+                .child(make_sidebar_item.clone()(
+                    &connection.name,
+                    &connection.location,
+                    cx,
+                    &server_icon,
+                ));
+            sidebar_items_after_separator = sidebar_items_after_separator.child(item);
+        }
+
+        let mut sidebar_items = div().rounded_bl_lg().px(px(8.)).py(px(10.)).flex_col();
+
+        for place in places(cx) {
+            let icon = place.icon(cx);
+            // A user folder that's set but gone can't be opened.
+            if place.missing {
+                sidebar_items = sidebar_items.child(
+                    div()
+                        .rounded(px(8.))
+                        .line_height(px(35.))
+                        .px(px(10.))
+                        .text_color(rgb(0x6b6b6b))
+                        .flex()
+                        .flex_row()
+                        .gap(px(12.))
+                        .items_center()
+                        .child(
+                            svg()
+                                .path(icon)
+                                .w(px(16.))
+                                .h(px(16.))
+                                .text_color(rgb(0x6b6b6b)),
+                        )
+                        .child(
+                            div()
+                                .child(place.label)
+                                .overflow_hidden()
+                                .whitespace_nowrap(),
+                        ),
+                );
+                continue;
+            }
+            sidebar_items = sidebar_items.child(make_sidebar_item.clone()(
+                place.label,
+                &place.path,
                 cx,
-                "icons/file_icons/hard_drive.svg",
+                &icon,
             ));
         }
 
-        let sidebar_items = div()
-            .rounded_bl_lg()
-            .px(px(8.))
-            .py(px(10.))
-            .flex_col()
-            .child(make_sidebar_item.clone()(
-                "Recent",
-                &RECENT,
-                cx,
-                "icons/file_icons/clock.svg",
-            ))
-            .child(make_sidebar_item.clone()(
-                "Favorites",
-                &FAVORITES,
-                cx,
-                "icons/file_icons/star.svg",
-            ))
-            .child(make_sidebar_item.clone()(
-                "Home",
-                &HOME,
-                cx,
-                "icons/file_icons/house.svg",
-            ))
-            .child(make_sidebar_item.clone()(
-                "Documents",
-                &DOCUMENTS,
-                cx,
-                "icons/file_icons/file_text.svg",
-            ))
-            .child(make_sidebar_item.clone()(
-                "Downloads",
-                &DOWNLOADS,
-                cx,
-                "icons/file_icons/download.svg",
-            ))
-            .child(make_sidebar_item.clone()(
-                "Music",
-                &MUSIC,
-                cx,
-                "icons/file_icons/music.svg",
-            ))
-            .child(make_sidebar_item.clone()(
-                "Pictures",
-                &PICTURES,
-                cx,
-                "icons/file_icons/image.svg",
-            ))
-            .child(make_sidebar_item.clone()(
-                "Videos",
-                &VIDEOS,
-                cx,
-                "icons/file_icons/video.svg",
-            ))
-            .child(make_sidebar_item.clone()(
-                "Trash",
-                &TRASH,
+        let bookmarks = Bookmarks::get(cx).to_vec();
+        if !bookmarks.is_empty() {
+            sidebar_items = sidebar_items.child(make_separator()).child(
+                div()
+                    .px(px(10.))
+                    .py(px(4.))
+                    .text_size(px(11.))
+                    .text_color(rgb(0xa3a3a3))
+                    .child("Bookmarks"),
+            );
+        }
+        let folder_icon = FileIcons::get(cx)
+            .get_type_icon("collapsed_folder")
+            .map(|icon| icon.to_string())
+            .unwrap_or_else(|| "icons/file_icons/folder.svg".into());
+        for bookmark in bookmarks {
+            let icon = if ::fs::location_scheme(&bookmark.path).is_some() {
+                &server_icon
+            } else {
+                &folder_icon
+            };
+            sidebar_items = sidebar_items.child(make_sidebar_item.clone()(
+                &bookmark.label,
+                &bookmark.path,
                 cx,
-                "icons/file_icons/trash.svg",
-            ))
-            .child(sidebar_items_after_separator);
+                icon,
+            ));
+        }
+
+        let sidebar_items = sidebar_items.child(sidebar_items_after_separator);
+
+        let dual_pane = self.panes.len() > 1;
+        let panes = self
+            .panes
+            .iter()
+            .enumerate()
+            .map(|(ix, pane)| {
+                div()
+                    .flex_1()
+                    .p(px(8.))
+                    .rounded(px(8.))
+                    .border_1()
+                    .border_color(if dual_pane && ix == self.active_pane {
+                        rgba(0xffffff26)
+                    } else {
+                        rgba(0x00000000)
+                    })
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(move |this, _event, cx| this.activate_pane(ix, cx)),
+                    )
+                    .child(pane.clone())
+            })
+            .collect::<Vec<_>>();
 
         div()
+            .key_context("FileExplorer")
+            .track_focus(&self.focus_handle)
+            .on_action(cx.listener(Self::global_search))
+            .on_action(cx.listener(Self::go_to_folder))
+            .on_action(cx.listener(Self::new_tab))
+            .on_action(cx.listener(Self::close_tab))
+            .on_action(cx.listener(Self::next_tab))
+            .on_action(cx.listener(Self::previous_tab))
+            .on_action(cx.listener(Self::reopen_closed_tab))
+            .on_action(cx.listener(Self::go_back))
+            .on_action(cx.listener(Self::go_forward))
+            .on_action(cx.listener(Self::show_grid))
+            .on_action(cx.listener(Self::show_details))
+            .on_action(cx.listener(Self::copy))
+            .on_action(cx.listener(Self::cut))
+            .on_action(cx.listener(Self::paste))
+            .on_action(cx.listener(Self::toggle_dual_pane))
+            .on_action(cx.listener(Self::switch_pane))
+            .on_action(cx.listener(Self::copy_to_other_pane))
+            .on_action(cx.listener(Self::move_to_other_pane))
+            .on_action(cx.listener(Self::toggle_preview))
+            .on_action(cx.listener(Self::quick_look))
+            .on_action(cx.listener(Self::show_properties))
+            .on_action(cx.listener(Self::analyze_current_folder))
+            .on_action(cx.listener(Self::compress))
+            .on_action(cx.listener(Self::extract_here))
+            .on_action(cx.listener(Self::extract_to))
+            .on_action(cx.listener(Self::connect_to_server))
+            .on_action(cx.listener(Self::rename))
+            .on_action(cx.listener(Self::delete))
+            .relative()
             .rounded_br_lg()
             .rounded_bl_lg()
             .flex()
@@ -418,7 +1609,17 @@ impl Render for Main {
                             .flex_col()
                             .w(px(150.))
                             .bg(rgb(0x19191a))
-                            .child(sidebar_items),
+                            .child(sidebar_items)
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .flex()
+                                    .flex_col()
+                                    .px(px(8.))
+                                    .pb(px(10.))
+                                    .child(make_separator())
+                                    .child(self.folder_tree.clone()),
+                            ),
                         div()
                             .rounded_br_lg()
                             .rounded_bl_lg()
@@ -426,60 +1627,118 @@ impl Render for Main {
                             .bg(rgb(0x232225))
                             .text_color(rgb(0xffffff))
                             .child(
-                                div().flex_1().p(px(16.)).child(
-                                    div()
-                                        .flex()
-                                        .flex_row()
-                                        .gap(px(20.))
-                                        .flex_wrap()
-                                        .children(self.folder_contents_elements(cx)),
-                                ),
+                                div()
+                                    .flex_1()
+                                    .p(px(16.))
+                                    .flex()
+                                    .flex_col()
+                                    .gap(px(12.))
+                                    .child(self.search_bar.clone())
+                                    .child(
+                                        div()
+                                            .flex_1()
+                                            .flex()
+                                            .flex_row()
+                                            .gap(px(12.))
+                                            .children(panes),
+                                    )
+                                    .child(self.render_jobs(cx)),
                             ),
-                    ]),
+                    ])
+                    .children(self.preview.clone()),
             )
+            .children(self.modal.clone().map(|modal| {
+                div()
+                    .absolute()
+                    .top_0()
+                    .left_0()
+                    .size_full()
+                    .flex()
+                    .justify_center()
+                    .pt(px(60.))
+                    .bg(rgba(0x00000066))
+                    .child(modal)
+            }))
+    }
+}
+
+impl FocusableView for Main {
+    fn focus_handle(&self, _cx: &AppContext) -> FocusHandle {
+        self.focus_handle.clone()
     }
 }
 
 fn main() {
     App::new().with_assets(Assets).run(|cx: &mut AppContext| {
-        let file_explorer_model = cx.new_model(|_cx| FileExplorer {
-            text: "Favorites".into(),
-            folder_contents: vec![],
-            path: PathBuf::new(),
-            drives: vec![],
-            current_folder: PathBuf::new(),
-        });
-
-        let title_bar_model = cx.new_model(|_cx| TitleBar {
-            path: "".to_string(),
+        cx.set_global(Settings::load());
+        file_icons::init(Assets, cx);
+        thumbnails::init(cx);
+        folder_sizes::init(cx);
+        archives::init(cx);
+        providers::init(cx);
+        user_dirs::init(cx);
+        bookmarks::init(cx);
+        let detect_content = Settings::get(cx).icons.detect_content;
+        cx.update_global::<FileIcons, _>(|file_icons, _cx| {
+            file_icons.set_detect_content(detect_content)
         });
+        frecency::init(FRECENCY.clone(), cx);
+        cx.set_global(Connections::load(CONNECTIONS.clone()));
+        cx.bind_keys([
+            KeyBinding::new("ctrl-shift-f", GlobalSearch, Some("FileExplorer")),
+            KeyBinding::new("ctrl-p", GoToFolder, Some("FileExplorer")),
+            KeyBinding::new("ctrl-l", GoToFolder, Some("FileExplorer")),
+            KeyBinding::new("ctrl-t", NewTab, Some("FileExplorer")),
+            KeyBinding::new("ctrl-w", CloseTab, Some("FileExplorer")),
+            KeyBinding::new("ctrl-tab", NextTab, Some("FileExplorer")),
+            KeyBinding::new("ctrl-shift-tab", PreviousTab, Some("FileExplorer")),
+            KeyBinding::new("ctrl-shift-t", ReopenClosedTab, Some("FileExplorer")),
+            KeyBinding::new("alt-left", GoBack, Some("FileExplorer")),
+            KeyBinding::new("alt-right", GoForward, Some("FileExplorer")),
+            KeyBinding::new("ctrl-1", ShowGrid, Some("FileExplorer")),
+            KeyBinding::new("ctrl-2", ShowDetails, Some("FileExplorer")),
+            KeyBinding::new("ctrl-c", Copy, Some("FileExplorer && !TextInput")),
+            KeyBinding::new("ctrl-x", Cut, Some("FileExplorer && !TextInput")),
+            KeyBinding::new("ctrl-v", Paste, Some("FileExplorer && !TextInput")),
+            KeyBinding::new("ctrl-\\", ToggleDualPane, Some("FileExplorer")),
+            KeyBinding::new("tab", SwitchPane, Some("FileExplorer && !TextInput")),
+            KeyBinding::new("f5", CopyToOtherPane, Some("FileExplorer")),
+            KeyBinding::new("f6", MoveToOtherPane, Some("FileExplorer")),
+            KeyBinding::new("alt-p", TogglePreview, Some("FileExplorer")),
+            KeyBinding::new(
+                "space",
+                QuickLook,
+                Some("FileExplorer && !TextInput && !QuickLook"),
+            ),
+            KeyBinding::new("alt-enter", ShowProperties, Some("FileExplorer")),
+            KeyBinding::new("ctrl-shift-u", AnalyzeDiskUsage, Some("FileExplorer")),
+            KeyBinding::new("ctrl-shift-c", Compress, Some("FileExplorer")),
+            KeyBinding::new("ctrl-shift-e", ExtractHere, Some("FileExplorer")),
+            KeyBinding::new("ctrl-alt-e", ExtractTo, Some("FileExplorer")),
+            KeyBinding::new("ctrl-k", ConnectToServer, Some("FileExplorer")),
+            KeyBinding::new("f2", Rename, Some("FileExplorer")),
+            KeyBinding::new("delete", Delete, Some("FileExplorer && !TextInput")),
+        ]);
 
-        cx.update_model(&file_explorer_model, |file_explorer, _ctx| {
+        let file_explorer_model = cx.new_model(|cx| {
+            let mut file_explorer = FileExplorer::new(vec![]);
             file_explorer.initialize_directories();
-            file_explorer.fetch_drives();
+            file_explorer.drives = FileExplorer::fetch_drives(Settings::get(cx).drives.show_all);
+            file_explorer.open_folder(HOME.clone(), cx);
+            file_explorer
         });
 
         let bounds = Bounds::centered(None, size(px(600.), px(600.)), cx);
 
-        let mut main_view = Main {
-            file_explorer: file_explorer_model,
-            title_bar: View {
-                model: title_bar_model,
-            },
-            new_path: None,
-        };
-
         cx.open_window(
             WindowOptions {
                 window_bounds: Some(WindowBounds::Windowed(bounds)),
                 ..Default::default()
             },
             move |cx| {
-                cx.new_view(|_cx| {
-                    let titlebar = _cx.new_view(|_cx| TitleBar::new("title_bar"));
-                    main_view.title_bar = titlebar;
-                    main_view
-                })
+                let main = cx.new_view(|cx| Main::new(file_explorer_model, cx));
+                cx.focus_view(&main);
+                main
             },
         );
     });
@@ -0,0 +1,538 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use ::fs::{ArchiveFormat, Drive, EntryKind, FileStat};
+use file_icons::FileIcons;
+use gpui::{
+    div, prelude::FluentBuilder, px, rgb, rgba, AnyElement, AppContext, Context,
+    InteractiveElement, IntoElement, Model, MouseButton, ParentElement, Render, SharedString,
+    StatefulInteractiveElement, Styled, Subscription, Task, ViewContext, VisualContext,
+};
+use search::{format_size, SearchQuery};
+use ui::{Clickable, FileItem, FileRow};
+
+use crate::{folder_sizes::FolderSizes, paths::HOME, providers::Providers, FileExplorer, ViewMode};
+
+const MAX_SEARCH_RESULTS: usize = 1000;
+const MAX_CLOSED_TABS: usize = 20;
+
+/// Enough of a closed tab to bring it back with its history intact.
+struct ClosedTab {
+    ix: usize,
+    history: Vec<PathBuf>,
+    history_ix: usize,
+    view_mode: ViewMode,
+}
+
+/// A tab and its observation of the file explorer in it, which ends when
+/// the tab is closed.
+struct Tab {
+    file_explorer: Model<FileExplorer>,
+    _observer: Subscription,
+}
+
+#[derive(Clone)]
+struct DraggedTab {
+    ix: usize,
+    label: SharedString,
+}
+
+impl Render for DraggedTab {
+    fn render(&mut self, _cx: &mut ViewContext<Self>) -> impl IntoElement {
+        div()
+            .px(px(12.))
+            .py(px(4.))
+            .rounded(px(6.))
+            .bg(rgb(0x2b2a2e))
+            .text_color(rgb(0xf3f3f3))
+            .child(self.label.clone())
+    }
+}
+
+/// A row of tabs, each browsing its own folder, and the contents of the
+/// active one.
+pub struct Pane {
+    tabs: Vec<Tab>,
+    active_tab: usize,
+    closed_tabs: Vec<ClosedTab>,
+    search_query: SearchQuery,
+    search_results: Option<Vec<PathBuf>>,
+    search_stats: HashMap<PathBuf, FileStat>,
+    search_cancel: Arc<AtomicBool>,
+    search_task: Option<Task<()>>,
+    current_path: PathBuf,
+}
+
+impl Pane {
+    pub fn new(file_explorer: Model<FileExplorer>, cx: &mut ViewContext<Self>) -> Self {
+        let mut this = Self {
+            tabs: vec![],
+            active_tab: 0,
+            closed_tabs: vec![],
+            search_query: SearchQuery::default(),
+            search_results: None,
+            search_stats: HashMap::new(),
+            search_cancel: Arc::new(AtomicBool::new(false)),
+            search_task: None,
+            current_path: PathBuf::new(),
+        };
+        this.add_tab(file_explorer, cx);
+        this
+    }
+
+    /// The file explorer of the active tab.
+    pub fn file_explorer(&self) -> &Model<FileExplorer> {
+        &self.tabs[self.active_tab].file_explorer
+    }
+
+    fn add_tab(&mut self, file_explorer: Model<FileExplorer>, cx: &mut ViewContext<Self>) {
+        self.insert_tab(self.tabs.len(), file_explorer, cx);
+    }
+
+    fn insert_tab(
+        &mut self,
+        ix: usize,
+        file_explorer: Model<FileExplorer>,
+        cx: &mut ViewContext<Self>,
+    ) {
+        let observer = cx.observe(&file_explorer, |this, file_explorer, cx| {
+            if *this.file_explorer() == file_explorer {
+                this.active_tab_changed(cx);
+            }
+            cx.notify();
+        });
+
+        let ix = ix.min(self.tabs.len());
+        self.tabs.insert(
+            ix,
+            Tab {
+                file_explorer,
+                _observer: observer,
+            },
+        );
+        self.activate_tab(ix, cx);
+    }
+
+    /// Opens `folder` in a new tab, next to the active one when `folder` is
+    /// given and at the end otherwise.
+    pub fn new_tab(&mut self, folder: Option<PathBuf>, cx: &mut ViewContext<Self>) {
+        let drives = self.file_explorer().read(cx).drives.clone();
+        let (ix, folder) = match folder {
+            Some(folder) => (self.active_tab + 1, folder),
+            None => (self.tabs.len(), HOME.clone()),
+        };
+
+        let file_explorer = cx.new_model(|cx| {
+            let mut file_explorer = FileExplorer::new(drives);
+            file_explorer.open_folder(folder, cx);
+            file_explorer
+        });
+        self.insert_tab(ix, file_explorer, cx);
+    }
+
+    /// Closes the tab at `ix`. Closing the last tab leaves a fresh one behind
+    /// so the pane is never empty.
+    pub fn close_tab(&mut self, ix: usize, cx: &mut ViewContext<Self>) {
+        if ix >= self.tabs.len() {
+            return;
+        }
+
+        let (history, history_ix, view_mode, drives) = {
+            let tab = self.tabs.remove(ix);
+            let file_explorer = tab.file_explorer.read(cx);
+            (
+                file_explorer.history.clone(),
+                file_explorer.history_ix,
+                file_explorer.view_mode,
+                file_explorer.drives.clone(),
+            )
+        };
+        if !history.is_empty() {
+            self.closed_tabs.push(ClosedTab {
+                ix,
+                history,
+                history_ix,
+                view_mode,
+            });
+            if self.closed_tabs.len() > MAX_CLOSED_TABS {
+                self.closed_tabs.remove(0);
+            }
+        }
+
+        if self.tabs.is_empty() {
+            let file_explorer = cx.new_model(|cx| {
+                let mut file_explorer = FileExplorer::new(drives);
+                file_explorer.open_folder(HOME.clone(), cx);
+                file_explorer
+            });
+            self.add_tab(file_explorer, cx);
+            return;
+        }
+
+        let active_tab = if ix < self.active_tab || self.active_tab >= self.tabs.len() {
+            self.active_tab.saturating_sub(1)
+        } else {
+            self.active_tab
+        };
+        self.activate_tab(active_tab, cx);
+    }
+
+    pub fn close_active_tab(&mut self, cx: &mut ViewContext<Self>) {
+        self.close_tab(self.active_tab, cx);
+    }
+
+    pub fn reopen_closed_tab(&mut self, cx: &mut ViewContext<Self>) {
+        let Some(closed_tab) = self.closed_tabs.pop() else {
+            return;
+        };
+
+        let drives = self.file_explorer().read(cx).drives.clone();
+        let file_explorer = cx.new_model(|cx| {
+            let mut file_explorer = FileExplorer::new(drives);
+            file_explorer.view_mode = closed_tab.view_mode;
+            file_explorer.restore_history(closed_tab.history, closed_tab.history_ix, cx);
+            file_explorer
+        });
+        self.insert_tab(closed_tab.ix, file_explorer, cx);
+    }
+
+    pub fn activate_tab(&mut self, ix: usize, cx: &mut ViewContext<Self>) {
+        if ix < self.tabs.len() {
+            self.active_tab = ix;
+            self.active_tab_changed(cx);
+            cx.notify();
+        }
+    }
+
+    pub fn activate_next_tab(&mut self, cx: &mut ViewContext<Self>) {
+        self.activate_tab((self.active_tab + 1) % self.tabs.len(), cx);
+    }
+
+    pub fn activate_previous_tab(&mut self, cx: &mut ViewContext<Self>) {
+        let ix = (self.active_tab + self.tabs.len() - 1) % self.tabs.len();
+        self.activate_tab(ix, cx);
+    }
+
+    fn move_tab(&mut self, from: usize, to: usize, cx: &mut ViewContext<Self>) {
+        if from == to || from >= self.tabs.len() || to >= self.tabs.len() {
+            return;
+        }
+
+        let active = self.file_explorer().clone();
+        let tab = self.tabs.remove(from);
+        self.tabs.insert(to, tab);
+        self.active_tab = self
+            .tabs
+            .iter()
+            .position(|tab| tab.file_explorer == active)
+            .unwrap_or(to);
+        cx.notify();
+    }
+
+    /// Re-reads `folders` in every tab showing one of them.
+    pub fn reload_folders(&mut self, folders: &[PathBuf], cx: &mut ViewContext<Self>) {
+        for tab in &self.tabs {
+            if folders.contains(&tab.file_explorer.read(cx).path) {
+                tab.file_explorer
+                    .update(cx, |file_explorer, cx| file_explorer.reload(cx));
+            }
+        }
+    }
+
+    pub fn set_drives(&mut self, drives: &[Drive], cx: &mut ViewContext<Self>) {
+        for tab in &self.tabs {
+            tab.file_explorer.update(cx, |file_explorer, cx| {
+                file_explorer.set_drives(drives.to_vec(), cx)
+            });
+        }
+    }
+
+    fn active_tab_changed(&mut self, cx: &mut ViewContext<Self>) {
+        let path = self.file_explorer().read(cx).path.clone();
+        if path != self.current_path {
+            self.current_path = path;
+            self.search(self.search_query.clone(), cx);
+        }
+    }
+
+    pub fn search(&mut self, query: SearchQuery, cx: &mut ViewContext<Self>) {
+        self.search_cancel.store(true, Ordering::Relaxed);
+        self.search_task = None;
+        self.search_query = query.clone();
+
+        if query.is_empty() {
+            self.search_results = None;
+            self.search_stats.clear();
+            cx.notify();
+            return;
+        }
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.search_cancel = cancel.clone();
+
+        let root = if self.current_path.as_os_str().is_empty() {
+            HOME.clone()
+        } else {
+            self.current_path.clone()
+        };
+        let icons = cx.try_global::<FileIcons>().cloned().unwrap_or_default();
+        let file_systems = Providers::get(cx);
+
+        self.search_task = Some(cx.spawn(|this, mut cx| async move {
+            let (results, stats) = cx
+                .background_executor()
+                .spawn(async move {
+                    let results = search::search(&root, &query, icons, MAX_SEARCH_RESULTS, &cancel);
+                    let stats = results
+                        .iter()
+                        .filter_map(|path| {
+                            let stat = file_systems.provider(path).ok()?.stat(path).ok()?;
+                            Some((path.clone(), stat))
+                        })
+                        .collect::<HashMap<_, _>>();
+                    (results, stats)
+                })
+                .await;
+
+            this.update(&mut cx, |this, cx| {
+                this.search_results = Some(results);
+                this.search_stats = stats;
+                cx.notify();
+            })
+            .ok();
+        }));
+    }
+
+    fn tab_label(path: &Path) -> SharedString {
+        if path.as_os_str().is_empty() {
+            "New Tab".into()
+        } else if path == HOME.as_path() {
+            "Home".into()
+        } else {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.to_string_lossy().into_owned())
+                .into()
+        }
+    }
+
+    fn render_tab_bar(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let tabs =
+            self.tabs
+                .iter()
+                .enumerate()
+                .map(|(ix, tab)| {
+                    let label = Self::tab_label(&tab.file_explorer.read(cx).path);
+                    let active = ix == self.active_tab;
+
+                    div()
+                        .id(("tab", ix))
+                        .flex()
+                        .flex_row()
+                        .items_center()
+                        .gap(px(6.))
+                        .max_w(px(160.))
+                        .px(px(10.))
+                        .py(px(4.))
+                        .rounded(px(6.))
+                        .text_color(rgb(0xf3f3f3))
+                        .when(active, |this| this.bg(rgba(0xffffff1a)))
+                        .hover(|style| style.bg(rgba(0xffffff0d)))
+                        .on_click(cx.listener(move |this, _event, cx| this.activate_tab(ix, cx)))
+                        .on_mouse_down(
+                            MouseButton::Middle,
+                            cx.listener(move |this, _event, cx| this.close_tab(ix, cx)),
+                        )
+                        .on_drag(
+                            DraggedTab {
+                                ix,
+                                label: label.clone(),
+                            },
+                            |tab, cx| cx.new_view(|_cx| tab.clone()),
+                        )
+                        .drag_over::<DraggedTab>(|style, _, _| style.bg(rgba(0xffffff26)))
+                        .on_drop(cx.listener(move |this, tab: &DraggedTab, cx| {
+                            this.move_tab(tab.ix, ix, cx)
+                        }))
+                        .child(
+                            div()
+                                .flex_1()
+                                .overflow_hidden()
+                                .whitespace_nowrap()
+                                .child(label),
+                        )
+                        .child(
+                            div()
+                                .id(("close-tab", ix))
+                                .px(px(4.))
+                                .rounded(px(4.))
+                                .text_color(rgba(0xffffff80))
+                                .hover(|style| style.bg(rgba(0xffffff1a)))
+                                .on_click(cx.listener(move |this, _event, cx| {
+                                    cx.stop_propagation();
+                                    this.close_tab(ix, cx)
+                                }))
+                                .child("×"),
+                        )
+                })
+                .collect::<Vec<_>>();
+
+        div()
+            .flex()
+            .flex_row()
+            .items_center()
+            .gap(px(4.))
+            .children(tabs)
+            .child(
+                div()
+                    .id("new-tab")
+                    .px(px(8.))
+                    .py(px(4.))
+                    .rounded(px(6.))
+                    .text_color(rgba(0xffffff80))
+                    .hover(|style| style.bg(rgba(0xffffff0d)))
+                    .on_click(cx.listener(|this, _event, cx| this.new_tab(None, cx)))
+                    .child("+"),
+            )
+    }
+
+    /// What the active tab shows, in order: search results while searching,
+    /// the folder's contents otherwise.
+    pub fn visible_items(&self, cx: &AppContext) -> Vec<PathBuf> {
+        match &self.search_results {
+            Some(results) => results.clone(),
+            None => self.file_explorer().read(cx).folder_contents.clone(),
+        }
+    }
+
+    fn folder_contents_elements(&mut self, cx: &mut ViewContext<Self>) -> Vec<AnyElement> {
+        let file_explorer = self.file_explorer().clone();
+        let folder_contents = self.visible_items(cx);
+        let (selection, view_mode) = {
+            let file_explorer = file_explorer.read(cx);
+            (file_explorer.selection.clone(), file_explorer.view_mode)
+        };
+
+        let file_systems = Providers::get(cx);
+
+        folder_contents
+            .iter()
+            .map(|item| {
+                // Stats come from when the folder was read or the search ran,
+                // never from asking the provider while drawing.
+                let stat = match &self.search_results {
+                    Some(_) => self.search_stats.get(item),
+                    None => file_explorer.read(cx).stat(item),
+                }
+                .cloned();
+                let is_folder = stat.as_ref().is_some_and(|stat| stat.is_dir());
+                // Archives open like folders but keep their own icons.
+                let opens = is_folder
+                    || (ArchiveFormat::from_path(item).is_some()
+                        && file_systems.is_local(item)
+                        && stat
+                            .as_ref()
+                            .is_some_and(|stat| stat.kind == EntryKind::File));
+                let selected = selection.contains(item);
+                let file_explorer = file_explorer.clone();
+                let item_path = item.clone();
+                let on_click = move |event: &gpui::ClickEvent, cx: &mut gpui::WindowContext| {
+                    file_explorer.update(cx, |file_explorer, cx| {
+                        if opens && event.down.click_count > 1 {
+                            file_explorer.open_folder(item_path.clone(), cx)
+                        } else {
+                            let toggle = event.down.modifiers.control;
+                            file_explorer.select(item_path.clone(), toggle, cx)
+                        }
+                    });
+                };
+
+                let element = match view_mode {
+                    ViewMode::Grid => FileItem::new(
+                        item,
+                        Some(Arc::new(Mutex::new(move |_path: &str| {}))),
+                        is_folder,
+                        stat.as_ref().map_or(0, |stat| stat.size),
+                        stat.as_ref().and_then(|stat| stat.modified),
+                    )
+                    .selected(selected)
+                    .on_click(on_click)
+                    .into_any_element(),
+                    ViewMode::Details => {
+                        // Only folders on disk are measured; other providers
+                        // know the size of theirs or leave it out.
+                        let size = match &stat {
+                            Some(stat) if is_folder && file_systems.is_local(item) => {
+                                FolderSizes::get(item, stat.modified, cx)
+                                    .map(|size| format_size(size.size))
+                                    .unwrap_or_default()
+                            }
+                            Some(stat) if is_folder && stat.size == 0 => String::new(),
+                            Some(stat) => format_size(stat.size),
+                            None => String::new(),
+                        };
+                        let modified = stat
+                            .as_ref()
+                            .and_then(|stat| stat.modified)
+                            .map(|modified| {
+                                let modified: chrono::DateTime<chrono::Local> = modified.into();
+                                modified.format("%Y-%m-%d %H:%M").to_string()
+                            })
+                            .unwrap_or_default();
+
+                        FileRow::new(item, is_folder, size, modified)
+                            .version(
+                                stat.as_ref().and_then(|stat| stat.modified),
+                                stat.as_ref().map_or(0, |stat| stat.size),
+                            )
+                            .selected(selected)
+                            .on_click(on_click)
+                            .into_any_element()
+                    }
+                };
+
+                let folder = item.clone();
+                div()
+                    .when(view_mode == ViewMode::Details, |this| this.w_full())
+                    .when(opens, |this| {
+                        this.on_mouse_down(
+                            MouseButton::Middle,
+                            cx.listener(move |this, _event, cx| {
+                                this.new_tab(Some(folder.clone()), cx)
+                            }),
+                        )
+                    })
+                    .child(element)
+                    .into_any_element()
+            })
+            .collect()
+    }
+}
+
+impl Render for Pane {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let view_mode = self.file_explorer().read(cx).view_mode;
+        let contents = self.folder_contents_elements(cx);
+
+        div()
+            .flex()
+            .flex_col()
+            .gap(px(12.))
+            .child(self.render_tab_bar(cx))
+            .child(
+                div()
+                    .flex()
+                    .map(|this| match view_mode {
+                        ViewMode::Grid => this.flex_row().gap(px(20.)).flex_wrap(),
+                        ViewMode::Details => this.flex_col().gap(px(2.)),
+                    })
+                    .children(contents),
+            )
+    }
+}
@@ -0,0 +1,745 @@
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::SystemTime,
+};
+
+use ::fs::{split_archive_path, ArchiveCache, FileSystemProvider, FileSystems};
+use file_icons::{sniff_mime, FileIcons, SNIFF_LEN};
+use gpui::{
+    div, img, px, rgb, rgba, svg, white, FontWeight, InteractiveElement, IntoElement, ObjectFit,
+    ParentElement, Pixels, Render, StatefulInteractiveElement, Styled, StyledImage, Task,
+    ViewContext,
+};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use search::format_size;
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+use thumbnails::{load_or_generate, ThumbnailSize};
+
+use crate::{archives::Archives, providers::Providers};
+
+/// Text files are read up to this many bytes.
+const MAX_TEXT_BYTES: usize = 256 * 1024;
+const MAX_TEXT_LINES: usize = 2000;
+/// Other files show a hex dump of this many bytes.
+const MAX_HEX_BYTES: usize = 4096;
+const HEX_BYTES_PER_LINE: usize = 16;
+const TAB_WIDTH: usize = 4;
+const THEME: &str = "base16-ocean.dark";
+/// Files in archives are extracted to be previewed, up to this size.
+const MAX_ARCHIVE_PREVIEW_BYTES: u64 = 64 * 1024 * 1024;
+const PLAIN_TEXT_COLOR: u32 = 0xc0c5ceff;
+
+/// A run of text in one colour.
+#[derive(Clone, Debug)]
+pub struct Span {
+    pub text: String,
+    pub color: u32,
+}
+
+#[derive(Clone, Debug)]
+pub enum PreviewContent {
+    Text {
+        lines: Vec<Vec<Span>>,
+        truncated: bool,
+    },
+    Image {
+        width: u32,
+        height: u32,
+    },
+    Hex {
+        lines: Vec<String>,
+        truncated: bool,
+    },
+    Markdown {
+        blocks: Vec<MarkdownBlock>,
+        truncated: bool,
+    },
+    /// A page or frame rendered by an external thumbnailer, for PDFs,
+    /// videos and the like.
+    Rendered {
+        thumbnail: PathBuf,
+    },
+    Folder {
+        items: usize,
+        size: u64,
+    },
+    Unreadable(String),
+}
+
+/// Markdown flattened to the blocks the preview knows how to show. Inline
+/// formatting is dropped.
+#[derive(Clone, Debug)]
+pub enum MarkdownBlock {
+    Heading(usize, String),
+    Paragraph(String),
+    Quote(String),
+    ListItem(usize, String),
+    Code(Vec<String>),
+    Rule,
+}
+
+#[derive(Clone, Debug)]
+pub struct Preview {
+    pub path: PathBuf,
+    pub is_folder: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub mime: Option<&'static str>,
+    pub permissions: Option<String>,
+    pub content: PreviewContent,
+}
+
+/// Reads what's needed to preview `path` from whichever provider it is on.
+/// Files only an external thumbnailer understands are rendered at
+/// `thumbnail_size` when they are on disk. Blocks, so call it off the main
+/// thread.
+pub fn load_preview(
+    path: &Path,
+    file_systems: &FileSystems,
+    archives: &ArchiveCache,
+    thumbnail_size: ThumbnailSize,
+) -> Preview {
+    if let Some((archive, inner)) =
+        split_archive_path(path).filter(|(_, inner)| !inner.as_os_str().is_empty())
+    {
+        return load_archive_preview(
+            path,
+            &archive,
+            &inner,
+            file_systems,
+            archives,
+            thumbnail_size,
+        );
+    }
+
+    let mut preview = Preview {
+        path: path.to_path_buf(),
+        is_folder: false,
+        size: 0,
+        modified: None,
+        mime: None,
+        permissions: None,
+        content: PreviewContent::Unreadable(String::new()),
+    };
+
+    let stat = file_systems
+        .provider(path)
+        .and_then(|provider| Ok((provider.stat(path)?, provider)));
+    let (stat, provider) = match stat {
+        Ok(stat) => stat,
+        Err(e) => {
+            preview.content = PreviewContent::Unreadable(e.to_string());
+            return preview;
+        }
+    };
+    let provider = provider.as_ref();
+    let local = file_systems.is_local(path);
+    preview.is_folder = stat.is_dir();
+    preview.modified = stat.modified;
+    // Only the disk has permissions to show.
+    if local {
+        preview.permissions = fs::metadata(path)
+            .ok()
+            .and_then(|metadata| permissions_string(&metadata));
+    }
+
+    if preview.is_folder {
+        let items = provider.list(path).map(|items| items.len()).unwrap_or(0);
+        // Remote folders aren't walked just to be previewed.
+        let size = if local {
+            file_systems.total_size(path)
+        } else {
+            stat.size
+        };
+        preview.size = size;
+        preview.content = PreviewContent::Folder { items, size };
+        return preview;
+    }
+
+    preview.size = stat.size;
+    preview.mime = read_start(provider, path, SNIFF_LEN)
+        .ok()
+        .filter(|header| !header.is_empty())
+        .and_then(|header| sniff_mime(&header));
+    preview.content = match preview.mime {
+        Some(mime) if mime.starts_with("image/") && mime != "image/svg+xml" && local => {
+            match image::image_dimensions(path) {
+                Ok((width, height)) => PreviewContent::Image { width, height },
+                Err(_) => load_hex(provider, path, preview.size),
+            }
+        }
+        Some(mime) if mime.starts_with("text/") && is_markdown(path) => {
+            load_markdown(provider, path, preview.size)
+        }
+        Some(mime) if mime.starts_with("text/") || mime == "image/svg+xml" => {
+            load_text(provider, path, preview.size)
+        }
+        // Empty files sniff as nothing; show them as empty text.
+        None if preview.size == 0 => PreviewContent::Text {
+            lines: vec![],
+            truncated: false,
+        },
+        _ if local => match load_or_generate(path, thumbnail_size) {
+            Ok(Some(thumbnail)) => PreviewContent::Rendered { thumbnail },
+            _ => load_hex(provider, path, preview.size),
+        },
+        _ => load_hex(provider, path, preview.size),
+    };
+    preview
+}
+
+/// Folders in archives are described from the listing; files are previewed
+/// from a temporary copy.
+fn load_archive_preview(
+    path: &Path,
+    archive: &Path,
+    inner: &Path,
+    file_systems: &FileSystems,
+    archives: &ArchiveCache,
+    thumbnail_size: ThumbnailSize,
+) -> Preview {
+    let mut preview = Preview {
+        path: path.to_path_buf(),
+        is_folder: false,
+        size: 0,
+        modified: None,
+        mime: None,
+        permissions: None,
+        content: PreviewContent::Unreadable(String::new()),
+    };
+
+    let listing = match archives.open(archive) {
+        Ok(listing) => listing,
+        Err(e) => {
+            preview.content = PreviewContent::Unreadable(e.to_string());
+            return preview;
+        }
+    };
+    let Some(entry) = listing.entry(inner) else {
+        preview.content = PreviewContent::Unreadable("Not found in the archive".into());
+        return preview;
+    };
+    preview.is_folder = entry.is_dir;
+    preview.size = entry.size;
+    preview.modified = entry.modified;
+
+    if entry.is_dir {
+        preview.content = PreviewContent::Folder {
+            items: listing.children(inner).count(),
+            size: entry.size,
+        };
+        return preview;
+    }
+    if entry.size > MAX_ARCHIVE_PREVIEW_BYTES {
+        preview.content = PreviewContent::Unreadable("Too large to preview in an archive".into());
+        return preview;
+    }
+
+    match listing.extract_temporary(inner) {
+        // The copy's path is kept, as images are drawn from it.
+        Ok(copy) => Preview {
+            modified: entry.modified,
+            ..load_preview(&copy, file_systems, archives, thumbnail_size)
+        },
+        Err(e) => {
+            preview.content = PreviewContent::Unreadable(e.to_string());
+            preview
+        }
+    }
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            ["md", "markdown", "mdown", "mkd"].contains(&extension.to_lowercase().as_str())
+        })
+}
+
+fn read_start(
+    provider: &dyn FileSystemProvider,
+    path: &Path,
+    limit: usize,
+) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(limit.min(64 * 1024));
+    provider
+        .read(path)?
+        .take(limit as u64)
+        .read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// The start of a text file, and whether there was more.
+fn read_text(
+    provider: &dyn FileSystemProvider,
+    path: &Path,
+    size: u64,
+) -> anyhow::Result<(String, bool)> {
+    let bytes = read_start(provider, path, MAX_TEXT_BYTES)?;
+    // Reading may have stopped in the middle of a character.
+    let text = String::from_utf8_lossy(match std::str::from_utf8(&bytes) {
+        Err(error) if error.error_len().is_none() => &bytes[..error.valid_up_to()],
+        _ => &bytes,
+    })
+    .replace('\t', &" ".repeat(TAB_WIDTH));
+    Ok((text, size > bytes.len() as u64))
+}
+
+fn load_text(provider: &dyn FileSystemProvider, path: &Path, size: u64) -> PreviewContent {
+    let (text, mut truncated) = match read_text(provider, path, size) {
+        Ok(text) => text,
+        Err(e) => return PreviewContent::Unreadable(e.to_string()),
+    };
+
+    let syntaxes = syntax_set();
+    let syntax = syntaxes
+        .find_syntax_for_file(path)
+        .ok()
+        .flatten()
+        .or_else(|| syntaxes.find_syntax_by_first_line(&text))
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+    let mut highlighter = HighlightLines::new(syntax, theme());
+
+    let mut lines = Vec::new();
+    for line in LinesWithEndings::from(&text) {
+        if lines.len() == MAX_TEXT_LINES {
+            truncated = true;
+            break;
+        }
+        let spans = match highlighter.highlight_line(line, syntaxes) {
+            Ok(ranges) => ranges
+                .into_iter()
+                .map(|(style, text)| Span {
+                    text: text.trim_end_matches(['\n', '\r']).to_string(),
+                    color: u32::from_be_bytes([
+                        style.foreground.r,
+                        style.foreground.g,
+                        style.foreground.b,
+                        style.foreground.a,
+                    ]),
+                })
+                .filter(|span| !span.text.is_empty())
+                .collect(),
+            Err(_) => vec![Span {
+                text: line.trim_end_matches(['\n', '\r']).to_string(),
+                color: PLAIN_TEXT_COLOR,
+            }],
+        };
+        lines.push(spans);
+    }
+
+    PreviewContent::Text { lines, truncated }
+}
+
+fn load_markdown(provider: &dyn FileSystemProvider, path: &Path, size: u64) -> PreviewContent {
+    let (text, truncated) = match read_text(provider, path, size) {
+        Ok(text) => text,
+        Err(e) => return PreviewContent::Unreadable(e.to_string()),
+    };
+
+    let mut blocks = Vec::new();
+    let mut current = String::new();
+    let mut code = Vec::new();
+    let mut list_depth = 0;
+    let mut in_quote = false;
+    let mut in_code = false;
+
+    for event in Parser::new(&text) {
+        match event {
+            Event::Start(Tag::List(_)) => {
+                if !current.trim().is_empty() {
+                    blocks.push(MarkdownBlock::ListItem(list_depth, take_text(&mut current)));
+                }
+                list_depth += 1;
+            }
+            Event::End(TagEnd::List(_)) => list_depth -= 1,
+            Event::End(TagEnd::Item) if !current.trim().is_empty() => {
+                blocks.push(MarkdownBlock::ListItem(list_depth, take_text(&mut current)));
+            }
+            Event::Start(Tag::BlockQuote) => in_quote = true,
+            Event::End(TagEnd::BlockQuote) => in_quote = false,
+            Event::Start(Tag::CodeBlock(_)) => in_code = true,
+            Event::End(TagEnd::CodeBlock) => {
+                in_code = false;
+                blocks.push(MarkdownBlock::Code(std::mem::take(&mut code)));
+            }
+            Event::End(TagEnd::Heading(level)) => blocks.push(MarkdownBlock::Heading(
+                level as usize,
+                take_text(&mut current),
+            )),
+            // Paragraphs inside list items end with the item.
+            Event::End(TagEnd::Paragraph) if list_depth == 0 => {
+                let text = take_text(&mut current);
+                blocks.push(if in_quote {
+                    MarkdownBlock::Quote(text)
+                } else {
+                    MarkdownBlock::Paragraph(text)
+                });
+            }
+            Event::End(TagEnd::Paragraph) => current.push(' '),
+            Event::Text(text) if in_code => {
+                code.extend(text.lines().map(String::from));
+            }
+            Event::Text(text) | Event::Code(text) => current.push_str(&text),
+            Event::SoftBreak => current.push(' '),
+            Event::HardBreak => current.push('\n'),
+            Event::Rule => blocks.push(MarkdownBlock::Rule),
+            _ => {}
+        }
+    }
+
+    PreviewContent::Markdown { blocks, truncated }
+}
+
+fn take_text(current: &mut String) -> String {
+    std::mem::take(current).trim().to_string()
+}
+
+fn load_hex(provider: &dyn FileSystemProvider, path: &Path, size: u64) -> PreviewContent {
+    let bytes = match read_start(provider, path, MAX_HEX_BYTES) {
+        Ok(bytes) => bytes,
+        Err(e) => return PreviewContent::Unreadable(e.to_string()),
+    };
+
+    let lines = bytes
+        .chunks(HEX_BYTES_PER_LINE)
+        .enumerate()
+        .map(|(ix, chunk)| {
+            let hex = chunk
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = chunk
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii_graphic() || byte == b' ' {
+                        byte as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>();
+            format!(
+                "{:08x}  {:<width$}  {}",
+                ix * HEX_BYTES_PER_LINE,
+                hex,
+                ascii,
+                width = HEX_BYTES_PER_LINE * 3 - 1
+            )
+        })
+        .collect();
+
+    PreviewContent::Hex {
+        lines,
+        truncated: size > bytes.len() as u64,
+    }
+}
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme() -> &'static Theme {
+    static THEME_SET: OnceLock<ThemeSet> = OnceLock::new();
+    &THEME_SET.get_or_init(ThemeSet::load_defaults).themes[THEME]
+}
+
+/// `rwxr-xr-x` style permissions.
+#[cfg(unix)]
+pub fn permissions_string(metadata: &fs::Metadata) -> Option<String> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(::fs::mode_string(metadata.permissions().mode()))
+}
+
+#[cfg(not(unix))]
+pub fn permissions_string(metadata: &fs::Metadata) -> Option<String> {
+    Some(if metadata.permissions().readonly() {
+        "Read-only".into()
+    } else {
+        "Read and write".into()
+    })
+}
+
+/// A panel on the right showing what the selected entry contains.
+#[derive(Default)]
+pub struct PreviewPane {
+    path: Option<PathBuf>,
+    preview: Option<Preview>,
+    load_task: Option<Task<()>>,
+}
+
+impl PreviewPane {
+    /// Previews `path`, loading it in the background. Whatever was still
+    /// loading for the previous path is dropped.
+    pub fn show(&mut self, path: Option<PathBuf>, cx: &mut ViewContext<Self>) {
+        if self.path == path {
+            return;
+        }
+        self.path = path.clone();
+        self.preview = None;
+        self.load_task = None;
+        cx.notify();
+
+        let Some(path) = path else {
+            return;
+        };
+        let file_systems = Providers::get(cx);
+        let archives = Archives::cache(cx);
+        let work = cx.background_executor().spawn(async move {
+            load_preview(&path, &file_systems, &archives, ThumbnailSize::Large)
+        });
+        self.load_task = Some(cx.spawn(|this, mut cx| async move {
+            let preview = work.await;
+            this.update(&mut cx, |this, cx| {
+                this.preview = Some(preview);
+                cx.notify();
+            })
+            .ok();
+        }));
+    }
+
+    /// Loads the current path again, e.g. after it changed on disk.
+    pub fn reload(&mut self, cx: &mut ViewContext<Self>) {
+        let path = self.path.take();
+        self.show(path, cx);
+    }
+}
+
+/// The body of a preview, shared by the preview pane and Quick Look. Images
+/// are fitted into `image_height`.
+pub fn render_content(
+    content: &PreviewContent,
+    path: &Path,
+    image_height: Pixels,
+) -> gpui::AnyElement {
+    let truncated_note = |truncated: bool| {
+        truncated.then(|| {
+            div()
+                .pt(px(6.))
+                .text_color(rgb(0xa3a3a3))
+                .child("Preview truncated")
+        })
+    };
+    let code = || {
+        div()
+            .flex()
+            .flex_col()
+            .p(px(8.))
+            .rounded(px(6.))
+            .bg(rgb(0x1b1b1d))
+            .font_family("monospace")
+            .text_size(px(11.))
+            .overflow_hidden()
+    };
+
+    match content {
+        PreviewContent::Text { lines, truncated } => {
+            div()
+                .child(code().children(lines.iter().map(|spans| {
+                    div()
+                        .flex()
+                        .flex_row()
+                        .min_h(px(14.))
+                        .whitespace_nowrap()
+                        .children(spans.iter().map(|span| {
+                            div().text_color(rgba(span.color)).child(span.text.clone())
+                        }))
+                })))
+                .children(truncated_note(*truncated))
+                .into_any_element()
+        }
+        PreviewContent::Image { width, height } => div()
+            .flex()
+            .flex_col()
+            .gap(px(6.))
+            .child(
+                img(path.to_path_buf())
+                    .w_full()
+                    .h(image_height)
+                    .object_fit(ObjectFit::Contain),
+            )
+            .child(
+                div()
+                    .text_color(rgb(0xa3a3a3))
+                    .child(format!("{} × {} pixels", width, height)),
+            )
+            .into_any_element(),
+        PreviewContent::Hex { lines, truncated } => div()
+            .child(
+                code().text_color(rgb(0xd4d4d4)).children(
+                    lines
+                        .iter()
+                        .map(|line| div().whitespace_nowrap().child(line.clone())),
+                ),
+            )
+            .children(truncated_note(*truncated))
+            .into_any_element(),
+        PreviewContent::Markdown { blocks, truncated } => div()
+            .flex()
+            .flex_col()
+            .gap(px(8.))
+            .children(blocks.iter().map(|block| {
+                match block {
+                    MarkdownBlock::Heading(level, text) => div()
+                        .text_size(px(match level {
+                            1 => 20.,
+                            2 => 17.,
+                            _ => 15.,
+                        }))
+                        .font_weight(FontWeight::BOLD)
+                        .child(text.clone()),
+                    MarkdownBlock::Paragraph(text) => div().child(text.clone()),
+                    MarkdownBlock::Quote(text) => div()
+                        .pl(px(8.))
+                        .border_l_2()
+                        .border_color(rgb(0x545454))
+                        .text_color(rgb(0xa3a3a3))
+                        .child(text.clone()),
+                    MarkdownBlock::ListItem(depth, text) => div()
+                        .flex()
+                        .flex_row()
+                        .gap(px(6.))
+                        .pl(px(depth.saturating_sub(1) as f32 * 16.))
+                        .child("•")
+                        .child(div().flex_1().child(text.clone())),
+                    MarkdownBlock::Code(lines) => {
+                        code()
+                            .text_color(rgb(0xd4d4d4))
+                            .children(lines.iter().map(|line| {
+                                div().min_h(px(14.)).whitespace_nowrap().child(line.clone())
+                            }))
+                    }
+                    MarkdownBlock::Rule => div().h(px(1.)).bg(rgb(0x545454)),
+                }
+            }))
+            .children(truncated_note(*truncated))
+            .into_any_element(),
+        PreviewContent::Rendered { thumbnail } => img(thumbnail.clone())
+            .w_full()
+            .h(image_height)
+            .object_fit(ObjectFit::Contain)
+            .into_any_element(),
+        PreviewContent::Folder { items, size } => div()
+            .text_color(rgb(0xd4d4d4))
+            .child(format!(
+                "{} {}, {} in total",
+                items,
+                if *items == 1 { "item" } else { "items" },
+                format_size(*size)
+            ))
+            .into_any_element(),
+        PreviewContent::Unreadable(error) => div()
+            .text_color(rgb(0xf87171))
+            .child(format!("Can't preview: {}", error))
+            .into_any_element(),
+    }
+}
+
+impl Render for PreviewPane {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let panel = div()
+            .id("preview")
+            .flex()
+            .flex_col()
+            .gap(px(12.))
+            .w(px(300.))
+            .h_full()
+            .p(px(16.))
+            .bg(rgb(0x19191a))
+            .text_color(rgb(0xf3f3f3))
+            .overflow_y_scroll();
+
+        let Some(path) = self.path.clone() else {
+            return panel.child(div().text_color(rgb(0xa3a3a3)).child("No selection"));
+        };
+
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string_lossy().into_owned());
+        let is_folder = self
+            .preview
+            .as_ref()
+            .is_some_and(|preview| preview.is_folder);
+        let icon = if is_folder {
+            FileIcons::get_folder_icon_for_path(&path, false, cx)
+        } else {
+            let (modified, size) = self
+                .preview
+                .as_ref()
+                .map_or((None, 0), |preview| (preview.modified, preview.size));
+            FileIcons::get_icon(&path, modified, size, cx)
+        };
+
+        let header = div()
+            .flex()
+            .flex_row()
+            .items_center()
+            .gap(px(8.))
+            .children(icon.map(|icon| {
+                svg()
+                    .path(icon.to_string())
+                    .w(px(20.))
+                    .h(px(20.))
+                    .flex_none()
+                    .text_color(white())
+            }))
+            .child(div().overflow_hidden().child(name));
+
+        let Some(preview) = &self.preview else {
+            return panel
+                .child(header)
+                .child(div().text_color(rgb(0xa3a3a3)).child("Loading…"));
+        };
+
+        let modified = preview
+            .modified
+            .map(|modified| {
+                let modified: chrono::DateTime<chrono::Local> = modified.into();
+                modified.format("%Y-%m-%d %H:%M").to_string()
+            })
+            .unwrap_or_default();
+        let kind = if preview.is_folder {
+            "Folder".to_string()
+        } else {
+            preview.mime.unwrap_or("Unknown").to_string()
+        };
+        let details = [
+            ("Kind", kind),
+            ("Size", format_size(preview.size)),
+            ("Modified", modified),
+            (
+                "Permissions",
+                preview.permissions.clone().unwrap_or_default(),
+            ),
+        ];
+
+        panel
+            .child(header)
+            .child(render_content(&preview.content, &preview.path, px(200.)))
+            .child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .gap(px(4.))
+                    .children(details.into_iter().map(|(label, value)| {
+                        div()
+                            .flex()
+                            .flex_row()
+                            .gap(px(8.))
+                            .child(div().w(px(80.)).text_color(rgb(0xa3a3a3)).child(label))
+                            .child(div().flex_1().overflow_hidden().child(value))
+                    })),
+            )
+    }
+}
@@ -0,0 +1,451 @@
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use fs::{AttributeChange, EntryKind, FileSystems, Properties};
+use gpui::{
+    div, prelude::FluentBuilder, px, rgb, rgba, AppContext, DismissEvent, EventEmitter,
+    FocusHandle, FocusableView, InteractiveElement, IntoElement, KeyDownEvent, ParentElement,
+    Render, StatefulInteractiveElement, Styled, Task, View, ViewContext, VisualContext,
+};
+use search::format_size;
+use ui::{Checkbox, Clickable, TextInput, TextInputEvent};
+
+use crate::{folder_sizes::FolderSizes, providers::Providers};
+
+/// Owner, group and others; read, write and execute.
+const PERMISSION_BITS: [u32; 9] = [
+    0o400, 0o200, 0o100, 0o040, 0o020, 0o010, 0o004, 0o002, 0o001,
+];
+
+/// Shows the properties of one or more entries and lets their permissions
+/// and owners be changed.
+pub struct PropertiesDialog {
+    paths: Vec<PathBuf>,
+    entries: Option<Vec<Result<Properties, String>>>,
+    /// Each permission bit as loaded and as edited; `None` where the entries
+    /// differ.
+    loaded_permissions: [Option<bool>; 9],
+    permissions: [Option<bool>; 9],
+    loaded_owner: String,
+    loaded_group: String,
+    owner: View<TextInput>,
+    group: View<TextInput>,
+    recursive: bool,
+    failures: Vec<String>,
+    task: Option<Task<()>>,
+    focus_handle: FocusHandle,
+}
+
+impl EventEmitter<DismissEvent> for PropertiesDialog {}
+
+impl PropertiesDialog {
+    pub fn new(paths: Vec<PathBuf>, cx: &mut ViewContext<Self>) -> Self {
+        let owner = cx.new_view(|cx| TextInput::new("Owner", cx));
+        let group = cx.new_view(|cx| TextInput::new("Group", cx));
+        for input in [&owner, &group] {
+            cx.subscribe(
+                input,
+                |this, _input, event: &TextInputEvent, cx| match event {
+                    TextInputEvent::Confirmed(_) => this.apply(cx),
+                    TextInputEvent::Cancelled => cx.emit(DismissEvent),
+                    TextInputEvent::Changed(_) => {}
+                },
+            )
+            .detach();
+        }
+
+        let mut this = Self {
+            paths,
+            entries: None,
+            loaded_permissions: [None; 9],
+            permissions: [None; 9],
+            loaded_owner: String::new(),
+            loaded_group: String::new(),
+            owner,
+            group,
+            recursive: false,
+            failures: Vec::new(),
+            task: None,
+            focus_handle: cx.focus_handle(),
+        };
+        this.load(cx);
+        this
+    }
+
+    fn load(&mut self, cx: &mut ViewContext<Self>) {
+        let paths = self.paths.clone();
+        let sizes = FolderSizes::cache(cx);
+        let file_systems = Providers::get(cx);
+        let work = cx.background_executor().spawn(async move {
+            paths
+                .iter()
+                .map(|path| {
+                    if file_systems.is_local(path) {
+                        fs::read_properties(path, &sizes)
+                    } else {
+                        Self::read_provider_properties(path, &file_systems)
+                    }
+                    .map_err(|e| e.to_string())
+                })
+                .collect::<Vec<_>>()
+        });
+        self.task = Some(cx.spawn(|this, mut cx| async move {
+            let entries = work.await;
+            this.update(&mut cx, |this, cx| this.loaded(entries, cx))
+                .ok();
+        }));
+    }
+
+    /// What the provider of a path off the disk knows about it, which leaves
+    /// out owners, permissions and the like.
+    fn read_provider_properties(
+        path: &Path,
+        file_systems: &FileSystems,
+    ) -> anyhow::Result<Properties> {
+        let stat = file_systems.provider(path)?.stat(path)?;
+        let size = if stat.is_dir() {
+            file_systems.total_size(path)
+        } else {
+            stat.size
+        };
+        Ok(Properties {
+            path: path.to_path_buf(),
+            kind: stat.kind,
+            size,
+            size_on_disk: None,
+            modified: stat.modified,
+            accessed: None,
+            created: None,
+            changed: None,
+            mode: None,
+            uid: None,
+            gid: None,
+            owner: None,
+            group: None,
+            inode: None,
+            links: None,
+            symlink_target: None,
+        })
+    }
+
+    fn loaded(&mut self, entries: Vec<Result<Properties, String>>, cx: &mut ViewContext<Self>) {
+        let loaded = entries
+            .iter()
+            .filter_map(|entry| entry.as_ref().ok())
+            .collect::<Vec<_>>();
+
+        for (ix, bit) in PERMISSION_BITS.iter().enumerate() {
+            let mut values = loaded
+                .iter()
+                .filter_map(|properties| properties.mode)
+                .map(|mode| mode & bit != 0);
+            let first = values.next();
+            self.loaded_permissions[ix] = first.filter(|first| values.all(|value| value == *first));
+        }
+        self.permissions = self.loaded_permissions;
+
+        let common = |name: fn(&Properties) -> Option<String>| {
+            let mut names = loaded.iter().map(|properties| name(properties));
+            let first = names.next().flatten();
+            if names.all(|name| name == first) {
+                first.unwrap_or_default()
+            } else {
+                String::new()
+            }
+        };
+        self.loaded_owner = common(|properties| properties.owner.clone());
+        self.loaded_group = common(|properties| properties.group.clone());
+        let (owner, group) = (self.loaded_owner.clone(), self.loaded_group.clone());
+        self.owner.update(cx, |input, cx| input.set_text(owner, cx));
+        self.group.update(cx, |input, cx| input.set_text(group, cx));
+
+        self.entries = Some(entries);
+        self.task = None;
+        cx.notify();
+    }
+
+    fn toggle_permission(&mut self, ix: usize, cx: &mut ViewContext<Self>) {
+        self.permissions[ix] = Some(self.permissions[ix] != Some(true));
+        cx.notify();
+    }
+
+    /// The edits made so far, as a change to apply.
+    fn change(&self, cx: &AppContext) -> AttributeChange {
+        let mut change = AttributeChange {
+            recursive: self.recursive,
+            ..Default::default()
+        };
+        for (ix, bit) in PERMISSION_BITS.iter().enumerate() {
+            // Recursing applies the shown permissions to everything inside,
+            // even the ones left untouched here.
+            if !self.recursive && self.permissions[ix] == self.loaded_permissions[ix] {
+                continue;
+            }
+            match self.permissions[ix] {
+                Some(true) => change.set_mode |= bit,
+                Some(false) => change.clear_mode |= bit,
+                None => {}
+            }
+        }
+
+        let owner = self.owner.read(cx).text().trim();
+        if !owner.is_empty() && owner != self.loaded_owner {
+            change.owner = Some(owner.to_string());
+        }
+        let group = self.group.read(cx).text().trim();
+        if !group.is_empty() && group != self.loaded_group {
+            change.group = Some(group.to_string());
+        }
+        change
+    }
+
+    fn apply(&mut self, cx: &mut ViewContext<Self>) {
+        let change = self.change(cx);
+        if change.is_empty() || self.task.is_some() {
+            return;
+        }
+
+        let paths = self.paths.clone();
+        let work = cx
+            .background_executor()
+            .spawn(async move { fs::apply_attribute_change(&paths, &change) });
+        self.failures.clear();
+        self.task = Some(cx.spawn(|this, mut cx| async move {
+            let failures = work.await;
+            this.update(&mut cx, |this, cx| {
+                this.failures = failures;
+                this.task = None;
+                this.load(cx);
+            })
+            .ok();
+        }));
+        cx.notify();
+    }
+
+    fn handle_key_down(&mut self, event: &KeyDownEvent, cx: &mut ViewContext<Self>) {
+        if event.keystroke.key == "escape" {
+            cx.emit(DismissEvent);
+            cx.stop_propagation();
+        }
+    }
+
+    fn render_details(&self, entries: &[Result<Properties, String>]) -> Vec<(String, String)> {
+        let format_time = |time: Option<SystemTime>| {
+            time.map(|time| {
+                let time: chrono::DateTime<chrono::Local> = time.into();
+                time.format("%Y-%m-%d %H:%M:%S").to_string()
+            })
+        };
+        let sizes = |size: u64| format!("{} ({} bytes)", format_size(size), size);
+
+        let loaded = entries
+            .iter()
+            .filter_map(|entry| entry.as_ref().ok())
+            .collect::<Vec<_>>();
+        let mut details = Vec::new();
+
+        if let [properties] = loaded.as_slice() {
+            let kind = match properties.kind {
+                EntryKind::File => "File",
+                EntryKind::Folder => "Folder",
+                EntryKind::Symlink => "Symbolic link",
+                EntryKind::Other => "Special file",
+            };
+            details.push(("Path".into(), properties.path.display().to_string()));
+            details.push(("Type".into(), kind.into()));
+            if let Some(target) = &properties.symlink_target {
+                details.push(("Link target".into(), target.display().to_string()));
+            }
+            details.push(("Size".into(), sizes(properties.size)));
+            if let Some(size_on_disk) = properties.size_on_disk {
+                details.push(("Size on disk".into(), sizes(size_on_disk)));
+            }
+            for (label, time) in [
+                ("Modified", properties.modified),
+                ("Accessed", properties.accessed),
+                ("Created", properties.created),
+                ("Changed", properties.changed),
+            ] {
+                if let Some(time) = format_time(time) {
+                    details.push((label.into(), time));
+                }
+            }
+            if let Some(inode) = properties.inode {
+                details.push(("Inode".into(), inode.to_string()));
+            }
+            if let Some(links) = properties.links {
+                details.push(("Links".into(), links.to_string()));
+            }
+        } else {
+            details.push(("Items".into(), loaded.len().to_string()));
+            details.push((
+                "Size".into(),
+                sizes(loaded.iter().map(|properties| properties.size).sum()),
+            ));
+            let on_disk = loaded
+                .iter()
+                .map(|properties| properties.size_on_disk)
+                .sum::<Option<u64>>();
+            if let Some(on_disk) = on_disk {
+                details.push(("Size on disk".into(), sizes(on_disk)));
+            }
+        }
+
+        for entry in entries {
+            if let Err(error) = entry {
+                details.push(("Error".into(), error.clone()));
+            }
+        }
+        details
+    }
+
+    fn render_permissions(&self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let header = div().flex().flex_row().child(div().w(px(80.))).children(
+            ["Read", "Write", "Execute"]
+                .map(|label| div().w(px(70.)).text_color(rgb(0xa3a3a3)).child(label)),
+        );
+
+        let rows = ["Owner", "Group", "Others"]
+            .iter()
+            .enumerate()
+            .map(|(row, label)| {
+                div()
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .child(div().w(px(80.)).text_color(rgb(0xa3a3a3)).child(*label))
+                    .children((0..3).map(|column| {
+                        let ix = row * 3 + column;
+                        div().w(px(70.)).child(
+                            Checkbox::new(("permission", ix), self.permissions[ix]).on_click(
+                                cx.listener(move |this, _event, cx| this.toggle_permission(ix, cx)),
+                            ),
+                        )
+                    }))
+            })
+            .collect::<Vec<_>>();
+
+        div()
+            .flex()
+            .flex_col()
+            .gap(px(6.))
+            .child(header)
+            .children(rows)
+    }
+}
+
+impl FocusableView for PropertiesDialog {
+    fn focus_handle(&self, _cx: &AppContext) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for PropertiesDialog {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let any_folder = self
+            .entries
+            .iter()
+            .flatten()
+            .flatten()
+            .any(|properties| properties.kind == EntryKind::Folder);
+        let busy = self.task.is_some();
+        let button = |id: &'static str, label: &'static str| {
+            div()
+                .id(id)
+                .px(px(12.))
+                .py(px(4.))
+                .rounded(px(6.))
+                .bg(rgba(0xffffff0d))
+                .hover(|style| style.bg(rgba(0xffffff1a)))
+                .child(label)
+        };
+
+        let body = match &self.entries {
+            None => div()
+                .text_color(rgb(0xa3a3a3))
+                .child("Reading properties…")
+                .into_any_element(),
+            Some(entries) => div()
+                .flex()
+                .flex_col()
+                .gap(px(12.))
+                .child(
+                    div().flex().flex_col().gap(px(4.)).children(
+                        self.render_details(entries)
+                            .into_iter()
+                            .map(|(label, value)| {
+                                div()
+                                    .flex()
+                                    .flex_row()
+                                    .gap(px(8.))
+                                    .child(div().w(px(100.)).text_color(rgb(0xa3a3a3)).child(label))
+                                    .child(div().flex_1().overflow_hidden().child(value))
+                            }),
+                    ),
+                )
+                .child(self.render_permissions(cx))
+                .child(
+                    div()
+                        .flex()
+                        .flex_row()
+                        .gap(px(8.))
+                        .child(div().flex_1().child(self.owner.clone()))
+                        .child(div().flex_1().child(self.group.clone())),
+                )
+                .when(any_folder, |this| {
+                    this.child(
+                        Checkbox::new("recursive", Some(self.recursive))
+                            .label("Apply to everything inside")
+                            .on_click(cx.listener(|this, _event, cx| {
+                                this.recursive = !this.recursive;
+                                cx.notify();
+                            })),
+                    )
+                })
+                .children((!self.failures.is_empty()).then(|| {
+                    div().flex().flex_col().text_color(rgb(0xf87171)).children(
+                        self.failures
+                            .iter()
+                            .map(|failure| div().child(failure.clone())),
+                    )
+                }))
+                .into_any_element(),
+        };
+
+        div()
+            .id("properties")
+            .track_focus(&self.focus_handle)
+            .on_key_down(cx.listener(Self::handle_key_down))
+            .w(px(480.))
+            .max_h(px(560.))
+            .overflow_y_scroll()
+            .p(px(16.))
+            .flex()
+            .flex_col()
+            .gap(px(12.))
+            .rounded(px(10.))
+            .bg(rgb(0x2b2a2e))
+            .border_1()
+            .border_color(rgba(0xffffff1a))
+            .text_color(rgb(0xf3f3f3))
+            .child(div().text_size(px(16.)).child("Properties"))
+            .child(body)
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .justify_end()
+                    .gap(px(8.))
+                    .child(
+                        button("close", "Close")
+                            .on_click(cx.listener(|_this, _event, cx| cx.emit(DismissEvent))),
+                    )
+                    .child(
+                        button("apply", if busy { "Applying…" } else { "Apply" })
+                            .on_click(cx.listener(|this, _event, cx| this.apply(cx))),
+                    ),
+            )
+    }
+}
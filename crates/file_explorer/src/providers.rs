@@ -0,0 +1,55 @@
+use std::{path::Path, sync::Arc};
+
+use fs::{FileStat, FileSystems, LocalFileSystem, SftpProvider, WebDavProvider};
+use gpui::{AppContext, Global};
+
+use crate::archives::Archives;
+
+/// The providers behind every location the explorer can show: the disk,
+/// archives, and whatever is registered for `scheme://` locations.
+pub struct Providers {
+    file_systems: FileSystems,
+    sftp: Arc<SftpProvider>,
+    webdav: Arc<WebDavProvider>,
+}
+
+impl Global for Providers {}
+
+/// Call after `archives::init`, whose cache the archive provider shares.
+pub fn init(cx: &mut AppContext) {
+    let file_systems = FileSystems::new(Arc::new(LocalFileSystem), Archives::cache(cx));
+    let sftp = Arc::new(SftpProvider::new());
+    file_systems.register("sftp", sftp.clone());
+    let webdav = Arc::new(WebDavProvider::new());
+    file_systems.register("dav", webdav.clone());
+    file_systems.register("davs", webdav.clone());
+    cx.set_global(Providers {
+        file_systems,
+        sftp,
+        webdav,
+    });
+}
+
+impl Providers {
+    pub fn get(cx: &AppContext) -> FileSystems {
+        cx.try_global::<Self>()
+            .map(|this| this.file_systems.clone())
+            .unwrap_or_else(|| FileSystems::new(Arc::new(LocalFileSystem), Archives::cache(cx)))
+    }
+
+    /// The provider behind `sftp://` locations, which the Connect to Server
+    /// dialog logs in with.
+    pub fn sftp(cx: &AppContext) -> Option<Arc<SftpProvider>> {
+        cx.try_global::<Self>().map(|this| this.sftp.clone())
+    }
+
+    /// The provider behind `dav://` and `davs://` locations, which remembers
+    /// their passwords.
+    pub fn webdav(cx: &AppContext) -> Option<Arc<WebDavProvider>> {
+        cx.try_global::<Self>().map(|this| this.webdav.clone())
+    }
+
+    pub fn stat(path: &Path, cx: &AppContext) -> Option<FileStat> {
+        Self::get(cx).provider(path).ok()?.stat(path).ok()
+    }
+}
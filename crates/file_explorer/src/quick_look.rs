@@ -0,0 +1,158 @@
+use std::path::PathBuf;
+
+use gpui::{
+    div, px, rgb, rgba, AppContext, DismissEvent, EventEmitter, FocusHandle, FocusableView,
+    InteractiveElement, IntoElement, KeyDownEvent, ParentElement, Render,
+    StatefulInteractiveElement, Styled, Task, ViewContext,
+};
+use search::format_size;
+use thumbnails::ThumbnailSize;
+
+use crate::{
+    archives::Archives,
+    preview::{load_preview, render_content, Preview},
+    providers::Providers,
+};
+
+/// A large preview over the whole window. The arrow keys step through the
+/// items of the current folder without touching the selection; Space or
+/// Escape close it.
+pub struct QuickLookModal {
+    items: Vec<PathBuf>,
+    ix: usize,
+    preview: Option<Preview>,
+    load_task: Option<Task<()>>,
+    focus_handle: FocusHandle,
+}
+
+impl EventEmitter<DismissEvent> for QuickLookModal {}
+
+impl QuickLookModal {
+    /// Previews `items[ix]`.
+    pub fn new(items: Vec<PathBuf>, ix: usize, cx: &mut ViewContext<Self>) -> Self {
+        let mut this = Self {
+            ix: ix.min(items.len().saturating_sub(1)),
+            items,
+            preview: None,
+            load_task: None,
+            focus_handle: cx.focus_handle(),
+        };
+        this.load(cx);
+        this
+    }
+
+    fn load(&mut self, cx: &mut ViewContext<Self>) {
+        self.preview = None;
+        let Some(path) = self.items.get(self.ix).cloned() else {
+            return;
+        };
+
+        let file_systems = Providers::get(cx);
+        let archives = Archives::cache(cx);
+        let work = cx.background_executor().spawn(async move {
+            load_preview(&path, &file_systems, &archives, ThumbnailSize::XLarge)
+        });
+        self.load_task = Some(cx.spawn(|this, mut cx| async move {
+            let preview = work.await;
+            this.update(&mut cx, |this, cx| {
+                this.preview = Some(preview);
+                cx.notify();
+            })
+            .ok();
+        }));
+        cx.notify();
+    }
+
+    fn step(&mut self, forward: bool, cx: &mut ViewContext<Self>) {
+        let ix = if forward {
+            self.ix + 1
+        } else {
+            self.ix.wrapping_sub(1)
+        };
+        if ix < self.items.len() {
+            self.ix = ix;
+            self.load(cx);
+        }
+    }
+
+    fn handle_key_down(&mut self, event: &KeyDownEvent, cx: &mut ViewContext<Self>) {
+        match event.keystroke.key.as_str() {
+            "space" | "escape" => cx.emit(DismissEvent),
+            "left" | "up" => self.step(false, cx),
+            "right" | "down" => self.step(true, cx),
+            _ => return,
+        }
+        cx.stop_propagation();
+    }
+}
+
+impl FocusableView for QuickLookModal {
+    fn focus_handle(&self, _cx: &AppContext) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for QuickLookModal {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let path = self.items.get(self.ix).cloned().unwrap_or_default();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string_lossy().into_owned());
+        let details = self.preview.as_ref().map(|preview| {
+            let kind = if preview.is_folder {
+                "Folder"
+            } else {
+                preview.mime.unwrap_or("Unknown")
+            };
+            format!("{} · {}", kind, format_size(preview.size))
+        });
+
+        let body = match &self.preview {
+            Some(preview) => render_content(&preview.content, &preview.path, px(420.)),
+            None => div()
+                .text_color(rgb(0xa3a3a3))
+                .child("Loading…")
+                .into_any_element(),
+        };
+
+        div()
+            .id("quick-look")
+            .key_context("QuickLook")
+            .track_focus(&self.focus_handle)
+            .on_key_down(cx.listener(Self::handle_key_down))
+            .on_mouse_down_out(cx.listener(|_this, _event, cx| cx.emit(DismissEvent)))
+            .w(px(720.))
+            .h(px(540.))
+            .flex()
+            .flex_col()
+            .gap(px(12.))
+            .p(px(16.))
+            .rounded(px(10.))
+            .bg(rgb(0x2b2a2e))
+            .border_1()
+            .border_color(rgba(0xffffff1a))
+            .text_color(rgb(0xf3f3f3))
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .gap(px(12.))
+                    .child(div().flex_1().overflow_hidden().child(name))
+                    .children(details.map(|details| div().text_color(rgb(0xa3a3a3)).child(details)))
+                    .child(div().text_color(rgb(0xa3a3a3)).child(format!(
+                        "{} of {}",
+                        self.ix + 1,
+                        self.items.len()
+                    ))),
+            )
+            .child(
+                div()
+                    .id("quick-look-body")
+                    .flex_1()
+                    .overflow_y_scroll()
+                    .child(body),
+            )
+    }
+}
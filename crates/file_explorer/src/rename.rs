@@ -0,0 +1,160 @@
+use std::path::PathBuf;
+
+use gpui::{
+    div, px, rgb, rgba, AppContext, DismissEvent, EventEmitter, FocusHandle, FocusableView,
+    InteractiveElement, IntoElement, Model, ParentElement, Render, StatefulInteractiveElement,
+    Styled, Task, View, ViewContext, VisualContext,
+};
+use ui::{TextInput, TextInputEvent};
+
+use crate::{providers::Providers, FileExplorer};
+
+/// Asks for a new name for one item, renaming it through whichever provider
+/// its location belongs to.
+pub struct RenameDialog {
+    path: PathBuf,
+    file_explorer: Model<FileExplorer>,
+    name: View<TextInput>,
+    error: Option<String>,
+    /// The rename under way, which a remote provider may take a while over.
+    renaming: Option<Task<()>>,
+}
+
+impl EventEmitter<DismissEvent> for RenameDialog {}
+
+impl RenameDialog {
+    pub fn new(
+        path: PathBuf,
+        file_explorer: Model<FileExplorer>,
+        cx: &mut ViewContext<Self>,
+    ) -> Self {
+        let name = cx.new_view(|cx| TextInput::new("Name", cx));
+        let current_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        name.update(cx, |input, cx| input.set_text(current_name, cx));
+        cx.subscribe(
+            &name,
+            |this, _input, event: &TextInputEvent, cx| match event {
+                TextInputEvent::Confirmed(_) => this.confirm(cx),
+                TextInputEvent::Cancelled => cx.emit(DismissEvent),
+                TextInputEvent::Changed(_) => {
+                    this.error = None;
+                    cx.notify();
+                }
+            },
+        )
+        .detach();
+
+        Self {
+            path,
+            file_explorer,
+            name,
+            error: None,
+            renaming: None,
+        }
+    }
+
+    fn confirm(&mut self, cx: &mut ViewContext<Self>) {
+        if self.renaming.is_some() {
+            return;
+        }
+        let name = self.name.read(cx).text().trim().to_string();
+        let Some(parent) = self.path.parent() else {
+            return;
+        };
+        if name.is_empty() || name.contains('/') {
+            return;
+        }
+        let target = parent.join(&name);
+        if target == self.path {
+            cx.emit(DismissEvent);
+            return;
+        }
+
+        let file_systems = Providers::get(cx);
+        let path = self.path.clone();
+        let work = cx.background_executor().spawn(async move {
+            let provider = file_systems.provider(&path)?;
+            if provider.stat(&target).is_ok() {
+                return Err(anyhow::anyhow!("{} already exists", name));
+            }
+            provider.rename(&path, &target)
+        });
+        self.renaming = Some(cx.spawn(|this, mut cx| async move {
+            let result = work.await;
+            this.update(&mut cx, |this, cx| this.renamed(result, cx))
+                .ok();
+        }));
+    }
+
+    fn renamed(&mut self, result: anyhow::Result<()>, cx: &mut ViewContext<Self>) {
+        self.renaming = None;
+        if let Err(e) = result {
+            eprintln!("Failed to rename: {}. Error: {}", self.path.display(), e);
+            self.error = Some(e.to_string());
+            cx.notify();
+            return;
+        }
+
+        self.file_explorer
+            .update(cx, |file_explorer, cx| file_explorer.reload(cx));
+        cx.emit(DismissEvent);
+    }
+}
+
+impl FocusableView for RenameDialog {
+    fn focus_handle(&self, cx: &AppContext) -> FocusHandle {
+        self.name.focus_handle(cx)
+    }
+}
+
+impl Render for RenameDialog {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let button = |id: &'static str, label: &'static str| {
+            div()
+                .id(id)
+                .px(px(12.))
+                .py(px(4.))
+                .rounded(px(6.))
+                .bg(rgba(0xffffff0d))
+                .hover(|style| style.bg(rgba(0xffffff1a)))
+                .child(label)
+        };
+
+        div()
+            .w(px(480.))
+            .p(px(16.))
+            .flex()
+            .flex_col()
+            .gap(px(12.))
+            .rounded(px(10.))
+            .bg(rgb(0x2b2a2e))
+            .border_1()
+            .border_color(rgba(0xffffff1a))
+            .text_color(rgb(0xf3f3f3))
+            .child(div().text_size(px(16.)).child("Rename"))
+            .child(self.name.clone())
+            .children(
+                self.error
+                    .clone()
+                    .map(|error| div().text_color(rgb(0xf87171)).child(error)),
+            )
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .justify_end()
+                    .gap(px(8.))
+                    .child(
+                        button("cancel", "Cancel")
+                            .on_click(cx.listener(|_this, _event, cx| cx.emit(DismissEvent))),
+                    )
+                    .child(
+                        button("rename", "Rename")
+                            .on_click(cx.listener(|this, _event, cx| this.confirm(cx))),
+                    ),
+            )
+    }
+}
@@ -0,0 +1,167 @@
+use gpui::{
+    div, px, rgba, EventEmitter, FocusHandle, FocusableView, InteractiveElement, IntoElement,
+    ParentElement, Render, Styled, View, ViewContext, VisualContext,
+};
+use search::{Age, AgeUnit, DateFilter, Filter, Kind, SearchQuery, SizeFilter};
+use ui::{TextInput, TextInputEvent};
+
+pub enum SearchEvent {
+    QueryChanged(SearchQuery),
+}
+
+pub struct SearchBar {
+    input: View<TextInput>,
+    query: SearchQuery,
+}
+
+impl EventEmitter<SearchEvent> for SearchBar {}
+
+impl SearchBar {
+    pub fn new(cx: &mut ViewContext<Self>) -> Self {
+        let input = cx.new_view(|cx| TextInput::new("Search, e.g. size:>100M modified:<2w", cx));
+        cx.subscribe(&input, Self::on_input_event).detach();
+
+        Self {
+            input,
+            query: SearchQuery::default(),
+        }
+    }
+
+    fn on_input_event(
+        &mut self,
+        _input: View<TextInput>,
+        event: &TextInputEvent,
+        cx: &mut ViewContext<Self>,
+    ) {
+        match event {
+            TextInputEvent::Changed(text) => {
+                self.query = SearchQuery::parse(text);
+                cx.emit(SearchEvent::QueryChanged(self.query.clone()));
+                cx.notify();
+            }
+            TextInputEvent::Cancelled => self.set_query(SearchQuery::default(), cx),
+            TextInputEvent::Confirmed(_) => {}
+        }
+    }
+
+    pub fn query(&self) -> &SearchQuery {
+        &self.query
+    }
+
+    fn set_query(&mut self, query: SearchQuery, cx: &mut ViewContext<Self>) {
+        let text = query.to_string();
+        self.input.update(cx, |input, cx| input.set_text(text, cx));
+    }
+
+    fn suggested_filters() -> Vec<Filter> {
+        vec![
+            Filter::Kind(Kind::Folder),
+            Filter::Kind(Kind::Image),
+            Filter::Kind(Kind::Document),
+            Filter::Modified(DateFilter::NewerThan(Age {
+                amount: 7,
+                unit: AgeUnit::Days,
+            })),
+            Filter::Size(SizeFilter::GreaterThan(100 << 20)),
+        ]
+    }
+
+    fn render_chip(
+        &self,
+        label: String,
+        active: bool,
+        on_click: impl Fn(&mut Self, &mut ViewContext<Self>) + 'static,
+        cx: &mut ViewContext<Self>,
+    ) -> impl IntoElement {
+        let label = if active {
+            format!("{} ×", label)
+        } else {
+            label
+        };
+
+        div()
+            .px(px(8.))
+            .py(px(2.))
+            .rounded(px(8.))
+            .text_size(px(12.))
+            .bg(if active {
+                rgba(0x3b82f64d)
+            } else {
+                rgba(0xffffff0d)
+            })
+            .hover(|style| style.bg(rgba(0xffffff1a)))
+            .on_mouse_down(
+                gpui::MouseButton::Left,
+                cx.listener(move |this, _event, cx| on_click(this, cx)),
+            )
+            .child(label)
+    }
+}
+
+impl FocusableView for SearchBar {
+    fn focus_handle(&self, cx: &gpui::AppContext) -> FocusHandle {
+        self.input.focus_handle(cx)
+    }
+}
+
+impl Render for SearchBar {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let mut chips = Vec::new();
+
+        for filter in self.query.filters.clone() {
+            chips.push(
+                self.render_chip(
+                    filter.label(),
+                    true,
+                    move |this, cx| this.set_query(this.query.clone().without_filter(&filter), cx),
+                    cx,
+                )
+                .into_any_element(),
+            );
+        }
+
+        for token in &self.query.invalid {
+            chips.push(
+                div()
+                    .px(px(8.))
+                    .py(px(2.))
+                    .rounded(px(8.))
+                    .text_size(px(12.))
+                    .bg(rgba(0xef44444d))
+                    .child(token.clone())
+                    .into_any_element(),
+            );
+        }
+
+        for filter in Self::suggested_filters() {
+            if self.query.contains(&filter) {
+                continue;
+            }
+            chips.push(
+                self.render_chip(
+                    filter.label(),
+                    false,
+                    move |this, cx| {
+                        this.set_query(this.query.clone().with_filter(filter.clone()), cx)
+                    },
+                    cx,
+                )
+                .into_any_element(),
+            );
+        }
+
+        div()
+            .flex()
+            .flex_col()
+            .gap(px(6.))
+            .child(self.input.clone())
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .flex_wrap()
+                    .gap(px(6.))
+                    .children(chips),
+            )
+    }
+}
@@ -0,0 +1,126 @@
+use std::{fs, path::PathBuf};
+
+use gpui::{AppContext, Global};
+use serde::{Deserialize, Serialize};
+
+use crate::paths::{HOME, SETTINGS};
+
+/// User settings, read once at startup from `settings.json`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub index: IndexSettings,
+    pub icons: IconSettings,
+    pub folder_sizes: FolderSizeSettings,
+    pub drives: DriveSettings,
+    pub bookmarks: BookmarkSettings,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct IndexSettings {
+    /// Whether to keep a file name index for global search.
+    pub enabled: bool,
+    pub roots: Vec<PathBuf>,
+    /// Folder names that are skipped wherever they appear.
+    pub excluded: Vec<String>,
+}
+
+impl Default for IndexSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            roots: vec![HOME.clone()],
+            excluded: vec![
+                ".git".into(),
+                ".cache".into(),
+                "node_modules".into(),
+                "target".into(),
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct IconSettings {
+    /// Whether to read the first bytes of files to pick icons for files
+    /// without a known extension or with a misleading one.
+    pub detect_content: bool,
+}
+
+impl Default for IconSettings {
+    fn default() -> Self {
+        Self {
+            detect_content: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FolderSizeSettings {
+    /// Whether to add up the size of folders in the details view.
+    pub enabled: bool,
+    /// Whether to count filesystems mounted inside a folder as part of it.
+    pub cross_mounts: bool,
+}
+
+impl Default for FolderSizeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cross_mounts: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DriveSettings {
+    /// Whether to list loop, snap, container and kernel filesystems in the
+    /// sidebar too.
+    pub show_all: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BookmarkSettings {
+    /// Whether to show the bookmarks of GTK file managers and KDE's places
+    /// in the sidebar.
+    pub import: bool,
+    /// Whether to add favorites to the GTK bookmarks file.
+    pub write_favorites: bool,
+}
+
+impl Default for BookmarkSettings {
+    fn default() -> Self {
+        Self {
+            import: true,
+            write_favorites: false,
+        }
+    }
+}
+
+impl Global for Settings {}
+
+impl Settings {
+    pub fn get(cx: &AppContext) -> &Self {
+        cx.global::<Self>()
+    }
+
+    pub fn load() -> Self {
+        let Ok(contents) = fs::read_to_string(SETTINGS.as_path()) else {
+            return Self::default();
+        };
+
+        serde_json::from_str(&contents).unwrap_or_else(|e| {
+            eprintln!(
+                "Failed to parse settings: {}. Error: {}",
+                SETTINGS.display(),
+                e
+            );
+            Self::default()
+        })
+    }
+}
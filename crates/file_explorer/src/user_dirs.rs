@@ -0,0 +1,235 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use fs::FsWatcher;
+use gpui::{AppContext, Global};
+
+#[cfg(target_os = "linux")]
+use {crate::paths::HOME, std::path::Path};
+
+/// How often a change to `user-dirs.dirs` is picked up.
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+const USER_DIRS_FILE: &str = "user-dirs.dirs";
+
+/// The Documents, Downloads, Music, Pictures and Videos folders. On Linux
+/// they come from `user-dirs.dirs`, and a folder that file doesn't set, or
+/// sets to the home folder, is `None`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UserDirs {
+    pub documents: Option<PathBuf>,
+    pub downloads: Option<PathBuf>,
+    pub music: Option<PathBuf>,
+    pub pictures: Option<PathBuf>,
+    pub videos: Option<PathBuf>,
+}
+
+impl Global for UserDirs {}
+
+/// Reads the folders, and again whenever `user-dirs.dirs` changes.
+pub fn init(cx: &mut AppContext) {
+    cx.set_global(UserDirs::load());
+
+    let Some(config_dir) = dirs::config_dir() else {
+        return;
+    };
+    let changed = Arc::new(AtomicBool::new(false));
+    // Editors save by replacing the file, so the folder is watched rather
+    // than the file itself.
+    let watcher = FsWatcher::new(&[config_dir], false, {
+        let changed = changed.clone();
+        move |events| {
+            let relevant = events.is_empty()
+                || events
+                    .iter()
+                    .any(|event| event.path.file_name() == Some(USER_DIRS_FILE.as_ref()));
+            if relevant {
+                changed.store(true, Ordering::Relaxed);
+            }
+        }
+    })
+    .map_err(|e| eprintln!("Failed to watch the user folders. Error: {}", e))
+    .ok();
+
+    cx.spawn(|mut cx| async move {
+        let _watcher = watcher;
+        loop {
+            cx.background_executor().timer(CHECK_INTERVAL).await;
+            if !changed.swap(false, Ordering::Relaxed) {
+                continue;
+            }
+            let updated = cx.update(|cx| {
+                let user_dirs = UserDirs::load();
+                if *UserDirs::get(cx) != user_dirs {
+                    cx.set_global(user_dirs);
+                    cx.refresh();
+                }
+            });
+            if updated.is_err() {
+                break;
+            }
+        }
+    })
+    .detach();
+}
+
+impl UserDirs {
+    pub fn get(cx: &AppContext) -> &Self {
+        static DEFAULT: UserDirs = UserDirs {
+            documents: None,
+            downloads: None,
+            music: None,
+            pictures: None,
+            videos: None,
+        };
+        cx.try_global::<Self>().unwrap_or(&DEFAULT)
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn load() -> Self {
+        let contents = dirs::config_dir()
+            .and_then(|config_dir| std::fs::read_to_string(config_dir.join(USER_DIRS_FILE)).ok())
+            .unwrap_or_default();
+        let entries = parse_user_dirs(&contents, &HOME);
+        let get = |name: &str| {
+            entries
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, path)| path.clone())
+                // Setting a folder to the home folder turns it off.
+                .filter(|path| *path != *HOME)
+        };
+
+        Self {
+            documents: get("DOCUMENTS"),
+            downloads: get("DOWNLOAD"),
+            music: get("MUSIC"),
+            pictures: get("PICTURES"),
+            videos: get("VIDEOS"),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn load() -> Self {
+        Self {
+            documents: dirs::document_dir(),
+            downloads: dirs::download_dir(),
+            music: dirs::audio_dir(),
+            pictures: dirs::picture_dir(),
+            videos: dirs::video_dir(),
+        }
+    }
+}
+
+/// Reads `XDG_<NAME>_DIR="$HOME/Folder"` lines into `(NAME, path)` pairs,
+/// skipping lines that aren't in that form, as `xdg-user-dirs` does.
+#[cfg(target_os = "linux")]
+fn parse_user_dirs(contents: &str, home: &Path) -> Vec<(String, PathBuf)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.starts_with('#') {
+                return None;
+            }
+            let (key, value) = line.split_once('=')?;
+            let name = key.trim().strip_prefix("XDG_")?.strip_suffix("_DIR")?;
+            let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+            let value = unescape(value);
+
+            let path = match value.strip_prefix("$HOME") {
+                Some("") => home.to_path_buf(),
+                Some(rest) => home.join(rest.strip_prefix('/')?),
+                None if value.starts_with('/') => PathBuf::from(value),
+                None => return None,
+            };
+            Some((name.to_string(), path))
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Vec<(String, PathBuf)> {
+        parse_user_dirs(contents, Path::new("/home/me"))
+    }
+
+    #[test]
+    fn parses_the_default_file() {
+        let contents = "\
+# This file is written by xdg-user-dirs-update
+XDG_DESKTOP_DIR=\"$HOME/Desktop\"
+XDG_DOWNLOAD_DIR=\"$HOME/Downloads\"
+XDG_MUSIC_DIR=\"/mnt/media/Music\"
+";
+        assert_eq!(
+            parse(contents),
+            [
+                ("DESKTOP".into(), PathBuf::from("/home/me/Desktop")),
+                ("DOWNLOAD".into(), PathBuf::from("/home/me/Downloads")),
+                ("MUSIC".into(), PathBuf::from("/mnt/media/Music")),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_the_home_folder_itself() {
+        assert_eq!(
+            parse("XDG_VIDEOS_DIR=\"$HOME\""),
+            [("VIDEOS".into(), PathBuf::from("/home/me"))]
+        );
+        assert_eq!(
+            parse("XDG_VIDEOS_DIR=\"$HOME/\""),
+            [("VIDEOS".into(), PathBuf::from("/home/me/"))]
+        );
+    }
+
+    #[test]
+    fn unescapes_values() {
+        assert_eq!(
+            parse(r#"XDG_DOCUMENTS_DIR="$HOME/My \"Docs\" \\ more""#),
+            [(
+                "DOCUMENTS".into(),
+                PathBuf::from(r#"/home/me/My "Docs" \ more"#)
+            )]
+        );
+    }
+
+    #[test]
+    fn skips_lines_in_other_forms() {
+        let contents = "\
+  # XDG_MUSIC_DIR=\"$HOME/Commented\"
+XDG_MUSIC_DIR=$HOME/Unquoted
+XDG_MUSIC_DIR=\"Music\"
+XDG_MUSIC_DIR=\"$HOMEMusic\"
+MUSIC_DIR=\"$HOME/Music\"
+XDG_MUSIC=\"$HOME/Music\"
+
+  XDG_PICTURES_DIR = \"$HOME/Pictures\"
+";
+        assert_eq!(
+            parse(contents),
+            [("PICTURES".into(), PathBuf::from("/home/me/Pictures"))]
+        );
+    }
+}
@@ -0,0 +1,334 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use gpui::AppContext;
+
+/// How much of a file is read to guess its type.
+pub const SNIFF_LEN: usize = 512;
+const MAX_CACHED: usize = 10_000;
+
+/// Guesses the MIME type of a file from its first bytes: magic numbers for
+/// binary formats, the interpreter of a `#!` line for scripts, and
+/// `text/plain` for anything else that looks like UTF-8 text.
+pub fn sniff_mime(bytes: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(usize, &[u8], &str)] = &[
+        (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+        (0, b"\xff\xd8\xff", "image/jpeg"),
+        (0, b"GIF87a", "image/gif"),
+        (0, b"GIF89a", "image/gif"),
+        (0, b"II*\0", "image/tiff"),
+        (0, b"MM\0*", "image/tiff"),
+        (0, b"\0\0\x01\0", "image/vnd.microsoft.icon"),
+        (0, b"8BPS", "image/vnd.adobe.photoshop"),
+        (0, b"%PDF-", "application/pdf"),
+        (0, b"PK\x03\x04", "application/zip"),
+        (0, b"\x1f\x8b", "application/gzip"),
+        (0, b"\xfd7zXZ\0", "application/x-xz"),
+        (0, b"\x28\xb5\x2f\xfd", "application/zstd"),
+        (0, b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+        (0, b"Rar!\x1a\x07", "application/vnd.rar"),
+        (257, b"ustar", "application/x-tar"),
+        (0, b"\x7fELF", "application/x-executable"),
+        (0, b"\xcf\xfa\xed\xfe", "application/x-mach-binary"),
+        (0, b"\xca\xfe\xba\xbe", "application/x-mach-binary"),
+        (0, b"SQLite format 3\0", "application/vnd.sqlite3"),
+        (0, b"ID3", "audio/mpeg"),
+        (0, b"\xff\xfb", "audio/mpeg"),
+        (0, b"\xff\xf3", "audio/mpeg"),
+        (0, b"fLaC", "audio/flac"),
+        (0, b"OggS", "audio/ogg"),
+        (0, b"MThd", "audio/midi"),
+        (0, b"\x1a\x45\xdf\xa3", "video/x-matroska"),
+        (0, b"wOFF", "font/woff"),
+        (0, b"wOF2", "font/woff2"),
+        (0, b"OTTO", "font/otf"),
+        (0, b"\0\x01\0\0\0", "font/ttf"),
+    ];
+
+    for (offset, magic, mime) in SIGNATURES {
+        if bytes.get(*offset..offset + magic.len()) == Some(*magic) {
+            return Some(mime);
+        }
+    }
+
+    // Signatures short enough to start a text file get a second look.
+    match bytes.get(..2) {
+        Some(b"BM") if bytes.get(6..10) == Some(&[0; 4]) => return Some("image/bmp"),
+        Some(b"MZ") if bytes.contains(&0) => {
+            return Some("application/vnd.microsoft.portable-executable")
+        }
+        Some(b"BZ") if bytes.get(4..10) == Some(b"\x31\x41\x59\x26\x53\x59") => {
+            return Some("application/x-bzip2")
+        }
+        _ => {}
+    }
+
+    // Containers that only tell what they hold a few bytes in.
+    match (bytes.get(..4), bytes.get(8..12)) {
+        (Some(b"RIFF"), Some(b"WEBP")) => return Some("image/webp"),
+        (Some(b"RIFF"), Some(b"WAVE")) => return Some("audio/wav"),
+        (Some(b"RIFF"), Some(b"AVI ")) => return Some("video/x-msvideo"),
+        _ => {}
+    }
+    if bytes.get(4..8) == Some(b"ftyp") {
+        return Some(match bytes.get(8..12) {
+            Some(b"heic" | b"heix" | b"mif1") => "image/heic",
+            Some(b"avif") => "image/avif",
+            Some(b"M4A ") => "audio/mp4",
+            Some(b"qt  ") => "video/quicktime",
+            _ => "video/mp4",
+        });
+    }
+
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        // The read may have cut a multi-byte character in half.
+        Err(error) if error.error_len().is_none() => {
+            std::str::from_utf8(&bytes[..error.valid_up_to()]).unwrap()
+        }
+        Err(_) => return None,
+    };
+    if text.contains('\0') {
+        return None;
+    }
+
+    if let Some(shebang) = text.strip_prefix("#!") {
+        return Some(mime_for_interpreter(shebang.lines().next().unwrap_or("")));
+    }
+
+    let start = text.trim_start();
+    if start.starts_with("<svg") || start.starts_with("<?xml") && text.contains("<svg") {
+        return Some("image/svg+xml");
+    }
+    if start.starts_with("<!DOCTYPE html") || start.starts_with("<html") {
+        return Some("text/html");
+    }
+
+    Some("text/plain")
+}
+
+/// Sniffs the MIME type of the file at `path` from its first bytes.
+pub fn sniff_file_mime(path: &Path) -> Option<&'static str> {
+    let mut header = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)
+        .ok()?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut header)
+        .ok()?;
+    // Empty files say nothing about what they are meant to hold.
+    if header.is_empty() {
+        return None;
+    }
+    sniff_mime(&header)
+}
+
+/// `#!/usr/bin/env python3 -u` and `#!/bin/python3` both mean Python.
+fn mime_for_interpreter(shebang: &str) -> &'static str {
+    let mut words = shebang.split_whitespace();
+    let mut interpreter = words.next().unwrap_or("");
+    if interpreter.ends_with("/env") {
+        interpreter = words.find(|word| !word.starts_with('-')).unwrap_or("");
+    }
+    let name = interpreter.rsplit('/').next().unwrap_or(interpreter);
+    let name = name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+    match name {
+        "sh" | "bash" | "zsh" | "fish" | "dash" | "ksh" | "nu" => "text/x-shellscript",
+        "python" => "text/x-python",
+        "node" | "deno" | "bun" => "text/javascript",
+        "ruby" => "text/x-ruby",
+        "perl" => "text/x-perl",
+        "php" => "text/x-php",
+        "lua" => "text/x-lua",
+        _ => "text/x-script",
+    }
+}
+
+/// The icon type in `file_types.json` for a MIME type.
+pub fn icon_type_for_mime(mime: &str) -> Option<&'static str> {
+    let (top, sub) = mime.split_once('/')?;
+    Some(match (top, sub) {
+        ("image", _) => "image",
+        ("audio", _) => "audio",
+        ("video", _) => "video",
+        ("font", _) => "font",
+        ("text", "x-shellscript") => "terminal",
+        ("text", "plain") => "text",
+        ("text", _) => "code",
+        ("application", "pdf") => "pdf",
+        ("application", "json") => "json",
+        ("application", "vnd.sqlite3") => "database",
+        (
+            "application",
+            "zip" | "gzip" | "x-xz" | "zstd" | "x-bzip2" | "x-7z-compressed" | "vnd.rar" | "x-tar",
+        ) => "archive",
+        ("application", "x-executable" | "x-mach-binary" | "vnd.microsoft.portable-executable") => {
+            "executable"
+        }
+        _ => return None,
+    })
+}
+
+#[derive(Debug)]
+struct CachedType {
+    modified: Option<SystemTime>,
+    size: u64,
+    /// `None` while the file is being sniffed.
+    mime: Option<Option<&'static str>>,
+}
+
+/// Sniffed MIME types, remembered per path until the listing shows the file
+/// with another modification time or size.
+#[derive(Debug, Default)]
+pub struct ContentTypes {
+    cache: Arc<Mutex<HashMap<PathBuf, CachedType>>>,
+}
+
+impl ContentTypes {
+    /// The MIME type of the file at `path`, which its listing says was
+    /// last `modified` and is `size` bytes, if it has been sniffed. Otherwise
+    /// starts sniffing it in the background and redraws the windows once a
+    /// type is found.
+    pub fn mime_for_file(
+        &self,
+        path: &Path,
+        modified: Option<SystemTime>,
+        size: u64,
+        cx: &AppContext,
+    ) -> Option<&'static str> {
+        {
+            let mut cache = self.cache.lock().unwrap();
+            if let Some(cached) = cache
+                .get(path)
+                .filter(|cached| cached.modified == modified && cached.size == size)
+            {
+                return cached.mime.flatten();
+            }
+
+            if cache.len() >= MAX_CACHED {
+                cache.retain(|_, cached| cached.mime.is_none());
+            }
+            cache.insert(
+                path.to_path_buf(),
+                CachedType {
+                    modified,
+                    size,
+                    mime: None,
+                },
+            );
+        }
+
+        let path = path.to_path_buf();
+        let work = cx.background_executor().spawn({
+            let path = path.clone();
+            async move { sniff_file_mime(&path) }
+        });
+        let cache = self.cache.clone();
+        cx.spawn(|mut cx| async move {
+            let mime = work.await;
+            cache.lock().unwrap().insert(
+                path,
+                CachedType {
+                    modified,
+                    size,
+                    mime: Some(mime),
+                },
+            );
+            if mime.is_some() {
+                cx.refresh().ok();
+            }
+        })
+        .detach();
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn padded(start: &[u8]) -> Vec<u8> {
+        let mut bytes = start.to_vec();
+        bytes.resize(64, 0x01);
+        bytes
+    }
+
+    #[test]
+    fn sniffs_magic_numbers() {
+        assert_eq!(sniff_mime(b"\x89PNG\r\n\x1a\n\0\0"), Some("image/png"));
+        assert_eq!(sniff_mime(b"\xff\xd8\xff\xe0"), Some("image/jpeg"));
+        assert_eq!(sniff_mime(b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(sniff_mime(b"PK\x03\x04\x14\0"), Some("application/zip"));
+        assert_eq!(
+            sniff_mime(b"\x7fELF\x02\x01"),
+            Some("application/x-executable")
+        );
+
+        let mut tar = vec![0; 300];
+        tar[257..262].copy_from_slice(b"ustar");
+        assert_eq!(sniff_mime(&tar), Some("application/x-tar"));
+    }
+
+    #[test]
+    fn sniffs_containers_by_what_they_hold() {
+        assert_eq!(
+            sniff_mime(&padded(b"RIFF\0\0\0\0WEBPVP8 ")),
+            Some("image/webp")
+        );
+        assert_eq!(
+            sniff_mime(&padded(b"RIFF\0\0\0\0WAVEfmt ")),
+            Some("audio/wav")
+        );
+        assert_eq!(
+            sniff_mime(&padded(b"\0\0\0\x18ftypheic")),
+            Some("image/heic")
+        );
+        assert_eq!(
+            sniff_mime(&padded(b"\0\0\0\x18ftypisom")),
+            Some("video/mp4")
+        );
+    }
+
+    #[test]
+    fn tells_text_from_short_signatures() {
+        assert_eq!(sniff_mime(b"BMW drivers"), Some("text/plain"));
+        assert_eq!(sniff_mime(b"BM\0\0\0\0\0\0\0\0"), Some("image/bmp"));
+        assert_eq!(sniff_mime(b"MZ is a text"), Some("text/plain"));
+    }
+
+    #[test]
+    fn sniffs_text() {
+        assert_eq!(sniff_mime("Grüße\n".as_bytes()), Some("text/plain"));
+        // A multi-byte character cut off by the end of the read.
+        assert_eq!(sniff_mime(&"é".as_bytes()[..1]), Some("text/plain"));
+        assert_eq!(sniff_mime(b"  <svg xmlns=\"\">"), Some("image/svg+xml"));
+        assert_eq!(sniff_mime(b"<!DOCTYPE html>"), Some("text/html"));
+        assert_eq!(sniff_mime(b"#!/bin/sh\necho"), Some("text/x-shellscript"));
+        assert_eq!(sniff_mime(b"text\0with a nul"), None);
+        assert_eq!(sniff_mime(b"\xff\xfe\xfd"), None);
+    }
+
+    #[test]
+    fn names_interpreters() {
+        assert_eq!(
+            mime_for_interpreter("/usr/bin/env python3"),
+            "text/x-python"
+        );
+        assert_eq!(
+            mime_for_interpreter("/usr/bin/env -S python3 -u"),
+            "text/x-python"
+        );
+        assert_eq!(mime_for_interpreter("/usr/bin/python3.12"), "text/x-python");
+        assert_eq!(mime_for_interpreter(" /bin/bash -e"), "text/x-shellscript");
+        assert_eq!(mime_for_interpreter("/usr/bin/env node"), "text/javascript");
+        assert_eq!(mime_for_interpreter("/usr/bin/awk -f"), "text/x-script");
+        assert_eq!(mime_for_interpreter(""), "text/x-script");
+    }
+}
@@ -1,4 +1,6 @@
-use std::{collections, path::Path, str, sync::Arc};
+mod content_type;
+
+use std::{collections, path::Path, str, sync::Arc, time::SystemTime};
 
 use collections::HashMap;
 
@@ -6,16 +8,25 @@ use gpui::{AppContext, AssetSource, Global};
 use serde_derive::Deserialize;
 use util::{maybe, paths::PathExt};
 
-#[derive(Deserialize, Debug)]
+pub use content_type::*;
+
+#[derive(Deserialize, Debug, Clone)]
 struct TypeConfig {
     icon: Arc<str>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct FileIcons {
+    /// Folder names with an icon of their own, like `.git` or `Music`.
+    #[serde(default)]
+    folders: HashMap<String, String>,
     stems: HashMap<String, String>,
     suffixes: HashMap<String, String>,
     types: HashMap<String, TypeConfig>,
+    /// Set when icons should also reflect what files contain, not just
+    /// their names.
+    #[serde(skip)]
+    content_types: Option<Arc<ContentTypes>>,
 }
 
 impl Global for FileIcons {}
@@ -41,28 +52,74 @@ impl FileIcons {
             .ok()
             .flatten()
             .and_then(|file| serde_json::from_str::<FileIcons>(str::from_utf8(&file).unwrap()).ok())
-            .unwrap_or_else(|| FileIcons {
-                stems: HashMap::default(),
-                suffixes: HashMap::default(),
-                types: HashMap::default(),
-            })
+            .unwrap_or_default()
     }
 
-    pub fn get_icon(path: &Path, cx: &AppContext) -> Option<Arc<str>> {
+    /// Returns the icon type (e.g. `image`, `archive`) associated with the
+    /// path's stem or suffix. Suffixes also match case-insensitively, so
+    /// `IMG_0001.JPG` is an image.
+    pub fn type_for_path(&self, path: &Path) -> Option<&str> {
+        let suffix = path.icon_stem_or_suffix()?;
+
+        self.stems
+            .get(suffix)
+            .or_else(|| self.suffixes.get(suffix))
+            .or_else(|| self.suffixes.get(&suffix.to_lowercase()))
+            .map(String::as_str)
+    }
+
+    /// Turns sniffing file contents for [`FileIcons::detect_type`] on or
+    /// off.
+    pub fn set_detect_content(&mut self, enabled: bool) {
+        self.content_types = enabled.then(|| Arc::new(ContentTypes::default()));
+    }
+
+    /// Like [`FileIcons::type_for_path`], but with content detection on,
+    /// extensionless files get the type of their contents and a recognized
+    /// signature wins over a name that says text or a different kind of
+    /// media. `modified` and `size` come from the file's listing; until its
+    /// contents have been sniffed, the name decides.
+    pub fn detect_type(
+        &self,
+        path: &Path,
+        modified: Option<SystemTime>,
+        size: u64,
+        cx: &AppContext,
+    ) -> Option<&str> {
+        let by_name = self.type_for_path(path);
+        let Some(content_types) = &self.content_types else {
+            return by_name;
+        };
+        let by_content = content_types
+            .mime_for_file(path, modified, size, cx)
+            .and_then(icon_type_for_mime);
+
+        const MEDIA: [&str; 3] = ["image", "audio", "video"];
+        match (by_name, by_content) {
+            (None, by_content) => by_content,
+            (Some("text"), Some(by_content)) => Some(by_content),
+            (Some(by_name), Some(by_content))
+                if MEDIA.contains(&by_content) && !MEDIA.contains(&by_name) =>
+            {
+                Some(by_content)
+            }
+            (by_name, _) => by_name,
+        }
+    }
+
+    pub fn get_icon(
+        path: &Path,
+        modified: Option<SystemTime>,
+        size: u64,
+        cx: &AppContext,
+    ) -> Option<Arc<str>> {
         let this = cx.try_global::<Self>()?;
 
         // FIXME: Associate a type with the languages and have the file's language
         //        override these associations
         maybe!({
-            let suffix = path.icon_stem_or_suffix()?;
-
-            if let Some(type_str) = this.stems.get(suffix) {
-                return this.get_type_icon(type_str);
-            }
-
-            this.suffixes
-                .get(suffix)
-                .and_then(|type_str| this.get_type_icon(type_str))
+            let type_str = this.detect_type(path, modified, size, cx)?;
+            this.get_type_icon(type_str)
         })
         .or_else(|| this.get_type_icon("default"))
     }
@@ -85,6 +142,22 @@ impl FileIcons {
         this.get_type_icon(key)
     }
 
+    /// The icon for a folder, specific to its name when `file_types.json`
+    /// maps it, and the generic folder icon otherwise.
+    pub fn get_folder_icon_for_path(
+        path: &Path,
+        expanded: bool,
+        cx: &AppContext,
+    ) -> Option<Arc<str>> {
+        let this = cx.try_global::<Self>()?;
+
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| this.folders.get(name))
+            .and_then(|type_str| this.get_type_icon(type_str))
+            .or_else(|| Self::get_folder_icon(expanded, cx))
+    }
+
     pub fn get_chevron_icon(expanded: bool, cx: &AppContext) -> Option<Arc<str>> {
         let this = cx.try_global::<Self>()?;
 
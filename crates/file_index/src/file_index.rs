@@ -0,0 +1,547 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self as std_fs, File, Metadata},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, RwLock,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use fs::{FsEvent, FsWatcher};
+use util::fuzzy::fuzzy_match;
+use walkdir::WalkDir;
+
+const INDEX_HEADER: &str = "vistar-index 1";
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EntryMetadata {
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl EntryMetadata {
+    fn from_metadata(metadata: &Metadata) -> Self {
+        Self {
+            is_dir: metadata.is_dir(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct IndexMatch {
+    pub path: PathBuf,
+    pub metadata: EntryMetadata,
+    pub score: i64,
+    /// Char indices of the matched characters in the file name, or in the
+    /// full path when the query contains a separator.
+    pub positions: Vec<usize>,
+}
+
+/// File names and basic metadata for everything below a set of roots.
+pub struct FileIndex {
+    roots: Vec<PathBuf>,
+    excluded: Vec<String>,
+    entries: BTreeMap<PathBuf, EntryMetadata>,
+}
+
+impl FileIndex {
+    pub fn new(roots: Vec<PathBuf>, excluded: Vec<String>) -> Self {
+        Self {
+            roots,
+            excluded,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Loads an index written by [`FileIndex::save`], dropping entries that
+    /// are no longer below one of `roots`.
+    pub fn load(path: &Path, roots: Vec<PathBuf>, excluded: Vec<String>) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("failed to open index {}", path.display()))?;
+        let mut lines = BufReader::new(file).lines();
+
+        if lines.next().transpose()?.as_deref() != Some(INDEX_HEADER) {
+            return Err(anyhow!("unsupported index format in {}", path.display()));
+        }
+
+        let mut index = Self::new(roots, excluded);
+        for line in lines {
+            let line = line?;
+            let mut fields = line.splitn(4, '\t');
+            let (Some(kind), Some(size), Some(modified), Some(entry_path)) =
+                (fields.next(), fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+
+            let entry_path = PathBuf::from(entry_path);
+            if !index.is_indexed(&entry_path) {
+                continue;
+            }
+
+            let modified = modified
+                .parse::<u64>()
+                .ok()
+                .filter(|secs| *secs != 0)
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+            index.entries.insert(
+                entry_path,
+                EntryMetadata {
+                    is_dir: kind == "d",
+                    size: size.parse().unwrap_or(0),
+                    modified,
+                },
+            );
+        }
+
+        Ok(index)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std_fs::create_dir_all(parent)?;
+        }
+
+        let temp_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        writeln!(writer, "{}", INDEX_HEADER)?;
+        for (entry_path, metadata) in &self.entries {
+            // Tabs and newlines would break the line format, and such names
+            // are rare enough to leave out.
+            let Some(entry_path) = entry_path.to_str().filter(|p| !p.contains(['\t', '\n']))
+            else {
+                continue;
+            };
+            let modified = metadata
+                .modified
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_secs());
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}",
+                if metadata.is_dir { "d" } else { "f" },
+                metadata.size,
+                modified,
+                entry_path
+            )?;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        std_fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Rebuilds the index from scratch by walking every root.
+    pub fn scan(&mut self) {
+        self.entries.clear();
+        for root in self.roots.clone() {
+            self.scan_dir(&root);
+        }
+    }
+
+    fn scan_dir(&mut self, dir: &Path) {
+        let excluded = self.excluded.clone();
+        let walker = WalkDir::new(dir)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|entry| !is_excluded_name(&excluded, entry.file_name()));
+
+        for entry in walker.filter_map(Result::ok) {
+            if let Ok(metadata) = entry.metadata() {
+                self.entries
+                    .insert(entry.into_path(), EntryMetadata::from_metadata(&metadata));
+            }
+        }
+    }
+
+    /// Brings the entry for `path` up to date after a change on disk.
+    pub fn refresh(&mut self, path: &Path) {
+        if !self.is_indexed(path) {
+            return;
+        }
+
+        match std_fs::symlink_metadata(path) {
+            Ok(metadata) => {
+                let previous = self
+                    .entries
+                    .insert(path.to_path_buf(), EntryMetadata::from_metadata(&metadata));
+                if metadata.is_dir() && previous.is_none() {
+                    self.scan_dir(path);
+                }
+            }
+            Err(_) => self.remove(path),
+        }
+    }
+
+    fn remove(&mut self, path: &Path) {
+        let removed = self
+            .entries
+            .range(path.to_path_buf()..)
+            .take_while(|(entry_path, _)| entry_path.starts_with(path))
+            .map(|(entry_path, _)| entry_path.clone())
+            .collect::<Vec<_>>();
+        for entry_path in removed {
+            self.entries.remove(&entry_path);
+        }
+    }
+
+    fn is_indexed(&self, path: &Path) -> bool {
+        self.roots.iter().any(|root| {
+            path.strip_prefix(root).is_ok_and(|relative| {
+                !relative.as_os_str().is_empty()
+                    && !relative
+                        .iter()
+                        .any(|component| is_excluded_name(&self.excluded, component))
+            })
+        })
+    }
+
+    /// Fuzzy matches `query` against file names, or against full paths if the
+    /// query contains a path separator.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<IndexMatch> {
+        let match_full_path = query.contains(std::path::MAIN_SEPARATOR);
+
+        let mut matches = self
+            .entries
+            .iter()
+            .filter_map(|(path, metadata)| {
+                let candidate = if match_full_path {
+                    path.to_string_lossy()
+                } else {
+                    path.file_name()?.to_string_lossy()
+                };
+                let fuzzy_match = fuzzy_match(query, &candidate)?;
+                Some(IndexMatch {
+                    path: path.clone(),
+                    metadata: *metadata,
+                    score: fuzzy_match.score,
+                    positions: fuzzy_match.positions,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let by_rank = |a: &IndexMatch, b: &IndexMatch| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a.path.as_os_str().len().cmp(&b.path.as_os_str().len()))
+        };
+        if matches.len() > limit && limit > 0 {
+            matches.select_nth_unstable_by(limit - 1, by_rank);
+            matches.truncate(limit);
+        }
+        matches.sort_by(by_rank);
+        matches.truncate(limit);
+        matches
+    }
+}
+
+fn is_excluded_name(excluded: &[String], name: &std::ffi::OsStr) -> bool {
+    name.to_str()
+        .is_some_and(|name| excluded.iter().any(|excluded| excluded == name))
+}
+
+/// Keeps a [`FileIndex`] current on a background thread.
+///
+/// The index on disk is loaded first so searches work right away, then the
+/// roots are rescanned and filesystem events applied as they arrive.
+pub struct IndexService {
+    index: Arc<RwLock<FileIndex>>,
+    ready: Arc<AtomicBool>,
+    messages: Sender<Message>,
+}
+
+/// What the index thread waits for.
+enum Message {
+    Events(Vec<FsEvent>),
+    Stop,
+}
+
+impl IndexService {
+    pub fn start(roots: Vec<PathBuf>, excluded: Vec<String>, index_path: PathBuf) -> Self {
+        let index = Arc::new(RwLock::new(FileIndex::new(roots.clone(), excluded.clone())));
+        let ready = Arc::new(AtomicBool::new(false));
+        let (messages, messages_rx) = mpsc::channel();
+
+        thread::Builder::new()
+            .name("file-index".into())
+            .spawn({
+                let index = index.clone();
+                let ready = ready.clone();
+                let messages = messages.clone();
+                move || {
+                    run_index(
+                        index,
+                        ready,
+                        messages,
+                        messages_rx,
+                        roots,
+                        excluded,
+                        index_path,
+                    )
+                }
+            })
+            .expect("failed to spawn file index thread");
+
+        Self {
+            index,
+            ready,
+            messages,
+        }
+    }
+
+    /// Whether the index holds anything yet, either loaded from disk or from
+    /// the initial scan.
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
+
+    pub fn search(&self, query: &str, limit: usize) -> Vec<IndexMatch> {
+        self.index.read().unwrap().search(query, limit)
+    }
+}
+
+impl Drop for IndexService {
+    fn drop(&mut self) {
+        self.messages.send(Message::Stop).ok();
+    }
+}
+
+fn run_index(
+    index: Arc<RwLock<FileIndex>>,
+    ready: Arc<AtomicBool>,
+    messages: Sender<Message>,
+    messages_rx: mpsc::Receiver<Message>,
+    roots: Vec<PathBuf>,
+    excluded: Vec<String>,
+    index_path: PathBuf,
+) {
+    if let Ok(loaded) = FileIndex::load(&index_path, roots.clone(), excluded.clone()) {
+        if !loaded.is_empty() {
+            *index.write().unwrap() = loaded;
+            ready.store(true, Ordering::Relaxed);
+        }
+    }
+
+    // Start watching before the scan so changes made during it aren't lost.
+    let _watcher = FsWatcher::new(&roots, true, move |events| {
+        messages.send(Message::Events(events)).ok();
+    })
+    .map_err(|error| eprintln!("Failed to watch indexed folders: {}", error))
+    .ok();
+
+    // Scanning happens outside the lock, so searches keep using the
+    // previous index until the new one is swapped in.
+    let rescan = || {
+        let mut fresh = FileIndex::new(roots.clone(), excluded.clone());
+        fresh.scan();
+        *index.write().unwrap() = fresh;
+    };
+    rescan();
+    ready.store(true, Ordering::Relaxed);
+
+    let save = |index: &RwLock<FileIndex>| {
+        if let Err(error) = index.read().unwrap().save(&index_path) {
+            eprintln!("Failed to save file index: {}", error);
+        }
+    };
+    save(&index);
+
+    let mut dirty = false;
+    let mut last_save = Instant::now();
+    loop {
+        match messages_rx.recv_timeout(SAVE_INTERVAL) {
+            // The watcher lost track of what changed.
+            Ok(Message::Events(events)) if events.is_empty() => {
+                rescan();
+                dirty = true;
+            }
+            Ok(Message::Events(events)) => {
+                let mut index = index.write().unwrap();
+                for event in events {
+                    index.refresh(&event.path);
+                }
+                dirty = true;
+            }
+            Ok(Message::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        if dirty && last_save.elapsed() >= SAVE_INTERVAL {
+            save(&index);
+            dirty = false;
+            last_save = Instant::now();
+        }
+    }
+
+    if dirty {
+        save(&index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_tree() -> (tempfile::TempDir, FileIndex) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std_fs::create_dir_all(root.join("docs/old")).unwrap();
+        std_fs::create_dir_all(root.join("target/debug")).unwrap();
+        std_fs::write(root.join("docs/notes.txt"), "notes").unwrap();
+        std_fs::write(root.join("docs/old/report.txt"), "report").unwrap();
+        std_fs::write(root.join("docs.txt"), "").unwrap();
+        std_fs::write(root.join("target/debug/app"), "").unwrap();
+
+        let mut index = FileIndex::new(vec![root], vec!["target".to_string()]);
+        index.scan();
+        (dir, index)
+    }
+
+    fn paths(index: &FileIndex, root: &Path) -> Vec<String> {
+        index
+            .entries
+            .keys()
+            .map(|path| {
+                path.strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn scans_everything_but_excluded_folders() {
+        let (dir, index) = make_tree();
+        let root = dir.path().join("root");
+        assert_eq!(
+            paths(&index, &root),
+            [
+                "docs",
+                "docs/notes.txt",
+                "docs/old",
+                "docs/old/report.txt",
+                "docs.txt"
+            ]
+        );
+        let notes = index.entries[&root.join("docs/notes.txt")];
+        assert!(!notes.is_dir);
+        assert_eq!(notes.size, 5);
+        assert!(index.entries[&root.join("docs")].is_dir);
+    }
+
+    #[test]
+    fn adds_entries_as_they_appear() {
+        let (dir, mut index) = make_tree();
+        let root = dir.path().join("root");
+        std_fs::write(root.join("new.txt"), "").unwrap();
+        std_fs::create_dir_all(root.join("photos/2024")).unwrap();
+        std_fs::write(root.join("photos/2024/me.png"), "").unwrap();
+        std_fs::write(root.join("target/debug/other"), "").unwrap();
+
+        index.refresh(&root.join("new.txt"));
+        // A new folder brings in what it holds.
+        index.refresh(&root.join("photos"));
+        index.refresh(&root.join("target/debug/other"));
+        index.refresh(&dir.path().join("outside.txt"));
+
+        assert_eq!(index.len(), 9);
+        for path in ["new.txt", "photos", "photos/2024", "photos/2024/me.png"] {
+            assert!(index.entries.contains_key(&root.join(path)), "{}", path);
+        }
+    }
+
+    #[test]
+    fn updates_changed_entries() {
+        let (dir, mut index) = make_tree();
+        let notes = dir.path().join("root/docs/notes.txt");
+        std_fs::write(&notes, "longer notes").unwrap();
+        index.refresh(&notes);
+        assert_eq!(index.entries[&notes].size, 12);
+    }
+
+    #[test]
+    fn removes_deleted_entries_and_what_they_held() {
+        let (dir, mut index) = make_tree();
+        let root = dir.path().join("root");
+        std_fs::remove_dir_all(root.join("docs")).unwrap();
+        index.refresh(&root.join("docs"));
+        assert_eq!(paths(&index, &root), ["docs.txt"]);
+    }
+
+    #[test]
+    fn searches_names_or_full_paths() {
+        let (dir, index) = make_tree();
+        let root = dir.path().join("root");
+        let found = |query| {
+            index
+                .search(query, 10)
+                .into_iter()
+                .map(|found| found.path.strip_prefix(&root).unwrap().to_path_buf())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(found("report"), [Path::new("docs/old/report.txt")]);
+        assert_eq!(found("xyz"), Vec::<PathBuf>::new());
+        // Only names are matched unless the query has a separator.
+        assert_eq!(found("oldrep"), Vec::<PathBuf>::new());
+        let query = format!("old{}rep", std::path::MAIN_SEPARATOR);
+        assert_eq!(found(&query), [Path::new("docs/old/report.txt")]);
+        assert_eq!(index.search("doc", 1).len(), 1);
+    }
+
+    #[test]
+    fn saves_and_loads_the_index() {
+        let (dir, index) = make_tree();
+        let root = dir.path().join("root");
+        let notes = root.join("docs/notes.txt");
+        File::options()
+            .write(true)
+            .open(&notes)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+            .unwrap();
+        let mut index = index;
+        index.refresh(&notes);
+
+        let index_path = dir.path().join("cache/index");
+        index.save(&index_path).unwrap();
+        let loaded = FileIndex::load(&index_path, vec![root.clone()], vec![]).unwrap();
+        assert_eq!(loaded.entries.keys().collect::<Vec<_>>(), {
+            index.entries.keys().collect::<Vec<_>>()
+        });
+        assert_eq!(loaded.entries[&notes], index.entries[&notes]);
+
+        // Entries no longer below a root are dropped.
+        let loaded = FileIndex::load(&index_path, vec![root.join("docs/old")], vec![]).unwrap();
+        assert_eq!(paths(&loaded, &root), ["docs/old/report.txt"]);
+    }
+
+    #[test]
+    fn refuses_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index");
+        std_fs::write(&path, "something else\n").unwrap();
+        assert!(FileIndex::load(&path, vec![], vec![]).is_err());
+        assert!(FileIndex::load(&dir.path().join("missing"), vec![], vec![]).is_err());
+    }
+}
@@ -0,0 +1,1004 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{BufReader, Read},
+    ops::Bound,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    transfer::{copy_data, write_or_remove},
+    unique_destination, TransferProgress,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+    TarZst,
+}
+
+/// The first extension of each format is the one new archives get.
+const EXTENSIONS: &[(&str, ArchiveFormat)] = &[
+    (".zip", ArchiveFormat::Zip),
+    (".tar", ArchiveFormat::Tar),
+    (".tar.gz", ArchiveFormat::TarGz),
+    (".tgz", ArchiveFormat::TarGz),
+    (".tar.xz", ArchiveFormat::TarXz),
+    (".txz", ArchiveFormat::TarXz),
+    (".tar.zst", ArchiveFormat::TarZst),
+    (".tzst", ArchiveFormat::TarZst),
+];
+
+impl ArchiveFormat {
+    /// The formats offered for new archives.
+    pub const CREATABLE: [ArchiveFormat; 4] = [
+        ArchiveFormat::Zip,
+        ArchiveFormat::TarGz,
+        ArchiveFormat::TarXz,
+        ArchiveFormat::TarZst,
+    ];
+
+    /// Recognises archives by their extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        split_name(path).map(|(_, format)| format)
+    }
+
+    /// The name of `path` without its archive extension, e.g. `photos` for
+    /// `photos.tar.gz`.
+    pub fn stem(path: &Path) -> Option<&str> {
+        split_name(path).map(|(stem, _)| stem)
+    }
+
+    /// The extension new archives get, with its dot.
+    pub fn extension(self) -> &'static str {
+        EXTENSIONS
+            .iter()
+            .find(|(_, format)| *format == self)
+            .map_or("", |(extension, _)| extension)
+    }
+
+    /// The extension without its dot, e.g. `tar.gz`.
+    pub fn name(self) -> &'static str {
+        &self.extension()[1..]
+    }
+}
+
+fn split_name(path: &Path) -> Option<(&str, ArchiveFormat)> {
+    let name = path.file_name()?.to_str()?;
+    EXTENSIONS.iter().find_map(|(extension, format)| {
+        let stem_len = name
+            .len()
+            .checked_sub(extension.len())
+            .filter(|len| *len > 0)?;
+        let suffix = name.get(stem_len..)?;
+        suffix
+            .eq_ignore_ascii_case(extension)
+            .then(|| (&name[..stem_len], *format))
+    })
+}
+
+/// A file or folder inside an archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    /// Relative to the top of the archive.
+    pub path: PathBuf,
+    pub is_dir: bool,
+    /// For folders, the size of everything below them.
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// Unix permission bits, where the archive records them.
+    pub mode: Option<u32>,
+}
+
+/// The listing of an archive, read once so it can be browsed like a folder.
+/// Folders that are only implied by the paths of the files in them are
+/// listed too.
+#[derive(Debug)]
+pub struct Archive {
+    path: PathBuf,
+    format: ArchiveFormat,
+    modified: Option<SystemTime>,
+    entries: BTreeMap<PathBuf, ArchiveEntry>,
+    /// Entries left out because their paths lead out of the archive.
+    refused: usize,
+    /// Where `extract_temporary` puts its copies, when not in the user's
+    /// cache.
+    cache_dir: Option<PathBuf>,
+}
+
+/// Links are made after everything else is extracted, so no file can be
+/// written through a link the archive just made.
+enum Link {
+    Symbolic { at: PathBuf, to: PathBuf },
+    Hard { at: PathBuf, to: PathBuf },
+}
+
+impl Link {
+    fn at(&self) -> &Path {
+        match self {
+            Link::Symbolic { at, .. } | Link::Hard { at, .. } => at,
+        }
+    }
+}
+
+impl Archive {
+    pub fn open(path: &Path) -> Result<Self> {
+        let format = ArchiveFormat::from_path(path)
+            .ok_or_else(|| anyhow!("{} isn't a supported archive", path.display()))?;
+        let modified = fs::metadata(path)?.modified().ok();
+        let mut archive = Self {
+            path: path.to_path_buf(),
+            format,
+            modified,
+            entries: BTreeMap::new(),
+            refused: 0,
+            cache_dir: None,
+        };
+
+        let (listed, refused) = match format {
+            ArchiveFormat::Zip => archive.list_zip()?,
+            _ => archive.list_tar()?,
+        };
+        archive.entries.insert(
+            PathBuf::new(),
+            ArchiveEntry {
+                path: PathBuf::new(),
+                is_dir: true,
+                size: 0,
+                modified,
+                mode: None,
+            },
+        );
+        for entry in listed {
+            archive.add(entry);
+        }
+        archive.refused = refused;
+        Ok(archive)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+
+    /// How many entries were left out because they would be extracted
+    /// outside the destination, like `../../.bashrc`.
+    pub fn refused_entries(&self) -> usize {
+        self.refused
+    }
+
+    /// The entry at `inner`; an empty path is the top of the archive.
+    pub fn entry(&self, inner: &Path) -> Option<&ArchiveEntry> {
+        self.entries.get(inner)
+    }
+
+    /// The entries directly inside the folder `inner`.
+    pub fn children<'a>(&'a self, inner: &'a Path) -> impl Iterator<Item = &'a ArchiveEntry> {
+        self.entries
+            .range::<Path, _>((Bound::Included(inner), Bound::Unbounded))
+            .take_while(move |(path, _)| path.starts_with(inner))
+            .filter(move |(path, _)| path.parent() == Some(inner))
+            .map(|(_, entry)| entry)
+    }
+
+    /// Adds `entry`, its parent folders if they aren't listed, and its size
+    /// to every folder above it.
+    fn add(&mut self, entry: ArchiveEntry) {
+        // A path can't be both a file and a folder; whichever came first wins.
+        let clashes = entry.path.ancestors().skip(1).any(|ancestor| {
+            self.entries
+                .get(ancestor)
+                .is_some_and(|listed| !listed.is_dir)
+        }) || self
+            .entries
+            .get(&entry.path)
+            .is_some_and(|listed| listed.is_dir != entry.is_dir);
+        if clashes {
+            return;
+        }
+
+        let size = if entry.is_dir { 0 } else { entry.size };
+        // Tar archives can hold a file more than once, when it was appended
+        // again; the later copy replaces the earlier one.
+        let replaced = match self.entries.get(&entry.path) {
+            Some(listed) if !listed.is_dir => listed.size,
+            _ => 0,
+        };
+        for ancestor in entry.path.ancestors().skip(1) {
+            let folder = self
+                .entries
+                .entry(ancestor.to_path_buf())
+                .or_insert_with(|| ArchiveEntry {
+                    path: ancestor.to_path_buf(),
+                    is_dir: true,
+                    size: 0,
+                    modified: None,
+                    mode: None,
+                });
+            folder.size = folder.size + size - replaced;
+        }
+        // Folders listed after their contents keep the size added so far.
+        let size = match self.entries.get(&entry.path) {
+            Some(folder) if entry.is_dir => folder.size,
+            _ => entry.size,
+        };
+        self.entries
+            .insert(entry.path.clone(), ArchiveEntry { size, ..entry });
+    }
+
+    /// The entries of a zip archive, and how many were refused.
+    fn list_zip(&self) -> Result<(Vec<ArchiveEntry>, usize)> {
+        let mut zip = zip::ZipArchive::new(BufReader::new(File::open(&self.path)?))?;
+        let mut entries = Vec::new();
+        let mut refused = 0;
+        for ix in 0..zip.len() {
+            let file = zip.by_index_raw(ix)?;
+            let Some(path) = file.enclosed_name().and_then(|name| sanitize(&name)) else {
+                refused += 1;
+                continue;
+            };
+            if path.as_os_str().is_empty() {
+                continue;
+            }
+            entries.push(ArchiveEntry {
+                path,
+                is_dir: file.is_dir(),
+                size: file.size(),
+                modified: file.last_modified().and_then(zip_time),
+                mode: file.unix_mode().map(|mode| mode & 0o7777),
+            });
+        }
+        Ok((entries, refused))
+    }
+
+    /// The entries of a tar archive, and how many were refused.
+    fn list_tar(&self) -> Result<(Vec<ArchiveEntry>, usize)> {
+        let mut tar = tar::Archive::new(tar_reader(&self.path, self.format)?);
+        let mut entries = Vec::new();
+        let mut refused = 0;
+        for entry in tar.entries()? {
+            let entry = entry?;
+            let header = entry.header();
+            let is_dir = match header.entry_type() {
+                tar::EntryType::Directory => true,
+                tar::EntryType::Regular
+                | tar::EntryType::Continuous
+                | tar::EntryType::Symlink
+                | tar::EntryType::Link => false,
+                _ => continue,
+            };
+            let Some(path) = sanitize(&entry.path()?) else {
+                refused += 1;
+                continue;
+            };
+            if path.as_os_str().is_empty() {
+                continue;
+            }
+            entries.push(ArchiveEntry {
+                path,
+                is_dir,
+                size: entry.size(),
+                modified: header
+                    .mtime()
+                    .ok()
+                    .map(|mtime| SystemTime::UNIX_EPOCH + Duration::from_secs(mtime)),
+                mode: header.mode().ok().map(|mode| mode & 0o7777),
+            });
+        }
+        Ok((entries, refused))
+    }
+
+    /// Writes the entry at `inner`, and everything below it when it's a
+    /// folder, to `destination`, which must not exist yet. If it fails or is
+    /// cancelled, whatever it wrote to `destination` is removed again.
+    /// Returns how many symlinks were left out because they point outside
+    /// `destination`.
+    pub fn extract(
+        &self,
+        inner: &Path,
+        destination: &Path,
+        progress: &TransferProgress,
+    ) -> Result<usize> {
+        let entry = self
+            .entry(inner)
+            .ok_or_else(|| anyhow!("{} isn't in {}", inner.display(), self.path.display()))?;
+        if destination.symlink_metadata().is_ok() {
+            return Err(anyhow!("{} already exists", destination.display()));
+        }
+
+        let result = (|| {
+            if entry.is_dir {
+                fs::create_dir(destination)?;
+            }
+
+            let mut links = Vec::new();
+            match self.format {
+                ArchiveFormat::Zip => self.extract_zip(inner, destination, progress, &mut links)?,
+                _ => self.extract_tar(inner, destination, progress, &mut links)?,
+            }
+
+            let mut refused = 0;
+            for link in links {
+                match link {
+                    Link::Symbolic { at, to } => {
+                        let relative = at.strip_prefix(destination).unwrap_or(&at);
+                        if link_stays_inside(relative, &to) {
+                            make_symlink(&to, &at)?;
+                        } else {
+                            refused += 1;
+                        }
+                    }
+                    // The file linked to may have been left out of the
+                    // extraction.
+                    Link::Hard { at, to } if to.exists() => fs::hard_link(&to, &at)?,
+                    Link::Hard { .. } => {}
+                }
+            }
+            Ok(refused)
+        })();
+
+        if result.is_err() {
+            remove_partial(destination);
+        }
+        result
+    }
+
+    /// Extracts everything into the folder `destination`. A single entry at
+    /// the top goes there as it is; more go into a new folder named after the
+    /// archive rather than spilling into the destination. Returns where it
+    /// all went, and how many entries were left out because they lead
+    /// outside of it.
+    pub fn extract_all(
+        &self,
+        destination: &Path,
+        progress: &TransferProgress,
+    ) -> Result<(PathBuf, usize)> {
+        let top = self.children(Path::new("")).collect::<Vec<_>>();
+        let (inner, name) = match top.as_slice() {
+            [entry] => (entry.path.clone(), entry.path.as_os_str().to_os_string()),
+            _ => (
+                PathBuf::new(),
+                ArchiveFormat::stem(&self.path).unwrap_or("Archive").into(),
+            ),
+        };
+        let target = unique_destination(destination, &name);
+        let refused_links = self.extract(&inner, &target, progress)?;
+        Ok((target, self.refused + refused_links))
+    }
+
+    fn extract_zip(
+        &self,
+        inner: &Path,
+        destination: &Path,
+        progress: &TransferProgress,
+        links: &mut Vec<Link>,
+    ) -> Result<()> {
+        let mut zip = zip::ZipArchive::new(BufReader::new(File::open(&self.path)?))?;
+        for ix in 0..zip.len() {
+            progress.check_cancelled()?;
+            let Some(path) = zip
+                .by_index_raw(ix)?
+                .enclosed_name()
+                .and_then(|name| sanitize(&name))
+            else {
+                continue;
+            };
+            let mut file = zip.by_index(ix)?;
+            let (Some(target), Some(entry)) = (
+                extraction_target(&path, inner, destination),
+                self.entries
+                    .get(&path)
+                    .filter(|entry| entry.is_dir == file.is_dir()),
+            ) else {
+                continue;
+            };
+
+            if file.is_dir() {
+                fs::create_dir_all(&target)?;
+            } else if file.is_symlink() {
+                let mut to = String::new();
+                file.read_to_string(&mut to)?;
+                replace_earlier(&target, links)?;
+                links.push(Link::Symbolic {
+                    at: target,
+                    to: PathBuf::from(to),
+                });
+            } else {
+                create_parent(&target)?;
+                replace_earlier(&target, links)?;
+                write_file(&mut file, &target, entry, progress)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn extract_tar(
+        &self,
+        inner: &Path,
+        destination: &Path,
+        progress: &TransferProgress,
+        links: &mut Vec<Link>,
+    ) -> Result<()> {
+        let mut tar = tar::Archive::new(tar_reader(&self.path, self.format)?);
+        for file in tar.entries()? {
+            progress.check_cancelled()?;
+            let mut file = file?;
+            let Some(path) = sanitize(&file.path()?) else {
+                continue;
+            };
+            let entry_type = file.header().entry_type();
+            let (Some(target), Some(entry)) = (
+                extraction_target(&path, inner, destination),
+                self.entries
+                    .get(&path)
+                    .filter(|entry| entry.is_dir == entry_type.is_dir()),
+            ) else {
+                continue;
+            };
+
+            match entry_type {
+                tar::EntryType::Directory => fs::create_dir_all(&target)?,
+                tar::EntryType::Regular | tar::EntryType::Continuous => {
+                    create_parent(&target)?;
+                    replace_earlier(&target, links)?;
+                    write_file(&mut file, &target, entry, progress)?;
+                }
+                tar::EntryType::Symlink => {
+                    if let Some(to) = file.link_name()? {
+                        replace_earlier(&target, links)?;
+                        links.push(Link::Symbolic {
+                            at: target,
+                            to: to.into_owned(),
+                        });
+                    }
+                }
+                tar::EntryType::Link => {
+                    let to = file
+                        .link_name()?
+                        .and_then(|to| sanitize(&to))
+                        .and_then(|to| extraction_target(&to, inner, destination));
+                    if let Some(to) = to {
+                        replace_earlier(&target, links)?;
+                        links.push(Link::Hard { at: target, to });
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Extracts the file at `inner` to a folder in the user's cache, for
+    /// things that can only read files on disk. Copies made earlier are
+    /// reused until the archive changes.
+    pub fn extract_temporary(&self, inner: &Path) -> Result<PathBuf> {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.path.hash(&mut hasher);
+        self.modified.hash(&mut hasher);
+        let target = private_extraction_dir(self.cache_dir.as_deref())?
+            .join(format!("{:016x}", hasher.finish()))
+            .join(inner);
+        if fs::symlink_metadata(&target).is_ok_and(|metadata| metadata.is_file()) {
+            return Ok(target);
+        }
+
+        let (Some(parent), Some(file_name)) = (target.parent(), target.file_name()) else {
+            return Err(anyhow!("{} can't be extracted", inner.display()));
+        };
+        fs::create_dir_all(parent)?;
+        // Extract under another name first, so a copy cut short isn't reused.
+        let mut partial_name = file_name.to_os_string();
+        partial_name.push(".part");
+        let partial = parent.join(partial_name);
+        fs::remove_file(&partial).ok();
+        self.extract(inner, &partial, &TransferProgress::default())?;
+        fs::rename(&partial, &target)?;
+        Ok(target)
+    }
+}
+
+/// The folder `extract_temporary` writes to, in `cache_dir` or else the
+/// user's cache. Only the user may get into it, so nobody else can read
+/// what's extracted there or plant files for it to hand out.
+fn private_extraction_dir(cache_dir: Option<&Path>) -> Result<PathBuf> {
+    let cache_dir = match cache_dir {
+        Some(cache_dir) => cache_dir.to_path_buf(),
+        None => dirs::cache_dir().ok_or_else(|| anyhow!("no cache directory"))?,
+    };
+    let dir = cache_dir.join("file_explorer").join("archives");
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(&dir)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let metadata = fs::symlink_metadata(&dir)?;
+        // Safety: `geteuid` can't fail.
+        if !metadata.is_dir() || metadata.uid() != unsafe { libc::geteuid() } {
+            return Err(anyhow!(
+                "{} isn't a folder of ours, so nothing is extracted there",
+                dir.display()
+            ));
+        }
+        if metadata.mode() & 0o077 != 0 {
+            fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+        }
+    }
+    Ok(dir)
+}
+
+/// Splits a path that goes through an archive, as if it were a folder, into
+/// the archive on disk and the path inside it. An archive itself splits into
+/// itself and an empty path.
+pub fn split_archive_path(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    let archive = path
+        .ancestors()
+        .find(|ancestor| ArchiveFormat::from_path(ancestor).is_some() && ancestor.is_file())?;
+    let inner = path.strip_prefix(archive).ok()?;
+    Some((archive.to_path_buf(), inner.to_path_buf()))
+}
+
+/// Archive listings read so far, each kept until the archive's modification
+/// time changes.
+#[derive(Clone, Default)]
+pub struct ArchiveCache {
+    archives: Arc<Mutex<HashMap<PathBuf, Arc<Archive>>>>,
+    cache_dir: Option<PathBuf>,
+}
+
+impl ArchiveCache {
+    /// A cache whose archives extract files for reading under `cache_dir`
+    /// rather than the user's cache.
+    pub fn with_cache_dir(cache_dir: PathBuf) -> Self {
+        Self {
+            cache_dir: Some(cache_dir),
+            ..Default::default()
+        }
+    }
+
+    /// The archive at `path`, read if it isn't cached.
+    pub fn open(&self, path: &Path) -> Result<Arc<Archive>> {
+        let modified = fs::metadata(path)?.modified().ok();
+        if let Some(archive) = self
+            .archives
+            .lock()
+            .unwrap()
+            .get(path)
+            .filter(|archive| archive.modified == modified)
+        {
+            return Ok(archive.clone());
+        }
+
+        let archive = Arc::new(Archive {
+            cache_dir: self.cache_dir.clone(),
+            ..Archive::open(path)?
+        });
+        self.archives
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), archive.clone());
+        Ok(archive)
+    }
+
+    /// The entry `path` points at, if it is inside an archive. Archives
+    /// themselves are plain files here.
+    pub fn entry(&self, path: &Path) -> Option<ArchiveEntry> {
+        let (archive, inner) = split_archive_path(path)?;
+        if inner.as_os_str().is_empty() {
+            return None;
+        }
+        self.open(&archive).ok()?.entry(&inner).cloned()
+    }
+}
+
+/// Makes an entry name safe to join onto a folder: leading slashes and `.`
+/// are dropped, and names that climb out with `..` are refused. The top of
+/// the archive, `./`, comes out empty.
+fn sanitize(name: &Path) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir | Component::RootDir => {}
+            Component::ParentDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(path)
+}
+
+/// Whether a symlink at `at`, relative to the top of the extraction, points
+/// inside it. `..` may only come first in the target, so it always climbs
+/// real folders rather than ones reached through another link.
+fn link_stays_inside(at: &Path, to: &Path) -> bool {
+    let mut depth = at.components().count().saturating_sub(1);
+    let mut climbing = true;
+    for component in to.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if climbing && depth > 0 => depth -= 1,
+            Component::Normal(_) => climbing = false,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+/// Where the entry at `path` goes when `inner` is extracted to
+/// `destination`, if it is part of that.
+fn extraction_target(path: &Path, inner: &Path, destination: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(inner).ok()?;
+    Some(if relative.as_os_str().is_empty() {
+        destination.to_path_buf()
+    } else {
+        destination.join(relative)
+    })
+}
+
+fn tar_reader(path: &Path, format: ArchiveFormat) -> Result<Box<dyn Read>> {
+    let file = BufReader::new(File::open(path)?);
+    Ok(match format {
+        ArchiveFormat::Tar => Box::new(file),
+        ArchiveFormat::TarGz => Box::new(flate2::bufread::MultiGzDecoder::new(file)),
+        ArchiveFormat::TarXz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(file)),
+        ArchiveFormat::TarZst => Box::new(zstd::Decoder::with_buffer(file)?),
+        ArchiveFormat::Zip => return Err(anyhow!("{} isn't a tar archive", path.display())),
+    })
+}
+
+/// Zip archives store local times without a time zone.
+fn zip_time(time: zip::DateTime) -> Option<SystemTime> {
+    use chrono::TimeZone;
+
+    let time = chrono::NaiveDate::from_ymd_opt(
+        time.year().into(),
+        time.month().into(),
+        time.day().into(),
+    )?
+    .and_hms_opt(
+        time.hour().into(),
+        time.minute().into(),
+        time.second().into(),
+    )?;
+    chrono::Local
+        .from_local_datetime(&time)
+        .earliest()
+        .map(SystemTime::from)
+}
+
+/// Removes what an extraction that didn't finish left at `destination`.
+fn remove_partial(destination: &Path) {
+    let result = match destination.symlink_metadata() {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(destination),
+        Ok(_) => fs::remove_file(destination),
+        Err(_) => return,
+    };
+    if let Err(e) = result {
+        eprintln!(
+            "Failed to remove partial extraction: {}. Error: {}",
+            destination.display(),
+            e
+        );
+    }
+}
+
+/// Archives can hold a path more than once, and like `tar -x`, the later
+/// entry replaces what the earlier one wrote or was going to link there.
+fn replace_earlier(target: &Path, links: &mut Vec<Link>) -> Result<()> {
+    links.retain(|link| link.at() != target);
+    match target.symlink_metadata() {
+        Ok(metadata) if !metadata.is_dir() => fs::remove_file(target)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(())
+}
+
+fn write_file(
+    reader: &mut dyn Read,
+    target: &Path,
+    entry: &ArchiveEntry,
+    progress: &TransferProgress,
+) -> Result<()> {
+    write_or_remove(target, |writer| {
+        copy_data(reader, writer, progress)?;
+        if let Some(modified) = entry.modified {
+            writer.set_modified(modified)?;
+        }
+        set_mode(writer, entry.mode)
+    })
+}
+
+#[cfg(unix)]
+fn set_mode(file: &File, mode: Option<u32>) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Set-user-ID and the like aren't kept.
+    if let Some(mode) = mode.map(|mode| mode & 0o777).filter(|mode| *mode != 0) {
+        file.set_permissions(fs::Permissions::from_mode(mode))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_mode(_file: &File, _mode: Option<u32>) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn make_symlink(to: &Path, at: &Path) -> Result<()> {
+    create_parent(at)?;
+    std::os::unix::fs::symlink(to, at)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_symlink(_to: &Path, _at: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    /// A tar archive of `(name, contents)` files, with `->` in a name making
+    /// the rest of it a symlink's target. Names are written as they are, so
+    /// they can lead outside the archive.
+    fn make_tar(path: &Path, entries: &[(&str, &str)]) {
+        let mut builder = tar::Builder::new(File::create(path).unwrap());
+        for (name, contents) in entries {
+            let mut header = tar::Header::new_gnu();
+            let (name, target) = match name.split_once(" -> ") {
+                Some((name, target)) => (name, Some(target)),
+                None => (*name, None),
+            };
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_mode(0o644);
+            match target {
+                Some(target) => {
+                    header.set_entry_type(tar::EntryType::Symlink);
+                    header.set_link_name(target).unwrap();
+                    header.set_size(0);
+                }
+                None => header.set_size(contents.len() as u64),
+            }
+            header.set_cksum();
+            builder.append(&header, contents.as_bytes()).unwrap();
+        }
+        builder.finish().unwrap();
+    }
+
+    fn make_zip(path: &Path, entries: &[(&str, &str)]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, contents) in entries {
+            match name.split_once(" -> ") {
+                Some((name, target)) => zip.add_symlink(name, target, options).unwrap(),
+                None => {
+                    zip.start_file(*name, options).unwrap();
+                    zip.write_all(contents.as_bytes()).unwrap();
+                }
+            }
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn sanitizes_entry_names() {
+        let sanitize = |name: &str| sanitize(Path::new(name));
+        assert_eq!(sanitize("./a/b"), Some(PathBuf::from("a/b")));
+        assert_eq!(sanitize("/etc/x"), Some(PathBuf::from("etc/x")));
+        assert_eq!(sanitize("./"), Some(PathBuf::new()));
+        assert_eq!(sanitize("../x"), None);
+        assert_eq!(sanitize("a/../../x"), None);
+        assert_eq!(sanitize("a/../b"), None);
+    }
+
+    #[test]
+    fn targets_entries_below_the_extracted_one() {
+        let destination = Path::new("/out/dir");
+        let target = |path: &str, inner: &str| {
+            extraction_target(Path::new(path), Path::new(inner), destination)
+        };
+        assert_eq!(target("dir/a/b", "dir"), Some(destination.join("a/b")));
+        assert_eq!(target("dir", "dir"), Some(destination.to_path_buf()));
+        assert_eq!(target("a", ""), Some(destination.join("a")));
+        assert_eq!(target("directory/a", "dir"), None);
+    }
+
+    #[test]
+    fn confines_symlink_targets() {
+        let inside = |at: &str, to: &str| link_stays_inside(Path::new(at), Path::new(to));
+        assert!(inside("a/link", "../b/c"));
+        assert!(inside("link", "./b"));
+        assert!(!inside("link", "../b"));
+        assert!(!inside("a/link", "/etc/passwd"));
+        assert!(!inside("a/link", "b/../../c"));
+        // `s/..` climbs out when `s` links to `.`.
+        assert!(!inside("a/link", "s/.."));
+    }
+
+    #[test]
+    fn leaves_out_entries_that_escape() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("evil.tar");
+        make_tar(
+            &path,
+            &[
+                ("../escaped", "x"),
+                ("top/up -> ../../outside", ""),
+                ("top/up/pwned", "x"),
+                ("top/abs -> /etc", ""),
+                ("top/docs/readme -> ../notes", ""),
+                ("top/notes", "hello"),
+            ],
+        );
+        let out = dir.path().join("out");
+        fs::create_dir(&out).unwrap();
+
+        let archive = Archive::open(&path).unwrap();
+        assert_eq!(archive.refused_entries(), 1);
+        let (target, refused) = archive
+            .extract_all(&out, &TransferProgress::default())
+            .unwrap();
+        assert_eq!(target, out.join("top"));
+        assert_eq!(refused, 3);
+
+        assert!(!dir.path().join("escaped").exists());
+        assert!(!dir.path().join("outside").exists());
+        assert!(target.join("up").symlink_metadata().is_err());
+        assert!(target.join("abs").symlink_metadata().is_err());
+        assert_eq!(
+            fs::read_to_string(target.join("docs/readme")).unwrap(),
+            "hello"
+        );
+    }
+
+    #[test]
+    fn leaves_out_zip_symlinks_that_escape() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("evil.zip");
+        make_zip(&path, &[("up -> ../outside", ""), ("file", "x")]);
+        let out = dir.path().join("out");
+        fs::create_dir(&out).unwrap();
+
+        let archive = Archive::open(&path).unwrap();
+        let (target, refused) = archive
+            .extract_all(&out, &TransferProgress::default())
+            .unwrap();
+        assert_eq!(refused, 1);
+        assert!(target.join("up").symlink_metadata().is_err());
+        assert_eq!(fs::read_to_string(target.join("file")).unwrap(), "x");
+    }
+
+    #[test]
+    fn later_entries_replace_earlier_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("appended.tar");
+        make_tar(
+            &path,
+            &[
+                ("a/file", "old contents"),
+                ("a/link -> file", ""),
+                ("a/file", "new"),
+                ("a/link", "now a file"),
+            ],
+        );
+
+        let archive = Archive::open(&path).unwrap();
+        assert_eq!(archive.entry(Path::new("a/file")).unwrap().size, 3);
+        assert_eq!(archive.entry(Path::new("a")).unwrap().size, 13);
+        let target = dir.path().join("a");
+        archive
+            .extract(Path::new("a"), &target, &TransferProgress::default())
+            .unwrap();
+        assert_eq!(fs::read_to_string(target.join("file")).unwrap(), "new");
+        assert!(target.join("link").symlink_metadata().unwrap().is_file());
+    }
+
+    #[test]
+    fn reuses_temporary_copies_until_the_archive_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.tar");
+        make_tar(&path, &[("a/note.txt", "first")]);
+        let cache = ArchiveCache::with_cache_dir(dir.path().join("cache"));
+
+        let copy = cache
+            .open(&path)
+            .unwrap()
+            .extract_temporary(Path::new("a/note.txt"))
+            .unwrap();
+        assert!(copy.starts_with(dir.path().join("cache")));
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "first");
+        let again = cache
+            .open(&path)
+            .unwrap()
+            .extract_temporary(Path::new("a/note.txt"))
+            .unwrap();
+        assert_eq!(again, copy);
+
+        make_tar(&path, &[("a/note.txt", "second")]);
+        let later = SystemTime::now() + Duration::from_secs(60);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        let changed = cache
+            .open(&path)
+            .unwrap()
+            .extract_temporary(Path::new("a/note.txt"))
+            .unwrap();
+        assert_ne!(changed, copy);
+        assert_eq!(fs::read_to_string(&changed).unwrap(), "second");
+        assert!(cache
+            .open(&path)
+            .unwrap()
+            .extract_temporary(Path::new("a/missing.txt"))
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_the_extraction_folder_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let extraction_dir = private_extraction_dir(Some(dir.path())).unwrap();
+        assert_eq!(extraction_dir, dir.path().join("file_explorer/archives"));
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&extraction_dir), 0o700);
+
+        fs::set_permissions(&extraction_dir, fs::Permissions::from_mode(0o755)).unwrap();
+        private_extraction_dir(Some(dir.path())).unwrap();
+        assert_eq!(mode(&extraction_dir), 0o700);
+    }
+
+    #[test]
+    fn extracts_next_to_existing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("photos.zip");
+        make_zip(&path, &[("a.jpg", "a"), ("b.jpg", "b")]);
+        fs::write(dir.path().join("photos"), "mine").unwrap();
+
+        let archive = Archive::open(&path).unwrap();
+        let progress = TransferProgress::default();
+        let (target, _) = archive.extract_all(dir.path(), &progress).unwrap();
+        assert_eq!(target, dir.path().join("photos (2)"));
+        assert_eq!(fs::read_to_string(target.join("b.jpg")).unwrap(), "b");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("photos")).unwrap(),
+            "mine"
+        );
+
+        // Extracting onto an existing path is refused and leaves it alone.
+        let existing = dir.path().join("photos");
+        assert!(archive
+            .extract(Path::new(""), &existing, &progress)
+            .is_err());
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "mine");
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    fs::{self, File, Metadata},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    transfer::{copy_data, write_or_remove},
+    ArchiveFormat, Cancelled, TransferProgress,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompressionLevel {
+    Fastest,
+    #[default]
+    Normal,
+    Smallest,
+}
+
+impl CompressionLevel {
+    pub const ALL: [CompressionLevel; 3] = [
+        CompressionLevel::Fastest,
+        CompressionLevel::Normal,
+        CompressionLevel::Smallest,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CompressionLevel::Fastest => "Fastest",
+            CompressionLevel::Normal => "Normal",
+            CompressionLevel::Smallest => "Smallest",
+        }
+    }
+
+    /// The level as the compressor of `format` numbers it.
+    fn value(self, format: ArchiveFormat) -> i32 {
+        match (format, self) {
+            (ArchiveFormat::TarZst, CompressionLevel::Fastest) => 1,
+            (ArchiveFormat::TarZst, CompressionLevel::Normal) => 3,
+            (ArchiveFormat::TarZst, CompressionLevel::Smallest) => 19,
+            (_, CompressionLevel::Fastest) => 1,
+            (_, CompressionLevel::Normal) => 6,
+            (_, CompressionLevel::Smallest) => 9,
+        }
+    }
+}
+
+/// Packs `sources`, with everything inside the folders among them, into a
+/// new archive at `destination`. Symlinks are stored as links. A cancelled
+/// or failed archive is removed again.
+pub fn create_archive(
+    sources: &[PathBuf],
+    destination: &Path,
+    format: ArchiveFormat,
+    level: CompressionLevel,
+    progress: &TransferProgress,
+) -> Result<()> {
+    let result = write_or_remove(destination, |file| {
+        let writer = BufWriter::new(file);
+        let level = level.value(format);
+        let writer = match format {
+            ArchiveFormat::Zip => write_zip(writer, sources, level, progress)?,
+            ArchiveFormat::Tar => write_tar(writer, sources, progress)?,
+            ArchiveFormat::TarGz => write_tar(
+                flate2::write::GzEncoder::new(writer, flate2::Compression::new(level as u32)),
+                sources,
+                progress,
+            )?
+            .finish()?,
+            ArchiveFormat::TarXz => write_tar(
+                xz2::write::XzEncoder::new(writer, level as u32),
+                sources,
+                progress,
+            )?
+            .finish()?,
+            ArchiveFormat::TarZst => {
+                write_tar(zstd::Encoder::new(writer, level)?, sources, progress)?.finish()?
+            }
+        };
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        Ok(())
+    });
+
+    // Cancelling shows up as a failed write inside the compressor.
+    if progress.is_cancelled() {
+        return Err(Cancelled.into());
+    }
+    result
+}
+
+/// Everything to pack, each with its name in the archive, folders before
+/// their contents.
+fn collect(sources: &[PathBuf], progress: &TransferProgress) -> Result<Vec<(PathBuf, String)>> {
+    fn walk(
+        path: PathBuf,
+        name: String,
+        progress: &TransferProgress,
+        items: &mut Vec<(PathBuf, String)>,
+    ) -> Result<()> {
+        progress.check_cancelled()?;
+        let is_dir = fs::symlink_metadata(&path)?.is_dir();
+        items.push((path.clone(), name.clone()));
+        if is_dir {
+            let mut entries = fs::read_dir(&path)?.collect::<io::Result<Vec<_>>>()?;
+            entries.sort_by_key(|entry| entry.file_name());
+            for entry in entries {
+                let child = format!("{}/{}", name, entry.file_name().to_string_lossy());
+                walk(entry.path(), child, progress, items)?;
+            }
+        }
+        Ok(())
+    }
+
+    let mut items = Vec::new();
+    for source in sources {
+        let name = source
+            .file_name()
+            .ok_or_else(|| anyhow!("{} can't be archived", source.display()))?
+            .to_string_lossy()
+            .into_owned();
+        walk(source.clone(), name, progress, &mut items)?;
+    }
+    Ok(items)
+}
+
+fn write_tar<W: Write>(writer: W, sources: &[PathBuf], progress: &TransferProgress) -> Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    for (path, name) in collect(sources, progress)? {
+        progress.check_cancelled()?;
+        let metadata = fs::symlink_metadata(&path)?;
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata(&metadata);
+
+        if metadata.file_type().is_symlink() {
+            header.set_size(0);
+            builder.append_link(&mut header, &name, fs::read_link(&path)?)?;
+        } else if metadata.is_dir() {
+            header.set_size(0);
+            builder.append_data(&mut header, format!("{}/", name), io::empty())?;
+        } else if metadata.is_file() {
+            let reader = Progress {
+                reader: File::open(&path)?,
+                progress,
+            };
+            builder.append_data(&mut header, &name, reader)?;
+        }
+    }
+    Ok(builder.into_inner()?)
+}
+
+fn write_zip<W: Write + io::Seek>(
+    writer: W,
+    sources: &[PathBuf],
+    level: i32,
+    progress: &TransferProgress,
+) -> Result<W> {
+    let mut zip = zip::ZipWriter::new(writer);
+    for (path, name) in collect(sources, progress)? {
+        progress.check_cancelled()?;
+        let metadata = fs::symlink_metadata(&path)?;
+        let options = zip_options(&metadata, level);
+
+        if metadata.file_type().is_symlink() {
+            zip.add_symlink(&name, fs::read_link(&path)?.to_string_lossy(), options)?;
+        } else if metadata.is_dir() {
+            zip.add_directory(&name, options)?;
+        } else if metadata.is_file() {
+            zip.start_file(&name, options)?;
+            copy_data(&mut File::open(&path)?, &mut zip, progress)?;
+        }
+    }
+    Ok(zip.finish()?)
+}
+
+fn zip_options(metadata: &Metadata, level: i32) -> zip::write::SimpleFileOptions {
+    let mut options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .compression_level(Some(level.into()))
+        .large_file(metadata.len() >= u32::MAX as u64);
+    if let Some(modified) = metadata.modified().ok().and_then(zip_time) {
+        options = options.last_modified_time(modified);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        options = options.unix_permissions(metadata.permissions().mode() & 0o777);
+    }
+    options
+}
+
+/// Zip archives store local times, from 1980 on.
+fn zip_time(time: std::time::SystemTime) -> Option<zip::DateTime> {
+    use chrono::{Datelike, Timelike};
+
+    let time: chrono::DateTime<chrono::Local> = time.into();
+    zip::DateTime::from_date_and_time(
+        time.year().try_into().ok()?,
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+    )
+    .ok()
+}
+
+/// Counts what's read through it and stops reading once cancelled.
+struct Progress<'a, R> {
+    reader: R,
+    progress: &'a TransferProgress,
+}
+
+impl<R: Read> Read for Progress<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.progress.is_cancelled() {
+            return Err(io::Error::other(Cancelled));
+        }
+        let read = self.reader.read(buf)?;
+        self.progress.advance(read as u64);
+        Ok(read)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::Archive;
+
+    fn make_folder(dir: &Path) -> PathBuf {
+        let folder = dir.join("docs");
+        fs::create_dir_all(folder.join("sub")).unwrap();
+        fs::write(folder.join("a.txt"), "hello").unwrap();
+        fs::write(folder.join("sub/b.txt"), "world!").unwrap();
+        std::os::unix::fs::symlink("a.txt", folder.join("link")).unwrap();
+        folder
+    }
+
+    #[test]
+    fn compresses_folders_in_every_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = make_folder(dir.path());
+        for format in ArchiveFormat::CREATABLE {
+            let destination = dir.path().join(format!("docs{}", format.extension()));
+            let progress = TransferProgress::default();
+            create_archive(
+                std::slice::from_ref(&folder),
+                &destination,
+                format,
+                CompressionLevel::Fastest,
+                &progress,
+            )
+            .unwrap();
+
+            let archive = Archive::open(&destination).unwrap();
+            let names = archive
+                .children(Path::new("docs"))
+                .map(|entry| entry.path.to_str().unwrap())
+                .collect::<Vec<_>>();
+            #[cfg(unix)]
+            assert_eq!(
+                names,
+                ["docs/a.txt", "docs/link", "docs/sub"],
+                "{:?}",
+                format
+            );
+            let sub = archive.entry(Path::new("docs/sub")).unwrap();
+            assert!(sub.is_dir);
+            assert_eq!(archive.entry(Path::new("docs/sub/b.txt")).unwrap().size, 6);
+            assert_eq!(progress.done_bytes.load(Ordering::Relaxed), 11);
+        }
+    }
+
+    #[test]
+    fn removes_cancelled_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = make_folder(dir.path());
+        let destination = dir.path().join("docs.zip");
+        let progress = TransferProgress::default();
+        progress.cancel();
+        let result = create_archive(
+            &[folder],
+            &destination,
+            ArchiveFormat::Zip,
+            CompressionLevel::Normal,
+            &progress,
+        );
+        assert!(result.unwrap_err().is::<Cancelled>());
+        assert!(!destination.exists());
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
+
+use anyhow::Result;
+
+use crate::{walk::Walker, Cancelled, FolderSizeOptions};
+
+/// Each folder keeps this many of its largest entries; the rest are merged
+/// into one [`UsageKind::Rest`] entry so scanning a whole drive stays small.
+const MAX_CHILDREN: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UsageKind {
+    File,
+    Folder,
+    /// Entries too small to list one by one.
+    Rest,
+}
+
+/// One entry of a disk usage scan, with its contents largest first.
+#[derive(Clone, Debug)]
+pub struct UsageNode {
+    pub name: String,
+    pub kind: UsageKind,
+    pub size: u64,
+    /// The files below this entry, or merged into it.
+    pub files: u64,
+    pub children: Vec<UsageNode>,
+}
+
+impl UsageNode {
+    /// The entry reached by following `names` down from this one.
+    pub fn descendant(&self, names: &[String]) -> Option<&UsageNode> {
+        names.iter().try_fold(self, |node, name| {
+            node.children.iter().find(|child| child.name == *name)
+        })
+    }
+
+    /// Drops the entry at `names`, taking its size and files off every folder
+    /// above it.
+    pub fn remove(&mut self, names: &[String]) -> Option<UsageNode> {
+        let (name, rest) = names.split_first()?;
+        let ix = self.children.iter().position(|child| child.name == *name)?;
+        let removed = if rest.is_empty() {
+            self.children.remove(ix)
+        } else {
+            let removed = self.children[ix].remove(rest)?;
+            self.children.sort_by(largest_first);
+            removed
+        };
+        self.size -= removed.size;
+        self.files -= removed.files;
+        Some(removed)
+    }
+}
+
+fn largest_first(a: &UsageNode, b: &UsageNode) -> std::cmp::Ordering {
+    b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name))
+}
+
+/// How far a scan has got, shared with whoever shows its progress.
+#[derive(Debug, Default)]
+pub struct ScanProgress {
+    pub files: AtomicU64,
+    pub bytes: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl ScanProgress {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Walks everything below `path` and records where the space goes. Symlinks
+/// are not followed and hard-linked files are counted once.
+pub fn scan_disk_usage(
+    path: &Path,
+    options: &FolderSizeOptions,
+    progress: &ScanProgress,
+) -> Result<UsageNode> {
+    let scan = Scan {
+        walker: Walker::new(path, options)?,
+        progress,
+    };
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string_lossy().into_owned());
+    scan.folder(path.to_path_buf(), name)
+}
+
+struct Scan<'a> {
+    walker: Walker,
+    progress: &'a ScanProgress,
+}
+
+impl Scan<'_> {
+    fn folder(&self, path: PathBuf, name: String) -> Result<UsageNode> {
+        let mut children = Vec::new();
+        // Folders that can't be read show up empty rather than stopping the
+        // scan.
+        for (entry, metadata) in self.walker.entries(&path) {
+            if self.progress.is_cancelled() {
+                return Err(Cancelled.into());
+            }
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            if metadata.is_dir() {
+                children.push(self.folder(entry.path(), name)?);
+            } else {
+                self.progress.files.fetch_add(1, Ordering::Relaxed);
+                self.progress
+                    .bytes
+                    .fetch_add(metadata.len(), Ordering::Relaxed);
+                children.push(UsageNode {
+                    name,
+                    kind: UsageKind::File,
+                    size: metadata.len(),
+                    files: 1,
+                    children: Vec::new(),
+                });
+            }
+        }
+
+        children.sort_by(largest_first);
+        if children.len() > MAX_CHILDREN {
+            let rest = children.split_off(MAX_CHILDREN - 1);
+            children.push(UsageNode {
+                name: format!("{} smaller items", rest.len()),
+                kind: UsageKind::Rest,
+                size: rest.iter().map(|node| node.size).sum(),
+                files: rest.iter().map(|node| node.files).sum(),
+                children: Vec::new(),
+            });
+            children.sort_by(largest_first);
+        }
+
+        Ok(UsageNode {
+            name,
+            kind: UsageKind::Folder,
+            size: children.iter().map(|node| node.size).sum(),
+            files: children.iter().map(|node| node.files).sum(),
+            children,
+        })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::walk::tests::walker_on_another_device;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn children(node: &UsageNode) -> Vec<(&str, u64)> {
+        node.children
+            .iter()
+            .map(|child| (child.name.as_str(), child.size))
+            .collect()
+    }
+
+    fn scan(path: &Path) -> UsageNode {
+        scan_disk_usage(
+            path,
+            &FolderSizeOptions::default(),
+            &ScanProgress::default(),
+        )
+        .unwrap()
+    }
+
+    fn make_tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("docs/old")).unwrap();
+        fs::write(dir.path().join("docs/a.txt"), vec![0; 100]).unwrap();
+        fs::write(dir.path().join("docs/old/b.txt"), vec![0; 300]).unwrap();
+        fs::write(dir.path().join("small"), vec![0; 10]).unwrap();
+        fs::write(dir.path().join("big"), vec![0; 1000]).unwrap();
+        dir
+    }
+
+    #[test]
+    fn sorts_entries_largest_first() {
+        let dir = make_tree();
+        let progress = ScanProgress::default();
+        let root = scan_disk_usage(dir.path(), &FolderSizeOptions::default(), &progress).unwrap();
+
+        assert_eq!(root.kind, UsageKind::Folder);
+        assert_eq!(root.size, 1410);
+        assert_eq!(root.files, 4);
+        assert_eq!(
+            children(&root),
+            [("big", 1000), ("docs", 400), ("small", 10)]
+        );
+        assert_eq!(progress.files.load(Ordering::Relaxed), 4);
+        assert_eq!(progress.bytes.load(Ordering::Relaxed), 1410);
+    }
+
+    #[test]
+    fn finds_and_removes_entries_by_their_names() {
+        let dir = make_tree();
+        let mut root = scan(dir.path());
+
+        let old = root.descendant(&names(&["docs", "old"])).unwrap();
+        assert_eq!(children(old), [("b.txt", 300)]);
+        assert!(root.descendant(&names(&["docs", "missing"])).is_none());
+        assert_eq!(root.descendant(&[]).unwrap().name, root.name);
+
+        let removed = root.remove(&names(&["docs", "old", "b.txt"])).unwrap();
+        assert_eq!(removed.size, 300);
+        assert_eq!((root.size, root.files), (1110, 3));
+        let docs = root.descendant(&names(&["docs"])).unwrap();
+        assert_eq!((docs.size, docs.files), (100, 1));
+        assert!(root.remove(&names(&["docs", "old", "b.txt"])).is_none());
+    }
+
+    #[test]
+    fn merges_the_smallest_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        for ix in 0..MAX_CHILDREN + 10 {
+            fs::write(dir.path().join(format!("{ix:03}")), vec![0; ix + 1]).unwrap();
+        }
+        let root = scan(dir.path());
+
+        assert_eq!(root.children.len(), MAX_CHILDREN);
+        assert_eq!(root.files, MAX_CHILDREN as u64 + 10);
+        let rest = root
+            .children
+            .iter()
+            .find(|child| child.kind == UsageKind::Rest)
+            .unwrap();
+        assert_eq!(rest.name, "11 smaller items");
+        assert_eq!(rest.files, 11);
+        assert_eq!(rest.size, (1..=11).sum::<u64>());
+    }
+
+    #[test]
+    fn counts_hard_linked_files_once() {
+        let dir = make_tree();
+        fs::hard_link(dir.path().join("big"), dir.path().join("docs/big")).unwrap();
+        let root = scan(dir.path());
+        assert_eq!(root.size, 1410);
+        assert_eq!(root.files, 4);
+    }
+
+    #[test]
+    fn stops_at_other_filesystems() {
+        let dir = make_tree();
+        let progress = ScanProgress::default();
+        let scan = |options| {
+            Scan {
+                walker: walker_on_another_device(&options),
+                progress: &progress,
+            }
+            .folder(dir.path().to_path_buf(), "root".to_string())
+            .unwrap()
+        };
+
+        let root = scan(FolderSizeOptions::default());
+        assert_eq!(children(&root), [("big", 1000), ("small", 10)]);
+        let root = scan(FolderSizeOptions { cross_mounts: true });
+        assert_eq!(
+            children(&root),
+            [("big", 1000), ("docs", 400), ("small", 10)]
+        );
+    }
+
+    #[test]
+    fn stops_when_cancelled() {
+        let dir = make_tree();
+        let progress = ScanProgress::default();
+        progress.cancel();
+        let error =
+            scan_disk_usage(dir.path(), &FolderSizeOptions::default(), &progress).unwrap_err();
+        assert!(error.is::<Cancelled>());
+    }
+}
@@ -0,0 +1,435 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::Result;
+
+/// A mounted filesystem, as listed in the sidebar.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Drive {
+    pub mount_point: PathBuf,
+    /// The device or source it is mounted from, e.g. `/dev/sda1`.
+    pub device: String,
+    /// The volume label, if it has one.
+    pub label: Option<String>,
+    pub fs_type: String,
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+    pub removable: bool,
+    /// Mounts of kernel, loop, snap, container and other internal
+    /// filesystems, and second mounts of something already listed, which are
+    /// hidden unless asked for.
+    pub pseudo: bool,
+}
+
+impl Drive {
+    /// The label, or else a name made up from the mount point.
+    pub fn display_name(&self) -> String {
+        if let Some(label) = &self.label {
+            return label.clone();
+        }
+        if self.mount_point.parent().is_none() {
+            return "File System".into();
+        }
+        self.mount_point
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.mount_point.to_string_lossy().into_owned())
+    }
+
+    pub fn used_bytes(&self) -> u64 {
+        self.total_bytes.saturating_sub(self.available_bytes)
+    }
+
+    /// Between 0 and 1, or 0 when the size is unknown.
+    pub fn used_fraction(&self) -> f32 {
+        if self.total_bytes == 0 {
+            return 0.;
+        }
+        (self.used_bytes() as f64 / self.total_bytes as f64) as f32
+    }
+}
+
+/// Calls back whenever something is mounted or unmounted, for as long as it
+/// is alive.
+pub struct MountWatcher {
+    stopped: Arc<AtomicBool>,
+}
+
+impl MountWatcher {
+    #[cfg(target_os = "linux")]
+    pub fn new(callback: impl Fn() + Send + 'static) -> Result<Self> {
+        let mountinfo = std::fs::File::open(MOUNTINFO)?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        std::thread::Builder::new()
+            .name("mount-watcher".into())
+            .spawn({
+                let stopped = stopped.clone();
+                move || watch_mounts(mountinfo, &stopped, callback)
+            })?;
+        Ok(Self { stopped })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(_callback: impl Fn() + Send + 'static) -> Result<Self> {
+        Err(anyhow::anyhow!(
+            "mount changes can't be watched on this platform"
+        ))
+    }
+}
+
+impl Drop for MountWatcher {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use linux::*;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{collections::HashSet, fs};
+
+    use super::*;
+
+    pub const MOUNTINFO: &str = "/proc/self/mountinfo";
+    /// How long the watcher waits for a change before checking whether it
+    /// has been dropped.
+    const POLL_TIMEOUT_MS: i32 = 1000;
+
+    /// Filesystems that hold no user files.
+    const PSEUDO_FS_TYPES: &[&str] = &[
+        "autofs",
+        "binfmt_misc",
+        "bpf",
+        "cgroup",
+        "cgroup2",
+        "configfs",
+        "debugfs",
+        "devpts",
+        "devtmpfs",
+        "efivarfs",
+        "fusectl",
+        "hugetlbfs",
+        "mqueue",
+        "nsfs",
+        "overlay",
+        "proc",
+        "pstore",
+        "ramfs",
+        "rpc_pipefs",
+        "securityfs",
+        "selinuxfs",
+        "squashfs",
+        "sysfs",
+        "tracefs",
+        "tmpfs",
+        "fuse.gvfsd-fuse",
+        "fuse.portal",
+    ];
+
+    /// Places only the system mounts things.
+    const PSEUDO_MOUNT_PREFIXES: &[&str] = &[
+        "/proc",
+        "/sys",
+        "/dev",
+        "/run",
+        "/snap",
+        "/var/snap",
+        "/var/lib/snapd",
+        "/var/lib/docker",
+        "/var/lib/containers",
+        "/var/lib/flatpak",
+    ];
+
+    /// Where desktops mount removable media, under the otherwise hidden
+    /// `/run`.
+    const MEDIA_PREFIXES: &[&str] = &["/run/media", "/media", "/mnt"];
+
+    /// Every mounted filesystem, with the ones not worth showing marked as
+    /// pseudo.
+    pub fn mounted_drives() -> Vec<Drive> {
+        match fs::read_to_string(MOUNTINFO) {
+            Ok(mountinfo) => parse_mountinfo(&mountinfo),
+            Err(e) => {
+                eprintln!("Failed to read mounts: {}. Error: {}", MOUNTINFO, e);
+                Vec::new()
+            }
+        }
+    }
+
+    fn parse_mountinfo(mountinfo: &str) -> Vec<Drive> {
+        let labels = labels();
+        let mut seen_devices = HashSet::new();
+        let mut drives = Vec::new();
+
+        for line in mountinfo.lines() {
+            // id parent major:minor root mount-point options [optional...] -
+            // fs-type source super-options
+            let Some((before, after)) = line.split_once(" - ") else {
+                continue;
+            };
+            let fields = before.split(' ').collect::<Vec<_>>();
+            let mut after = after.split(' ');
+            let (Some(device_number), Some(mount_point)) = (fields.get(2), fields.get(4)) else {
+                continue;
+            };
+            let (Some(fs_type), Some(source)) = (after.next(), after.next()) else {
+                continue;
+            };
+
+            let mount_point = PathBuf::from(unescape(mount_point));
+            let device = unescape(source);
+            let mount_str = mount_point.to_string_lossy();
+            let under = |prefix: &&str| {
+                mount_str == *prefix || mount_str.starts_with(&format!("{}/", prefix))
+            };
+            let in_media = MEDIA_PREFIXES.iter().any(under);
+
+            // Bind mounts show part of a filesystem that's already listed, so
+            // only the first mount of each device is kept. Its root needn't
+            // be `/`: Fedora mounts `/` from the `/root` btrfs subvolume, for
+            // one.
+            let pseudo = PSEUDO_FS_TYPES.contains(&fs_type)
+                || device.starts_with("/dev/loop")
+                || (!in_media && PSEUDO_MOUNT_PREFIXES.iter().any(under))
+                || !seen_devices.insert(device_number.to_string());
+
+            // Asking a dead network filesystem for its size can block, so
+            // only drives that are shown are asked.
+            let (total_bytes, available_bytes) = if pseudo {
+                (0, 0)
+            } else {
+                capacity(&mount_point).unwrap_or((0, 0))
+            };
+            let canonical_device = canonical(&device);
+            drives.push(Drive {
+                label: labels
+                    .iter()
+                    .find(|(_, label_device)| *label_device == canonical_device)
+                    .map(|(label, _)| label.clone()),
+                removable: is_removable(device_number),
+                mount_point,
+                device,
+                fs_type: fs_type.to_string(),
+                total_bytes,
+                available_bytes,
+                pseudo,
+            });
+        }
+        drives
+    }
+
+    /// The kernel reports changes to the mount table as an exceptional
+    /// condition on any open mountinfo file.
+    pub(super) fn watch_mounts(mut mountinfo: fs::File, stopped: &AtomicBool, callback: impl Fn()) {
+        use std::{
+            io::{Read, Seek, SeekFrom},
+            os::fd::AsRawFd,
+        };
+
+        let mut contents = Vec::new();
+        while !stopped.load(Ordering::Relaxed) {
+            let mut poll = libc::pollfd {
+                fd: mountinfo.as_raw_fd(),
+                events: libc::POLLPRI,
+                revents: 0,
+            };
+            let ready = unsafe { libc::poll(&mut poll, 1, POLL_TIMEOUT_MS) };
+            if ready < 0 {
+                let error = std::io::Error::last_os_error();
+                if error.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                eprintln!("Failed to watch mounts: {}. Error: {}", MOUNTINFO, error);
+                break;
+            }
+            if poll.revents & (libc::POLLPRI | libc::POLLERR) == 0 {
+                continue;
+            }
+
+            // Read the table again so the next poll waits for a new change.
+            contents.clear();
+            mountinfo.seek(SeekFrom::Start(0)).ok();
+            mountinfo.read_to_end(&mut contents).ok();
+            callback();
+        }
+    }
+
+    /// Undoes the octal escapes mountinfo uses for spaces, tabs, newlines
+    /// and backslashes.
+    fn unescape(field: &str) -> String {
+        let bytes = field.as_bytes();
+        let mut unescaped = Vec::with_capacity(bytes.len());
+        let mut ix = 0;
+        while ix < bytes.len() {
+            let escaped = (bytes[ix] == b'\\')
+                .then(|| bytes.get(ix + 1..ix + 4))
+                .flatten()
+                .and_then(|digits| std::str::from_utf8(digits).ok())
+                .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+            if let Some(byte) = escaped {
+                unescaped.push(byte);
+                ix += 4;
+                continue;
+            }
+            unescaped.push(bytes[ix]);
+            ix += 1;
+        }
+        String::from_utf8_lossy(&unescaped).into_owned()
+    }
+
+    /// Resolves `/dev/disk/by-uuid/...` and the like. Sources of virtual
+    /// filesystems, such as `proc`, are left alone.
+    fn canonical(device: &str) -> PathBuf {
+        let device = PathBuf::from(device);
+        if !device.is_absolute() {
+            return device;
+        }
+        fs::canonicalize(&device).unwrap_or(device)
+    }
+
+    /// Volume labels and the devices they belong to, from the symlinks udev
+    /// keeps in `/dev/disk/by-label`.
+    fn labels() -> Vec<(String, PathBuf)> {
+        let Ok(entries) = fs::read_dir("/dev/disk/by-label") else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let device = fs::canonicalize(entry.path()).ok()?;
+                let label = decode_label(&entry.file_name().to_string_lossy());
+                Some((label, device))
+            })
+            .collect()
+    }
+
+    /// udev writes unsafe characters of labels as `\xNN`.
+    fn decode_label(name: &str) -> String {
+        let mut bytes = Vec::with_capacity(name.len());
+        let mut rest = name.as_bytes();
+        while let Some((&byte, tail)) = rest.split_first() {
+            if byte == b'\\' && tail.first() == Some(&b'x') && tail.len() >= 3 {
+                if let Some(decoded) = std::str::from_utf8(&tail[1..3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    bytes.push(decoded);
+                    rest = &tail[3..];
+                    continue;
+                }
+            }
+            bytes.push(byte);
+            rest = tail;
+        }
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    /// Whether the block device `major:minor` is, or is a partition of, a
+    /// removable or USB disk.
+    fn is_removable(device_number: &str) -> bool {
+        let Ok(device) = fs::canonicalize(Path::new("/sys/dev/block").join(device_number)) else {
+            return false;
+        };
+        if device.components().any(|component| {
+            component
+                .as_os_str()
+                .to_str()
+                .is_some_and(|name| name.starts_with("usb"))
+        }) {
+            return true;
+        }
+        // Partitions don't have the flag themselves; their disk does.
+        [device.as_path(), device.parent().unwrap_or(&device)]
+            .iter()
+            .any(|folder| {
+                fs::read_to_string(folder.join("removable")).is_ok_and(|flag| flag.trim() == "1")
+            })
+    }
+
+    fn capacity(mount_point: &Path) -> Option<(u64, u64)> {
+        use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+        let path = CString::new(mount_point.as_os_str().as_bytes()).ok()?;
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+            return None;
+        }
+        let block = stat.f_frsize as u64;
+        Some((stat.f_blocks as u64 * block, stat.f_bavail as u64 * block))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// A Fedora-like table: `/` and `/home` are btrfs subvolumes of one
+        /// filesystem, and `/srv/grub` binds a folder of `/boot`.
+        const MOUNTINFO: &str = "\
+22 1 0:32 /root / rw,relatime shared:1 - btrfs /dev/vda3 rw,compress=zstd:1,subvol=/root
+23 22 0:21 / /proc rw,nosuid,nodev,noexec,relatime shared:12 - proc proc rw
+24 22 0:5 / /dev rw,nosuid shared:2 - devtmpfs devtmpfs rw,size=4096k
+60 22 0:32 /home /home rw,relatime shared:31 - btrfs /dev/vda3 rw,compress=zstd:1,subvol=/home
+61 22 252:2 / /boot rw,relatime shared:33 - ext4 /dev/vda2 rw
+62 22 252:2 /grub2 /srv/grub rw,relatime shared:33 - ext4 /dev/vda2 rw
+63 22 7:0 / /var/lib/snapd/snap/core/1 ro,nodev,relatime shared:40 - squashfs /dev/loop0 ro
+80 22 8:17 / /run/media/me/My\\040Disk rw,nosuid,nodev,relatime shared:50 - vfat /dev/sdb1 rw
+not a mount
+";
+
+        fn shown(drives: &[Drive]) -> Vec<&str> {
+            drives
+                .iter()
+                .filter(|drive| !drive.pseudo)
+                .map(|drive| drive.mount_point.to_str().unwrap())
+                .collect()
+        }
+
+        #[test]
+        fn parses_every_mount() {
+            let drives = parse_mountinfo(MOUNTINFO);
+            assert_eq!(drives.len(), 8);
+            assert_eq!(drives[0].mount_point, Path::new("/"));
+            assert_eq!(drives[0].device, "/dev/vda3");
+            assert_eq!(drives[0].fs_type, "btrfs");
+            assert_eq!(drives[1].device, "proc");
+        }
+
+        #[test]
+        fn keeps_the_first_mount_of_each_device() {
+            let drives = parse_mountinfo(MOUNTINFO);
+            assert_eq!(shown(&drives), ["/", "/boot", "/run/media/me/My Disk"]);
+        }
+
+        #[test]
+        fn leaves_hidden_mounts_unsized() {
+            for drive in parse_mountinfo(MOUNTINFO) {
+                if drive.pseudo {
+                    assert_eq!((drive.total_bytes, drive.available_bytes), (0, 0));
+                }
+            }
+        }
+
+        #[test]
+        fn unescapes_octal_escapes() {
+            assert_eq!(unescape(r"/mnt/My\040Disk"), "/mnt/My Disk");
+            assert_eq!(unescape(r"a\011b\012c\134d"), "a\tb\nc\\d");
+            // Escapes that aren't three octal digits are kept as they are.
+            assert_eq!(unescape(r"a\09b\04"), r"a\09b\04");
+        }
+
+        #[test]
+        fn decodes_labels() {
+            assert_eq!(decode_label(r"My\x20Disk"), "My Disk");
+            assert_eq!(decode_label(r"a\x2fb"), "a/b");
+            assert_eq!(decode_label(r"Caf\xc3\xa9"), "Café");
+            assert_eq!(decode_label(r"bad\xzz\x2"), r"bad\xzz\x2");
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::SystemTime,
+};
+
+use crate::walk::{blocks_size, Walker};
+
+const MAX_WORKERS: usize = 8;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FolderSizeOptions {
+    /// Whether to count folders on other filesystems mounted below the one
+    /// being measured.
+    pub cross_mounts: bool,
+}
+
+/// What a folder holds, counting hard-linked files once.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FolderSize {
+    /// The apparent size of the files.
+    pub size: u64,
+    /// The space allocated for the files and folders, where the platform
+    /// reports it.
+    pub size_on_disk: Option<u64>,
+    pub files: u64,
+    pub folders: u64,
+}
+
+impl FolderSize {
+    fn add(&mut self, other: &FolderSize) {
+        self.size += other.size;
+        self.size_on_disk = self
+            .size_on_disk
+            .zip(other.size_on_disk)
+            .map(|(a, b)| a + b);
+        self.files += other.files;
+        self.folders += other.folders;
+    }
+}
+
+/// Folders still to read and how many workers are busy reading one.
+struct Walk {
+    queue: Vec<PathBuf>,
+    busy: usize,
+}
+
+/// Adds up everything below `path` on a few threads. Symlinks are not
+/// followed.
+pub fn folder_size(path: &Path, options: &FolderSizeOptions) -> FolderSize {
+    let Ok(walker) = Walker::new(path, options) else {
+        return FolderSize::default();
+    };
+
+    let walk = Mutex::new(Walk {
+        queue: vec![path.to_path_buf()],
+        busy: 0,
+    });
+    let changed = Condvar::new();
+    let workers = thread::available_parallelism()
+        .map_or(1, |workers| workers.get())
+        .min(MAX_WORKERS);
+
+    let mut total = FolderSize {
+        size_on_disk: Some(0),
+        ..Default::default()
+    };
+    thread::scope(|scope| {
+        let workers = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut size = FolderSize {
+                        size_on_disk: Some(0),
+                        ..Default::default()
+                    };
+                    loop {
+                        let folder = {
+                            let mut walk = walk.lock().unwrap();
+                            loop {
+                                if let Some(folder) = walk.queue.pop() {
+                                    walk.busy += 1;
+                                    break Some(folder);
+                                }
+                                if walk.busy == 0 {
+                                    break None;
+                                }
+                                walk = changed.wait(walk).unwrap();
+                            }
+                        };
+                        let Some(folder) = folder else {
+                            changed.notify_all();
+                            return size;
+                        };
+
+                        let subfolders = read_folder(&folder, &walker, &mut size);
+                        let mut walk = walk.lock().unwrap();
+                        walk.queue.extend(subfolders);
+                        walk.busy -= 1;
+                        changed.notify_all();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for worker in workers {
+            if let Ok(size) = worker.join() {
+                total.add(&size);
+            }
+        }
+    });
+    total
+}
+
+/// Adds the entries of `folder` to `size` and returns the folders in it that
+/// should be read too.
+fn read_folder(folder: &Path, walker: &Walker, size: &mut FolderSize) -> Vec<PathBuf> {
+    let mut subfolders = Vec::new();
+    for (entry, metadata) in walker.entries(folder) {
+        if metadata.is_dir() {
+            size.folders += 1;
+            subfolders.push(entry.path());
+        } else {
+            size.files += 1;
+            size.size += metadata.len();
+        }
+        size.size_on_disk = size
+            .size_on_disk
+            .zip(blocks_size(&metadata))
+            .map(|(a, b)| a + b);
+    }
+    subfolders
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Cached {
+    modified: SystemTime,
+    size: FolderSize,
+}
+
+/// Folder sizes worked out so far, each kept until the folder's modification
+/// time changes or [`FolderSizeCache::invalidate`] is called for something
+/// inside it.
+///
+/// A folder's modification time only changes when entries are added to or
+/// removed from it directly, so changes further down have to be reported by a
+/// watcher.
+#[derive(Clone, Default)]
+pub struct FolderSizeCache {
+    options: FolderSizeOptions,
+    sizes: Arc<Mutex<HashMap<PathBuf, Cached>>>,
+}
+
+impl FolderSizeCache {
+    pub fn new(options: FolderSizeOptions) -> Self {
+        Self {
+            options,
+            sizes: Default::default(),
+        }
+    }
+
+    /// The size of `path` if it was worked out while the folder had the
+    /// modification time `modified`.
+    pub fn cached(&self, path: &Path, modified: SystemTime) -> Option<FolderSize> {
+        self.sizes
+            .lock()
+            .unwrap()
+            .get(path)
+            .filter(|cached| cached.modified == modified)
+            .map(|cached| cached.size)
+    }
+
+    /// The size of `path`, working it out if it isn't cached. Blocks while
+    /// the folder is walked.
+    pub fn size(&self, path: &Path) -> FolderSize {
+        // Read the time first so changes made during the walk aren't hidden.
+        let modified = fs::metadata(path).and_then(|metadata| metadata.modified());
+        if let Some(size) = modified
+            .as_ref()
+            .ok()
+            .and_then(|modified| self.cached(path, *modified))
+        {
+            return size;
+        }
+
+        let size = folder_size(path, &self.options);
+        if let Ok(modified) = modified {
+            self.sizes
+                .lock()
+                .unwrap()
+                .insert(path.to_path_buf(), Cached { modified, size });
+        }
+        size
+    }
+
+    /// Forgets the sizes of the folders that hold `path`, and of `path`
+    /// itself and everything below it.
+    pub fn invalidate(&self, path: &Path) {
+        self.sizes
+            .lock()
+            .unwrap()
+            .retain(|folder, _| !path.starts_with(folder) && !folder.starts_with(path));
+    }
+
+    pub fn clear(&self) {
+        self.sizes.lock().unwrap().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.sizes.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{os::unix::fs::symlink, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn counts_hard_linked_files_once() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("a")).unwrap();
+        fs::write(dir.path().join("a/file"), "12345").unwrap();
+        fs::hard_link(dir.path().join("a/file"), dir.path().join("link")).unwrap();
+        fs::write(dir.path().join("other"), "123").unwrap();
+
+        let size = folder_size(dir.path(), &FolderSizeOptions::default());
+        assert_eq!(size.size, 8);
+        assert_eq!(size.files, 2);
+        assert_eq!(size.folders, 1);
+    }
+
+    #[test]
+    fn doesnt_follow_symlinked_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = dir.path().join("outside");
+        fs::create_dir(&outside).unwrap();
+        fs::write(outside.join("big"), vec![0; 1000]).unwrap();
+        let folder = dir.path().join("folder");
+        fs::create_dir(&folder).unwrap();
+        fs::write(folder.join("small"), "12").unwrap();
+        symlink(&outside, folder.join("link")).unwrap();
+
+        let size = folder_size(&folder, &FolderSizeOptions::default());
+        // The link itself counts as a file the length of its target.
+        assert_eq!(size.size, 2 + outside.as_os_str().len() as u64);
+        assert_eq!(size.files, 2);
+        assert_eq!(size.folders, 0);
+    }
+
+    #[test]
+    fn invalidates_folders_above_and_below() {
+        let cache = FolderSizeCache::default();
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+        let folders = ["/a", "/a/b", "/a/b/c", "/a/b2", "/a/bc", "/d"];
+        for folder in folders {
+            let size = FolderSize {
+                files: 1,
+                ..Default::default()
+            };
+            cache
+                .sizes
+                .lock()
+                .unwrap()
+                .insert(PathBuf::from(folder), Cached { modified, size });
+        }
+
+        cache.invalidate(Path::new("/a/b"));
+        let mut kept = folders
+            .into_iter()
+            .filter(|folder| cache.cached(Path::new(folder), modified).is_some())
+            .collect::<Vec<_>>();
+        kept.sort();
+        assert_eq!(kept, ["/a/b2", "/a/bc", "/d"]);
+    }
+
+    #[test]
+    fn measures_folders_again_once_they_change() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), "123").unwrap();
+        let cache = FolderSizeCache::default();
+        let modified = || fs::metadata(dir.path()).unwrap().modified().unwrap();
+
+        assert_eq!(cache.cached(dir.path(), modified()), None);
+        assert_eq!(cache.size(dir.path()).size, 3);
+        assert_eq!(cache.cached(dir.path(), modified()).unwrap().size, 3);
+        assert_eq!(
+            cache.cached(dir.path(), modified() + Duration::from_secs(1)),
+            None
+        );
+    }
+}
@@ -0,0 +1,132 @@
+mod archive;
+mod compress;
+mod disk_usage;
+mod drives;
+mod folder_size;
+mod properties;
+mod provider;
+mod remote;
+mod sftp;
+mod transfer;
+mod trash;
+mod walk;
+mod webdav;
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use notify::{
+    event::{ModifyKind, RenameMode},
+    EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+
+pub use archive::*;
+pub use compress::*;
+pub use disk_usage::*;
+pub use drives::*;
+pub use folder_size::*;
+pub use properties::*;
+pub use provider::*;
+pub use remote::*;
+pub use sftp::*;
+pub use transfer::*;
+pub use trash::*;
+pub use webdav::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FsEventKind {
+    Created,
+    Removed,
+    Modified,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FsEvent {
+    pub path: PathBuf,
+    pub kind: FsEventKind,
+}
+
+/// Watches a set of paths for as long as it is alive.
+pub struct FsWatcher {
+    watcher: RecommendedWatcher,
+}
+
+impl FsWatcher {
+    /// Calls `callback` with batches of events below `paths`.
+    ///
+    /// Renames are reported as a removal of the old path followed by the
+    /// creation of the new one. An empty batch means events were dropped and
+    /// anything derived from the watched paths should be rescanned.
+    pub fn new(
+        paths: &[PathBuf],
+        recursive: bool,
+        callback: impl Fn(Vec<FsEvent>) + Send + 'static,
+    ) -> Result<Self> {
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+                Ok(event) if !event.need_rescan() => {
+                    let events = translate_event(event);
+                    if !events.is_empty() {
+                        callback(events);
+                    }
+                }
+                _ => callback(Vec::new()),
+            })?;
+
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        for path in paths {
+            watcher.watch(path, mode)?;
+        }
+
+        Ok(Self { watcher })
+    }
+
+    pub fn watch(&mut self, path: &Path, recursive: bool) -> Result<()> {
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        Ok(self.watcher.watch(path, mode)?)
+    }
+
+    pub fn unwatch(&mut self, path: &Path) -> Result<()> {
+        Ok(self.watcher.unwatch(path)?)
+    }
+}
+
+fn translate_event(event: notify::Event) -> Vec<FsEvent> {
+    let kind = match event.kind {
+        EventKind::Create(_) => FsEventKind::Created,
+        EventKind::Remove(_) => FsEventKind::Removed,
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => FsEventKind::Removed,
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => FsEventKind::Created,
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            let mut paths = event.paths.into_iter();
+            return paths
+                .next()
+                .map(|path| FsEvent {
+                    path,
+                    kind: FsEventKind::Removed,
+                })
+                .into_iter()
+                .chain(paths.map(|path| FsEvent {
+                    path,
+                    kind: FsEventKind::Created,
+                }))
+                .collect();
+        }
+        EventKind::Modify(_) => FsEventKind::Modified,
+        EventKind::Access(_) | EventKind::Any | EventKind::Other => return Vec::new(),
+    };
+
+    event
+        .paths
+        .into_iter()
+        .map(|path| FsEvent { path, kind })
+        .collect()
+}
@@ -0,0 +1,479 @@
+use std::{
+    fs::{self, Metadata},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{walk::blocks_size, FolderSizeCache};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Folder,
+    Symlink,
+    Other,
+}
+
+/// Everything the properties dialog shows about one entry.
+#[derive(Clone, Debug)]
+pub struct Properties {
+    pub path: PathBuf,
+    pub kind: EntryKind,
+    /// For folders, the size of everything inside.
+    pub size: u64,
+    /// The space the allocated blocks take, which differs from `size` for
+    /// sparse and compressed files.
+    pub size_on_disk: Option<u64>,
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+    /// When the metadata, rather than the contents, last changed.
+    pub changed: Option<SystemTime>,
+    /// The permission bits, e.g. `0o644`.
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    pub inode: Option<u64>,
+    pub links: Option<u64>,
+    pub symlink_target: Option<PathBuf>,
+}
+
+/// Reads the properties of `path` without following it if it is a symlink.
+/// Folders are measured through `sizes`, which may walk them, so call it off
+/// the main thread.
+pub fn read_properties(path: &Path, sizes: &FolderSizeCache) -> Result<Properties> {
+    let metadata = fs::symlink_metadata(path)?;
+    let file_type = metadata.file_type();
+    let kind = if file_type.is_symlink() {
+        EntryKind::Symlink
+    } else if file_type.is_dir() {
+        EntryKind::Folder
+    } else if file_type.is_file() {
+        EntryKind::File
+    } else {
+        EntryKind::Other
+    };
+
+    let (size, size_on_disk) = if kind == EntryKind::Folder {
+        let size = sizes.size(path);
+        (size.size, size.size_on_disk)
+    } else {
+        (metadata.len(), blocks_size(&metadata))
+    };
+
+    let mut properties = Properties {
+        path: path.to_path_buf(),
+        kind,
+        size,
+        size_on_disk,
+        modified: metadata.modified().ok(),
+        accessed: metadata.accessed().ok(),
+        created: metadata.created().ok(),
+        changed: None,
+        mode: None,
+        uid: None,
+        gid: None,
+        owner: None,
+        group: None,
+        inode: None,
+        links: None,
+        symlink_target: fs::read_link(path).ok(),
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        properties.changed = SystemTime::UNIX_EPOCH.checked_add(std::time::Duration::from_secs(
+            metadata.ctime().max(0) as u64,
+        ));
+        properties.mode = Some(metadata.mode() & 0o7777);
+        properties.uid = Some(metadata.uid());
+        properties.gid = Some(metadata.gid());
+        properties.owner = user_name(metadata.uid());
+        properties.group = group_name(metadata.gid());
+        properties.inode = Some(metadata.ino());
+        properties.links = Some(metadata.nlink());
+    }
+
+    Ok(properties)
+}
+
+/// Permission and ownership changes to apply to a set of entries.
+#[derive(Clone, Debug, Default)]
+pub struct AttributeChange {
+    /// Permission bits to turn on.
+    pub set_mode: u32,
+    /// Permission bits to turn off.
+    pub clear_mode: u32,
+    /// A user name or numeric id.
+    pub owner: Option<String>,
+    /// A group name or numeric id.
+    pub group: Option<String>,
+    /// Whether to change everything inside folders as well.
+    pub recursive: bool,
+}
+
+impl AttributeChange {
+    pub fn is_empty(&self) -> bool {
+        self.set_mode == 0 && self.clear_mode == 0 && self.owner.is_none() && self.group.is_none()
+    }
+}
+
+/// Applies `change` to each of `paths`, carrying on past failures. Returns
+/// one message per entry that couldn't be changed.
+pub fn apply_attribute_change(paths: &[PathBuf], change: &AttributeChange) -> Vec<String> {
+    let ids = (|| {
+        let uid = change.owner.as_deref().map(resolve_user).transpose()?;
+        let gid = change.group.as_deref().map(resolve_group).transpose()?;
+        anyhow::Ok((uid, gid))
+    })();
+    let (uid, gid) = match ids {
+        Ok(ids) => ids,
+        Err(e) => return vec![e.to_string()],
+    };
+
+    let mut failures = Vec::new();
+    for path in paths {
+        apply_to(path, change, uid, gid, &mut failures);
+    }
+    failures
+}
+
+fn apply_to(
+    path: &Path,
+    change: &AttributeChange,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    failures: &mut Vec<String>,
+) {
+    let mut record = |result: Result<()>| {
+        if let Err(e) = result {
+            eprintln!("Failed to change: {}. Error: {}", path.display(), e);
+            failures.push(format!("{}: {}", path.display(), e));
+        }
+    };
+
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => return record(Err(e.into())),
+    };
+    let is_symlink = metadata.file_type().is_symlink();
+    let descend = change.recursive && metadata.is_dir();
+
+    // Symlinks have no permissions of their own; chmod would change the
+    // target.
+    let mode = (!is_symlink).then(|| permission_bits(&metadata)).flatten();
+    let granted = mode.map(|mode| mode | change.set_mode);
+    let final_mode = granted.map(|mode| mode & !change.clear_mode);
+
+    // Grant before and revoke after walking a folder, so it stays readable
+    // while its contents are changed.
+    let first_mode = if descend { granted } else { final_mode };
+    if let Some(first_mode) = first_mode.filter(|first_mode| Some(*first_mode) != mode) {
+        record(set_permission_bits(path, first_mode));
+    }
+    if uid.is_some() || gid.is_some() {
+        record(change_owner(path, uid, gid));
+    }
+
+    if descend {
+        match fs::read_dir(path) {
+            Ok(entries) => {
+                for entry in entries.filter_map(Result::ok) {
+                    apply_to(&entry.path(), change, uid, gid, failures);
+                }
+            }
+            Err(e) => failures.push(format!("{}: {}", path.display(), e)),
+        }
+        if let Some(final_mode) = final_mode.filter(|final_mode| Some(*final_mode) != granted) {
+            if let Err(e) = set_permission_bits(path, final_mode) {
+                failures.push(format!("{}: {}", path.display(), e));
+            }
+        }
+    }
+}
+
+/// `rwxr-xr-x` style permissions for the lowest nine bits of `mode`.
+pub fn mode_string(mode: u32) -> String {
+    let bits = [
+        (0o400, 'r'),
+        (0o200, 'w'),
+        (0o100, 'x'),
+        (0o040, 'r'),
+        (0o020, 'w'),
+        (0o010, 'x'),
+        (0o004, 'r'),
+        (0o002, 'w'),
+        (0o001, 'x'),
+    ];
+    bits.iter()
+        .map(|&(bit, c)| if mode & bit != 0 { c } else { '-' })
+        .collect()
+}
+
+#[cfg(unix)]
+fn permission_bits(metadata: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn permission_bits(_metadata: &Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn set_permission_bits(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_permission_bits(_path: &Path, _mode: u32) -> Result<()> {
+    Err(anyhow!("permissions can't be changed on this platform"))
+}
+
+#[cfg(unix)]
+fn change_owner(path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+    std::os::unix::fs::lchown(path, uid, gid)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn change_owner(_path: &Path, _uid: Option<u32>, _gid: Option<u32>) -> Result<()> {
+    Err(anyhow!("owners can't be changed on this platform"))
+}
+
+fn resolve_user(user: &str) -> Result<u32> {
+    user.parse()
+        .ok()
+        .or_else(|| user_id(user))
+        .ok_or_else(|| anyhow!("no such user: {}", user))
+}
+
+fn resolve_group(group: &str) -> Result<u32> {
+    group
+        .parse()
+        .ok()
+        .or_else(|| group_id(group))
+        .ok_or_else(|| anyhow!("no such group: {}", group))
+}
+
+#[cfg(unix)]
+mod accounts {
+    use std::ffi::{CStr, CString};
+
+    const BUFFER_SIZE: usize = 16 * 1024;
+
+    pub fn user_name(uid: u32) -> Option<String> {
+        let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut result = std::ptr::null_mut();
+        let status = unsafe {
+            libc::getpwuid_r(
+                uid,
+                &mut passwd,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+        if status != 0 || result.is_null() {
+            return None;
+        }
+        Some(
+            unsafe { CStr::from_ptr(passwd.pw_name) }
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+
+    pub fn group_name(gid: u32) -> Option<String> {
+        let mut group: libc::group = unsafe { std::mem::zeroed() };
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut result = std::ptr::null_mut();
+        let status = unsafe {
+            libc::getgrgid_r(
+                gid,
+                &mut group,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+        if status != 0 || result.is_null() {
+            return None;
+        }
+        Some(
+            unsafe { CStr::from_ptr(group.gr_name) }
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+
+    pub fn user_id(name: &str) -> Option<u32> {
+        let name = CString::new(name).ok()?;
+        let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut result = std::ptr::null_mut();
+        let status = unsafe {
+            libc::getpwnam_r(
+                name.as_ptr(),
+                &mut passwd,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+        (status == 0 && !result.is_null()).then_some(passwd.pw_uid)
+    }
+
+    pub fn group_id(name: &str) -> Option<u32> {
+        let name = CString::new(name).ok()?;
+        let mut group: libc::group = unsafe { std::mem::zeroed() };
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut result = std::ptr::null_mut();
+        let status = unsafe {
+            libc::getgrnam_r(
+                name.as_ptr(),
+                &mut group,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+        (status == 0 && !result.is_null()).then_some(group.gr_gid)
+    }
+}
+
+#[cfg(not(unix))]
+mod accounts {
+    pub fn user_name(_uid: u32) -> Option<String> {
+        None
+    }
+
+    pub fn group_name(_gid: u32) -> Option<String> {
+        None
+    }
+
+    pub fn user_id(_name: &str) -> Option<u32> {
+        None
+    }
+
+    pub fn group_id(_name: &str) -> Option<u32> {
+        None
+    }
+}
+
+pub use accounts::*;
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    use super::*;
+
+    fn set_mode(path: &Path, mode: u32) {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    fn mode(path: &Path) -> u32 {
+        fs::symlink_metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn writes_modes_like_ls() {
+        assert_eq!(mode_string(0o755), "rwxr-xr-x");
+        assert_eq!(mode_string(0o644), "rw-r--r--");
+        assert_eq!(mode_string(0o100640), "rw-r-----");
+        assert_eq!(mode_string(0o4711), "rwx--x--x");
+        assert_eq!(mode_string(0), "---------");
+    }
+
+    #[test]
+    fn reads_the_mode_of_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+        fs::write(&path, "123").unwrap();
+        set_mode(&path, 0o640);
+
+        let properties = read_properties(&path, &FolderSizeCache::default()).unwrap();
+        assert_eq!(properties.kind, EntryKind::File);
+        assert_eq!(properties.size, 3);
+        assert_eq!(properties.mode, Some(0o640));
+    }
+
+    #[test]
+    fn changes_modes_of_everything_inside_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("folder");
+        fs::create_dir_all(folder.join("sub")).unwrap();
+        fs::write(folder.join("sub/file"), "").unwrap();
+        let outside = dir.path().join("outside");
+        fs::write(&outside, "").unwrap();
+        symlink(&outside, folder.join("link")).unwrap();
+        for path in [&folder, &folder.join("sub")] {
+            set_mode(path, 0o755);
+        }
+        for path in [&folder.join("sub/file"), &outside] {
+            set_mode(path, 0o644);
+        }
+
+        let change = AttributeChange {
+            set_mode: 0o070,
+            clear_mode: 0o007,
+            recursive: true,
+            ..Default::default()
+        };
+        let failures = apply_attribute_change(std::slice::from_ref(&folder), &change);
+        assert_eq!(failures, Vec::<String>::new());
+
+        assert_eq!(mode(&folder), 0o770);
+        assert_eq!(mode(&folder.join("sub")), 0o770);
+        assert_eq!(mode(&folder.join("sub/file")), 0o670);
+        // Links are left alone rather than changing what they point to.
+        assert_eq!(mode(&outside), 0o644);
+    }
+
+    #[test]
+    fn changes_only_the_entry_itself_unless_recursive() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("folder");
+        fs::create_dir(&folder).unwrap();
+        fs::write(folder.join("file"), "").unwrap();
+        set_mode(&folder, 0o755);
+        set_mode(&folder.join("file"), 0o644);
+
+        let change = AttributeChange {
+            clear_mode: 0o055,
+            ..Default::default()
+        };
+        let failures = apply_attribute_change(std::slice::from_ref(&folder), &change);
+        assert_eq!(failures, Vec::<String>::new());
+        assert_eq!(mode(&folder), 0o700);
+        assert_eq!(mode(&folder.join("file")), 0o644);
+    }
+
+    #[test]
+    fn carries_on_past_missing_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        fs::write(&file, "").unwrap();
+        set_mode(&file, 0o600);
+
+        let change = AttributeChange {
+            set_mode: 0o004,
+            ..Default::default()
+        };
+        let failures = apply_attribute_change(&[dir.path().join("missing"), file.clone()], &change);
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].contains("missing"), "{}", failures[0]);
+        assert_eq!(mode(&file), 0o604);
+    }
+}
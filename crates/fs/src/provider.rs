@@ -0,0 +1,924 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::OsStr,
+    fs::{self, File},
+    io::{self, Cursor, Read, Write},
+    ops::Bound,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    copy_path, move_path, split_archive_path, total_size,
+    transfer::{copy_data, remove_on_error, unique_name},
+    ArchiveCache, EntryKind, FsEvent, FsEventKind, FsWatcher, TransferProgress,
+};
+
+/// What a provider knows about one entry. Symlinks are followed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileStat {
+    pub kind: EntryKind,
+    /// For folders, the size of everything inside where the provider knows
+    /// it without walking them, and 0 otherwise.
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl FileStat {
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Folder
+    }
+}
+
+/// Keeps a watch going for as long as it is alive.
+pub struct Watch {
+    _inner: Box<dyn Send>,
+}
+
+impl Watch {
+    pub fn new(inner: impl Send + 'static) -> Self {
+        Self {
+            _inner: Box::new(inner),
+        }
+    }
+}
+
+pub type WatchCallback = Box<dyn Fn(Vec<FsEvent>) + Send + Sync>;
+
+/// What [`FileSystemProvider::write`] hands out. The file is only complete
+/// once [`close`](FileWriter::close) succeeds; a writer dropped without it
+/// may leave nothing, or part of the file, behind.
+pub trait FileWriter: Write + Send {
+    fn close(self: Box<Self>) -> Result<()>;
+}
+
+impl FileWriter for File {
+    fn close(mut self: Box<Self>) -> Result<()> {
+        self.flush()?;
+        Ok(())
+    }
+}
+
+/// Somewhere files live: the local disk, the inside of an archive, a remote
+/// server. Everything the explorer shows of a location goes through its
+/// provider. Calls may block, so make them off the main thread where they
+/// can be slow.
+pub trait FileSystemProvider: Send + Sync {
+    /// The paths of the entries in `folder`, in no particular order.
+    fn list(&self, folder: &Path) -> Result<Vec<PathBuf>>;
+
+    fn stat(&self, path: &Path) -> Result<FileStat>;
+
+    fn read(&self, path: &Path) -> Result<Box<dyn Read + Send>>;
+
+    /// Creates the file at `path`, or replaces what's in it.
+    fn write(&self, path: &Path) -> Result<Box<dyn FileWriter>>;
+
+    fn create_folder(&self, path: &Path) -> Result<()>;
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()>;
+
+    /// Deletes `path`, with everything inside if it is a folder.
+    fn delete(&self, path: &Path) -> Result<()>;
+
+    /// Calls `callback` with what changes in `folder`, not counting its
+    /// subfolders. As with [`FsWatcher`], an empty batch means events were
+    /// lost and the folder should be read again.
+    fn watch(&self, folder: &Path, _callback: WatchCallback) -> Result<Watch> {
+        Err(anyhow!("{} can't be watched", folder.display()))
+    }
+}
+
+/// The scheme of a location such as `sftp://user@host/path`; `None` for
+/// plain paths.
+pub fn location_scheme(path: &Path) -> Option<&str> {
+    let (scheme, _) = path.to_str()?.split_once("://")?;
+    let valid = scheme.chars().next()?.is_ascii_alphabetic()
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then_some(scheme)
+}
+
+/// Finds the provider behind a path: the one registered for its scheme,
+/// archives for paths that go through one, and the local disk for the rest.
+#[derive(Clone)]
+pub struct FileSystems {
+    local: Arc<dyn FileSystemProvider>,
+    archives: Arc<ArchiveCache>,
+    schemes: Arc<RwLock<HashMap<String, Arc<dyn FileSystemProvider>>>>,
+}
+
+impl FileSystems {
+    /// `local` stands in for the disk, which is [`LocalFileSystem`] except
+    /// when the explorer runs on a [`MemoryFileSystem`] for testing.
+    pub fn new(local: Arc<dyn FileSystemProvider>, archives: ArchiveCache) -> Self {
+        Self {
+            local,
+            archives: Arc::new(archives),
+            schemes: Default::default(),
+        }
+    }
+
+    /// Routes `scheme://...` locations to `provider`, replacing whatever
+    /// handled them before.
+    pub fn register(&self, scheme: &str, provider: Arc<dyn FileSystemProvider>) {
+        self.schemes
+            .write()
+            .unwrap()
+            .insert(scheme.to_lowercase(), provider);
+    }
+
+    pub fn provider(&self, path: &Path) -> Result<Arc<dyn FileSystemProvider>> {
+        if let Some(scheme) = location_scheme(path) {
+            return self
+                .schemes
+                .read()
+                .unwrap()
+                .get(&scheme.to_lowercase())
+                .cloned()
+                .ok_or_else(|| anyhow!("{}:// locations aren't supported", scheme));
+        }
+        if split_archive_path(path).is_some() {
+            return Ok(self.archives.clone());
+        }
+        Ok(self.local.clone())
+    }
+
+    /// Whether `path` is on the local disk, outside any archive.
+    pub fn is_local(&self, path: &Path) -> bool {
+        location_scheme(path).is_none() && split_archive_path(path).is_none()
+    }
+
+    /// A path in `dir` named after `file_name` that doesn't exist yet, on
+    /// whichever provider `dir` belongs to.
+    pub fn unique_destination(&self, dir: &Path, file_name: &OsStr) -> PathBuf {
+        match self.provider(dir) {
+            Ok(provider) => {
+                unique_name(dir, file_name, |candidate| provider.stat(candidate).is_ok())
+            }
+            Err(_) => dir.join(file_name),
+        }
+    }
+
+    /// The size of `path` and, for folders, everything below it.
+    pub fn total_size(&self, path: &Path) -> u64 {
+        if self.is_local(path) {
+            return total_size(path);
+        }
+        match self.provider(path) {
+            Ok(provider) => walk_size(provider.as_ref(), path),
+            Err(_) => 0,
+        }
+    }
+
+    /// Copies `source` to `destination`, recursing into folders, whichever
+    /// providers the two are on.
+    pub fn copy(
+        &self,
+        source: &Path,
+        destination: &Path,
+        progress: &TransferProgress,
+    ) -> Result<()> {
+        if self.is_local(source) && self.is_local(destination) {
+            return copy_path(source, destination, progress);
+        }
+        let from = self.provider(source)?;
+        let to = self.provider(destination)?;
+        copy_across(from.as_ref(), source, to.as_ref(), destination, progress)
+    }
+
+    /// Moves `source` to `destination`: a rename when one provider has both,
+    /// and otherwise a copy followed by deleting the source.
+    pub fn move_path(
+        &self,
+        source: &Path,
+        destination: &Path,
+        progress: &TransferProgress,
+    ) -> Result<()> {
+        if self.is_local(source) && self.is_local(destination) {
+            return move_path(source, destination, progress);
+        }
+        if destination.starts_with(source) {
+            return Err(anyhow!("cannot move a folder into itself"));
+        }
+
+        let from = self.provider(source)?;
+        let to = self.provider(destination)?;
+        if Arc::ptr_eq(&from, &to) && from.rename(source, destination).is_ok() {
+            progress.advance(walk_size(to.as_ref(), destination));
+            return Ok(());
+        }
+        copy_across(from.as_ref(), source, to.as_ref(), destination, progress)?;
+        from.delete(source)
+    }
+}
+
+fn walk_size(provider: &dyn FileSystemProvider, path: &Path) -> u64 {
+    match provider.stat(path) {
+        Ok(stat) if stat.is_dir() => provider
+            .list(path)
+            .map(|children| {
+                children
+                    .iter()
+                    .map(|child| walk_size(provider, child))
+                    .sum()
+            })
+            .unwrap_or(0),
+        Ok(stat) => stat.size,
+        Err(_) => 0,
+    }
+}
+
+fn copy_across(
+    from: &dyn FileSystemProvider,
+    source: &Path,
+    to: &dyn FileSystemProvider,
+    destination: &Path,
+    progress: &TransferProgress,
+) -> Result<()> {
+    if destination.starts_with(source) {
+        return Err(anyhow!("cannot copy a folder into itself"));
+    }
+    progress.check_cancelled()?;
+
+    if from.stat(source)?.is_dir() {
+        to.create_folder(destination)?;
+        for child in from.list(source)? {
+            if let Some(name) = child.file_name() {
+                copy_across(from, &child, to, &destination.join(name), progress)?;
+            }
+        }
+        return Ok(());
+    }
+
+    let mut reader = from.read(source)?;
+    let mut writer = to.write(destination)?;
+    let result = copy_data(&mut reader, &mut writer, progress).and_then(|()| writer.close());
+    remove_on_error(result, || {
+        to.delete(destination).ok();
+    })
+}
+
+/// The disk, through `std::fs`.
+pub struct LocalFileSystem;
+
+impl FileSystemProvider for LocalFileSystem {
+    fn list(&self, folder: &Path) -> Result<Vec<PathBuf>> {
+        Ok(fs::read_dir(folder)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect())
+    }
+
+    fn stat(&self, path: &Path) -> Result<FileStat> {
+        let metadata = fs::metadata(path)?;
+        let kind = if metadata.is_dir() {
+            EntryKind::Folder
+        } else if metadata.is_file() {
+            EntryKind::File
+        } else {
+            EntryKind::Other
+        };
+        Ok(FileStat {
+            kind,
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    fn read(&self, path: &Path) -> Result<Box<dyn Read + Send>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn write(&self, path: &Path) -> Result<Box<dyn FileWriter>> {
+        Ok(Box::new(File::create(path)?))
+    }
+
+    fn create_folder(&self, path: &Path) -> Result<()> {
+        Ok(fs::create_dir(path)?)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        Ok(fs::rename(from, to)?)
+    }
+
+    fn delete(&self, path: &Path) -> Result<()> {
+        if fs::symlink_metadata(path)?.is_dir() {
+            fs::remove_dir_all(path)?;
+        } else {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn watch(&self, folder: &Path, callback: WatchCallback) -> Result<Watch> {
+        let watcher = FsWatcher::new(&[folder.to_path_buf()], false, callback)?;
+        Ok(Watch::new(watcher))
+    }
+}
+
+/// Archives, browsed read-only.
+impl FileSystemProvider for ArchiveCache {
+    fn list(&self, folder: &Path) -> Result<Vec<PathBuf>> {
+        let (archive_path, inner) =
+            split_archive_path(folder).ok_or_else(|| anyhow!("not inside an archive"))?;
+        let archive = self.open(&archive_path)?;
+        if !archive.entry(&inner).is_some_and(|entry| entry.is_dir) {
+            return Err(anyhow!("{} is not a folder", folder.display()));
+        }
+        Ok(archive
+            .children(&inner)
+            .map(|entry| archive_path.join(&entry.path))
+            .collect())
+    }
+
+    fn stat(&self, path: &Path) -> Result<FileStat> {
+        let Some(entry) = self.entry(path) else {
+            // The archive itself.
+            return LocalFileSystem.stat(path);
+        };
+        Ok(FileStat {
+            kind: if entry.is_dir {
+                EntryKind::Folder
+            } else {
+                EntryKind::File
+            },
+            size: entry.size,
+            modified: entry.modified,
+        })
+    }
+
+    fn read(&self, path: &Path) -> Result<Box<dyn Read + Send>> {
+        let (archive_path, inner) =
+            split_archive_path(path).ok_or_else(|| anyhow!("not inside an archive"))?;
+        if inner.as_os_str().is_empty() {
+            return LocalFileSystem.read(path);
+        }
+        let extracted = self.open(&archive_path)?.extract_temporary(&inner)?;
+        Ok(Box::new(File::open(extracted)?))
+    }
+
+    fn write(&self, _path: &Path) -> Result<Box<dyn FileWriter>> {
+        Err(anyhow!("archives can't be changed"))
+    }
+
+    fn create_folder(&self, _path: &Path) -> Result<()> {
+        Err(anyhow!("archives can't be changed"))
+    }
+
+    fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
+        Err(anyhow!("archives can't be changed"))
+    }
+
+    fn delete(&self, _path: &Path) -> Result<()> {
+        Err(anyhow!("archives can't be changed"))
+    }
+}
+
+/// Files kept in memory, for trying out the explorer without touching the
+/// disk. Paths are absolute, and `/` always exists.
+#[derive(Clone, Default)]
+pub struct MemoryFileSystem {
+    state: Arc<Mutex<MemoryState>>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    /// Folders have no contents.
+    entries: BTreeMap<PathBuf, MemoryEntry>,
+    watchers: Vec<(PathBuf, Arc<WatchCallback>)>,
+    /// Events waiting to be delivered once the lock is released, so a
+    /// callback can use the file system again.
+    pending: Vec<(Arc<WatchCallback>, FsEvent)>,
+}
+
+struct MemoryEntry {
+    contents: Option<Vec<u8>>,
+    modified: SystemTime,
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file, and any folders above it that are missing.
+    pub fn insert_file(&self, path: &Path, contents: impl Into<Vec<u8>>) {
+        self.update(|state| {
+            for folder in path.ancestors().skip(1) {
+                if !state.exists(folder) {
+                    state.insert(folder, None);
+                }
+            }
+            state.insert(path, Some(contents.into()));
+        })
+    }
+
+    /// Changes the state under its lock, then tells watchers what changed.
+    fn update<R>(&self, change: impl FnOnce(&mut MemoryState) -> R) -> R {
+        let (result, pending) = {
+            let mut state = self.state.lock().unwrap();
+            let result = change(&mut state);
+            (result, std::mem::take(&mut state.pending))
+        };
+        for (callback, event) in pending {
+            callback(vec![event]);
+        }
+        result
+    }
+
+    fn not_found(path: &Path) -> anyhow::Error {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} doesn't exist", path.display()),
+        )
+        .into()
+    }
+}
+
+impl MemoryState {
+    fn exists(&self, path: &Path) -> bool {
+        path.parent().is_none() || self.entries.contains_key(path)
+    }
+
+    fn is_folder(&self, path: &Path) -> bool {
+        path.parent().is_none()
+            || self
+                .entries
+                .get(path)
+                .is_some_and(|entry| entry.contents.is_none())
+    }
+
+    fn insert(&mut self, path: &Path, contents: Option<Vec<u8>>) {
+        let created = !self.entries.contains_key(path);
+        self.entries.insert(
+            path.to_path_buf(),
+            MemoryEntry {
+                contents,
+                modified: SystemTime::now(),
+            },
+        );
+        let kind = if created {
+            FsEventKind::Created
+        } else {
+            FsEventKind::Modified
+        };
+        self.notify(path, kind);
+    }
+
+    /// Everything at or below `path`.
+    fn subtree(&self, path: &Path) -> Vec<PathBuf> {
+        self.entries
+            .range::<Path, _>((Bound::Included(path), Bound::Unbounded))
+            .map(|(entry_path, _)| entry_path)
+            .take_while(|entry_path| entry_path.starts_with(path))
+            .cloned()
+            .collect()
+    }
+
+    fn notify(&mut self, path: &Path, kind: FsEventKind) {
+        for (folder, callback) in &self.watchers {
+            if path.parent() == Some(folder.as_path()) {
+                self.pending.push((
+                    callback.clone(),
+                    FsEvent {
+                        path: path.to_path_buf(),
+                        kind,
+                    },
+                ));
+            }
+        }
+    }
+
+    fn check_parent(&self, path: &Path) -> Result<()> {
+        match path.parent() {
+            Some(parent) if self.is_folder(parent) => Ok(()),
+            Some(parent) => Err(MemoryFileSystem::not_found(parent)),
+            None => Err(anyhow!("{} already exists", path.display())),
+        }
+    }
+}
+
+impl FileSystemProvider for MemoryFileSystem {
+    fn list(&self, folder: &Path) -> Result<Vec<PathBuf>> {
+        let state = self.state.lock().unwrap();
+        if !state.is_folder(folder) {
+            return Err(Self::not_found(folder));
+        }
+        Ok(state
+            .subtree(folder)
+            .into_iter()
+            .filter(|path| path.parent() == Some(folder))
+            .collect())
+    }
+
+    fn stat(&self, path: &Path) -> Result<FileStat> {
+        let state = self.state.lock().unwrap();
+        if path.parent().is_none() {
+            return Ok(FileStat {
+                kind: EntryKind::Folder,
+                size: 0,
+                modified: None,
+            });
+        }
+        let entry = state
+            .entries
+            .get(path)
+            .ok_or_else(|| Self::not_found(path))?;
+        Ok(FileStat {
+            kind: if entry.contents.is_some() {
+                EntryKind::File
+            } else {
+                EntryKind::Folder
+            },
+            size: entry
+                .contents
+                .as_ref()
+                .map_or(0, |contents| contents.len() as u64),
+            modified: Some(entry.modified),
+        })
+    }
+
+    fn read(&self, path: &Path) -> Result<Box<dyn Read + Send>> {
+        let state = self.state.lock().unwrap();
+        let contents = state
+            .entries
+            .get(path)
+            .and_then(|entry| entry.contents.clone())
+            .ok_or_else(|| Self::not_found(path))?;
+        Ok(Box::new(Cursor::new(contents)))
+    }
+
+    fn write(&self, path: &Path) -> Result<Box<dyn FileWriter>> {
+        let state = self.state.lock().unwrap();
+        state.check_parent(path)?;
+        if state.is_folder(path) {
+            return Err(anyhow!("{} is a folder", path.display()));
+        }
+        Ok(Box::new(MemoryWriter {
+            fs: self.clone(),
+            path: path.to_path_buf(),
+            contents: Vec::new(),
+        }))
+    }
+
+    fn create_folder(&self, path: &Path) -> Result<()> {
+        self.update(|state| {
+            state.check_parent(path)?;
+            if state.exists(path) {
+                return Err(anyhow!("{} already exists", path.display()));
+            }
+            state.insert(path, None);
+            Ok(())
+        })
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.update(|state| {
+            if !state.entries.contains_key(from) {
+                return Err(Self::not_found(from));
+            }
+            if to.starts_with(from) {
+                return Err(anyhow!("cannot move a folder into itself"));
+            }
+            state.check_parent(to)?;
+            if state.is_folder(to) {
+                return Err(anyhow!("{} is a folder", to.display()));
+            }
+
+            for path in state.subtree(from) {
+                let entry = state.entries.remove(&path).unwrap();
+                let inside = path.strip_prefix(from).unwrap();
+                let moved = if inside.as_os_str().is_empty() {
+                    to.to_path_buf()
+                } else {
+                    to.join(inside)
+                };
+                state.entries.insert(moved, entry);
+            }
+            state.notify(from, FsEventKind::Removed);
+            state.notify(to, FsEventKind::Created);
+            Ok(())
+        })
+    }
+
+    fn delete(&self, path: &Path) -> Result<()> {
+        self.update(|state| {
+            if !state.entries.contains_key(path) {
+                return Err(Self::not_found(path));
+            }
+            for removed in state.subtree(path) {
+                state.entries.remove(&removed);
+            }
+            state.notify(path, FsEventKind::Removed);
+            Ok(())
+        })
+    }
+
+    fn watch(&self, folder: &Path, callback: WatchCallback) -> Result<Watch> {
+        let callback = Arc::new(callback);
+        self.state
+            .lock()
+            .unwrap()
+            .watchers
+            .push((folder.to_path_buf(), callback.clone()));
+        Ok(Watch::new(MemoryWatch {
+            fs: self.clone(),
+            callback,
+        }))
+    }
+}
+
+/// Stores what was written once it is closed. Dropping it leaves the file
+/// as it was.
+struct MemoryWriter {
+    fs: MemoryFileSystem,
+    path: PathBuf,
+    contents: Vec<u8>,
+}
+
+impl Write for MemoryWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.contents.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl FileWriter for MemoryWriter {
+    fn close(self: Box<Self>) -> Result<()> {
+        let Self { fs, path, contents } = *self;
+        fs.update(|state| {
+            state.check_parent(&path)?;
+            state.insert(&path, Some(contents));
+            Ok(())
+        })
+    }
+}
+
+struct MemoryWatch {
+    fs: MemoryFileSystem,
+    callback: Arc<WatchCallback>,
+}
+
+impl Drop for MemoryWatch {
+    fn drop(&mut self) {
+        self.fs
+            .state
+            .lock()
+            .unwrap()
+            .watchers
+            .retain(|(_, callback)| !Arc::ptr_eq(callback, &self.callback));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        paths.sort();
+        paths
+    }
+
+    fn memory_fs() -> MemoryFileSystem {
+        let fs = MemoryFileSystem::new();
+        fs.insert_file(Path::new("/a/one.txt"), "one");
+        fs.insert_file(Path::new("/a/b/two.txt"), "two");
+        fs.insert_file(Path::new("/c.txt"), "c");
+        fs
+    }
+
+    #[test]
+    fn lists_only_direct_children() {
+        let fs = memory_fs();
+        assert_eq!(
+            sorted(fs.list(Path::new("/")).unwrap()),
+            [PathBuf::from("/a"), PathBuf::from("/c.txt")]
+        );
+        assert_eq!(
+            sorted(fs.list(Path::new("/a")).unwrap()),
+            [PathBuf::from("/a/b"), PathBuf::from("/a/one.txt")]
+        );
+        assert!(fs.list(Path::new("/c.txt")).is_err());
+        assert!(fs.list(Path::new("/missing")).is_err());
+    }
+
+    #[test]
+    fn stats_files_and_folders() {
+        let fs = memory_fs();
+        let file = fs.stat(Path::new("/a/one.txt")).unwrap();
+        assert_eq!(file.kind, EntryKind::File);
+        assert_eq!(file.size, 3);
+        assert!(fs.stat(Path::new("/a/b")).unwrap().is_dir());
+        assert!(fs.stat(Path::new("/")).unwrap().is_dir());
+    }
+
+    #[test]
+    fn renames_a_subtree() {
+        let fs = memory_fs();
+        fs.rename(Path::new("/a"), Path::new("/d")).unwrap();
+        assert!(fs.stat(Path::new("/a")).is_err());
+        assert!(fs.stat(Path::new("/a/b/two.txt")).is_err());
+
+        let mut contents = String::new();
+        fs.read(Path::new("/d/b/two.txt"))
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "two");
+        assert_eq!(
+            sorted(fs.list(Path::new("/d")).unwrap()),
+            [PathBuf::from("/d/b"), PathBuf::from("/d/one.txt")]
+        );
+        assert!(fs.rename(Path::new("/d"), Path::new("/d/b/e")).is_err());
+    }
+
+    #[test]
+    fn deletes_a_subtree() {
+        let fs = memory_fs();
+        fs.delete(Path::new("/a")).unwrap();
+        assert_eq!(fs.list(Path::new("/")).unwrap(), [PathBuf::from("/c.txt")]);
+        assert!(fs.stat(Path::new("/a/b/two.txt")).is_err());
+        assert!(fs.delete(Path::new("/a")).is_err());
+    }
+
+    #[test]
+    fn writes_when_the_writer_is_closed() {
+        let fs = memory_fs();
+        let mut writer = fs.write(Path::new("/a/new.txt")).unwrap();
+        writer.write_all(b"new").unwrap();
+        writer.flush().unwrap();
+        assert!(fs.stat(Path::new("/a/new.txt")).is_err());
+        writer.close().unwrap();
+        assert_eq!(fs.stat(Path::new("/a/new.txt")).unwrap().size, 3);
+
+        let mut writer = fs.write(Path::new("/a/new.txt")).unwrap();
+        writer.write_all(b"abandoned").unwrap();
+        drop(writer);
+        assert_eq!(fs.stat(Path::new("/a/new.txt")).unwrap().size, 3);
+        assert!(fs.write(Path::new("/missing/new.txt")).is_err());
+    }
+
+    #[test]
+    fn watchers_see_changes_in_their_folder() {
+        let fs = memory_fs();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let watch = fs
+            .watch(Path::new("/a"), {
+                let events = events.clone();
+                Box::new(move |batch| events.lock().unwrap().extend(batch))
+            })
+            .unwrap();
+
+        fs.create_folder(Path::new("/a/f")).unwrap();
+        fs.insert_file(Path::new("/a/one.txt"), "changed");
+        fs.insert_file(Path::new("/a/b/deeper.txt"), "not seen");
+        fs.rename(Path::new("/a/f"), Path::new("/g")).unwrap();
+        fs.delete(Path::new("/a/b")).unwrap();
+        drop(watch);
+        fs.delete(Path::new("/a/one.txt")).unwrap();
+
+        let events = events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|event| (event.path.clone(), event.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                (PathBuf::from("/a/f"), FsEventKind::Created),
+                (PathBuf::from("/a/one.txt"), FsEventKind::Modified),
+                (PathBuf::from("/a/f"), FsEventKind::Removed),
+                (PathBuf::from("/a/b"), FsEventKind::Removed),
+            ]
+        );
+    }
+
+    #[test]
+    fn watchers_can_use_the_file_system() {
+        let fs = memory_fs();
+        let listed = Arc::new(Mutex::new(Vec::new()));
+        let _watch = fs
+            .watch(Path::new("/a"), {
+                let fs = fs.clone();
+                let listed = listed.clone();
+                Box::new(move |_| *listed.lock().unwrap() = fs.list(Path::new("/a")).unwrap())
+            })
+            .unwrap();
+
+        fs.delete(Path::new("/a/b")).unwrap();
+        assert_eq!(*listed.lock().unwrap(), [PathBuf::from("/a/one.txt")]);
+    }
+
+    /// `photos.zip` and `photos.tar.gz` in a new folder, each holding
+    /// `top.txt` and `dir/file.txt`, with a file system that reads them
+    /// through the archive provider.
+    fn archives() -> (tempfile::TempDir, FileSystems) {
+        let dir = tempfile::tempdir().unwrap();
+        let files = [("top.txt", "top"), ("dir/file.txt", "inside")];
+
+        let mut zip = zip::ZipWriter::new(File::create(dir.path().join("photos.zip")).unwrap());
+        for (name, contents) in files {
+            zip.start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let gz = flate2::write::GzEncoder::new(
+            File::create(dir.path().join("photos.tar.gz")).unwrap(),
+            flate2::Compression::fast(),
+        );
+        let mut tar = tar::Builder::new(gz);
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            tar.append_data(&mut header, name, contents.as_bytes())
+                .unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let file_systems = FileSystems::new(
+            Arc::new(LocalFileSystem),
+            ArchiveCache::with_cache_dir(dir.path().join("cache")),
+        );
+        (dir, file_systems)
+    }
+
+    #[test]
+    fn routes_paths_through_archives() {
+        let (dir, file_systems) = archives();
+        let archive = dir.path().join("photos.zip");
+        assert_eq!(
+            split_archive_path(&archive.join("dir/file.txt")),
+            Some((archive.clone(), PathBuf::from("dir/file.txt")))
+        );
+        assert_eq!(
+            split_archive_path(&archive),
+            Some((archive.clone(), PathBuf::new()))
+        );
+        assert_eq!(split_archive_path(&dir.path().join("top.txt")), None);
+        assert!(file_systems.is_local(dir.path()));
+        assert!(!file_systems.is_local(&archive.join("dir")));
+
+        // A folder named like an archive is just a folder.
+        let folder = dir.path().join("folder.zip");
+        fs::create_dir(&folder).unwrap();
+        assert_eq!(split_archive_path(&folder.join("a")), None);
+    }
+
+    #[test]
+    fn browses_archives_like_folders() {
+        let (dir, file_systems) = archives();
+        for name in ["photos.zip", "photos.tar.gz"] {
+            let archive = dir.path().join(name);
+            let provider = file_systems.provider(&archive.join("dir")).unwrap();
+
+            assert_eq!(
+                sorted(provider.list(&archive).unwrap()),
+                [archive.join("dir"), archive.join("top.txt")],
+                "{}",
+                name
+            );
+            assert_eq!(
+                provider.list(&archive.join("dir")).unwrap(),
+                [archive.join("dir/file.txt")]
+            );
+
+            assert_eq!(provider.stat(&archive).unwrap().kind, EntryKind::File);
+            assert!(provider.stat(&archive.join("dir")).unwrap().is_dir());
+            let file = provider.stat(&archive.join("dir/file.txt")).unwrap();
+            assert_eq!((file.kind, file.size), (EntryKind::File, 6));
+
+            let mut contents = String::new();
+            provider
+                .read(&archive.join("dir/file.txt"))
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            assert_eq!(contents, "inside");
+        }
+    }
+
+    #[test]
+    fn misses_entries_that_arent_in_archives() {
+        let (dir, file_systems) = archives();
+        let archive = dir.path().join("photos.tar.gz");
+        let provider = file_systems.provider(&archive.join("dir")).unwrap();
+        let missing = archive.join("dir/missing.txt");
+        assert!(provider.stat(&missing).is_err());
+        assert!(provider.read(&missing).is_err());
+        assert!(provider.list(&archive.join("missing")).is_err());
+        assert!(provider.list(&archive.join("top.txt")).is_err());
+        assert!(provider.write(&missing).is_err());
+    }
+}
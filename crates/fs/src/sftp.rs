@@ -0,0 +1,322 @@
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Result};
+use ssh2::{CheckResult, ErrorCode, KnownHostFileKind, Session, Sftp};
+
+use crate::{EntryKind, FileStat, FileSystemProvider, FileWriter, RemoteLocation};
+
+const DEFAULT_PORT: u16 = 22;
+const TIMEOUT_MS: u32 = 15_000;
+/// Tried in this order after the keys held by the SSH agent.
+const KEY_NAMES: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// `sftp://user@host:port/path` locations. One connection is kept per
+/// server and made again when it drops. Only key-based logins are
+/// supported, with the keys of the SSH agent or those in `~/.ssh`.
+#[derive(Default)]
+pub struct SftpProvider {
+    connections: Mutex<HashMap<String, Arc<Connection>>>,
+    /// What listings said about the entries in them, so showing a folder
+    /// doesn't ask the server about each entry again.
+    stats: Mutex<HashMap<PathBuf, FileStat>>,
+}
+
+struct Connection {
+    _session: Session,
+    sftp: Sftp,
+}
+
+impl SftpProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connects to the server of `location` if not connected yet, and returns
+    /// the location of the home folder there.
+    pub fn connect(&self, location: &Path) -> Result<PathBuf> {
+        let remote = RemoteLocation::parse(location)?;
+        let home = self.call(location, |sftp, _| sftp.realpath(Path::new(".")))?;
+        Ok(remote.with_path(&home.to_string_lossy()))
+    }
+
+    /// Runs `f` with the connection to the server of `location` and the path
+    /// there. A connection that failed is dropped, to be made again next time.
+    fn call<T>(
+        &self,
+        location: &Path,
+        f: impl FnOnce(&Sftp, &Path) -> Result<T, ssh2::Error>,
+    ) -> Result<T> {
+        let remote = RemoteLocation::parse(location)?;
+        let server = remote.server();
+        let connection = self.connection(&remote)?;
+        f(&connection.sftp, Path::new(&remote.path)).map_err(|e| {
+            if matches!(e.code(), ErrorCode::Session(_)) {
+                self.connections.lock().unwrap().remove(&server);
+            }
+            anyhow!("{}", e)
+        })
+    }
+
+    /// Connects without holding the lock, so a server that is slow to answer
+    /// doesn't hold up calls to the others.
+    fn connection(&self, remote: &RemoteLocation) -> Result<Arc<Connection>> {
+        if let Some(connection) = self.connections.lock().unwrap().get(&remote.server()) {
+            return Ok(connection.clone());
+        }
+        let session = open_session(remote)?;
+        let connection = Arc::new(Connection {
+            sftp: session.sftp()?,
+            _session: session,
+        });
+        // Another call may have connected meanwhile; keep the first.
+        Ok(self
+            .connections
+            .lock()
+            .unwrap()
+            .entry(remote.server())
+            .or_insert(connection)
+            .clone())
+    }
+
+    fn forget_below(&self, location: &Path) {
+        self.stats
+            .lock()
+            .unwrap()
+            .retain(|path, _| !path.starts_with(location));
+    }
+}
+
+impl FileSystemProvider for SftpProvider {
+    fn list(&self, folder: &Path) -> Result<Vec<PathBuf>> {
+        let entries = self.call(folder, |sftp, path| {
+            let mut entries = Vec::new();
+            for (entry_path, mut stat) in sftp.readdir(path)? {
+                // Listings describe links rather than what they point to.
+                if stat.file_type().is_symlink() {
+                    stat = sftp.stat(&entry_path).unwrap_or(stat);
+                }
+                entries.push((entry_path, stat));
+            }
+            Ok(entries)
+        })?;
+
+        let mut stats = self.stats.lock().unwrap();
+        Ok(entries
+            .into_iter()
+            .filter_map(|(entry_path, stat)| {
+                let path = folder.join(entry_path.file_name()?);
+                stats.insert(path.clone(), convert_stat(&stat));
+                Some(path)
+            })
+            .collect())
+    }
+
+    fn stat(&self, path: &Path) -> Result<FileStat> {
+        if let Some(stat) = self.stats.lock().unwrap().get(path) {
+            return Ok(stat.clone());
+        }
+        let stat = convert_stat(&self.call(path, |sftp, remote| sftp.stat(remote))?);
+        self.stats
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), stat.clone());
+        Ok(stat)
+    }
+
+    fn read(&self, path: &Path) -> Result<Box<dyn Read + Send>> {
+        let file = self.call(path, |sftp, remote| sftp.open(remote))?;
+        Ok(Box::new(file))
+    }
+
+    fn write(&self, path: &Path) -> Result<Box<dyn FileWriter>> {
+        self.forget_below(path);
+        let file = self.call(path, |sftp, remote| sftp.create(remote))?;
+        Ok(Box::new(file))
+    }
+
+    fn create_folder(&self, path: &Path) -> Result<()> {
+        self.call(path, |sftp, remote| sftp.mkdir(remote, 0o755))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let to_remote = RemoteLocation::parse(to)?;
+        if RemoteLocation::parse(from)?.server() != to_remote.server() {
+            return Err(anyhow!("can't rename across servers"));
+        }
+        self.forget_below(from);
+        self.forget_below(to);
+        self.call(from, |sftp, remote| {
+            sftp.rename(remote, Path::new(&to_remote.path), None)
+        })
+    }
+
+    fn delete(&self, path: &Path) -> Result<()> {
+        self.forget_below(path);
+        self.call(path, delete_recursively)
+    }
+}
+
+impl FileWriter for ssh2::File {
+    fn close(mut self: Box<Self>) -> Result<()> {
+        self.flush()?;
+        ssh2::File::close(&mut self)?;
+        Ok(())
+    }
+}
+
+fn delete_recursively(sftp: &Sftp, path: &Path) -> Result<(), ssh2::Error> {
+    // Links to folders are removed, not what's in the folders.
+    if !sftp.lstat(path)?.is_dir() {
+        return sftp.unlink(path);
+    }
+    for (child, _) in sftp.readdir(path)? {
+        delete_recursively(sftp, &child)?;
+    }
+    sftp.rmdir(path)
+}
+
+fn convert_stat(stat: &ssh2::FileStat) -> FileStat {
+    let kind = if stat.is_dir() {
+        EntryKind::Folder
+    } else if stat.is_file() {
+        EntryKind::File
+    } else if stat.file_type().is_symlink() {
+        // A link that points nowhere.
+        EntryKind::Symlink
+    } else {
+        EntryKind::Other
+    };
+    FileStat {
+        kind,
+        size: if stat.is_dir() {
+            0
+        } else {
+            stat.size.unwrap_or(0)
+        },
+        modified: stat
+            .mtime
+            .map(|mtime| SystemTime::UNIX_EPOCH + Duration::from_secs(mtime)),
+    }
+}
+
+fn open_session(remote: &RemoteLocation) -> Result<Session> {
+    let port = remote.port.unwrap_or(DEFAULT_PORT);
+    let user = remote
+        .user
+        .clone()
+        .or_else(|| std::env::var("USER").ok())
+        .ok_or_else(|| anyhow!("no user name given for {}", remote.host))?;
+
+    let tcp = connect_tcp(&remote.host, port)?;
+    let mut session = Session::new()?;
+    session.set_tcp_stream(tcp);
+    session.set_timeout(TIMEOUT_MS);
+    session.handshake()?;
+    check_host_key(&session, &remote.host, port)?;
+    authenticate(&session, &user)?;
+    Ok(session)
+}
+
+/// Tries each address of `host` in turn, giving up on each after the
+/// timeout.
+fn connect_tcp(host: &str, port: u16) -> Result<TcpStream> {
+    let timeout = Duration::from_millis(TIMEOUT_MS.into());
+    let mut last_error = None;
+    for address in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(tcp) => return Ok(tcp),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(match last_error {
+        Some(e) => e.into(),
+        None => anyhow!("{} has no addresses", host),
+    })
+}
+
+/// Checks the server's key against `~/.ssh/known_hosts`. Like OpenSSH with
+/// `StrictHostKeyChecking=accept-new`, keys of new servers are added, and
+/// servers whose key changed are refused.
+fn check_host_key(session: &Session, host: &str, port: u16) -> Result<()> {
+    let known_hosts_path = ssh_folder()?.join("known_hosts");
+    let mut known_hosts = session.known_hosts()?;
+    if known_hosts_path.exists() {
+        known_hosts.read_file(&known_hosts_path, KnownHostFileKind::OpenSSH)?;
+    }
+    let (key, key_type) = session
+        .host_key()
+        .ok_or_else(|| anyhow!("{} sent no host key", host))?;
+
+    match known_hosts.check_port(host, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(anyhow!(
+            "the host key of {} has changed. If that's expected, remove its old key from {}",
+            host,
+            known_hosts_path.display()
+        )),
+        CheckResult::Failure => Err(anyhow!("couldn't check the host key of {}", host)),
+        CheckResult::NotFound => {
+            let name = if port == DEFAULT_PORT {
+                host.to_string()
+            } else {
+                format!("[{}]:{}", host, port)
+            };
+            // Append the new key rather than writing the whole list back, so
+            // entries libssh2 doesn't understand are kept.
+            let mut new_host = session.known_hosts()?;
+            new_host.add(&name, key, "", key_type.into())?;
+            if let Some(entry) = new_host.hosts()?.first() {
+                let line = new_host.write_string(entry, KnownHostFileKind::OpenSSH)?;
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&known_hosts_path)?;
+                writeln!(file, "{}", line.trim_end())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn authenticate(session: &Session, user: &str) -> Result<()> {
+    // The agent holds the keys that have a passphrase.
+    if session.userauth_agent(user).is_ok() && session.authenticated() {
+        return Ok(());
+    }
+
+    let ssh_folder = ssh_folder()?;
+    for name in KEY_NAMES {
+        let private_key = ssh_folder.join(name);
+        if !private_key.exists() {
+            continue;
+        }
+        let public_key = ssh_folder.join(format!("{}.pub", name));
+        let public_key = public_key.exists().then_some(public_key.as_path());
+        if session
+            .userauth_pubkey_file(user, public_key, &private_key, None)
+            .is_ok()
+            && session.authenticated()
+        {
+            return Ok(());
+        }
+    }
+    Err(anyhow!(
+        "the server accepted none of the keys for {} in the SSH agent or {}",
+        user,
+        ssh_folder.display()
+    ))
+}
+
+fn ssh_folder() -> Result<PathBuf> {
+    dirs::home_dir()
+        .map(|home| home.join(".ssh"))
+        .ok_or_else(|| anyhow!("failed to determine home directory"))
+}
@@ -0,0 +1,231 @@
+use std::{
+    ffi::OsStr,
+    fmt,
+    fs::{self, File},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
+
+use anyhow::{anyhow, Result};
+
+pub(crate) const BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Returned by transfers that stopped because they were cancelled.
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// How far a copy or move has got, shared with whoever shows its progress.
+#[derive(Debug, Default)]
+pub struct TransferProgress {
+    pub total_bytes: AtomicU64,
+    pub done_bytes: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl TransferProgress {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Between 0 and 1, or 0 while the total is unknown.
+    pub fn fraction(&self) -> f32 {
+        let total = self.total_bytes.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0.;
+        }
+        (self.done_bytes.load(Ordering::Relaxed) as f64 / total as f64).min(1.) as f32
+    }
+
+    pub(crate) fn advance(&self, bytes: u64) {
+        self.done_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn check_cancelled(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(Cancelled.into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The size of `path` and, for folders, everything below it. Symlinks are not
+/// followed.
+pub fn total_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| total_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// A path in `dir` named after `file_name` that doesn't exist yet, adding
+/// " (2)", " (3)" and so on before the extension when needed.
+pub fn unique_destination(dir: &Path, file_name: &OsStr) -> PathBuf {
+    unique_name(dir, file_name, |candidate| {
+        fs::symlink_metadata(candidate).is_ok()
+    })
+}
+
+/// Like [`unique_destination`], with `taken` telling which paths exist.
+pub(crate) fn unique_name(dir: &Path, file_name: &OsStr, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    let candidate = dir.join(file_name);
+    if !taken(&candidate) {
+        return candidate;
+    }
+
+    let name = Path::new(file_name);
+    let stem = name
+        .file_stem()
+        .unwrap_or(file_name)
+        .to_string_lossy()
+        .into_owned();
+    let extension = name
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    (2..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !taken(candidate))
+        .unwrap()
+}
+
+/// Copies `source` to `destination`, recursing into folders. Symlinks are
+/// copied as links rather than followed.
+pub fn copy_path(source: &Path, destination: &Path, progress: &TransferProgress) -> Result<()> {
+    if destination.starts_with(source) {
+        return Err(anyhow!("cannot copy a folder into itself"));
+    }
+
+    let metadata = fs::symlink_metadata(source)?;
+    if metadata.file_type().is_symlink() {
+        copy_symlink(source, destination)
+    } else if metadata.is_dir() {
+        fs::create_dir(destination)?;
+        for entry in fs::read_dir(source)? {
+            progress.check_cancelled()?;
+            let entry = entry?;
+            copy_path(
+                &entry.path(),
+                &destination.join(entry.file_name()),
+                progress,
+            )?;
+        }
+        fs::set_permissions(destination, metadata.permissions())?;
+        Ok(())
+    } else {
+        copy_file(source, destination, progress)
+    }
+}
+
+/// Moves `source` to `destination`, copying and then deleting it when the two
+/// are on different filesystems.
+pub fn move_path(source: &Path, destination: &Path, progress: &TransferProgress) -> Result<()> {
+    if destination.starts_with(source) {
+        return Err(anyhow!("cannot move a folder into itself"));
+    }
+
+    if fs::rename(source, destination).is_ok() {
+        progress.advance(total_size(destination));
+        return Ok(());
+    }
+
+    copy_path(source, destination, progress)?;
+    if fs::symlink_metadata(source)?.is_dir() {
+        fs::remove_dir_all(source)?;
+    } else {
+        fs::remove_file(source)?;
+    }
+    Ok(())
+}
+
+fn copy_file(source: &Path, destination: &Path, progress: &TransferProgress) -> Result<()> {
+    let mut reader = File::open(source)?;
+    write_or_remove(destination, |writer| {
+        copy_data(&mut reader, writer, progress)?;
+        writer.set_permissions(reader.metadata()?.permissions())?;
+        Ok(())
+    })
+}
+
+/// Copies everything `reader` has to `writer`, counting it in `progress`
+/// and stopping once cancelled.
+pub(crate) fn copy_data(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    progress: &TransferProgress,
+) -> Result<()> {
+    let mut buffer = vec![0; BUFFER_SIZE];
+    loop {
+        progress.check_cancelled()?;
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(());
+        }
+        writer.write_all(&buffer[..read])?;
+        progress.advance(read as u64);
+    }
+}
+
+/// Creates the file `path`, which must not exist yet, and lets `write` fill
+/// it in, removing it again if that fails.
+pub(crate) fn write_or_remove(
+    path: &Path,
+    write: impl FnOnce(&mut File) -> Result<()>,
+) -> Result<()> {
+    let mut file = File::options().write(true).create_new(true).open(path)?;
+    remove_on_error(write(&mut file), || {
+        fs::remove_file(path).ok();
+    })
+}
+
+/// Passes on the result of writing a new file, calling `remove` first if it
+/// failed or was cancelled: half a file left behind would pass for the
+/// whole of it.
+pub(crate) fn remove_on_error<T>(result: Result<T>, remove: impl FnOnce()) -> Result<T> {
+    if result.is_err() {
+        remove();
+    }
+    result
+}
+
+#[cfg(unix)]
+fn copy_symlink(source: &Path, destination: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(source)?, destination)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn copy_symlink(source: &Path, destination: &Path) -> Result<()> {
+    let target = fs::read_link(source)?;
+    if fs::metadata(source).is_ok_and(|metadata| metadata.is_dir()) {
+        std::os::windows::fs::symlink_dir(target, destination)?;
+    } else {
+        std::os::windows::fs::symlink_file(target, destination)?;
+    }
+    Ok(())
+}
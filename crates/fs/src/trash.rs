@@ -0,0 +1,151 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+/// Moves `path` to the trash, returning where it ended up.
+///
+/// Follows the freedesktop.org trash specification: entries go to the home
+/// trash when they are on the same filesystem, and otherwise to a
+/// `.Trash-$uid` folder at the top of their own filesystem, so trashing never
+/// copies anything.
+#[cfg(unix)]
+pub fn trash(path: &Path) -> Result<PathBuf> {
+    use std::{fs, io::Write, os::unix::fs::MetadataExt};
+
+    use anyhow::anyhow;
+
+    let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return Err(anyhow!("{} can't be trashed", path.display()));
+    };
+    // Resolve the folder but not the entry itself, which may be a symlink.
+    let path = parent.canonicalize()?.join(file_name);
+    let device = fs::symlink_metadata(&path)?.dev();
+
+    let home_trash = dirs::data_local_dir()
+        .ok_or_else(|| anyhow!("no home folder to put the trash in"))?
+        .join("Trash");
+    // The home trash may not exist yet, so check the nearest folder that does.
+    let on_home_device = home_trash
+        .ancestors()
+        .find_map(|folder| fs::metadata(folder).ok())
+        .is_some_and(|folder| folder.dev() == device);
+    // The home trash records full paths; the others paths from the top of
+    // their filesystem, so they stay right wherever it is mounted.
+    let (trash, recorded_path) = if on_home_device {
+        (home_trash, path.clone())
+    } else {
+        let top = mount_top(&path, device);
+        let recorded_path = path.strip_prefix(&top).unwrap_or(&path).to_path_buf();
+        (
+            top.join(format!(".Trash-{}", unsafe { libc::getuid() })),
+            recorded_path,
+        )
+    };
+
+    let files = trash.join("files");
+    let (name, mut info, info_path) = claim_name(&trash, &files, Path::new(file_name))?;
+    let destination = files.join(name);
+
+    let deleted = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S");
+    let result = (|| {
+        write!(
+            info,
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            escape_path(&recorded_path),
+            deleted
+        )?;
+        fs::rename(&path, &destination)?;
+        anyhow::Ok(())
+    })();
+    if let Err(e) = result {
+        fs::remove_file(&info_path).ok();
+        return Err(e);
+    }
+    Ok(destination)
+}
+
+#[cfg(not(unix))]
+pub fn trash(_path: &Path) -> Result<PathBuf> {
+    Err(anyhow::anyhow!("the trash isn't supported on this platform"))
+}
+
+/// Picks a name for `file_name` in the trash, adding ".2", ".3" and so on
+/// before the extension when needed. The info file is created first, as the
+/// specification asks, so two trashings can't pick the same name.
+#[cfg(unix)]
+fn claim_name(
+    trash: &Path,
+    files: &Path,
+    file_name: &Path,
+) -> Result<(std::ffi::OsString, std::fs::File, PathBuf)> {
+    use std::{
+        fs::{DirBuilder, File},
+        io::ErrorKind,
+        os::unix::fs::DirBuilderExt,
+    };
+
+    let info = trash.join("info");
+    for folder in [trash, files, &info] {
+        DirBuilder::new().recursive(true).mode(0o700).create(folder)?;
+    }
+
+    for n in 1.. {
+        let mut name = file_name.as_os_str().to_os_string();
+        if n > 1 {
+            name = file_name.file_stem().unwrap_or_default().to_os_string();
+            name.push(format!(".{}", n));
+            if let Some(extension) = file_name.extension() {
+                name.push(".");
+                name.push(extension);
+            }
+        }
+        if files.join(&name).symlink_metadata().is_ok() {
+            continue;
+        }
+
+        let mut info_name = name.clone();
+        info_name.push(".trashinfo");
+        let info_path = info.join(info_name);
+        match File::options()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(file) => return Ok((name, file, info_path)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    unreachable!()
+}
+
+/// The top folder of the filesystem `path` is on.
+#[cfg(unix)]
+fn mount_top(path: &Path, device: u64) -> PathBuf {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut top = path;
+    while let Some(parent) = top.parent() {
+        match std::fs::metadata(parent) {
+            Ok(metadata) if metadata.dev() == device => top = parent,
+            _ => break,
+        }
+    }
+    top.to_path_buf()
+}
+
+/// Percent-encodes `path` the way `.trashinfo` files store it.
+#[cfg(unix)]
+fn escape_path(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut escaped = String::new();
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    escaped
+}
@@ -0,0 +1,167 @@
+use std::{
+    collections::HashSet,
+    fs::{self, DirEntry, Metadata},
+    io,
+    path::Path,
+    sync::Mutex,
+};
+
+use crate::FolderSizeOptions;
+
+/// Picks the entries a walk below one folder counts. Symlinks are not
+/// followed, folders on other filesystems are left out unless
+/// [`FolderSizeOptions::cross_mounts`] is set, and files with more than one
+/// name are counted once.
+pub(crate) struct Walker {
+    root_device: Option<u64>,
+    cross_mounts: bool,
+    linked: Mutex<HashSet<(u64, u64)>>,
+}
+
+impl Walker {
+    pub(crate) fn new(root: &Path, options: &FolderSizeOptions) -> io::Result<Self> {
+        let metadata = fs::symlink_metadata(root)?;
+        Ok(Self::on_device(device(&metadata), options))
+    }
+
+    fn on_device(root_device: Option<u64>, options: &FolderSizeOptions) -> Self {
+        Self {
+            root_device,
+            cross_mounts: options.cross_mounts,
+            linked: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// The entries of `folder` to count, each with its own metadata rather
+    /// than its target's. Folders that can't be read have none.
+    pub(crate) fn entries<'a>(
+        &'a self,
+        folder: &Path,
+    ) -> impl Iterator<Item = (DirEntry, Metadata)> + 'a {
+        fs::read_dir(folder)
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                // Doesn't follow symlinks.
+                let metadata = entry.metadata().ok()?;
+                self.counts(&metadata).then_some((entry, metadata))
+            })
+    }
+
+    fn counts(&self, metadata: &Metadata) -> bool {
+        if metadata.is_dir() {
+            self.cross_mounts || device(metadata) == self.root_device
+        } else {
+            hard_link_id(metadata).is_none_or(|id| self.linked.lock().unwrap().insert(id))
+        }
+    }
+}
+
+#[cfg(unix)]
+fn device(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+/// Identifies files with more than one name, so they are counted once.
+#[cfg(unix)]
+fn hard_link_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn hard_link_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(unix)]
+pub(crate) fn blocks_size(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    // `blocks` always counts 512-byte units, whatever the block size.
+    Some(metadata.blocks() * 512)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn blocks_size(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(all(test, unix))]
+pub(crate) mod tests {
+    use std::{os::unix::fs::symlink, path::PathBuf};
+
+    use super::*;
+
+    /// A walker that takes every folder for a mount of another filesystem.
+    pub(crate) fn walker_on_another_device(options: &FolderSizeOptions) -> Walker {
+        Walker::on_device(Some(u64::MAX), options)
+    }
+
+    fn names(walker: &Walker, folder: &Path) -> Vec<String> {
+        let mut names = walker
+            .entries(folder)
+            .map(|(entry, _)| entry.file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    fn folder_with_everything() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("file"), "123").unwrap();
+        fs::hard_link(root.join("file"), root.join("sub/link")).unwrap();
+        fs::create_dir(dir.path().join("outside")).unwrap();
+        symlink(dir.path().join("outside"), root.join("symlink")).unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn doesnt_follow_symlinks() {
+        let (_dir, root) = folder_with_everything();
+        let walker = Walker::new(&root, &FolderSizeOptions::default()).unwrap();
+        let (_, metadata) = walker
+            .entries(&root)
+            .find(|(entry, _)| entry.file_name() == "symlink")
+            .unwrap();
+        assert!(metadata.is_symlink());
+    }
+
+    #[test]
+    fn counts_hard_links_once() {
+        let (_dir, root) = folder_with_everything();
+        let walker = Walker::new(&root, &FolderSizeOptions::default()).unwrap();
+        assert_eq!(names(&walker, &root), ["file", "sub", "symlink"]);
+        assert_eq!(names(&walker, &root.join("sub")), Vec::<String>::new());
+
+        // A fresh walk counts it again.
+        let walker = Walker::new(&root, &FolderSizeOptions::default()).unwrap();
+        assert_eq!(names(&walker, &root.join("sub")), ["link"]);
+    }
+
+    #[test]
+    fn stops_at_other_filesystems_unless_asked() {
+        let (_dir, root) = folder_with_everything();
+        let walker = walker_on_another_device(&FolderSizeOptions::default());
+        assert_eq!(names(&walker, &root), ["file", "symlink"]);
+
+        let walker = walker_on_another_device(&FolderSizeOptions { cross_mounts: true });
+        assert_eq!(names(&walker, &root), ["file", "sub", "symlink"]);
+    }
+
+    #[test]
+    fn has_nothing_for_unreadable_folders() {
+        let (dir, _root) = folder_with_everything();
+        let walker = Walker::new(dir.path(), &FolderSizeOptions::default()).unwrap();
+        assert_eq!(walker.entries(&dir.path().join("missing")).count(), 0);
+        assert!(Walker::new(&dir.path().join("missing"), &FolderSizeOptions::default()).is_err());
+    }
+}
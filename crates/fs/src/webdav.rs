@@ -0,0 +1,590 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{sync_channel, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Result};
+use percent_encoding::percent_decode_str;
+use roxmltree::Node;
+use ureq::{Agent, AgentBuilder, Request, Response};
+use url::Url;
+
+use crate::{EntryKind, FileStat, FileSystemProvider, FileWriter, RemoteLocation};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+/// How many chunks an upload can fall behind the writer before writing
+/// blocks.
+const UPLOAD_QUEUE: usize = 4;
+
+const DAV: &str = "DAV:";
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<propfind xmlns="DAV:"><prop><resourcetype/><getcontentlength/><getlastmodified/></prop></propfind>"#;
+
+/// `dav://` and `davs://` locations: WebDAV over HTTP and HTTPS. Folders are
+/// listed with `PROPFIND`, files travel with `GET` and `PUT`, and user names
+/// and passwords in locations are sent with basic auth.
+pub struct WebDavProvider {
+    agent: Agent,
+    /// Passwords given once per server, so locations shown and saved can
+    /// leave them out.
+    passwords: Mutex<HashMap<String, String>>,
+    /// What listings said about the entries in them, so showing a folder
+    /// doesn't ask the server about each entry again.
+    stats: Mutex<HashMap<PathBuf, FileStat>>,
+}
+
+impl Default for WebDavProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebDavProvider {
+    pub fn new() -> Self {
+        Self {
+            // Redirects are handled here, since the ones servers send for
+            // folders without a trailing slash would otherwise turn
+            // `PROPFIND` into `GET`.
+            agent: AgentBuilder::new()
+                .timeout_connect(CONNECT_TIMEOUT)
+                .timeout_read(READ_TIMEOUT)
+                .redirects(0)
+                .build(),
+            passwords: Default::default(),
+            stats: Default::default(),
+        }
+    }
+
+    /// Whether `location` is a WebDAV one naming a user but no password, and
+    /// no password was given for its server since the start.
+    pub fn needs_password(&self, location: &Path) -> bool {
+        RemoteLocation::parse(location).is_ok_and(|remote| {
+            matches!(remote.scheme.as_str(), "dav" | "davs")
+                && remote.user.is_some()
+                && remote.password.is_none()
+                && !self
+                    .passwords
+                    .lock()
+                    .unwrap()
+                    .contains_key(&remote.server())
+        })
+    }
+
+    /// The `http(s)://` URL of `location`, with the password remembered for
+    /// its server if it has none.
+    fn url(&self, location: &Path) -> Result<Url> {
+        let remote = RemoteLocation::parse(location)?;
+        let scheme = match remote.scheme.as_str() {
+            "dav" => "http",
+            "davs" => "https",
+            scheme => return Err(anyhow!("{}:// locations aren't WebDAV", scheme)),
+        };
+        let host = if remote.host.contains(':') {
+            format!("[{}]", remote.host)
+        } else {
+            remote.host.clone()
+        };
+
+        let invalid = || anyhow!("{} is not a valid location", location.display());
+        let mut url = Url::parse(&format!("{}://{}/", scheme, host)).map_err(|_| invalid())?;
+        url.set_port(remote.port).map_err(|_| invalid())?;
+        url.set_path(&remote.path);
+        if let Some(user) = &remote.user {
+            let mut passwords = self.passwords.lock().unwrap();
+            let password = match &remote.password {
+                Some(password) => {
+                    passwords.insert(remote.server(), password.clone());
+                    Some(password)
+                }
+                None => passwords.get(&remote.server()),
+            };
+            url.set_username(user).map_err(|_| invalid())?;
+            url.set_password(password.map(String::as_str))
+                .map_err(|_| invalid())?;
+        }
+        Ok(url)
+    }
+
+    /// Folders are addressed with a trailing slash, which some servers
+    /// insist on.
+    fn folder_url(&self, location: &Path) -> Result<Url> {
+        let mut url = self.url(location)?;
+        if !url.path().ends_with('/') {
+            url.set_path(&format!("{}/", url.path()));
+        }
+        Ok(url)
+    }
+
+    fn request(&self, method: &str, url: &Url) -> Request {
+        self.agent.request_url(method, url)
+    }
+
+    /// Asks about `url` and, with `Depth: 1`, what's in it. A redirect to
+    /// the same path with a trailing slash is followed once.
+    fn propfind(&self, url: Url, depth: u8) -> Result<Vec<(String, FileStat)>> {
+        let propfind = |url: &Url| {
+            send(
+                self.request("PROPFIND", url)
+                    .set("Depth", &depth.to_string())
+                    .set("Content-Type", "application/xml; charset=utf-8"),
+                Some(PROPFIND_BODY.as_bytes()),
+            )
+        };
+        let mut response = propfind(&url)?;
+        if (300..400).contains(&response.status()) && !url.path().ends_with('/') {
+            let mut folder_url = url.clone();
+            folder_url.set_path(&format!("{}/", url.path()));
+            response = propfind(&folder_url)?;
+        }
+        if response.status() != 207 {
+            return Err(status_error(&response));
+        }
+        parse_multistatus(&response.into_string()?)
+    }
+
+    fn forget_below(&self, location: &Path) {
+        self.stats
+            .lock()
+            .unwrap()
+            .retain(|path, _| !path.starts_with(location));
+    }
+}
+
+impl FileSystemProvider for WebDavProvider {
+    fn list(&self, folder: &Path) -> Result<Vec<PathBuf>> {
+        let url = self.folder_url(folder)?;
+        let folder_path = decode_path(url.path());
+        let entries = self.propfind(url, 1)?;
+
+        let mut stats = self.stats.lock().unwrap();
+        Ok(entries
+            .into_iter()
+            .filter_map(|(href, stat)| {
+                // The folder describes itself too.
+                if decode_path(&href).trim_end_matches('/') == folder_path.trim_end_matches('/') {
+                    return None;
+                }
+                let path = folder.join(entry_name(&href)?);
+                stats.insert(path.clone(), stat);
+                Some(path)
+            })
+            .collect())
+    }
+
+    fn stat(&self, path: &Path) -> Result<FileStat> {
+        if let Some(stat) = self.stats.lock().unwrap().get(path) {
+            return Ok(stat.clone());
+        }
+        let (_, stat) = self
+            .propfind(self.url(path)?, 0)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("the server said nothing about {}", path.display()))?;
+        self.stats
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), stat.clone());
+        Ok(stat)
+    }
+
+    fn read(&self, path: &Path) -> Result<Box<dyn Read + Send>> {
+        let response = send(self.request("GET", &self.url(path)?), None)?;
+        if response.status() != 200 {
+            return Err(status_error(&response));
+        }
+        Ok(Box::new(response.into_reader()))
+    }
+
+    /// The upload streams from a background thread. Closing the writer
+    /// finishes it and reports whether the server took the file; dropping it
+    /// unclosed breaks off the upload.
+    fn write(&self, path: &Path) -> Result<Box<dyn FileWriter>> {
+        self.forget_below(path);
+        let request = self.request("PUT", &self.url(path)?);
+        let (sender, receiver) = sync_channel(UPLOAD_QUEUE);
+        let aborted = Arc::new(AtomicBool::new(false));
+        let upload = thread::spawn({
+            let aborted = aborted.clone();
+            move || {
+                let response = match request.send(ChannelReader {
+                    receiver,
+                    aborted,
+                    chunk: Vec::new(),
+                    position: 0,
+                }) {
+                    Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+                    Err(error) => return Err(anyhow!("{}", error)),
+                };
+                match response.status() {
+                    200..=299 => Ok(()),
+                    _ => Err(status_error(&response)),
+                }
+            }
+        });
+        Ok(Box::new(DavWriter {
+            sender: Some(sender),
+            upload: Some(upload),
+            aborted,
+        }))
+    }
+
+    fn create_folder(&self, path: &Path) -> Result<()> {
+        let response = send(self.request("MKCOL", &self.folder_url(path)?), None)?;
+        match response.status() {
+            201 => Ok(()),
+            405 => Err(anyhow!("{} already exists", path.display())),
+            _ => Err(status_error(&response)),
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let is_folder = self.stat(from)?.is_dir();
+        let (from_url, mut to_url) = if is_folder {
+            (self.folder_url(from)?, self.folder_url(to)?)
+        } else {
+            (self.url(from)?, self.url(to)?)
+        };
+        if from_url.origin() != to_url.origin() {
+            return Err(anyhow!("can't rename across servers"));
+        }
+        // The destination goes in a header, where credentials don't belong.
+        to_url.set_username("").ok();
+        to_url.set_password(None).ok();
+
+        self.forget_below(from);
+        self.forget_below(to);
+        let response = send(
+            self.request("MOVE", &from_url)
+                .set("Destination", to_url.as_str())
+                .set("Overwrite", "F"),
+            None,
+        )?;
+        match response.status() {
+            201 | 204 => Ok(()),
+            412 => Err(anyhow!("{} already exists", to.display())),
+            _ => Err(status_error(&response)),
+        }
+    }
+
+    /// Deleting a folder deletes everything in it, on the server's side.
+    fn delete(&self, path: &Path) -> Result<()> {
+        let url = if self.stat(path)?.is_dir() {
+            self.folder_url(path)?
+        } else {
+            self.url(path)?
+        };
+        self.forget_below(path);
+        let response = send(self.request("DELETE", &url), None)?;
+        match response.status() {
+            200..=299 => Ok(()),
+            _ => Err(status_error(&response)),
+        }
+    }
+}
+
+/// Sends `request`, turning error statuses into responses so callers can
+/// tell them apart.
+fn send(request: Request, body: Option<&[u8]>) -> Result<Response> {
+    let result = match body {
+        Some(body) => request.send_bytes(body),
+        None => request.call(),
+    };
+    match result {
+        Ok(response) | Err(ureq::Error::Status(_, response)) => Ok(response),
+        Err(error) => Err(anyhow!("{}", error)),
+    }
+}
+
+fn status_error(response: &Response) -> anyhow::Error {
+    match response.status() {
+        401 => anyhow!("the server refused the user name or password"),
+        403 => anyhow!("the server doesn't allow that"),
+        404 => anyhow!("not found on the server"),
+        status => anyhow!("the server answered {} {}", status, response.status_text()),
+    }
+}
+
+fn decode_path(path: &str) -> String {
+    percent_decode_str(path).decode_utf8_lossy().into_owned()
+}
+
+/// The decoded last segment of `href`. Names that would lead somewhere else
+/// once joined to the folder, such as `..` or ones with an encoded `/`, are
+/// refused.
+fn entry_name(href: &str) -> Option<String> {
+    let name = decode_path(href.trim_end_matches('/').rsplit('/').next()?);
+    let valid = !matches!(name.as_str(), "" | "." | "..") && !name.contains(['/', '\0']);
+    valid.then_some(name)
+}
+
+/// The path and properties of each resource in a `207 Multi-Status` answer.
+/// `href`s may be full URLs or just paths, and stay percent-encoded.
+fn parse_multistatus(xml: &str) -> Result<Vec<(String, FileStat)>> {
+    let document = roxmltree::Document::parse(xml)?;
+
+    let mut entries = Vec::new();
+    for response in document
+        .descendants()
+        .filter(|node| node.has_tag_name((DAV, "response")))
+    {
+        let Some(href) = child(response, "href").and_then(|href| href.text()) else {
+            continue;
+        };
+        let href = href.trim();
+        let href = match Url::parse(href) {
+            Ok(url) => url.path().to_string(),
+            Err(_) => href.to_string(),
+        };
+
+        // Only properties the server found count.
+        let Some(prop) = response
+            .children()
+            .filter(|node| node.has_tag_name((DAV, "propstat")))
+            .find(|propstat| {
+                child(*propstat, "status")
+                    .and_then(|status| status.text())
+                    .is_some_and(|status| status.contains(" 200 "))
+            })
+            .and_then(|propstat| child(propstat, "prop"))
+        else {
+            continue;
+        };
+
+        let is_folder = child(prop, "resourcetype")
+            .is_some_and(|resource_type| child(resource_type, "collection").is_some());
+        let size = child(prop, "getcontentlength")
+            .and_then(|length| length.text())
+            .and_then(|length| length.trim().parse().ok())
+            .unwrap_or(0);
+        let modified = child(prop, "getlastmodified")
+            .and_then(|modified| modified.text())
+            .and_then(|modified| chrono::DateTime::parse_from_rfc2822(modified.trim()).ok())
+            .map(SystemTime::from);
+
+        entries.push((
+            href,
+            FileStat {
+                kind: if is_folder {
+                    EntryKind::Folder
+                } else {
+                    EntryKind::File
+                },
+                size: if is_folder { 0 } else { size },
+                modified,
+            },
+        ));
+    }
+    Ok(entries)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|child| child.has_tag_name((DAV, name)))
+}
+
+/// Hands what's written to the thread running the `PUT` request.
+struct DavWriter {
+    sender: Option<SyncSender<Vec<u8>>>,
+    upload: Option<JoinHandle<Result<()>>>,
+    /// Tells the request body to fail rather than end, so the server
+    /// doesn't keep a file cut short.
+    aborted: Arc<AtomicBool>,
+}
+
+impl DavWriter {
+    fn finish(&mut self) -> io::Result<()> {
+        // Closing the channel ends the request body.
+        self.sender = None;
+        match self.upload.take().map(JoinHandle::join) {
+            Some(Ok(Ok(()))) | None => Ok(()),
+            Some(Ok(Err(error))) => Err(io::Error::other(error.to_string())),
+            Some(Err(_)) => Err(io::Error::other("the upload failed")),
+        }
+    }
+}
+
+impl FileWriter for DavWriter {
+    fn close(mut self: Box<Self>) -> Result<()> {
+        Ok(self.finish()?)
+    }
+}
+
+impl Write for DavWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let sent = self
+            .sender
+            .as_ref()
+            .is_some_and(|sender| sender.send(buf.to_vec()).is_ok());
+        if sent {
+            return Ok(buf.len());
+        }
+        // The request ended early; its error says why.
+        self.finish()?;
+        Err(io::Error::new(
+            io::ErrorKind::BrokenPipe,
+            "the upload already ended",
+        ))
+    }
+
+    /// Chunks are sent as they are written, so there is nothing to flush;
+    /// only closing the writer ends the upload.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for DavWriter {
+    fn drop(&mut self) {
+        if self.upload.is_some() {
+            self.aborted.store(true, Ordering::Relaxed);
+            // The request fails once it reads the closed channel; there is
+            // no need to wait for that.
+            self.sender = None;
+            self.upload = None;
+        }
+    }
+}
+
+struct ChannelReader {
+    receiver: Receiver<Vec<u8>>,
+    aborted: Arc<AtomicBool>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            let chunk = self.receiver.recv();
+            if self.aborted.load(Ordering::Relaxed) {
+                return Err(io::Error::other("the upload was abandoned"));
+            }
+            match chunk {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                // The writer is done.
+                Err(_) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len() - self.position);
+        buf[..len].copy_from_slice(&self.chunk[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MULTISTATUS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="DAV:">
+  <d:response>
+    <d:href>/docs/</d:href>
+    <d:propstat>
+      <d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>http://example.com/docs/sub%20dir/</d:href>
+    <d:propstat>
+      <d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+  <d:response>
+    <d:href> /docs/a%23b.txt </d:href>
+    <d:propstat>
+      <d:prop><d:getcontentlength>42</d:getcontentlength></d:prop>
+      <d:status>HTTP/1.1 404 Not Found</d:status>
+    </d:propstat>
+    <d:propstat>
+      <d:prop>
+        <d:resourcetype/>
+        <d:getcontentlength> 12 </d:getcontentlength>
+        <d:getlastmodified>Tue, 01 Oct 2024 10:00:00 GMT</d:getlastmodified>
+      </d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/docs/unknown</d:href>
+    <d:propstat>
+      <d:prop><d:resourcetype/></d:prop>
+      <d:status>HTTP/1.1 403 Forbidden</d:status>
+    </d:propstat>
+  </d:response>
+</d:multistatus>"#;
+
+    #[test]
+    fn parses_multistatus() {
+        let entries = parse_multistatus(MULTISTATUS).unwrap();
+        let hrefs = entries
+            .iter()
+            .map(|(href, _)| href.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(hrefs, ["/docs/", "/docs/sub%20dir/", "/docs/a%23b.txt"]);
+
+        assert!(entries[1].1.is_dir());
+        assert_eq!(entries[1].1.size, 0);
+        let file = &entries[2].1;
+        assert_eq!(file.kind, EntryKind::File);
+        assert_eq!(file.size, 12);
+        assert_eq!(
+            file.modified,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_727_776_800))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_multistatus() {
+        assert!(parse_multistatus("<d:multistatus xmlns:d=\"DAV:\">").is_err());
+        assert!(parse_multistatus("<multistatus xmlns=\"other\"/>")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn decodes_hrefs() {
+        assert_eq!(
+            decode_path("/docs/sub%20dir/a%23b.txt"),
+            "/docs/sub dir/a#b.txt"
+        );
+        assert_eq!(decode_path("/caf%C3%A9"), "/café");
+        assert_eq!(decode_path("/bad%FF"), "/bad\u{fffd}");
+    }
+
+    #[test]
+    fn names_entries_after_the_last_segment() {
+        assert_eq!(entry_name("/docs/sub%20dir/").as_deref(), Some("sub dir"));
+        assert_eq!(entry_name("/docs/a%23b.txt").as_deref(), Some("a#b.txt"));
+    }
+
+    #[test]
+    fn refuses_names_that_leave_the_folder() {
+        for href in [
+            "/docs/..",
+            "/docs/%2E%2E",
+            "/docs/.",
+            "/docs/..%2F..%2Fetc",
+            "/docs/a%2Fb",
+            "/docs/a%00b",
+            "/",
+            "",
+        ] {
+            assert_eq!(entry_name(href), None, "{}", href);
+        }
+    }
+}
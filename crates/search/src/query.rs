@@ -0,0 +1,673 @@
+use std::{fmt, time::Duration};
+
+use chrono::NaiveDate;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Folder,
+    Image,
+    Audio,
+    Video,
+    Document,
+    Archive,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 6] = [
+        Kind::Folder,
+        Kind::Image,
+        Kind::Audio,
+        Kind::Video,
+        Kind::Document,
+        Kind::Archive,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Folder => "folder",
+            Kind::Image => "image",
+            Kind::Audio => "audio",
+            Kind::Video => "video",
+            Kind::Document => "document",
+            Kind::Archive => "archive",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Kind::Folder => "Folders",
+            Kind::Image => "Images",
+            Kind::Audio => "Audio",
+            Kind::Video => "Videos",
+            Kind::Document => "Documents",
+            Kind::Archive => "Archives",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        let name = name.strip_suffix('s').unwrap_or(&name);
+        Self::ALL.into_iter().find(|kind| {
+            kind.name() == name || (*kind == Kind::Folder && (name == "dir" || name == "directory"))
+        })
+    }
+
+    /// Maps a type from `file_icons`' `file_types.json` onto a kind.
+    pub fn from_icon_type(typ: &str) -> Option<Self> {
+        match typ {
+            "image" => Some(Kind::Image),
+            "audio" => Some(Kind::Audio),
+            "video" => Some(Kind::Video),
+            "document" | "pdf" | "text" | "spreadsheet" | "presentation" => Some(Kind::Document),
+            "archive" => Some(Kind::Archive),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SizeFilter {
+    LessThan(u64),
+    GreaterThan(u64),
+    Equal(u64),
+    Between(u64, u64),
+}
+
+impl SizeFilter {
+    pub fn matches(&self, size: u64) -> bool {
+        match *self {
+            SizeFilter::LessThan(limit) => size < limit,
+            SizeFilter::GreaterThan(limit) => size > limit,
+            SizeFilter::Equal(limit) => size == limit,
+            SizeFilter::Between(low, high) => (low..=high).contains(&size),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AgeUnit {
+    Hours,
+    Days,
+    Weeks,
+    Months,
+    Years,
+}
+
+/// A relative age like `2w`, measured back from the time of the search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Age {
+    pub amount: u32,
+    pub unit: AgeUnit,
+}
+
+impl Age {
+    pub fn duration(&self) -> Duration {
+        let hours = match self.unit {
+            AgeUnit::Hours => 1,
+            AgeUnit::Days => 24,
+            AgeUnit::Weeks => 24 * 7,
+            AgeUnit::Months => 24 * 30,
+            AgeUnit::Years => 24 * 365,
+        };
+        Duration::from_secs(self.amount as u64 * hours * 60 * 60)
+    }
+
+    fn parse(input: &str) -> Option<Self> {
+        let split = input.find(|c: char| !c.is_ascii_digit())?;
+        let (amount, unit) = input.split_at(split);
+        let unit = match unit {
+            "h" => AgeUnit::Hours,
+            "d" => AgeUnit::Days,
+            "w" => AgeUnit::Weeks,
+            "m" | "mo" => AgeUnit::Months,
+            "y" => AgeUnit::Years,
+            _ => return None,
+        };
+        Some(Self {
+            amount: amount.parse().ok()?,
+            unit,
+        })
+    }
+}
+
+impl fmt::Display for Age {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self.unit {
+            AgeUnit::Hours => "h",
+            AgeUnit::Days => "d",
+            AgeUnit::Weeks => "w",
+            AgeUnit::Months => "m",
+            AgeUnit::Years => "y",
+        };
+        write!(f, "{}{}", self.amount, unit)
+    }
+}
+
+/// Relative filters (`<2w`) compare against the age of the file, absolute ones
+/// (`>2024-01-01`) against its calendar date.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DateFilter {
+    NewerThan(Age),
+    OlderThan(Age),
+    After(NaiveDate),
+    Before(NaiveDate),
+    On(NaiveDate),
+}
+
+impl DateFilter {
+    fn parse(input: &str) -> Option<Self> {
+        let (comparison, value) = split_comparison(input);
+        if let Some(age) = Age::parse(value) {
+            return match comparison {
+                Comparison::Less => Some(DateFilter::NewerThan(age)),
+                Comparison::Greater => Some(DateFilter::OlderThan(age)),
+                Comparison::Equal => None,
+            };
+        }
+
+        let date = NaiveDate::parse_from_str(value, DATE_FORMAT).ok()?;
+        Some(match comparison {
+            Comparison::Less => DateFilter::Before(date),
+            Comparison::Greater => DateFilter::After(date),
+            Comparison::Equal => DateFilter::On(date),
+        })
+    }
+
+    fn label(&self) -> String {
+        match self {
+            DateFilter::NewerThan(age) => format!("in the last {}", age),
+            DateFilter::OlderThan(age) => format!("more than {} ago", age),
+            DateFilter::After(date) => format!("after {}", date.format(DATE_FORMAT)),
+            DateFilter::Before(date) => format!("before {}", date.format(DATE_FORMAT)),
+            DateFilter::On(date) => format!("on {}", date.format(DATE_FORMAT)),
+        }
+    }
+}
+
+impl fmt::Display for DateFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DateFilter::NewerThan(age) => write!(f, "<{}", age),
+            DateFilter::OlderThan(age) => write!(f, ">{}", age),
+            DateFilter::After(date) => write!(f, ">{}", date.format(DATE_FORMAT)),
+            DateFilter::Before(date) => write!(f, "<{}", date.format(DATE_FORMAT)),
+            DateFilter::On(date) => write!(f, "{}", date.format(DATE_FORMAT)),
+        }
+    }
+}
+
+/// Permission bits, matched against the file's mode on unix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PermissionFilter {
+    /// `perm:755`, the permission bits must be exactly these.
+    Exact(u32),
+    /// `perm:+x`, the owner must have all of these bits.
+    Has(u32),
+}
+
+impl PermissionFilter {
+    pub fn matches(&self, mode: u32) -> bool {
+        match *self {
+            PermissionFilter::Exact(bits) => mode & 0o7777 == bits,
+            PermissionFilter::Has(bits) => mode & bits == bits,
+        }
+    }
+
+    fn parse(input: &str) -> Option<Self> {
+        if let Some(letters) = input.strip_prefix('+') {
+            let mut bits = 0;
+            for letter in letters.chars() {
+                bits |= match letter {
+                    'r' => 0o400,
+                    'w' => 0o200,
+                    'x' => 0o100,
+                    _ => return None,
+                };
+            }
+            return (bits != 0).then_some(PermissionFilter::Has(bits));
+        }
+
+        u32::from_str_radix(input, 8)
+            .ok()
+            .filter(|bits| *bits <= 0o7777)
+            .map(PermissionFilter::Exact)
+    }
+}
+
+impl fmt::Display for PermissionFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            PermissionFilter::Exact(bits) => write!(f, "{:o}", bits),
+            PermissionFilter::Has(bits) => {
+                write!(f, "+")?;
+                for (bit, letter) in [(0o400, 'r'), (0o200, 'w'), (0o100, 'x')] {
+                    if bits & bit != 0 {
+                        write!(f, "{}", letter)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    Size(SizeFilter),
+    Modified(DateFilter),
+    Created(DateFilter),
+    Kind(Kind),
+    Owner(String),
+    Permissions(PermissionFilter),
+}
+
+impl Filter {
+    fn parse(key: &str, value: &str) -> Option<Self> {
+        match key {
+            "size" => parse_size_filter(value).map(Filter::Size),
+            "modified" | "mtime" => DateFilter::parse(value).map(Filter::Modified),
+            "created" | "ctime" => DateFilter::parse(value).map(Filter::Created),
+            "kind" | "type" => Kind::from_name(value).map(Filter::Kind),
+            "owner" | "user" => (!value.is_empty()).then(|| Filter::Owner(value.to_string())),
+            "perm" | "mode" => PermissionFilter::parse(value).map(Filter::Permissions),
+            _ => None,
+        }
+    }
+
+    fn is_filter_key(key: &str) -> bool {
+        matches!(
+            key,
+            "size"
+                | "modified"
+                | "mtime"
+                | "created"
+                | "ctime"
+                | "kind"
+                | "type"
+                | "owner"
+                | "user"
+                | "perm"
+                | "mode"
+        )
+    }
+
+    /// Human readable description, used for the filter chips.
+    pub fn label(&self) -> String {
+        match self {
+            Filter::Size(SizeFilter::LessThan(size)) => {
+                format!("Smaller than {}", format_size(*size))
+            }
+            Filter::Size(SizeFilter::GreaterThan(size)) => {
+                format!("Larger than {}", format_size(*size))
+            }
+            Filter::Size(SizeFilter::Equal(size)) => format!("Exactly {}", format_size(*size)),
+            Filter::Size(SizeFilter::Between(low, high)) => {
+                format!("{} to {}", format_size(*low), format_size(*high))
+            }
+            Filter::Modified(date) => format!("Modified {}", date.label()),
+            Filter::Created(date) => format!("Created {}", date.label()),
+            Filter::Kind(kind) => kind.label().to_string(),
+            Filter::Owner(owner) => format!("Owned by {}", owner),
+            Filter::Permissions(permissions) => format!("Permissions {}", permissions),
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::Size(SizeFilter::LessThan(size)) => write!(f, "size:<{}", size_token(*size)),
+            Filter::Size(SizeFilter::GreaterThan(size)) => {
+                write!(f, "size:>{}", size_token(*size))
+            }
+            Filter::Size(SizeFilter::Equal(size)) => write!(f, "size:{}", size_token(*size)),
+            Filter::Size(SizeFilter::Between(low, high)) => {
+                write!(f, "size:{}..{}", size_token(*low), size_token(*high))
+            }
+            Filter::Modified(date) => write!(f, "modified:{}", date),
+            Filter::Created(date) => write!(f, "created:{}", date),
+            Filter::Kind(kind) => write!(f, "kind:{}", kind.name()),
+            Filter::Owner(owner) => write!(f, "owner:{}", owner),
+            Filter::Permissions(permissions) => write!(f, "perm:{}", permissions),
+        }
+    }
+}
+
+/// A name query combined with filters, all of which must match.
+///
+/// Parsed from input like `report size:>100M modified:<2w kind:document`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchQuery {
+    pub name: String,
+    pub filters: Vec<Filter>,
+    /// Tokens that look like filters but could not be parsed, usually because
+    /// they are still being typed.
+    pub invalid: Vec<String>,
+}
+
+impl SearchQuery {
+    pub fn parse(input: &str) -> Self {
+        let mut query = SearchQuery::default();
+        let mut name_terms = Vec::new();
+
+        for token in input.split_whitespace() {
+            let Some((key, value)) = token.split_once(':') else {
+                name_terms.push(token);
+                continue;
+            };
+
+            let key = key.to_lowercase();
+            if !Filter::is_filter_key(&key) {
+                name_terms.push(token);
+                continue;
+            }
+
+            match Filter::parse(&key, value) {
+                Some(filter) => query.filters.push(filter),
+                None => query.invalid.push(token.to_string()),
+            }
+        }
+
+        query.name = name_terms.join(" ");
+        query
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.name.is_empty() && self.filters.is_empty()
+    }
+
+    pub fn name_terms(&self) -> impl Iterator<Item = String> + '_ {
+        self.name.split_whitespace().map(str::to_lowercase)
+    }
+
+    pub fn contains(&self, filter: &Filter) -> bool {
+        self.filters.contains(filter)
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        if !self.contains(&filter) {
+            self.filters.push(filter);
+        }
+        self
+    }
+
+    pub fn without_filter(mut self, filter: &Filter) -> Self {
+        self.filters.retain(|existing| existing != filter);
+        self
+    }
+}
+
+impl fmt::Display for SearchQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tokens = Some(self.name.clone())
+            .filter(|name| !name.is_empty())
+            .into_iter()
+            .chain(self.filters.iter().map(ToString::to_string))
+            .chain(self.invalid.iter().cloned())
+            .collect::<Vec<_>>();
+        write!(f, "{}", tokens.join(" "))
+    }
+}
+
+enum Comparison {
+    Less,
+    Greater,
+    Equal,
+}
+
+fn split_comparison(input: &str) -> (Comparison, &str) {
+    if let Some(value) = input.strip_prefix('<') {
+        (Comparison::Less, value)
+    } else if let Some(value) = input.strip_prefix('>') {
+        (Comparison::Greater, value)
+    } else {
+        (Comparison::Equal, input.strip_prefix('=').unwrap_or(input))
+    }
+}
+
+const SIZE_UNITS: [(&str, u64); 5] = [
+    ("T", 1 << 40),
+    ("G", 1 << 30),
+    ("M", 1 << 20),
+    ("K", 1 << 10),
+    ("B", 1),
+];
+
+fn parse_size(input: &str) -> Option<u64> {
+    let split = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(input.len());
+    let (amount, unit) = input.split_at(split);
+    let amount: f64 = amount.parse().ok()?;
+
+    let unit = unit.to_uppercase();
+    let unit = unit
+        .strip_suffix("IB")
+        .or_else(|| unit.strip_suffix('B').filter(|unit| !unit.is_empty()))
+        .unwrap_or(&unit);
+    let multiplier = if unit.is_empty() {
+        1
+    } else {
+        SIZE_UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .map(|(_, multiplier)| *multiplier)?
+    };
+
+    Some((amount * multiplier as f64) as u64)
+}
+
+fn parse_size_filter(input: &str) -> Option<SizeFilter> {
+    if let Some((low, high)) = input.split_once("..") {
+        return Some(SizeFilter::Between(parse_size(low)?, parse_size(high)?));
+    }
+
+    let (comparison, value) = split_comparison(input);
+    let size = parse_size(value)?;
+    Some(match comparison {
+        Comparison::Less => SizeFilter::LessThan(size),
+        Comparison::Greater => SizeFilter::GreaterThan(size),
+        Comparison::Equal => SizeFilter::Equal(size),
+    })
+}
+
+fn size_token(size: u64) -> String {
+    SIZE_UNITS
+        .iter()
+        .find(|(_, multiplier)| size != 0 && size.is_multiple_of(*multiplier))
+        .map(|(unit, multiplier)| format!("{}{}", size / multiplier, unit))
+        .unwrap_or_else(|| size.to_string())
+}
+
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["bytes", "KB", "MB", "GB", "TB"];
+
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024. && unit < UNITS.len() - 1 {
+        value /= 1024.;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", size, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn splits_name_from_filters() {
+        let query = SearchQuery::parse("quarterly report size:>100M kind:document");
+        assert_eq!(query.name, "quarterly report");
+        assert_eq!(
+            query.filters,
+            vec![
+                Filter::Size(SizeFilter::GreaterThan(100 << 20)),
+                Filter::Kind(Kind::Document),
+            ]
+        );
+        assert!(query.invalid.is_empty());
+    }
+
+    #[test]
+    fn unknown_keys_are_part_of_the_name() {
+        let query = SearchQuery::parse("notes:todo http://example.com");
+        assert_eq!(query.name, "notes:todo http://example.com");
+        assert!(query.filters.is_empty());
+    }
+
+    #[test]
+    fn unfinished_filters_are_invalid() {
+        let query = SearchQuery::parse("size:> kind:spaceship perm:+q");
+        assert!(query.filters.is_empty());
+        assert_eq!(query.invalid, ["size:>", "kind:spaceship", "perm:+q"]);
+        assert!(query.is_empty());
+    }
+
+    #[test]
+    fn size_units() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("10b"), Some(10));
+        assert_eq!(parse_size("4K"), Some(4 << 10));
+        assert_eq!(parse_size("4kb"), Some(4 << 10));
+        assert_eq!(parse_size("4KiB"), Some(4 << 10));
+        assert_eq!(parse_size("1.5G"), Some(3 << 29));
+        assert_eq!(parse_size("2T"), Some(2 << 40));
+        assert_eq!(parse_size("3Q"), None);
+        assert_eq!(parse_size("M"), None);
+    }
+
+    #[test]
+    fn size_comparisons_and_ranges() {
+        let parse = parse_size_filter;
+        assert_eq!(parse("<1M"), Some(SizeFilter::LessThan(1 << 20)));
+        assert_eq!(parse(">1M"), Some(SizeFilter::GreaterThan(1 << 20)));
+        assert_eq!(parse("=1M"), Some(SizeFilter::Equal(1 << 20)));
+        assert_eq!(parse("1M"), Some(SizeFilter::Equal(1 << 20)));
+        assert_eq!(
+            parse("1M..2M"),
+            Some(SizeFilter::Between(1 << 20, 2 << 20))
+        );
+        assert_eq!(parse("1M.."), None);
+
+        let between = SizeFilter::Between(10, 20);
+        assert!(between.matches(10) && between.matches(20));
+        assert!(!between.matches(9) && !between.matches(21));
+    }
+
+    #[test]
+    fn relative_ages() {
+        assert_eq!(
+            Age::parse("3d"),
+            Some(Age {
+                amount: 3,
+                unit: AgeUnit::Days
+            })
+        );
+        assert_eq!(Age::parse("2mo").map(|age| age.unit), Some(AgeUnit::Months));
+        assert_eq!(Age::parse("2x"), None);
+        assert_eq!(Age::parse("d"), None);
+        assert_eq!(
+            Age::parse("2w").unwrap().duration(),
+            Duration::from_secs(14 * 24 * 60 * 60)
+        );
+    }
+
+    #[test]
+    fn date_filters() {
+        let age = |amount, unit| Age { amount, unit };
+        assert_eq!(
+            DateFilter::parse("<2w"),
+            Some(DateFilter::NewerThan(age(2, AgeUnit::Weeks)))
+        );
+        assert_eq!(
+            DateFilter::parse(">1y"),
+            Some(DateFilter::OlderThan(age(1, AgeUnit::Years)))
+        );
+        // An age needs a direction.
+        assert_eq!(DateFilter::parse("2w"), None);
+        assert_eq!(
+            DateFilter::parse(">2024-01-31"),
+            Some(DateFilter::After(date(2024, 1, 31)))
+        );
+        assert_eq!(
+            DateFilter::parse("<2024-01-31"),
+            Some(DateFilter::Before(date(2024, 1, 31)))
+        );
+        assert_eq!(
+            DateFilter::parse("2024-01-31"),
+            Some(DateFilter::On(date(2024, 1, 31)))
+        );
+        assert_eq!(DateFilter::parse("2024-02-30"), None);
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!(Kind::from_name("images"), Some(Kind::Image));
+        assert_eq!(Kind::from_name("Dir"), Some(Kind::Folder));
+        assert_eq!(Kind::from_name("directory"), Some(Kind::Folder));
+        assert_eq!(Kind::from_name("spaceship"), None);
+    }
+
+    #[test]
+    fn permissions() {
+        assert_eq!(
+            PermissionFilter::parse("755"),
+            Some(PermissionFilter::Exact(0o755))
+        );
+        assert_eq!(
+            PermissionFilter::parse("+rx"),
+            Some(PermissionFilter::Has(0o500))
+        );
+        assert_eq!(PermissionFilter::parse("+"), None);
+        assert_eq!(PermissionFilter::parse("789"), None);
+        assert_eq!(PermissionFilter::parse("17777"), None);
+
+        assert!(PermissionFilter::Exact(0o644).matches(0o100644));
+        assert!(!PermissionFilter::Exact(0o644).matches(0o100755));
+        assert!(PermissionFilter::Has(0o100).matches(0o755));
+        assert!(!PermissionFilter::Has(0o300).matches(0o555));
+    }
+
+    #[test]
+    fn round_trips_through_display() {
+        for input in [
+            "report size:>100M modified:<2w kind:document",
+            "size:1K..3G created:>2024-01-31 owner:alice perm:+rx",
+            "perm:750 size:<1500B",
+        ] {
+            let query = SearchQuery::parse(input);
+            assert_eq!(query.to_string(), input);
+            assert_eq!(SearchQuery::parse(&query.to_string()), query);
+        }
+    }
+
+    #[test]
+    fn adding_and_removing_filters() {
+        let filter = Filter::Kind(Kind::Image);
+        let query = SearchQuery::parse("cat")
+            .with_filter(filter.clone())
+            .with_filter(filter.clone());
+        assert_eq!(query.filters, std::slice::from_ref(&filter));
+        assert!(query.without_filter(&filter).filters.is_empty());
+    }
+
+    #[test]
+    fn formats_sizes() {
+        assert_eq!(format_size(0), "0 bytes");
+        assert_eq!(format_size(1023), "1023 bytes");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(5 << 30), "5.0 GB");
+    }
+}
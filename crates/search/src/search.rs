@@ -0,0 +1,159 @@
+mod query;
+
+use std::{
+    fs::Metadata,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Local};
+use file_icons::FileIcons;
+use walkdir::WalkDir;
+
+pub use query::*;
+
+/// A [`SearchQuery`] prepared for matching many entries, with owner names
+/// resolved up front.
+pub struct Matcher {
+    name_terms: Vec<String>,
+    filters: Vec<Filter>,
+    owners: Vec<Option<u32>>,
+    icons: FileIcons,
+    now: SystemTime,
+}
+
+impl Matcher {
+    pub fn new(query: &SearchQuery, icons: FileIcons) -> Self {
+        let owners = query
+            .filters
+            .iter()
+            .map(|filter| match filter {
+                Filter::Owner(owner) => util::users::uid_by_name(owner),
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            name_terms: query.name_terms().collect(),
+            filters: query.filters.clone(),
+            owners,
+            icons,
+            now: SystemTime::now(),
+        }
+    }
+
+    pub fn matches(&self, path: &Path, metadata: &Metadata) -> bool {
+        if !self.name_terms.is_empty() {
+            let Some(name) = path.file_name() else {
+                return false;
+            };
+            let name = name.to_string_lossy().to_lowercase();
+            if !self.name_terms.iter().all(|term| name.contains(term)) {
+                return false;
+            }
+        }
+
+        self.filters
+            .iter()
+            .zip(&self.owners)
+            .all(|(filter, owner)| self.filter_matches(filter, *owner, path, metadata))
+    }
+
+    fn filter_matches(
+        &self,
+        filter: &Filter,
+        owner: Option<u32>,
+        path: &Path,
+        metadata: &Metadata,
+    ) -> bool {
+        match filter {
+            Filter::Size(size) => !metadata.is_dir() && size.matches(metadata.len()),
+            Filter::Modified(date) => metadata
+                .modified()
+                .is_ok_and(|time| self.date_matches(date, time)),
+            Filter::Created(date) => metadata
+                .created()
+                .is_ok_and(|time| self.date_matches(date, time)),
+            Filter::Kind(Kind::Folder) => metadata.is_dir(),
+            Filter::Kind(kind) => {
+                !metadata.is_dir()
+                    && self
+                        .icons
+                        .type_for_path(path)
+                        .and_then(Kind::from_icon_type)
+                        == Some(*kind)
+            }
+            Filter::Owner(_) => owner.is_some_and(|uid| owner_matches(uid, metadata)),
+            Filter::Permissions(permissions) => permissions_match(permissions, metadata),
+        }
+    }
+
+    fn date_matches(&self, filter: &DateFilter, time: SystemTime) -> bool {
+        let age = self.now.duration_since(time).unwrap_or_default();
+        let date = DateTime::<Local>::from(time).date_naive();
+
+        match filter {
+            DateFilter::NewerThan(limit) => age <= limit.duration(),
+            DateFilter::OlderThan(limit) => age > limit.duration(),
+            DateFilter::After(limit) => date > *limit,
+            DateFilter::Before(limit) => date < *limit,
+            DateFilter::On(limit) => date == *limit,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn owner_matches(uid: u32, metadata: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    metadata.uid() == uid
+}
+
+#[cfg(not(unix))]
+fn owner_matches(_uid: u32, _metadata: &Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn permissions_match(permissions: &PermissionFilter, metadata: &Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    permissions.matches(metadata.permissions().mode())
+}
+
+#[cfg(not(unix))]
+fn permissions_match(_permissions: &PermissionFilter, _metadata: &Metadata) -> bool {
+    false
+}
+
+/// Recursively searches `root`, returning at most `limit` matching paths.
+///
+/// Stops early once `cancel` is set.
+pub fn search(
+    root: &Path,
+    query: &SearchQuery,
+    icons: FileIcons,
+    limit: usize,
+    cancel: &AtomicBool,
+) -> Vec<PathBuf> {
+    let matcher = Matcher::new(query, icons);
+    let mut results = Vec::new();
+
+    for entry in WalkDir::new(root)
+        .min_depth(1)
+        .into_iter()
+        .filter_map(Result::ok)
+    {
+        if cancel.load(Ordering::Relaxed) || results.len() >= limit {
+            break;
+        }
+
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if matcher.matches(entry.path(), &metadata) {
+            results.push(entry.into_path());
+        }
+    }
+
+    results
+}
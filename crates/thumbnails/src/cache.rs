@@ -0,0 +1,479 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{anyhow, Context, Result};
+use file_icons::sniff_file_mime;
+use image::{DynamicImage, ImageFormat, ImageReader};
+use url::Url;
+
+use crate::{thumbnailer_for, Thumbnailer};
+
+const SOFTWARE: &str = "file_explorer";
+
+/// The thumbnail sizes from the freedesktop thumbnail spec.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ThumbnailSize {
+    /// At most 128x128.
+    Normal,
+    /// At most 256x256.
+    Large,
+    /// At most 512x512.
+    XLarge,
+}
+
+impl ThumbnailSize {
+    pub fn pixels(self) -> u32 {
+        match self {
+            ThumbnailSize::Normal => 128,
+            ThumbnailSize::Large => 256,
+            ThumbnailSize::XLarge => 512,
+        }
+    }
+
+    fn dir_name(self) -> &'static str {
+        match self {
+            ThumbnailSize::Normal => "normal",
+            ThumbnailSize::Large => "large",
+            ThumbnailSize::XLarge => "x-large",
+        }
+    }
+}
+
+/// `$XDG_CACHE_HOME/thumbnails`, shared with every other desktop app.
+pub fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|cache| cache.join("thumbnails"))
+}
+
+/// Whether `path` may get a thumbnail. Thumbnails of thumbnails are never
+/// made.
+pub fn can_thumbnail(path: &Path) -> bool {
+    !cache_dir().is_some_and(|cache| path.starts_with(cache))
+}
+
+/// The `file://` URI the spec keys thumbnails by.
+pub fn thumbnail_uri(path: &Path) -> Option<String> {
+    let path = fs::canonicalize(path).ok()?;
+    Url::from_file_path(path).ok().map(String::from)
+}
+
+fn cache_file_name(uri: &str) -> String {
+    format!("{:x}.png", md5::compute(uri))
+}
+
+/// Where the thumbnail for `uri` lives: the MD5 of the URI, in hex, with a
+/// `.png` extension.
+pub fn thumbnail_path(uri: &str, size: ThumbnailSize) -> Option<PathBuf> {
+    cache_dir().map(|cache| thumbnail_path_in(&cache, uri, size))
+}
+
+fn thumbnail_path_in(cache: &Path, uri: &str, size: ThumbnailSize) -> PathBuf {
+    cache.join(size.dir_name()).join(cache_file_name(uri))
+}
+
+/// Where we note that making a thumbnail for `uri` failed, so it isn't
+/// tried again until the file changes.
+pub fn fail_path(uri: &str) -> Option<PathBuf> {
+    cache_dir().map(|cache| fail_path_in(&cache, uri))
+}
+
+fn fail_path_in(cache: &Path, uri: &str) -> PathBuf {
+    cache.join("fail").join(SOFTWARE).join(cache_file_name(uri))
+}
+
+/// Returns the cached thumbnail for `path`, making one first when there is
+/// none or the file changed since it was made. Images we can decode are
+/// scaled here; other files go through an installed `.thumbnailer`.
+/// Returns `None` when no thumbnail can be made for this kind of file or
+/// for files that aren't on disk, or when making one failed before. Blocks,
+/// so call it off the main thread.
+pub fn load_or_generate(path: &Path, size: ThumbnailSize) -> Result<Option<PathBuf>> {
+    let cache = cache_dir().ok_or_else(|| anyhow!("no cache directory"))?;
+    load_or_generate_in(&cache, path, size)
+}
+
+/// [`load_or_generate`] with the thumbnails kept in `cache`.
+fn load_or_generate_in(cache: &Path, path: &Path, size: ThumbnailSize) -> Result<Option<PathBuf>> {
+    // Remote locations and files inside archives have no thumbnails.
+    let Ok(metadata) = fs::metadata(path) else {
+        return Ok(None);
+    };
+    if !metadata.is_file() {
+        return Ok(None);
+    }
+    let uri = thumbnail_uri(path).ok_or_else(|| anyhow!("not a local file"))?;
+    let thumbnail = thumbnail_path_in(cache, &uri, size);
+    let fail = fail_path_in(cache, &uri);
+    let mtime = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|mtime| mtime.as_secs())
+        .unwrap_or(0);
+
+    if is_up_to_date(&thumbnail, &uri, mtime) {
+        return Ok(Some(thumbnail));
+    }
+    if is_up_to_date(&fail, &uri, mtime) {
+        return Ok(None);
+    }
+
+    let thumbnailer = if can_decode(path) {
+        None
+    } else {
+        match sniff_file_mime(path).and_then(thumbnailer_for) {
+            Some(thumbnailer) => Some(thumbnailer),
+            None => return Ok(None),
+        }
+    };
+
+    let text = vec![
+        ("Thumb::URI", uri.clone()),
+        ("Thumb::MTime", mtime.to_string()),
+        ("Thumb::Size", metadata.len().to_string()),
+        ("Software", SOFTWARE.to_string()),
+    ];
+    let result = match thumbnailer {
+        None => generate_from_image(path, &thumbnail, size, text.clone()),
+        Some(thumbnailer) => generate_with_thumbnailer(
+            cache,
+            thumbnailer,
+            path,
+            &uri,
+            &thumbnail,
+            size,
+            text.clone(),
+        ),
+    };
+
+    if result.is_err() {
+        if let Err(e) = write_png(&fail, 1, 1, &[0; 4], &text) {
+            eprintln!(
+                "Failed to record thumbnail failure: {}. Error: {}",
+                fail.display(),
+                e
+            );
+        }
+    }
+    result.map(|()| Some(thumbnail))
+}
+
+/// Whether `path` is an image we can decode ourselves.
+fn can_decode(path: &Path) -> bool {
+    ImageFormat::from_path(path).is_ok_and(|format| format.reading_enabled())
+}
+
+fn generate_from_image(
+    path: &Path,
+    thumbnail: &Path,
+    size: ThumbnailSize,
+    mut text: Vec<(&str, String)>,
+) -> Result<()> {
+    let image = ImageReader::open(path)?
+        .with_guessed_format()?
+        .decode()
+        .with_context(|| format!("decoding {}", path.display()))?;
+    text.push(("Thumb::Image::Width", image.width().to_string()));
+    text.push(("Thumb::Image::Height", image.height().to_string()));
+    save_thumbnail(image, thumbnail, size, &text)
+}
+
+/// Runs `thumbnailer` into a scratch folder next to the cache, then stores
+/// what it made with the spec's keys added.
+fn generate_with_thumbnailer(
+    cache: &Path,
+    thumbnailer: &Thumbnailer,
+    path: &Path,
+    uri: &str,
+    thumbnail: &Path,
+    size: ThumbnailSize,
+    text: Vec<(&str, String)>,
+) -> Result<()> {
+    let scratch = cache.join(format!(
+        ".{}-{}-{}",
+        SOFTWARE,
+        std::process::id(),
+        cache_file_name(uri).trim_end_matches(".png")
+    ));
+    create_private_dir(&scratch)?;
+    let output = scratch.join("thumbnail.png");
+
+    let result = (|| {
+        thumbnailer.run(path, uri, &output, size.pixels())?;
+        let image = ImageReader::open(&output)?
+            .with_guessed_format()?
+            .decode()?;
+        save_thumbnail(image, thumbnail, size, &text)
+    })();
+
+    fs::remove_dir_all(&scratch).ok();
+    result
+}
+
+fn save_thumbnail(
+    image: DynamicImage,
+    thumbnail: &Path,
+    size: ThumbnailSize,
+    text: &[(&str, String)],
+) -> Result<()> {
+    let pixels = size.pixels();
+    // Only ever scale down; small images are stored as they are.
+    let image = if image.width() > pixels || image.height() > pixels {
+        image.thumbnail(pixels, pixels)
+    } else {
+        image
+    }
+    .to_rgba8();
+
+    write_png(
+        thumbnail,
+        image.width(),
+        image.height(),
+        image.as_raw(),
+        text,
+    )
+}
+
+/// Whether `thumbnail` exists and was made from the current version of the
+/// file, per its `Thumb::URI` and `Thumb::MTime` keys.
+pub fn is_up_to_date(thumbnail: &Path, uri: &str, mtime: u64) -> bool {
+    let Ok(file) = File::open(thumbnail) else {
+        return false;
+    };
+    let Ok(reader) = png::Decoder::new(BufReader::new(file)).read_info() else {
+        return false;
+    };
+
+    let info = reader.info();
+    let text = info
+        .uncompressed_latin1_text
+        .iter()
+        .map(|chunk| (chunk.keyword.as_str(), chunk.text.clone()))
+        .chain(
+            info.utf8_text
+                .iter()
+                .filter_map(|chunk| Some((chunk.keyword.as_str(), chunk.get_text().ok()?))),
+        )
+        .collect::<Vec<_>>();
+    let value = |key: &str| {
+        text.iter()
+            .find(|(keyword, _)| *keyword == key)
+            .map(|(_, value)| value.as_str())
+    };
+
+    value("Thumb::URI") == Some(uri)
+        && value("Thumb::MTime").and_then(|value| value.parse::<u64>().ok()) == Some(mtime)
+}
+
+/// Writes an RGBA PNG with the given text chunks. The image is written to a
+/// temporary file first and renamed into place, so other apps never see half
+/// a thumbnail.
+pub(crate) fn write_png(
+    destination: &Path,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+    text: &[(&str, String)],
+) -> Result<()> {
+    let dir = destination
+        .parent()
+        .ok_or_else(|| anyhow!("thumbnail has no parent folder"))?;
+    create_private_dir(dir)?;
+
+    let temporary = dir.join(format!(
+        ".{}.{}.tmp",
+        destination
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy(),
+        std::process::id()
+    ));
+    let result = (|| {
+        let file = create_private_file(&temporary)?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        for (keyword, value) in text {
+            encoder.add_text_chunk(keyword.to_string(), value.clone())?;
+        }
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(rgba)?;
+        writer.finish()?;
+        fs::rename(&temporary, destination)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        fs::remove_file(&temporary).ok();
+    }
+    result
+}
+
+/// The spec asks for thumbnail folders only the user can read.
+fn create_private_dir(dir: &Path) -> Result<()> {
+    if dir.is_dir() {
+        return Ok(());
+    }
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)?;
+    Ok(())
+}
+
+fn create_private_file(path: &Path) -> Result<File> {
+    let mut options = File::options();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    Ok(options.open(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+
+    const URI: &str = "file:///home/jens/photos/me.png";
+
+    fn set_mtime(path: &Path, secs: u64) {
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    fn mtime(path: &Path) -> u64 {
+        fs::metadata(path)
+            .unwrap()
+            .modified()
+            .unwrap()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn names_thumbnails_after_the_md5_of_the_uri() {
+        // The example from the thumbnail spec.
+        let cache = Path::new("/cache/thumbnails");
+        assert_eq!(
+            thumbnail_path_in(cache, URI, ThumbnailSize::Normal),
+            cache.join("normal/c6ee772d9e49320e97ec29a7eb5b1697.png")
+        );
+        assert_eq!(
+            thumbnail_path_in(cache, URI, ThumbnailSize::XLarge),
+            cache.join("x-large/c6ee772d9e49320e97ec29a7eb5b1697.png")
+        );
+        assert_eq!(
+            fail_path_in(cache, URI),
+            cache.join("fail/file_explorer/c6ee772d9e49320e97ec29a7eb5b1697.png")
+        );
+    }
+
+    #[test]
+    fn percent_encodes_uris() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a b#é.png");
+        fs::write(&path, "").unwrap();
+        let uri = thumbnail_uri(&path).unwrap();
+        assert!(uri.starts_with("file:///"), "{}", uri);
+        assert!(uri.ends_with("/a%20b%23%C3%A9.png"), "{}", uri);
+        assert_eq!(Url::parse(&uri).unwrap().to_file_path().unwrap(), {
+            fs::canonicalize(&path).unwrap()
+        });
+        assert_eq!(thumbnail_uri(&dir.path().join("missing.png")), None);
+    }
+
+    #[test]
+    fn thumbnails_of_other_versions_are_stale() {
+        let dir = tempfile::tempdir().unwrap();
+        let thumbnail = dir.path().join("thumbnail.png");
+        let text = [
+            ("Thumb::URI", URI.to_string()),
+            ("Thumb::MTime", "1700000000".to_string()),
+        ];
+        write_png(&thumbnail, 1, 1, &[0; 4], &text).unwrap();
+
+        assert!(is_up_to_date(&thumbnail, URI, 1_700_000_000));
+        assert!(!is_up_to_date(&thumbnail, URI, 1_700_000_001));
+        assert!(!is_up_to_date(
+            &thumbnail,
+            "file:///other.png",
+            1_700_000_000
+        ));
+        assert!(!is_up_to_date(&dir.path().join("missing.png"), URI, 0));
+    }
+
+    #[test]
+    fn makes_thumbnails_again_when_files_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let path = dir.path().join("photo.png");
+        image::RgbaImage::new(300, 150).save(&path).unwrap();
+        set_mtime(&path, 1_000);
+
+        let thumbnail = load_or_generate_in(&cache, &path, ThumbnailSize::Normal)
+            .unwrap()
+            .unwrap();
+        let uri = thumbnail_uri(&path).unwrap();
+        assert_eq!(
+            thumbnail,
+            thumbnail_path_in(&cache, &uri, ThumbnailSize::Normal)
+        );
+        assert_eq!(image::image_dimensions(&thumbnail).unwrap(), (128, 64));
+        assert!(is_up_to_date(&thumbnail, &uri, 1_000));
+
+        set_mtime(&path, 2_000);
+        assert!(!is_up_to_date(&thumbnail, &uri, mtime(&path)));
+        load_or_generate_in(&cache, &path, ThumbnailSize::Normal).unwrap();
+        assert!(is_up_to_date(&thumbnail, &uri, 2_000));
+    }
+
+    #[test]
+    fn remembers_failures_until_files_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let path = dir.path().join("broken.png");
+        fs::write(&path, "not a png").unwrap();
+        set_mtime(&path, 1_000);
+        let uri = thumbnail_uri(&path).unwrap();
+        let fail = fail_path_in(&cache, &uri);
+
+        assert!(load_or_generate_in(&cache, &path, ThumbnailSize::Normal).is_err());
+        assert!(is_up_to_date(&fail, &uri, 1_000));
+        // The failure isn't tried again...
+        assert_eq!(
+            load_or_generate_in(&cache, &path, ThumbnailSize::Normal).unwrap(),
+            None
+        );
+        // ...until the file changes.
+        set_mtime(
+            &path,
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        );
+        assert!(load_or_generate_in(&cache, &path, ThumbnailSize::Normal).is_err());
+        assert!(is_up_to_date(&fail, &uri, mtime(&path)));
+    }
+
+    #[test]
+    fn skips_files_that_arent_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        for path in [dir.path().join("a.zip/photo.png"), dir.path().to_path_buf()] {
+            assert_eq!(
+                load_or_generate_in(&cache, &path, ThumbnailSize::Normal).unwrap(),
+                None
+            );
+        }
+    }
+}
@@ -0,0 +1,408 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{Once, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+
+/// How long a thumbnailer may run before it is killed.
+const TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// Limits for thumbnailers run without bubblewrap.
+#[cfg(target_os = "linux")]
+const MAX_MEMORY: u64 = 2 << 30;
+#[cfg(target_os = "linux")]
+const MAX_FILE_SIZE: u64 = 64 << 20;
+
+/// An external program that makes thumbnails for some MIME types, as
+/// described by a `.thumbnailer` file.
+#[derive(Debug)]
+pub struct Thumbnailer {
+    exec: Vec<String>,
+    mime_types: Vec<String>,
+}
+
+/// Every installed thumbnailer. Read once, the first time one is needed.
+pub fn thumbnailers() -> &'static [Thumbnailer] {
+    static THUMBNAILERS: OnceLock<Vec<Thumbnailer>> = OnceLock::new();
+    THUMBNAILERS.get_or_init(load_thumbnailers)
+}
+
+/// The thumbnailer to use for files of type `mime`.
+pub fn thumbnailer_for(mime: &str) -> Option<&'static Thumbnailer> {
+    thumbnailers()
+        .iter()
+        .find(|thumbnailer| thumbnailer.mime_types.iter().any(|ty| ty == mime))
+}
+
+/// `$XDG_DATA_HOME/thumbnailers` followed by `thumbnailers` in each of
+/// `$XDG_DATA_DIRS`, most important first.
+fn thumbnailer_dirs() -> Vec<PathBuf> {
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+
+    dirs::data_dir()
+        .into_iter()
+        .chain(env::split_paths(&data_dirs))
+        .map(|dir| dir.join("thumbnailers"))
+        .collect()
+}
+
+fn load_thumbnailers() -> Vec<Thumbnailer> {
+    let mut seen = Vec::new();
+    let mut thumbnailers = Vec::new();
+
+    for dir in thumbnailer_dirs() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut paths = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "thumbnailer"))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        for path in paths {
+            // A file in a more important folder hides one with the same name.
+            let name = path.file_name().map(|name| name.to_os_string());
+            if seen.contains(&name) {
+                continue;
+            }
+            seen.push(name);
+
+            match fs::read_to_string(&path) {
+                Ok(contents) => thumbnailers.extend(Thumbnailer::parse(&contents)),
+                Err(e) => eprintln!(
+                    "Failed to read thumbnailer: {}. Error: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+    }
+    thumbnailers
+}
+
+impl Thumbnailer {
+    /// Parses the `[Thumbnailer Entry]` group of a `.thumbnailer` file. Entries
+    /// whose `TryExec` program isn't installed are skipped.
+    fn parse(contents: &str) -> Option<Self> {
+        let mut in_entry = false;
+        let mut try_exec = None;
+        let mut exec = None;
+        let mut mime_types = Vec::new();
+
+        for line in contents.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_entry = line == "[Thumbnailer Entry]";
+                continue;
+            }
+            if !in_entry || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match key.trim() {
+                "TryExec" => try_exec = Some(value.trim().to_string()),
+                "Exec" => exec = Some(split_exec(value.trim())),
+                "MimeType" => {
+                    mime_types = value
+                        .split(';')
+                        .map(str::trim)
+                        .filter(|ty| !ty.is_empty())
+                        .map(String::from)
+                        .collect()
+                }
+                _ => {}
+            }
+        }
+
+        let exec = exec.filter(|exec| !exec.is_empty())?;
+        let program = try_exec.as_deref().unwrap_or(&exec[0]);
+        if mime_types.is_empty() || find_program(program).is_none() {
+            return None;
+        }
+        Some(Self { exec, mime_types })
+    }
+
+    /// Runs the thumbnailer for `input`, asking for a PNG at most `size`
+    /// pixels wide and high at `output`. Runs inside a bubblewrap sandbox
+    /// without network or write access outside the output's folder when
+    /// `bwrap` is installed, or else confined as well as we can manage
+    /// without it, and is killed if it takes longer than [`TIMEOUT`].
+    pub fn run(&self, input: &Path, uri: &str, output: &Path, size: u32) -> Result<()> {
+        let args = self
+            .exec
+            .iter()
+            .map(|arg| expand_arg(arg, input, uri, output, size))
+            .collect::<Vec<_>>();
+
+        let mut command = match find_program("bwrap") {
+            Some(bwrap) => sandboxed(bwrap, input, output, &args),
+            None => confined(&args)?,
+        };
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        // A group of its own, so a timeout also kills whatever it started.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let mut child = command.spawn()?;
+
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if started.elapsed() > TIMEOUT {
+                #[cfg(unix)]
+                unsafe {
+                    libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+                }
+                child.kill().ok();
+                child.wait().ok();
+                return Err(anyhow!("{} timed out", args[0]));
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+
+        if !status.success() {
+            return Err(anyhow!("{} exited with {}", args[0], status));
+        }
+        if !output.is_file() {
+            return Err(anyhow!("{} didn't write a thumbnail", args[0]));
+        }
+        Ok(())
+    }
+}
+
+/// Wraps the thumbnailer in bubblewrap: the whole filesystem read-only, a
+/// private `/tmp`, only the output folder writable, and no network.
+fn sandboxed(bwrap: PathBuf, input: &Path, output: &Path, args: &[String]) -> Command {
+    let output_dir = output.parent().unwrap_or(output);
+    let mut command = Command::new(bwrap);
+    command
+        .args(["--ro-bind", "/", "/"])
+        .args(["--dev", "/dev"])
+        .args(["--proc", "/proc"])
+        .args(["--tmpfs", "/tmp"])
+        // After the tmpfs, so files in /tmp can still be thumbnailed.
+        .arg("--ro-bind")
+        .args([input, input])
+        .arg("--bind")
+        .args([output_dir, output_dir])
+        .args(["--unshare-all", "--die-with-parent", "--new-session", "--"])
+        .args(args);
+    command
+}
+
+/// Without bubblewrap, the thumbnailer runs in new user and network
+/// namespaces, so it has no network, and with limits on its memory, CPU
+/// time and the size of files it writes. If the namespaces can't be made it
+/// doesn't run at all, and the file is noted as failed.
+#[cfg(target_os = "linux")]
+fn confined(args: &[String]) -> Result<Command> {
+    use std::{io, os::unix::process::CommandExt};
+
+    warn_once(
+        "bwrap isn't installed, so thumbnailers run without network but can write \
+         wherever you can. Install bubblewrap to sandbox them fully.",
+    );
+    let mut command = Command::new(&args[0]);
+    command.args(&args[1..]);
+    // Safety: the closure only makes system calls, which is all that's
+    // allowed between `fork` and `exec`.
+    unsafe {
+        command.pre_exec(|| {
+            let limit = |resource, value: u64| {
+                let limit = libc::rlimit {
+                    rlim_cur: value,
+                    rlim_max: value,
+                };
+                match libc::setrlimit(resource, &limit) {
+                    0 => Ok(()),
+                    _ => Err(io::Error::last_os_error()),
+                }
+            };
+            if libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            limit(libc::RLIMIT_AS, MAX_MEMORY)?;
+            limit(libc::RLIMIT_CPU, TIMEOUT.as_secs())?;
+            limit(libc::RLIMIT_FSIZE, MAX_FILE_SIZE)?;
+            limit(libc::RLIMIT_CORE, 0)
+        });
+    }
+    Ok(command)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn confined(args: &[String]) -> Result<Command> {
+    warn_once("bwrap isn't installed, so thumbnailers aren't run.");
+    Err(anyhow!(
+        "{} wasn't run, since bwrap isn't installed",
+        args[0]
+    ))
+}
+
+fn warn_once(message: &str) {
+    static WARNING: Once = Once::new();
+    WARNING.call_once(|| eprintln!("{}", message));
+}
+
+/// Expands the `%i`, `%u`, `%o` and `%s` field codes in one argument.
+fn expand_arg(arg: &str, input: &Path, uri: &str, output: &Path, size: u32) -> String {
+    let mut expanded = String::new();
+    let mut chars = arg.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('i') => expanded.push_str(&input.to_string_lossy()),
+            Some('u') => expanded.push_str(uri),
+            Some('o') => expanded.push_str(&output.to_string_lossy()),
+            Some('s') => expanded.push_str(&size.to_string()),
+            Some('%') => expanded.push('%'),
+            Some(other) => {
+                expanded.push('%');
+                expanded.push(other);
+            }
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+
+/// Splits an `Exec` line into arguments, honouring double quotes and
+/// backslash escapes as desktop entries do.
+fn split_exec(exec: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quoted = false;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_arg = true;
+            }
+            '\\' if quoted => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    args
+}
+
+/// Looks `program` up in `$PATH` unless it's already a path.
+fn find_program(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        let path = PathBuf::from(program);
+        return path.is_file().then_some(path);
+    }
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_exec_lines() {
+        assert_eq!(
+            split_exec("gdk-pixbuf-thumbnailer -s %s %u %o"),
+            ["gdk-pixbuf-thumbnailer", "-s", "%s", "%u", "%o"]
+        );
+        assert_eq!(
+            split_exec(r#"  sh  -c "echo \"a b\" \\ done"  "" "#),
+            ["sh", "-c", r#"echo "a b" \ done"#, ""]
+        );
+        assert!(split_exec("   ").is_empty());
+    }
+
+    #[test]
+    fn expands_field_codes() {
+        let input = Path::new("/home/me/a b.pdf");
+        let output = Path::new("/tmp/out.png");
+        let expand = |arg| expand_arg(arg, input, "file:///home/me/a%20b.pdf", output, 256);
+        assert_eq!(expand("%i"), "/home/me/a b.pdf");
+        assert_eq!(expand("%u"), "file:///home/me/a%20b.pdf");
+        assert_eq!(expand("--output=%o"), "--output=/tmp/out.png");
+        assert_eq!(expand("%sx%s"), "256x256");
+        assert_eq!(expand("100%%"), "100%");
+        assert_eq!(expand("%f %"), "%f %");
+    }
+
+    #[test]
+    fn parses_thumbnailer_entries() {
+        let thumbnailer = Thumbnailer::parse(
+            "[Thumbnailer Entry]\n\
+             # A comment\n\
+             TryExec=sh\n\
+             Exec=sh -c \"cp %i %o\"\n\
+             MimeType=application/x-foo;application/x-bar;\n\
+             [Other Group]\n\
+             MimeType=text/plain\n",
+        )
+        .unwrap();
+        assert_eq!(thumbnailer.exec, ["sh", "-c", "cp %i %o"]);
+        assert_eq!(
+            thumbnailer.mime_types,
+            ["application/x-foo", "application/x-bar"]
+        );
+    }
+
+    #[test]
+    fn skips_unusable_entries() {
+        // Not installed.
+        assert!(Thumbnailer::parse(
+            "[Thumbnailer Entry]\nExec=no-such-thumbnailer %i %o\nMimeType=text/plain\n"
+        )
+        .is_none());
+        // No types.
+        assert!(Thumbnailer::parse("[Thumbnailer Entry]\nExec=sh %i %o\n").is_none());
+        // Outside the group.
+        assert!(Thumbnailer::parse("Exec=sh %i %o\nMimeType=text/plain\n").is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn confined_thumbnailers_have_no_network() {
+        let args = ["sh", "-c", "test $(grep -c : /proc/self/net/dev) = 1"].map(String::from);
+        let Ok(status) = confined(&args).unwrap().status() else {
+            // User namespaces are turned off here.
+            return;
+        };
+        assert!(status.success());
+    }
+}
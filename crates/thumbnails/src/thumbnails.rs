@@ -0,0 +1,110 @@
+mod cache;
+mod thumbnailer;
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use gpui::{AppContext, Global};
+
+pub use cache::*;
+pub use thumbnailer::*;
+
+const MAX_CACHED: usize = 10_000;
+
+#[derive(Clone, Debug)]
+enum State {
+    Pending,
+    Ready(PathBuf),
+    Unavailable,
+}
+
+#[derive(Debug)]
+struct Cached {
+    modified: SystemTime,
+    state: State,
+}
+
+/// Thumbnails for the files on screen, looked up in (or added to) the
+/// freedesktop thumbnail cache on background threads.
+#[derive(Default)]
+pub struct Thumbnails {
+    cache: Arc<Mutex<HashMap<(PathBuf, ThumbnailSize), Cached>>>,
+}
+
+impl Global for Thumbnails {}
+
+pub fn init(cx: &mut AppContext) {
+    cx.set_global(Thumbnails::default())
+}
+
+impl Thumbnails {
+    /// The thumbnail for `path` if it is ready. Otherwise starts loading or
+    /// making one in the background and redraws the windows once it's done.
+    /// `modified` comes from the file's listing; when it changes, the
+    /// thumbnail is looked up again.
+    pub fn get(
+        path: &Path,
+        modified: Option<SystemTime>,
+        size: ThumbnailSize,
+        cx: &mut AppContext,
+    ) -> Option<PathBuf> {
+        if !can_thumbnail(path) {
+            return None;
+        }
+        let modified = modified?;
+        let cache = cx.try_global::<Self>()?.cache.clone();
+        let key = (path.to_path_buf(), size);
+
+        {
+            let mut cache = cache.lock().unwrap();
+            if let Some(cached) = cache.get(&key).filter(|cached| cached.modified == modified) {
+                return match &cached.state {
+                    State::Ready(thumbnail) => Some(thumbnail.clone()),
+                    State::Pending | State::Unavailable => None,
+                };
+            }
+
+            if cache.len() >= MAX_CACHED {
+                cache.retain(|_, cached| matches!(cached.state, State::Pending));
+            }
+            cache.insert(
+                key.clone(),
+                Cached {
+                    modified,
+                    state: State::Pending,
+                },
+            );
+        }
+
+        let work = cx.background_executor().spawn({
+            let path = key.0.clone();
+            async move { load_or_generate(&path, size) }
+        });
+        cx.spawn(|mut cx| async move {
+            let state = match work.await {
+                Ok(Some(thumbnail)) => State::Ready(thumbnail),
+                Ok(None) => State::Unavailable,
+                Err(e) => {
+                    eprintln!(
+                        "Failed to create thumbnail: {}. Error: {}",
+                        key.0.display(),
+                        e
+                    );
+                    State::Unavailable
+                }
+            };
+            cache
+                .lock()
+                .unwrap()
+                .insert(key, Cached { modified, state });
+            cx.refresh().ok();
+        })
+        .detach();
+
+        None
+    }
+}
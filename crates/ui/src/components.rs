@@ -1,5 +1,13 @@
+mod checkbox;
 mod file_item;
+mod file_row;
+mod picker;
+mod text_input;
 mod title_bar;
 
+pub use checkbox::*;
 pub use file_item::*;
+pub use file_row::*;
+pub use picker::*;
+pub use text_input::*;
 pub use title_bar::*;
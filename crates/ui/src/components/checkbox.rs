@@ -0,0 +1,76 @@
+use gpui::{
+    div, prelude::FluentBuilder, px, rgb, rgba, white, ClickEvent, ElementId, InteractiveElement,
+    IntoElement, ParentElement, RenderOnce, SharedString, StatefulInteractiveElement, Styled,
+    WindowContext,
+};
+
+use crate::Clickable;
+
+/// A checkbox with an optional label. `None` shows the mixed state, for
+/// settings that differ between the items being edited.
+#[derive(IntoElement)]
+pub struct Checkbox {
+    id: ElementId,
+    checked: Option<bool>,
+    label: Option<SharedString>,
+    click_handler: Option<Box<dyn Fn(&ClickEvent, &mut WindowContext)>>,
+}
+
+impl Checkbox {
+    pub fn new(id: impl Into<ElementId>, checked: Option<bool>) -> Self {
+        Self {
+            id: id.into(),
+            checked,
+            label: None,
+            click_handler: None,
+        }
+    }
+
+    pub fn label(mut self, label: impl Into<SharedString>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+}
+
+impl RenderOnce for Checkbox {
+    fn render(self, _cx: &mut WindowContext) -> impl IntoElement {
+        let mark = match self.checked {
+            Some(true) => "✓",
+            Some(false) => "",
+            None => "−",
+        };
+
+        div()
+            .id(self.id)
+            .flex()
+            .flex_row()
+            .items_center()
+            .gap(px(6.))
+            .when_some(self.click_handler, |this, handler| {
+                this.on_click(move |event, cx| handler(event, cx))
+            })
+            .child(
+                div()
+                    .w(px(14.))
+                    .h(px(14.))
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .rounded(px(3.))
+                    .border_1()
+                    .border_color(rgba(0xffffff33))
+                    .when(self.checked != Some(false), |this| this.bg(rgb(0x3b82f6)))
+                    .text_color(white())
+                    .text_size(px(11.))
+                    .child(mark),
+            )
+            .children(self.label)
+    }
+}
+
+impl Clickable for Checkbox {
+    fn on_click(mut self, handler: impl Fn(&ClickEvent, &mut WindowContext) + 'static) -> Self {
+        self.click_handler = Some(Box::new(handler));
+        self
+    }
+}
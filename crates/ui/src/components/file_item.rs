@@ -1,44 +1,67 @@
 use std::{
-    fs::{self, Metadata},
     path::{Path, PathBuf},
     sync::{Arc, Mutex},
     time::SystemTime,
 };
 
 use gpui::{
-    div, px, rgb, rgba, svg, white, AnyElement, InteractiveElement, IntoElement, ParentElement,
-    Styled,
+    div, img, prelude::FluentBuilder, px, rgb, rgba, svg, white, AnyElement, ClickEvent, ElementId,
+    InteractiveElement, IntoElement, ObjectFit, ParentElement, RenderOnce,
+    StatefulInteractiveElement, Styled, StyledImage, WindowContext,
 };
 
+use file_icons::FileIcons;
+use thumbnails::{ThumbnailSize, Thumbnails};
+
+use crate::Clickable;
+
+#[derive(IntoElement)]
 pub struct FileItem {
     path: PathBuf,
     name: String,
-    metadata: Metadata,
+    size: u64,
+    modified: Option<SystemTime>,
     on_click: Option<Arc<Mutex<dyn FnMut(&str) + Send + Sync>>>,
+    click_handler: Option<Box<dyn Fn(&ClickEvent, &mut WindowContext)>>,
     is_folder: bool,
+    selected: bool,
 }
 
 impl FileItem {
+    /// `size` and `modified` come from whichever file system `path` is on,
+    /// which may not be the local one.
     pub fn new(
         path: &Path,
         on_click: Option<Arc<Mutex<dyn FnMut(&str) + Send + Sync>>>,
         is_folder: bool,
+        size: u64,
+        modified: Option<SystemTime>,
     ) -> Self {
-        let metadata = fs::metadata(path).expect("Unable to read metadata");
-        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string_lossy().into_owned());
 
         Self {
             path: path.to_path_buf(),
             name,
-            metadata,
+            size,
+            modified,
             on_click,
+            click_handler: None,
             is_folder,
+            selected: false,
         }
     }
 
+    pub fn selected(mut self, selected: bool) -> Self {
+        self.selected = selected;
+        self
+    }
+
     fn format_metadata(&self) -> String {
-        let size = self.metadata.len();
-        let modified = self.metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        let size = self.size;
+        let modified = self.modified.unwrap_or(SystemTime::UNIX_EPOCH);
         let modified: chrono::DateTime<chrono::Utc> = modified.into();
         format!(
             "{} bytes, modified: {}",
@@ -48,22 +71,51 @@ impl FileItem {
     }
 }
 
-impl IntoElement for FileItem {
-    type Element = AnyElement;
-
-    fn into_element(self) -> AnyElement {
+impl RenderOnce for FileItem {
+    fn render(self, cx: &mut WindowContext) -> impl IntoElement {
         let path_clone = self.path.clone();
         let click_handler = self.on_click.clone();
         let icon_path = if self.is_folder {
-            "icons/file_icons/folder.svg"
+            FileIcons::get_folder_icon_for_path(&self.path, false, cx)
+        } else {
+            FileIcons::get_icon(&self.path, self.modified, self.size, cx)
+        }
+        .map(|icon| icon.to_string())
+        .unwrap_or_else(|| {
+            if self.is_folder {
+                "icons/file_icons/folder.svg".into()
+            } else {
+                "icons/file_icons/file_text.svg".into()
+            }
+        });
+
+        let thumbnail = if self.is_folder {
+            None
         } else {
-            "icons/file_icons/file_text.svg"
+            Thumbnails::get(&self.path, self.modified, ThumbnailSize::Normal, cx)
+        };
+        let icon: AnyElement = match thumbnail {
+            Some(thumbnail) => img(thumbnail)
+                .w(px(45.))
+                .h(px(45.))
+                .object_fit(ObjectFit::Contain)
+                .into_any_element(),
+            None => svg()
+                .path(icon_path)
+                .w(px(45.))
+                .h(px(45.))
+                .text_color(white())
+                .into_any_element(),
         };
 
         div()
+            .id(ElementId::Name(
+                self.path.to_string_lossy().into_owned().into(),
+            ))
             .rounded(px(8.))
             .px(px(10.))
             .py(px(5.))
+            .when(self.selected, |this| this.bg(rgba(0xffffff1a)))
             .hover(|style| style.bg(rgba(0xffffff0d)))
             .on_mouse_down(gpui::MouseButton::Left, move |_event, _cx| {
                 if let Some(handler) = click_handler.clone() {
@@ -71,6 +123,9 @@ impl IntoElement for FileItem {
                     handler(&path_clone.to_str().unwrap());
                 }
             })
+            .when_some(self.click_handler, |this, handler| {
+                this.on_click(move |event, cx| handler(event, cx))
+            })
             .child(
                 div()
                     .w(px(60.))
@@ -86,16 +141,16 @@ impl IntoElement for FileItem {
                             .items_center()
                             .content_center()
                             .justify_center()
-                            .child(
-                                svg()
-                                    .path(icon_path)
-                                    .w(px(45.))
-                                    .h(px(45.))
-                                    .text_color(white()),
-                            ),
+                            .child(icon),
                         div().flex().flex_wrap().child(self.name.clone()),
                     ]),
             )
-            .into_any_element()
+    }
+}
+
+impl Clickable for FileItem {
+    fn on_click(mut self, handler: impl Fn(&ClickEvent, &mut WindowContext) + 'static) -> Self {
+        self.click_handler = Some(Box::new(handler));
+        self
     }
 }
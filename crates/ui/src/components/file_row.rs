@@ -0,0 +1,135 @@
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use gpui::{
+    div, prelude::FluentBuilder, px, rgba, svg, white, ClickEvent, ElementId, InteractiveElement,
+    IntoElement, ParentElement, RenderOnce, SharedString, StatefulInteractiveElement, Styled,
+    WindowContext,
+};
+
+use file_icons::FileIcons;
+
+use crate::Clickable;
+
+/// A single line of the details view: icon, name, size and modification
+/// date.
+#[derive(IntoElement)]
+pub struct FileRow {
+    path: PathBuf,
+    name: String,
+    size: SharedString,
+    modified: SharedString,
+    /// The listing's modification time and size, which tell whether the
+    /// type sniffed from the file's contents still holds.
+    version: (Option<SystemTime>, u64),
+    is_folder: bool,
+    selected: bool,
+    click_handler: Option<Box<dyn Fn(&ClickEvent, &mut WindowContext)>>,
+}
+
+impl FileRow {
+    pub fn new(
+        path: &Path,
+        is_folder: bool,
+        size: impl Into<SharedString>,
+        modified: impl Into<SharedString>,
+    ) -> Self {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string_lossy().into_owned());
+
+        Self {
+            path: path.to_path_buf(),
+            name,
+            size: size.into(),
+            modified: modified.into(),
+            version: (None, 0),
+            is_folder,
+            selected: false,
+            click_handler: None,
+        }
+    }
+
+    pub fn selected(mut self, selected: bool) -> Self {
+        self.selected = selected;
+        self
+    }
+
+    /// The modification time and size from the file's listing.
+    pub fn version(mut self, modified: Option<SystemTime>, size: u64) -> Self {
+        self.version = (modified, size);
+        self
+    }
+}
+
+impl RenderOnce for FileRow {
+    fn render(self, cx: &mut WindowContext) -> impl IntoElement {
+        let icon_path = if self.is_folder {
+            FileIcons::get_folder_icon_for_path(&self.path, false, cx)
+        } else {
+            FileIcons::get_icon(&self.path, self.version.0, self.version.1, cx)
+        }
+        .map(|icon| icon.to_string())
+        .unwrap_or_else(|| {
+            if self.is_folder {
+                "icons/file_icons/folder.svg".into()
+            } else {
+                "icons/file_icons/file_text.svg".into()
+            }
+        });
+
+        div()
+            .id(ElementId::Name(
+                self.path.to_string_lossy().into_owned().into(),
+            ))
+            .w_full()
+            .flex()
+            .flex_row()
+            .items_center()
+            .gap(px(10.))
+            .rounded(px(6.))
+            .px(px(8.))
+            .py(px(3.))
+            .when(self.selected, |this| this.bg(rgba(0xffffff1a)))
+            .hover(|style| style.bg(rgba(0xffffff0d)))
+            .when_some(self.click_handler, |this, handler| {
+                this.on_click(move |event, cx| handler(event, cx))
+            })
+            .child(
+                svg()
+                    .path(icon_path)
+                    .w(px(16.))
+                    .h(px(16.))
+                    .text_color(white()),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .child(self.name),
+            )
+            .child(
+                div()
+                    .w(px(80.))
+                    .text_color(rgba(0xffffff99))
+                    .child(self.size),
+            )
+            .child(
+                div()
+                    .w(px(130.))
+                    .text_color(rgba(0xffffff99))
+                    .child(self.modified),
+            )
+    }
+}
+
+impl Clickable for FileRow {
+    fn on_click(mut self, handler: impl Fn(&ClickEvent, &mut WindowContext) + 'static) -> Self {
+        self.click_handler = Some(Box::new(handler));
+        self
+    }
+}
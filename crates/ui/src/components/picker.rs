@@ -0,0 +1,173 @@
+use gpui::{
+    div, prelude::FluentBuilder, px, rgb, rgba, AnyElement, AppContext, DismissEvent, EventEmitter,
+    FocusHandle, FocusableView, InteractiveElement, IntoElement, KeyDownEvent, ParentElement,
+    Render, SharedString, Styled, Task, View, ViewContext, VisualContext,
+};
+
+use crate::{TextInput, TextInputEvent};
+
+const MAX_VISIBLE_MATCHES: usize = 12;
+
+pub trait PickerDelegate: Sized + 'static {
+    fn placeholder_text(&self) -> SharedString;
+    fn match_count(&self) -> usize;
+
+    /// Recomputes the matches for `query`. Delegates that match in the
+    /// background call [`Picker::matches_updated`] once they are done.
+    fn update_matches(&mut self, query: String, cx: &mut ViewContext<Picker<Self>>) -> Task<()>;
+
+    /// Called with the selected match, if any, before the picker dismisses
+    /// itself.
+    fn confirm(&mut self, ix: Option<usize>, query: &str, cx: &mut ViewContext<Picker<Self>>);
+
+    fn render_match(
+        &self,
+        ix: usize,
+        selected: bool,
+        cx: &mut ViewContext<Picker<Self>>,
+    ) -> AnyElement;
+
+    /// Shown in place of the matches when there are none.
+    fn render_empty(&self, _cx: &mut ViewContext<Picker<Self>>) -> Option<AnyElement> {
+        None
+    }
+}
+
+pub struct Picker<D: PickerDelegate> {
+    pub delegate: D,
+    input: View<TextInput>,
+    selected_index: usize,
+    pending_update: Option<Task<()>>,
+}
+
+impl<D: PickerDelegate> EventEmitter<DismissEvent> for Picker<D> {}
+
+impl<D: PickerDelegate> Picker<D> {
+    pub fn new(delegate: D, cx: &mut ViewContext<Self>) -> Self {
+        let placeholder = delegate.placeholder_text();
+        let input = cx.new_view(|cx| TextInput::new(placeholder, cx));
+        cx.subscribe(&input, Self::on_input_event).detach();
+
+        let mut this = Self {
+            delegate,
+            input,
+            selected_index: 0,
+            pending_update: None,
+        };
+        this.update_matches(String::new(), cx);
+        this
+    }
+
+    pub fn query(&self, cx: &AppContext) -> String {
+        self.input.read(cx).text().to_string()
+    }
+
+    pub fn matches_updated(&mut self, cx: &mut ViewContext<Self>) {
+        self.selected_index = 0;
+        self.pending_update = None;
+        cx.notify();
+    }
+
+    fn update_matches(&mut self, query: String, cx: &mut ViewContext<Self>) {
+        self.selected_index = 0;
+        self.pending_update = Some(self.delegate.update_matches(query, cx));
+        cx.notify();
+    }
+
+    fn on_input_event(
+        &mut self,
+        _input: View<TextInput>,
+        event: &TextInputEvent,
+        cx: &mut ViewContext<Self>,
+    ) {
+        match event {
+            TextInputEvent::Changed(query) => self.update_matches(query.clone(), cx),
+            TextInputEvent::Confirmed(query) => self.confirm(query.clone(), cx),
+            TextInputEvent::Cancelled => cx.emit(DismissEvent),
+        }
+    }
+
+    fn confirm(&mut self, query: String, cx: &mut ViewContext<Self>) {
+        let selected =
+            (self.selected_index < self.delegate.match_count()).then_some(self.selected_index);
+        self.delegate.confirm(selected, &query, cx);
+        cx.emit(DismissEvent);
+    }
+
+    fn handle_key_down(&mut self, event: &KeyDownEvent, cx: &mut ViewContext<Self>) {
+        let match_count = self.delegate.match_count();
+        match event.keystroke.key.as_str() {
+            "up" => self.selected_index = self.selected_index.saturating_sub(1),
+            "down" if match_count > 0 => {
+                self.selected_index = (self.selected_index + 1).min(match_count - 1)
+            }
+            _ => return,
+        }
+
+        cx.stop_propagation();
+        cx.notify();
+    }
+}
+
+impl<D: PickerDelegate> FocusableView for Picker<D> {
+    fn focus_handle(&self, cx: &AppContext) -> FocusHandle {
+        self.input.focus_handle(cx)
+    }
+}
+
+impl<D: PickerDelegate> Render for Picker<D> {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let match_count = self.delegate.match_count();
+        let start = (self.selected_index + 1).saturating_sub(MAX_VISIBLE_MATCHES);
+        let end = (start + MAX_VISIBLE_MATCHES).min(match_count);
+
+        let matches = (start..end)
+            .map(|ix| {
+                let selected = ix == self.selected_index;
+                div()
+                    .rounded(px(6.))
+                    .px(px(8.))
+                    .py(px(4.))
+                    .when(selected, |this| this.bg(rgba(0xffffff1a)))
+                    .hover(|style| style.bg(rgba(0xffffff0d)))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(move |this, _event, cx| {
+                            this.selected_index = ix;
+                            let query = this.query(cx);
+                            this.confirm(query, cx);
+                        }),
+                    )
+                    .child(self.delegate.render_match(ix, selected, cx))
+            })
+            .collect::<Vec<_>>();
+
+        let empty = if match_count == 0 {
+            self.delegate.render_empty(cx)
+        } else {
+            None
+        };
+
+        div()
+            .w(px(480.))
+            .p(px(8.))
+            .flex()
+            .flex_col()
+            .gap(px(6.))
+            .rounded(px(10.))
+            .bg(rgb(0x2b2a2e))
+            .border_1()
+            .border_color(rgba(0xffffff1a))
+            .text_color(rgb(0xf3f3f3))
+            .on_key_down(cx.listener(Self::handle_key_down))
+            .child(self.input.clone())
+            .children(matches)
+            .children(empty.map(|empty| {
+                div()
+                    .px(px(8.))
+                    .py(px(4.))
+                    .text_color(rgba(0xffffff80))
+                    .child(empty)
+            }))
+    }
+}
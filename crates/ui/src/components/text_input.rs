@@ -0,0 +1,136 @@
+use gpui::{
+    div, px, rgb, rgba, white, EventEmitter, FocusHandle, FocusableView, InteractiveElement,
+    IntoElement, KeyDownEvent, ParentElement, Render, SharedString, Styled, ViewContext,
+};
+
+pub enum TextInputEvent {
+    Changed(String),
+    Confirmed(String),
+    Cancelled,
+}
+
+pub struct TextInput {
+    text: String,
+    placeholder: SharedString,
+    /// Shows dots instead of the text, for passwords.
+    masked: bool,
+    focus_handle: FocusHandle,
+}
+
+impl EventEmitter<TextInputEvent> for TextInput {}
+
+impl TextInput {
+    pub fn new(placeholder: impl Into<SharedString>, cx: &mut ViewContext<Self>) -> Self {
+        Self {
+            text: String::new(),
+            placeholder: placeholder.into(),
+            masked: false,
+            focus_handle: cx.focus_handle(),
+        }
+    }
+
+    pub fn masked(mut self) -> Self {
+        self.masked = true;
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn set_text(&mut self, text: impl Into<String>, cx: &mut ViewContext<Self>) {
+        self.text = text.into();
+        cx.emit(TextInputEvent::Changed(self.text.clone()));
+        cx.notify();
+    }
+
+    fn handle_key_down(&mut self, event: &KeyDownEvent, cx: &mut ViewContext<Self>) {
+        let keystroke = &event.keystroke;
+
+        match keystroke.key.as_str() {
+            "backspace" => {
+                if keystroke.modifiers.control {
+                    let trimmed = self.text.trim_end();
+                    let word_start = trimmed
+                        .rfind(char::is_whitespace)
+                        .map(|ix| ix + 1)
+                        .unwrap_or(0);
+                    self.text.truncate(word_start);
+                } else {
+                    self.text.pop();
+                }
+                cx.emit(TextInputEvent::Changed(self.text.clone()));
+            }
+            "enter" => cx.emit(TextInputEvent::Confirmed(self.text.clone())),
+            "escape" => cx.emit(TextInputEvent::Cancelled),
+            _ => {
+                // Leave shortcuts to the key bindings.
+                if keystroke.modifiers.control || keystroke.modifiers.platform {
+                    return;
+                }
+
+                match &keystroke.ime_key {
+                    Some(input) => self.text.push_str(input),
+                    None if keystroke.key.chars().count() == 1 => {
+                        self.text.push_str(&keystroke.key)
+                    }
+                    None => return,
+                }
+                cx.emit(TextInputEvent::Changed(self.text.clone()));
+            }
+        }
+
+        cx.stop_propagation();
+        cx.notify();
+    }
+}
+
+impl FocusableView for TextInput {
+    fn focus_handle(&self, _cx: &gpui::AppContext) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for TextInput {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let focused = self.focus_handle.is_focused(cx);
+
+        let content = if self.text.is_empty() {
+            div()
+                .text_color(rgba(0xffffff66))
+                .child(self.placeholder.clone())
+        } else if self.masked {
+            div()
+                .text_color(white())
+                .child("•".repeat(self.text.chars().count()))
+        } else {
+            div().text_color(white()).child(self.text.clone())
+        };
+
+        div()
+            .key_context("TextInput")
+            .track_focus(&self.focus_handle)
+            .on_key_down(cx.listener(Self::handle_key_down))
+            .on_mouse_down(
+                gpui::MouseButton::Left,
+                cx.listener(|this, _event, cx| cx.focus(&this.focus_handle)),
+            )
+            .h(px(26.))
+            .px(px(8.))
+            .rounded(px(8.))
+            .flex()
+            .flex_row()
+            .items_center()
+            .overflow_hidden()
+            .whitespace_nowrap()
+            .bg(rgb(0x19191a))
+            .border_1()
+            .border_color(if focused {
+                rgba(0xffffff33)
+            } else {
+                rgba(0xffffff0d)
+            })
+            .child(content)
+            .children(focused.then(|| div().w(px(1.)).h(px(14.)).bg(white())))
+    }
+}
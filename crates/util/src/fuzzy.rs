@@ -0,0 +1,124 @@
+//! Subsequence matching for palettes, in the spirit of fzf.
+
+const MATCH_SCORE: i64 = 16;
+const CONSECUTIVE_BONUS: i64 = 8;
+const BOUNDARY_BONUS: i64 = 10;
+const GAP_PENALTY: i64 = 1;
+const MAX_GAP_PENALTY: i64 = 8;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    /// Char indices into the candidate of the matched query characters.
+    pub positions: Vec<usize>,
+}
+
+/// Matches `query` as a case-insensitive subsequence of `candidate`.
+///
+/// Whitespace in the query is ignored. Matches on word boundaries and runs of
+/// consecutive characters score higher, gaps and long candidates lower.
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<FuzzyMatch> {
+    let query = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<_>>();
+    if query.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+
+    let candidate = candidate.chars().collect::<Vec<_>>();
+    let mut positions = Vec::with_capacity(query.len());
+    let mut score = 0;
+    let mut query_ix = 0;
+    let mut last_match: Option<usize> = None;
+
+    for (ix, c) in candidate.iter().enumerate() {
+        if query_ix == query.len() {
+            break;
+        }
+        if !c.to_lowercase().eq(std::iter::once(query[query_ix])) {
+            continue;
+        }
+
+        score += MATCH_SCORE;
+        if is_boundary(&candidate, ix) {
+            score += BOUNDARY_BONUS;
+        }
+        match last_match {
+            Some(last) if last + 1 == ix => score += CONSECUTIVE_BONUS,
+            Some(last) => score -= ((ix - last - 1) as i64 * GAP_PENALTY).min(MAX_GAP_PENALTY),
+            None => score -= (ix as i64 * GAP_PENALTY).min(MAX_GAP_PENALTY),
+        }
+
+        positions.push(ix);
+        last_match = Some(ix);
+        query_ix += 1;
+    }
+
+    if query_ix < query.len() {
+        return None;
+    }
+
+    score -= candidate.len() as i64 / 8;
+    Some(FuzzyMatch { score, positions })
+}
+
+fn is_boundary(candidate: &[char], ix: usize) -> bool {
+    let Some(previous) = ix.checked_sub(1).map(|ix| candidate[ix]) else {
+        return true;
+    };
+    let current = candidate[ix];
+
+    matches!(previous, '/' | '\\' | '_' | '-' | '.' | ' ')
+        || (previous.is_lowercase() && current.is_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(query: &str, candidate: &str) -> i64 {
+        fuzzy_match(query, candidate).unwrap().score
+    }
+
+    #[test]
+    fn matches_subsequences_ignoring_case_and_spaces() {
+        let expected = fuzzy_match("fx", "file_explorer").unwrap();
+        assert_eq!(expected.positions, [0, 6]);
+        assert_eq!(fuzzy_match("F X", "file_explorer"), Some(expected));
+        assert_eq!(fuzzy_match("", "anything").unwrap().positions, []);
+    }
+
+    #[test]
+    fn misses_candidates_without_the_query_in_order() {
+        assert_eq!(fuzzy_match("xyz", "file_explorer"), None);
+        assert_eq!(fuzzy_match("rf", "fr"), None);
+        assert_eq!(fuzzy_match("files", "file"), None);
+    }
+
+    #[test]
+    fn prefers_prefixes() {
+        assert!(score("set", "settings.rs") > score("set", "reset.rs"));
+    }
+
+    #[test]
+    fn prefers_word_boundaries() {
+        assert!(score("fb", "foo_bar") > score("fb", "foobar"));
+        assert!(score("fb", "fooBar") > score("fb", "foobar"));
+        assert!(score("fb", "foo/bar") > score("fb", "foobar"));
+    }
+
+    #[test]
+    fn prefers_consecutive_matches() {
+        assert!(score("abc", "abcxxx") > score("abc", "axbxcx"));
+    }
+
+    #[test]
+    fn prefers_shorter_candidates() {
+        assert!(score("main", "main.rs") > score("main", "main_window_controller.rs"));
+    }
+}
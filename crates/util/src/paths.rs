@@ -4,7 +4,16 @@ use std::{
 };
 
 lazy_static::lazy_static! {
-    pub static ref HOME: PathBuf = dirs::home_dir().expect("failed to determine home directory");
+    pub static ref HOME: PathBuf = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/"));
+}
+
+/// Expands a leading `~` to the home directory.
+pub fn expand_tilde(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some("") => HOME.clone(),
+        Some(rest) if rest.starts_with(['/', std::path::MAIN_SEPARATOR]) => HOME.join(&rest[1..]),
+        _ => PathBuf::from(path),
+    }
 }
 
 pub trait PathExt {
@@ -0,0 +1,142 @@
+//! Lookups between numeric user/group ids and their names.
+
+#[cfg(unix)]
+use std::{
+    ffi::{c_char, c_int, CStr, CString},
+    mem::MaybeUninit,
+    ptr,
+};
+
+/// Runs a reentrant `get*_r` lookup, growing the string buffer until the
+/// entry fits. The lookups are made from search threads, so the
+/// non-reentrant versions, which share static storage across the process,
+/// can't be used.
+#[cfg(unix)]
+fn lookup<T, R>(
+    call: impl Fn(*mut T, *mut c_char, usize, *mut *mut T) -> c_int,
+    read: impl FnOnce(&T) -> R,
+) -> Option<R> {
+    let mut buffer = vec![0 as c_char; 1024];
+    loop {
+        let mut entry = MaybeUninit::<T>::uninit();
+        let mut result = ptr::null_mut();
+        let error = call(
+            entry.as_mut_ptr(),
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        );
+        if error == libc::ERANGE && buffer.len() < 1 << 20 {
+            buffer.resize(buffer.len() * 2, 0);
+            continue;
+        }
+        if error != 0 || result.is_null() {
+            return None;
+        }
+        // Safety: on success `result` points at `entry`, whose strings point
+        // into `buffer`, and both outlive `read`.
+        return Some(read(unsafe { &*result }));
+    }
+}
+
+#[cfg(unix)]
+pub fn user_name(uid: u32) -> Option<String> {
+    lookup(
+        |passwd, buffer, len, result| unsafe { libc::getpwuid_r(uid, passwd, buffer, len, result) },
+        |passwd: &libc::passwd| {
+            // Safety: `pw_name` is a NUL-terminated string in the buffer.
+            unsafe { CStr::from_ptr(passwd.pw_name) }
+                .to_string_lossy()
+                .into_owned()
+        },
+    )
+}
+
+#[cfg(unix)]
+pub fn group_name(gid: u32) -> Option<String> {
+    lookup(
+        |group, buffer, len, result| unsafe { libc::getgrgid_r(gid, group, buffer, len, result) },
+        |group: &libc::group| {
+            // Safety: `gr_name` is a NUL-terminated string in the buffer.
+            unsafe { CStr::from_ptr(group.gr_name) }
+                .to_string_lossy()
+                .into_owned()
+        },
+    )
+}
+
+/// Resolves a user name, or a plain numeric uid, to a uid.
+#[cfg(unix)]
+pub fn uid_by_name(name: &str) -> Option<u32> {
+    if let Ok(uid) = name.parse() {
+        return Some(uid);
+    }
+
+    let name = CString::new(name).ok()?;
+    lookup(
+        |passwd, buffer, len, result| unsafe {
+            libc::getpwnam_r(name.as_ptr(), passwd, buffer, len, result)
+        },
+        |passwd: &libc::passwd| passwd.pw_uid,
+    )
+}
+
+/// Resolves a group name, or a plain numeric gid, to a gid.
+#[cfg(unix)]
+pub fn gid_by_name(name: &str) -> Option<u32> {
+    if let Ok(gid) = name.parse() {
+        return Some(gid);
+    }
+
+    let name = CString::new(name).ok()?;
+    lookup(
+        |group, buffer, len, result| unsafe {
+            libc::getgrnam_r(name.as_ptr(), group, buffer, len, result)
+        },
+        |group: &libc::group| group.gr_gid,
+    )
+}
+
+#[cfg(not(unix))]
+pub fn user_name(_uid: u32) -> Option<String> {
+    None
+}
+
+#[cfg(not(unix))]
+pub fn group_name(_gid: u32) -> Option<String> {
+    None
+}
+
+#[cfg(not(unix))]
+pub fn uid_by_name(_name: &str) -> Option<u32> {
+    None
+}
+
+#[cfg(not(unix))]
+pub fn gid_by_name(_name: &str) -> Option<u32> {
+    None
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn root_round_trips() {
+        assert_eq!(user_name(0).as_deref(), Some("root"));
+        assert_eq!(uid_by_name("root"), Some(0));
+        assert_eq!(gid_by_name(&group_name(0).unwrap()), Some(0));
+    }
+
+    #[test]
+    fn numeric_names_are_ids() {
+        assert_eq!(uid_by_name("4242"), Some(4242));
+        assert_eq!(gid_by_name("4242"), Some(4242));
+    }
+
+    #[test]
+    fn unknown_names_are_none() {
+        assert_eq!(uid_by_name("no-such-user-here"), None);
+        assert_eq!(user_name(u32::MAX - 1), None);
+    }
+}
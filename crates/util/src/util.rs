@@ -1,4 +1,6 @@
+pub mod fuzzy;
 pub mod paths;
+pub mod users;
 
 #[macro_export]
 macro_rules! maybe {